@@ -0,0 +1,8 @@
+-- Replace free-form PAT scope strings with a bitflag of scopes (see models::pats::Scopes)
+ALTER TABLE pats ADD COLUMN scopes BIGINT NOT NULL DEFAULT 0;
+
+-- Existing tokens keep every scope a PAT may hold, except withdrawing payouts
+UPDATE pats SET scopes = 4194235;
+
+ALTER TABLE pats ALTER COLUMN scopes DROP DEFAULT;
+ALTER TABLE pats DROP COLUMN scope;
@@ -0,0 +1,15 @@
+-- Store PATs as salted hashes instead of plaintext, keeping a short non-secret prefix for lookup and identification
+ALTER TABLE pats ADD COLUMN token_prefix VARCHAR(12);
+ALTER TABLE pats ADD COLUMN token_salt VARCHAR(32);
+ALTER TABLE pats ADD COLUMN token_hash VARCHAR(128);
+
+UPDATE pats SET token_prefix = LEFT(access_token, 12), token_salt = md5(random()::text);
+UPDATE pats SET token_hash = encode(sha512(convert_to(token_salt || access_token, 'UTF8')), 'hex');
+
+ALTER TABLE pats ALTER COLUMN token_prefix SET NOT NULL;
+ALTER TABLE pats ALTER COLUMN token_salt SET NOT NULL;
+ALTER TABLE pats ALTER COLUMN token_hash SET NOT NULL;
+
+ALTER TABLE pats DROP COLUMN access_token;
+
+CREATE INDEX pats_token_prefix ON pats (token_prefix);
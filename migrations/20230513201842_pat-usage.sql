@@ -0,0 +1,9 @@
+-- User-supplied PAT names, existing tokens are named after their prefix
+ALTER TABLE pats ADD COLUMN name VARCHAR(255);
+UPDATE pats SET name = token_prefix;
+ALTER TABLE pats ALTER COLUMN name SET NOT NULL;
+
+-- PAT usage tracking, updated in batches by queue::pats::PatQueue
+ALTER TABLE pats ADD COLUMN last_used_at TIMESTAMP NULL;
+ALTER TABLE pats ADD COLUMN last_used_ip VARCHAR(64) NULL;
+ALTER TABLE pats ADD COLUMN usage_count BIGINT NOT NULL DEFAULT 0;
@@ -0,0 +1,2 @@
+-- Whether the owner of a PAT has been notified that it is about to expire. Reset whenever the expiry changes.
+ALTER TABLE pats ADD COLUMN expiry_notified BOOLEAN NOT NULL DEFAULT FALSE;
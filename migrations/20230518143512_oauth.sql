@@ -0,0 +1,42 @@
+CREATE TABLE oauth_clients (
+    id bigint PRIMARY KEY,
+    name varchar(255) NOT NULL,
+    icon_url varchar(2048) NULL,
+    secret_salt varchar(32) NOT NULL,
+    secret_hash varchar(128) NOT NULL,
+    redirect_uris varchar(2048)[] NOT NULL,
+    -- bitflag of the scopes the client may request
+    max_scopes bigint NOT NULL,
+    created_by bigint REFERENCES users NOT NULL,
+    created timestamptz DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+
+CREATE INDEX oauth_clients_created_by ON oauth_clients (created_by);
+
+-- A user's grant of scopes to a client, holding the refresh token used to obtain new access tokens
+CREATE TABLE oauth_authorizations (
+    id bigint PRIMARY KEY,
+    client_id bigint REFERENCES oauth_clients ON DELETE CASCADE NOT NULL,
+    user_id bigint REFERENCES users ON DELETE CASCADE NOT NULL,
+    scopes bigint NOT NULL,
+    created timestamptz DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    refresh_token_prefix varchar(12) NOT NULL,
+    refresh_token_salt varchar(32) NOT NULL,
+    refresh_token_hash varchar(128) NOT NULL,
+    refresh_expires timestamptz NOT NULL
+);
+
+CREATE INDEX oauth_authorizations_user_id ON oauth_authorizations (user_id);
+CREATE INDEX oauth_authorizations_refresh_token_prefix ON oauth_authorizations (refresh_token_prefix);
+
+-- OAuth access tokens are stored as personal access tokens belonging to an authorization
+ALTER TABLE pats ADD COLUMN oauth_authorization_id bigint REFERENCES oauth_authorizations ON DELETE CASCADE NULL;
+
+-- Pending authorization requests reuse the states table. Once the user accepts, the row holds the authorization code.
+ALTER TABLE states ADD COLUMN oauth_client_id bigint REFERENCES oauth_clients ON DELETE CASCADE NULL;
+ALTER TABLE states ADD COLUMN oauth_scopes bigint NULL;
+ALTER TABLE states ADD COLUMN oauth_state varchar(500) NULL;
+ALTER TABLE states ADD COLUMN oauth_code_challenge varchar(128) NULL;
+ALTER TABLE states ADD COLUMN oauth_user_id bigint REFERENCES users ON DELETE CASCADE NULL;
+ALTER TABLE states ADD COLUMN oauth_code_salt varchar(32) NULL;
+ALTER TABLE states ADD COLUMN oauth_code_hash varchar(128) NULL;
@@ -0,0 +1,17 @@
+-- Accounts of the local identity provider, used instead of Minos/Kratos in development and tests.
+-- The ID is stored in users.kratos_id, like the IDs of Kratos identities.
+CREATE TABLE local_identities (
+    id varchar(255) PRIMARY KEY,
+    email varchar(255) NOT NULL UNIQUE,
+    username varchar(255) NOT NULL,
+    name varchar(255) NULL,
+    password_hash varchar(255) NOT NULL,
+    created timestamptz DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+
+CREATE TABLE local_sessions (
+    id bigint PRIMARY KEY,
+    identity_id varchar(255) REFERENCES local_identities ON DELETE CASCADE NOT NULL,
+    created timestamptz DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    expires timestamptz NOT NULL
+);
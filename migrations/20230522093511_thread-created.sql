@@ -0,0 +1,10 @@
+-- Used to order the moderation inbox. Threads created before this column existed are dated by
+-- their first message.
+ALTER TABLE threads ADD COLUMN created timestamptz DEFAULT CURRENT_TIMESTAMP NOT NULL;
+
+UPDATE threads t
+SET created = m.created
+FROM (
+    SELECT thread_id, MIN(created) created FROM threads_messages GROUP BY thread_id
+) m
+WHERE m.thread_id = t.id;
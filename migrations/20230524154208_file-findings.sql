@@ -0,0 +1,12 @@
+-- Findings of the validation of uploaded files, such as the reasons a file was not made primary
+CREATE TABLE files_findings (
+    id bigserial PRIMARY KEY,
+    file_id bigint REFERENCES files NOT NULL,
+    severity varchar(64) NOT NULL,
+    code varchar(64) NOT NULL,
+    message varchar(2048) NOT NULL,
+    path varchar(2048) NULL
+);
+
+CREATE INDEX files_findings_file_id ON files_findings (file_id);
+CREATE INDEX files_findings_code ON files_findings (code);
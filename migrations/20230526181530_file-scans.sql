@@ -0,0 +1,2 @@
+-- The verdict of the malware scan of each file, which is NULL until the file was scanned
+ALTER TABLE files ADD COLUMN scan_verdict varchar(64) NULL;
@@ -0,0 +1,16 @@
+-- Resumable uploads of version files. The received data is stored on the disk of the instance
+-- which created the session, until it is referenced by a new version or file, or abandoned.
+CREATE TABLE upload_sessions (
+    id bigint PRIMARY KEY,
+    user_id bigint REFERENCES users ON DELETE CASCADE NOT NULL,
+    file_name varchar(2048) NOT NULL,
+    size bigint NOT NULL,
+    received bigint DEFAULT 0 NOT NULL,
+    -- Set once the upload is finalized and its hashes were checked
+    sha1 varchar(40) NULL,
+    sha512 varchar(128) NULL,
+    created timestamptz DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    updated timestamptz DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+
+CREATE INDEX upload_sessions_user_id ON upload_sessions (user_id);
@@ -0,0 +1,17 @@
+-- Version files stored once by their content, and shared by every file with the same hash.
+-- A blob is released once no file references it, and deleted from the file host after.
+-- Files uploaded before blobs were introduced have no blob and keep their own storage.
+CREATE TABLE blobs (
+    sha512 varchar(128) PRIMARY KEY,
+    path varchar(2048) NOT NULL,
+    host_file_id varchar(2048) NOT NULL,
+    size integer NOT NULL,
+    created timestamptz DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    released timestamptz NULL
+);
+
+CREATE INDEX blobs_released ON blobs (released) WHERE released IS NOT NULL;
+
+ALTER TABLE files ADD COLUMN blob varchar(128) NULL REFERENCES blobs;
+
+CREATE INDEX files_blob ON files (blob);
@@ -0,0 +1,14 @@
+-- Where each file is stored when files are replicated to several storage backends.
+-- A file missing from a backend has no host file ID until it is repaired from another backend,
+-- and a file which could not be deleted from a backend is kept until deleting it is retried.
+CREATE TABLE file_placements (
+    path varchar(2048) NOT NULL,
+    backend varchar(64) NOT NULL,
+    host_file_id varchar(2048) NULL,
+    content_type varchar(255) NOT NULL,
+    status varchar(64) NOT NULL,
+    updated timestamptz DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    PRIMARY KEY (path, backend)
+);
+
+CREATE INDEX file_placements_pending ON file_placements (updated) WHERE status != 'stored';
@@ -0,0 +1,14 @@
+-- Problems found by auditing the stored version files against the database: files whose content
+-- no longer matches their hash, files whose object is missing from the file host, and objects on
+-- the file host which no file references. Orphaned objects are deleted once they have been
+-- orphaned for a grace period, and findings which no longer apply are removed by the next audit.
+CREATE TABLE storage_audit_findings (
+    path varchar(2048) NOT NULL,
+    kind varchar(64) NOT NULL,
+    host_file_id varchar(2048) NULL,
+    expected_sha512 varchar(128) NULL,
+    actual_sha512 varchar(128) NULL,
+    first_found timestamptz DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    last_found timestamptz DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    PRIMARY KEY (path, kind)
+);
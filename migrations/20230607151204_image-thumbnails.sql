@@ -0,0 +1,4 @@
+-- Smaller renditions of project icons and gallery images, as a list of their size and URL.
+-- Images uploaded before thumbnails were introduced have none.
+ALTER TABLE mods ADD COLUMN icon_thumbnails jsonb DEFAULT '[]' NOT NULL;
+ALTER TABLE mods_gallery ADD COLUMN thumbnails jsonb DEFAULT '[]' NOT NULL;
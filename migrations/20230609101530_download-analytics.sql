@@ -0,0 +1,15 @@
+-- Downloads counted per project, version and day, broken down by the country and launcher they
+-- came from. Rows are added to as the download queue is indexed, and are served to the members
+-- of a project's team with the permission to view its analytics.
+CREATE TABLE download_analytics (
+    mod_id bigint REFERENCES mods ON DELETE CASCADE NOT NULL,
+    version_id bigint REFERENCES versions ON DELETE CASCADE NOT NULL,
+    day date NOT NULL,
+    -- ISO 3166-1 alpha-2 code, or XX if unknown
+    country varchar(2) NOT NULL,
+    launcher varchar(64) NOT NULL,
+    downloads integer NOT NULL,
+    PRIMARY KEY (mod_id, version_id, day, country, launcher)
+);
+
+CREATE INDEX download_analytics_mod_day ON download_analytics (mod_id, day);
@@ -0,0 +1,3 @@
+-- Files which were never scanned are picked up by the scan queue, such as files whose scan was
+-- lost to a restart
+CREATE INDEX files_unscanned ON files (id) WHERE scan_verdict IS NULL;
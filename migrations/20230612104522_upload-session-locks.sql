@@ -0,0 +1,3 @@
+-- Upload sessions are locked in the database while they receive a chunk, so they can be resumed on
+-- any instance. Their data is stored on a volume shared by all instances, see UPLOAD_SESSION_PATH.
+ALTER TABLE upload_sessions ADD COLUMN locked_until timestamptz NULL;
@@ -0,0 +1,156 @@
+use crate::database::models::DatabaseError;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditFindingKind {
+    /// The content of a stored file does not match its SHA512 hash
+    HashMismatch,
+    /// A file references an object which is missing from the file host
+    MissingObject,
+    /// An object on the file host is not referenced by any file
+    OrphanedObject,
+}
+
+impl AuditFindingKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditFindingKind::HashMismatch => "hash_mismatch",
+            AuditFindingKind::MissingObject => "missing_object",
+            AuditFindingKind::OrphanedObject => "orphaned_object",
+        }
+    }
+
+    pub fn from_str(string: &str) -> AuditFindingKind {
+        match string {
+            "hash_mismatch" => AuditFindingKind::HashMismatch,
+            "missing_object" => AuditFindingKind::MissingObject,
+            _ => AuditFindingKind::OrphanedObject,
+        }
+    }
+}
+
+/// A problem found by auditing the stored version files
+#[derive(Clone, Debug, Serialize)]
+pub struct AuditFinding {
+    /// The name of the object on the file host
+    pub path: String,
+    pub kind: AuditFindingKind,
+    /// The ID the file host gave the object, if it is known
+    pub host_file_id: Option<String>,
+    pub expected_sha512: Option<String>,
+    pub actual_sha512: Option<String>,
+    pub first_found: DateTime<Utc>,
+    pub last_found: DateTime<Utc>,
+}
+
+impl AuditFinding {
+    /// Records a finding. If it was already found, it keeps when it was first found.
+    pub async fn upsert<'a, E>(&self, exec: E) -> Result<(), DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        sqlx::query!(
+            "
+            INSERT INTO storage_audit_findings (
+                path, kind, host_file_id, expected_sha512, actual_sha512, first_found, last_found
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            ON CONFLICT (path, kind) DO UPDATE
+            SET host_file_id = EXCLUDED.host_file_id, expected_sha512 = EXCLUDED.expected_sha512,
+                actual_sha512 = EXCLUDED.actual_sha512, last_found = EXCLUDED.last_found
+            ",
+            self.path,
+            self.kind.as_str(),
+            self.host_file_id,
+            self.expected_sha512,
+            self.actual_sha512,
+            self.first_found,
+            self.last_found,
+        )
+        .execute(exec)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Gets the findings of a kind, or of every kind, most recently found first
+    pub async fn get_all<'a, E>(
+        kind: Option<AuditFindingKind>,
+        exec: E,
+    ) -> Result<Vec<AuditFinding>, DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        let findings = sqlx::query!(
+            "
+            SELECT path, kind, host_file_id, expected_sha512, actual_sha512, first_found, last_found
+            FROM storage_audit_findings
+            WHERE $1::varchar IS NULL OR kind = $1
+            ORDER BY last_found DESC, path ASC
+            ",
+            kind.map(|x| x.as_str()),
+        )
+        .fetch_all(exec)
+        .await?
+        .into_iter()
+        .map(|x| AuditFinding {
+            path: x.path,
+            kind: AuditFindingKind::from_str(&x.kind),
+            host_file_id: x.host_file_id,
+            expected_sha512: x.expected_sha512,
+            actual_sha512: x.actual_sha512,
+            first_found: x.first_found,
+            last_found: x.last_found,
+        })
+        .collect();
+
+        Ok(findings)
+    }
+
+    pub async fn remove<'a, E>(
+        path: &str,
+        kind: AuditFindingKind,
+        exec: E,
+    ) -> Result<(), DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        sqlx::query!(
+            "
+            DELETE FROM storage_audit_findings
+            WHERE path = $1 AND kind = $2
+            ",
+            path,
+            kind.as_str(),
+        )
+        .execute(exec)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Removes the findings of a kind which were not found again since the audit started
+    pub async fn remove_stale<'a, E>(
+        kind: AuditFindingKind,
+        audit_started: DateTime<Utc>,
+        exec: E,
+    ) -> Result<(), DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        sqlx::query!(
+            "
+            DELETE FROM storage_audit_findings
+            WHERE kind = $1 AND last_found < $2
+            ",
+            kind.as_str(),
+            audit_started,
+        )
+        .execute(exec)
+        .await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,167 @@
+use crate::database::models::DatabaseError;
+
+/// The stored content of version files, shared by every file with the same SHA512 hash
+#[derive(Clone, Debug)]
+pub struct Blob {
+    pub sha512: String,
+    /// The name of the blob on the file host
+    pub path: String,
+    /// The ID the file host gave the blob, which is needed to delete it
+    pub host_file_id: String,
+    pub size: i32,
+}
+
+impl Blob {
+    /// The path of a new blob, which keeps the name of the file it was first uploaded as so CDNs
+    /// serve downloads under that name. Blobs are still only stored once per hash.
+    pub fn path_for(sha512: &str, file_name: &str) -> String {
+        format!("data/blobs/{sha512}/{file_name}")
+    }
+
+    /// The URL of the blob on the CDN
+    pub fn url(&self, cdn_url: &str) -> String {
+        match self.path.rsplit_once('/') {
+            Some((dir, name)) => format!("{cdn_url}/{dir}/{}", urlencoding::encode(name)),
+            None => format!("{cdn_url}/{}", self.path),
+        }
+    }
+
+    /// Gets the blob with the hash so it can be referenced by another file. A released blob is
+    /// taken back, and the row is locked until the transaction ends so it can't be deleted meanwhile.
+    pub async fn reuse(
+        sha512: &str,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ) -> Result<Option<Blob>, DatabaseError> {
+        let blob = sqlx::query!(
+            "
+            UPDATE blobs
+            SET released = NULL
+            WHERE sha512 = $1
+            RETURNING sha512, path, host_file_id, size
+            ",
+            sha512,
+        )
+        .fetch_optional(&mut *transaction)
+        .await?;
+
+        Ok(blob.map(|x| Blob {
+            sha512: x.sha512,
+            path: x.path,
+            host_file_id: x.host_file_id,
+            size: x.size,
+        }))
+    }
+
+    /// Claims the hash of a blob before it is uploaded, so a concurrent upload of the same content
+    /// waits for the transaction to end instead of uploading to the same path. Returns false if a
+    /// blob with the hash was inserted concurrently, in which case that one should be used instead.
+    pub async fn claim(
+        sha512: &str,
+        path: &str,
+        size: i32,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ) -> Result<bool, DatabaseError> {
+        let result = sqlx::query!(
+            "
+            INSERT INTO blobs (sha512, path, host_file_id, size)
+            VALUES ($1, $2, '', $3)
+            ON CONFLICT (sha512) DO NOTHING
+            ",
+            sha512,
+            path,
+            size,
+        )
+        .execute(&mut *transaction)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Stores the ID the file host gave a claimed blob once it is uploaded
+    pub async fn set_host_file_id(
+        &self,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ) -> Result<(), DatabaseError> {
+        sqlx::query!(
+            "
+            UPDATE blobs
+            SET host_file_id = $2, size = $3
+            WHERE sha512 = $1
+            ",
+            self.sha512,
+            self.host_file_id,
+            self.size,
+        )
+        .execute(&mut *transaction)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Releases the blobs which are no longer referenced by any file, after their files were deleted
+    pub async fn release_unreferenced(
+        blobs: &[String],
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ) -> Result<(), sqlx::Error> {
+        if blobs.is_empty() {
+            return Ok(());
+        }
+
+        sqlx::query!(
+            "
+            UPDATE blobs
+            SET released = NOW()
+            WHERE sha512 = ANY($1) AND NOT EXISTS(SELECT 1 FROM files f WHERE f.blob = blobs.sha512)
+            ",
+            blobs,
+        )
+        .execute(&mut *transaction)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Gets the released blobs which were not taken back, locking them until the transaction ends
+    /// so they can't be reused while they are deleted from the file host
+    pub async fn get_released(
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ) -> Result<Vec<Blob>, DatabaseError> {
+        let blobs = sqlx::query!(
+            "
+            SELECT sha512, path, host_file_id, size FROM blobs
+            WHERE released IS NOT NULL AND NOT EXISTS(SELECT 1 FROM files f WHERE f.blob = blobs.sha512)
+            FOR UPDATE SKIP LOCKED
+            ",
+        )
+        .fetch_all(&mut *transaction)
+        .await?
+        .into_iter()
+        .map(|x| Blob {
+            sha512: x.sha512,
+            path: x.path,
+            host_file_id: x.host_file_id,
+            size: x.size,
+        })
+        .collect();
+
+        Ok(blobs)
+    }
+
+    /// Deletes a released blob once it was deleted from the file host
+    pub async fn remove(
+        sha512: &str,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ) -> Result<(), DatabaseError> {
+        sqlx::query!(
+            "
+            DELETE FROM blobs
+            WHERE sha512 = $1
+            ",
+            sha512,
+        )
+        .execute(&mut *transaction)
+        .await?;
+
+        Ok(())
+    }
+}
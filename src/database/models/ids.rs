@@ -1,6 +1,6 @@
 use super::DatabaseError;
 use crate::models::ids::base62_impl::to_base62;
-use crate::models::ids::random_base62_rng;
+use crate::models::ids::random_base62;
 use censor::Censor;
 use serde::Deserialize;
 use sqlx::sqlx_macros::Type;
@@ -12,9 +12,8 @@ macro_rules! generate_ids {
         $vis async fn $function_name(
             con: &mut sqlx::Transaction<'_, sqlx::Postgres>,
         ) -> Result<$return_type, DatabaseError> {
-            let mut rng = rand::thread_rng();
             let length = $id_length;
-            let mut id = random_base62_rng(&mut rng, length);
+            let mut id = random_base62(length);
             let mut retry_count = 0;
             let censor = Censor::Standard + Censor::Sex;
 
@@ -25,7 +24,7 @@ macro_rules! generate_ids {
                     .await?;
 
                 if results.exists.unwrap_or(true) || censor.check(&*to_base62(id)) {
-                    id = random_base62_rng(&mut rng, length);
+                    id = random_base62(length);
                 } else {
                     break;
                 }
@@ -91,6 +90,37 @@ generate_ids!(
     PatId
 );
 
+generate_ids!(
+    pub generate_oauth_client_id,
+    OAuthClientId,
+    8,
+    "SELECT EXISTS(SELECT 1 FROM oauth_clients WHERE id=$1)",
+    OAuthClientId
+);
+generate_ids!(
+    pub generate_oauth_authorization_id,
+    OAuthAuthorizationId,
+    8,
+    "SELECT EXISTS(SELECT 1 FROM oauth_authorizations WHERE id=$1)",
+    OAuthAuthorizationId
+);
+
+generate_ids!(
+    pub generate_local_session_id,
+    LocalSessionId,
+    8,
+    "SELECT EXISTS(SELECT 1 FROM local_sessions WHERE id=$1)",
+    LocalSessionId
+);
+
+generate_ids!(
+    pub generate_upload_session_id,
+    UploadSessionId,
+    8,
+    "SELECT EXISTS(SELECT 1 FROM upload_sessions WHERE id=$1)",
+    UploadSessionId
+);
+
 generate_ids!(
     pub generate_user_id,
     UserId,
@@ -185,10 +215,25 @@ pub struct FileId(pub i64);
 #[sqlx(transparent)]
 pub struct StateId(pub i64);
 
-#[derive(Copy, Clone, Debug, Type)]
+#[derive(Copy, Clone, Debug, Type, Hash, Eq, PartialEq)]
 #[sqlx(transparent)]
 pub struct PatId(pub i64);
 
+#[derive(Copy, Clone, Debug, Type)]
+#[sqlx(transparent)]
+pub struct OAuthClientId(pub i64);
+#[derive(Copy, Clone, Debug, Type)]
+#[sqlx(transparent)]
+pub struct OAuthAuthorizationId(pub i64);
+
+#[derive(Copy, Clone, Debug, Type)]
+#[sqlx(transparent)]
+pub struct LocalSessionId(pub i64);
+
+#[derive(Copy, Clone, Debug, Type)]
+#[sqlx(transparent)]
+pub struct UploadSessionId(pub i64);
+
 #[derive(Copy, Clone, Debug, Type, Deserialize)]
 #[sqlx(transparent)]
 pub struct NotificationId(pub i64);
@@ -289,3 +334,43 @@ impl From<ThreadMessageId> for ids::ThreadMessageId {
         ids::ThreadMessageId(id.0 as u64)
     }
 }
+impl From<ids::PatId> for PatId {
+    fn from(id: ids::PatId) -> Self {
+        PatId(id.0 as i64)
+    }
+}
+impl From<PatId> for ids::PatId {
+    fn from(id: PatId) -> Self {
+        ids::PatId(id.0 as u64)
+    }
+}
+impl From<ids::OAuthClientId> for OAuthClientId {
+    fn from(id: ids::OAuthClientId) -> Self {
+        OAuthClientId(id.0 as i64)
+    }
+}
+impl From<OAuthClientId> for ids::OAuthClientId {
+    fn from(id: OAuthClientId) -> Self {
+        ids::OAuthClientId(id.0 as u64)
+    }
+}
+impl From<ids::OAuthAuthorizationId> for OAuthAuthorizationId {
+    fn from(id: ids::OAuthAuthorizationId) -> Self {
+        OAuthAuthorizationId(id.0 as i64)
+    }
+}
+impl From<OAuthAuthorizationId> for ids::OAuthAuthorizationId {
+    fn from(id: OAuthAuthorizationId) -> Self {
+        ids::OAuthAuthorizationId(id.0 as u64)
+    }
+}
+impl From<ids::UploadSessionId> for UploadSessionId {
+    fn from(id: ids::UploadSessionId) -> Self {
+        UploadSessionId(id.0 as i64)
+    }
+}
+impl From<UploadSessionId> for ids::UploadSessionId {
+    fn from(id: UploadSessionId) -> Self {
+        ids::UploadSessionId(id.0 as u64)
+    }
+}
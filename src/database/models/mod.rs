@@ -1,8 +1,11 @@
 use thiserror::Error;
 
+pub mod audit_finding_item;
+pub mod blob_item;
 pub mod categories;
 pub mod ids;
 pub mod notification_item;
+pub mod placement_item;
 pub mod project_item;
 pub mod report_item;
 pub mod team_item;
@@ -1,6 +1,7 @@
 use super::ids::*;
 use crate::database::models::DatabaseError;
 use crate::models::notifications::NotificationBody;
+use crate::util::pagination::Cursor;
 use chrono::{DateTime, Utc};
 use serde::Deserialize;
 
@@ -149,6 +150,37 @@ impl Notification {
             .await
     }
 
+    // Returns the IDs of a page of the user's notifications, newest first
+    pub async fn get_user_page<'a, E>(
+        user_id: UserId,
+        cursor: Option<Cursor>,
+        limit: i64,
+        exec: E,
+    ) -> Result<Vec<NotificationId>, sqlx::Error>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        use futures::stream::TryStreamExt;
+
+        sqlx::query!(
+            "
+            SELECT n.id FROM notifications n
+            WHERE n.user_id = $1
+            AND ($2::timestamptz IS NULL OR (n.created, n.id) < ($2, $3))
+            ORDER BY n.created DESC, n.id DESC
+            LIMIT $4
+            ",
+            user_id as UserId,
+            cursor.map(|x| x.created),
+            cursor.map(|x| x.id),
+            limit,
+        )
+        .fetch_many(exec)
+        .try_filter_map(|e| async { Ok(e.right().map(|n| NotificationId(n.id))) })
+        .try_collect::<Vec<NotificationId>>()
+        .await
+    }
+
     pub async fn get_many_user<'a, E>(
         user_id: UserId,
         exec: E,
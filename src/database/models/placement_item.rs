@@ -0,0 +1,165 @@
+use crate::database::models::DatabaseError;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum PlacementStatus {
+    /// The file is stored on the backend
+    Stored,
+    /// Uploading the file to the backend failed, so it should be copied from another backend
+    Missing,
+    /// Deleting the file from the backend failed, so it should be deleted again
+    Deleting,
+}
+
+impl PlacementStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PlacementStatus::Stored => "stored",
+            PlacementStatus::Missing => "missing",
+            PlacementStatus::Deleting => "deleting",
+        }
+    }
+
+    pub fn from_str(string: &str) -> PlacementStatus {
+        match string {
+            "missing" => PlacementStatus::Missing,
+            "deleting" => PlacementStatus::Deleting,
+            _ => PlacementStatus::Stored,
+        }
+    }
+}
+
+/// Where a file is stored when files are replicated to several storage backends
+#[derive(Clone, Debug)]
+pub struct Placement {
+    /// The name of the file on the file hosts
+    pub path: String,
+    /// The name of the storage backend, such as `s3`
+    pub backend: String,
+    /// The ID the backend gave the file, which is needed to delete it. None if it is missing.
+    pub host_file_id: Option<String>,
+    pub content_type: String,
+    pub status: PlacementStatus,
+}
+
+impl Placement {
+    /// Inserts the placement, replacing the previous placement of the file on the backend
+    pub async fn upsert<'a, E>(&self, exec: E) -> Result<(), DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        sqlx::query!(
+            "
+            INSERT INTO file_placements (path, backend, host_file_id, content_type, status)
+            VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT (path, backend) DO UPDATE
+            SET host_file_id = EXCLUDED.host_file_id, content_type = EXCLUDED.content_type,
+                status = EXCLUDED.status, updated = NOW()
+            ",
+            self.path,
+            self.backend,
+            self.host_file_id,
+            self.content_type,
+            self.status.as_str(),
+        )
+        .execute(exec)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn get_for_path<'a, E>(path: &str, exec: E) -> Result<Vec<Placement>, DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        let placements = sqlx::query!(
+            "
+            SELECT path, backend, host_file_id, content_type, status
+            FROM file_placements
+            WHERE path = $1
+            ",
+            path,
+        )
+        .fetch_all(exec)
+        .await?
+        .into_iter()
+        .map(|x| Placement {
+            path: x.path,
+            backend: x.backend,
+            host_file_id: x.host_file_id,
+            content_type: x.content_type,
+            status: PlacementStatus::from_str(&x.status),
+        })
+        .collect();
+
+        Ok(placements)
+    }
+
+    /// Gets the placements which are missing or still have to be deleted, least recently
+    /// attempted first
+    pub async fn get_pending<'a, E>(limit: i64, exec: E) -> Result<Vec<Placement>, DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        let placements = sqlx::query!(
+            "
+            SELECT path, backend, host_file_id, content_type, status
+            FROM file_placements
+            WHERE status != 'stored'
+            ORDER BY updated ASC
+            LIMIT $1
+            ",
+            limit,
+        )
+        .fetch_all(exec)
+        .await?
+        .into_iter()
+        .map(|x| Placement {
+            path: x.path,
+            backend: x.backend,
+            host_file_id: x.host_file_id,
+            content_type: x.content_type,
+            status: PlacementStatus::from_str(&x.status),
+        })
+        .collect();
+
+        Ok(placements)
+    }
+
+    /// Marks a pending placement as attempted, so other placements are retried first
+    pub async fn touch<'a, E>(&self, exec: E) -> Result<(), DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        sqlx::query!(
+            "
+            UPDATE file_placements
+            SET updated = NOW()
+            WHERE path = $1 AND backend = $2
+            ",
+            self.path,
+            self.backend,
+        )
+        .execute(exec)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn remove<'a, E>(&self, exec: E) -> Result<(), DatabaseError>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        sqlx::query!(
+            "
+            DELETE FROM file_placements
+            WHERE path = $1 AND backend = $2
+            ",
+            self.path,
+            self.backend,
+        )
+        .execute(exec)
+        .await?;
+
+        Ok(())
+    }
+}
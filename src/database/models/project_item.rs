@@ -1,5 +1,5 @@
 use super::ids::*;
-use crate::models::projects::{MonetizationStatus, ProjectStatus};
+use crate::models::projects::{MonetizationStatus, ProjectStatus, Thumbnail};
 use chrono::{DateTime, Utc};
 use serde::Deserialize;
 
@@ -45,6 +45,8 @@ pub struct GalleryItem {
     pub description: Option<String>,
     pub created: DateTime<Utc>,
     pub ordering: i64,
+    #[serde(default)]
+    pub thumbnails: Vec<Thumbnail>,
 }
 
 impl GalleryItem {
@@ -56,10 +58,10 @@ impl GalleryItem {
         sqlx::query!(
             "
             INSERT INTO mods_gallery (
-                mod_id, image_url, featured, title, description, ordering
+                mod_id, image_url, featured, title, description, ordering, thumbnails
             )
             VALUES (
-                $1, $2, $3, $4, $5, $6
+                $1, $2, $3, $4, $5, $6, $7
             )
             ",
             project_id as ProjectId,
@@ -67,7 +69,8 @@ impl GalleryItem {
             self.featured,
             self.title,
             self.description,
-            self.ordering
+            self.ordering,
+            serde_json::to_value(&self.thumbnails).unwrap_or_default(),
         )
         .execute(&mut *transaction)
         .await?;
@@ -84,6 +87,7 @@ pub struct ProjectBuilder {
     pub description: String,
     pub body: String,
     pub icon_url: Option<String>,
+    pub icon_thumbnails: Vec<Thumbnail>,
     pub issues_url: Option<String>,
     pub source_url: Option<String>,
     pub wiki_url: Option<String>,
@@ -131,6 +135,7 @@ impl ProjectBuilder {
             downloads: 0,
             follows: 0,
             icon_url: self.icon_url,
+            icon_thumbnails: self.icon_thumbnails,
             issues_url: self.issues_url,
             source_url: self.source_url,
             wiki_url: self.wiki_url,
@@ -214,6 +219,7 @@ pub struct Project {
     pub downloads: i32,
     pub follows: i32,
     pub icon_url: Option<String>,
+    pub icon_thumbnails: Vec<Thumbnail>,
     pub issues_url: Option<String>,
     pub source_url: Option<String>,
     pub wiki_url: Option<String>,
@@ -245,14 +251,16 @@ impl Project {
                 published, downloads, icon_url, issues_url,
                 source_url, wiki_url, status, requested_status, discord_url,
                 client_side, server_side, license_url, license,
-                slug, project_type, color, thread_id, monetization_status
+                slug, project_type, color, thread_id, monetization_status,
+                icon_thumbnails
             )
             VALUES (
                 $1, $2, $3, $4, $5,
                 $6, $7, $8, $9,
                 $10, $11, $12, $13, $14,
                 $15, $16, $17, $18,
-                LOWER($19), $20, $21, $22, $23
+                LOWER($19), $20, $21, $22, $23,
+                $24
             )
             ",
             self.id as ProjectId,
@@ -278,6 +286,7 @@ impl Project {
             self.color.map(|x| x as i32),
             self.thread_id.map(|x| x.0),
             self.monetization_status.as_str(),
+            serde_json::to_value(&self.icon_thumbnails).unwrap_or_default(),
         )
         .execute(&mut *transaction)
         .await?;
@@ -315,7 +324,8 @@ impl Project {
                    issues_url, source_url, wiki_url, discord_url, license_url,
                    team_id, client_side, server_side, license, slug,
                    moderation_message, moderation_message_body,
-                   webhook_sent, color, loaders, game_versions, thread_id, monetization_status
+                   webhook_sent, color, loaders, game_versions, thread_id, monetization_status,
+                   icon_thumbnails
             FROM mods
             WHERE id = ANY($1)
             ",
@@ -332,6 +342,7 @@ impl Project {
                 downloads: m.downloads,
                 body_url: None,
                 icon_url: m.icon_url,
+                icon_thumbnails: serde_json::from_value(m.icon_thumbnails).unwrap_or_default(),
                 published: m.published,
                 updated: m.updated,
                 issues_url: m.issues_url,
@@ -650,7 +661,7 @@ impl Project {
         sqlx::query!(
             "
             SELECT m.id id, m.project_type project_type, m.title title, m.description description, m.downloads downloads, m.follows follows,
-            m.icon_url icon_url, m.body body, m.published published,
+            m.icon_url icon_url, m.icon_thumbnails icon_thumbnails, m.body body, m.published published,
             m.updated updated, m.approved approved, m.queued, m.status status, m.requested_status requested_status,
             m.issues_url issues_url, m.source_url source_url, m.wiki_url wiki_url, m.discord_url discord_url, m.license_url license_url,
             m.team_id team_id, m.client_side client_side, m.server_side server_side, m.license license, m.slug slug, m.moderation_message moderation_message, m.moderation_message_body moderation_message_body,
@@ -659,7 +670,7 @@ impl Project {
             ARRAY_AGG(DISTINCT c.category) filter (where c.category is not null and mc.is_additional is false) categories,
             ARRAY_AGG(DISTINCT c.category) filter (where c.category is not null and mc.is_additional is true) additional_categories,
             JSONB_AGG(DISTINCT jsonb_build_object('id', v.id, 'date_published', v.date_published)) filter (where v.id is not null) versions,
-            JSONB_AGG(DISTINCT jsonb_build_object('image_url', mg.image_url, 'featured', mg.featured, 'title', mg.title, 'description', mg.description, 'created', mg.created, 'ordering', mg.ordering)) filter (where mg.image_url is not null) gallery,
+            JSONB_AGG(DISTINCT jsonb_build_object('image_url', mg.image_url, 'featured', mg.featured, 'title', mg.title, 'description', mg.description, 'created', mg.created, 'ordering', mg.ordering, 'thumbnails', mg.thumbnails)) filter (where mg.image_url is not null) gallery,
             JSONB_AGG(DISTINCT jsonb_build_object('platform_id', md.joining_platform_id, 'platform_short', dp.short, 'platform_name', dp.name,'url', md.url)) filter (where md.joining_platform_id is not null) donations
             FROM mods m
             INNER JOIN project_types pt ON pt.id = m.project_type
@@ -692,6 +703,7 @@ impl Project {
                             downloads: m.downloads,
                             body_url: None,
                             icon_url: m.icon_url.clone(),
+                            icon_thumbnails: serde_json::from_value(m.icon_thumbnails).unwrap_or_default(),
                             published: m.published,
                             updated: m.updated,
                             issues_url: m.issues_url.clone(),
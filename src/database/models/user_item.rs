@@ -1,5 +1,7 @@
 use super::ids::{ProjectId, UserId};
+use crate::models::projects::ProjectStatus;
 use crate::models::users::{Badges, RecipientType, RecipientWallet};
+use crate::util::pagination::Cursor;
 use chrono::{DateTime, Utc};
 use rust_decimal::Decimal;
 
@@ -226,6 +228,84 @@ impl User {
         Ok(projects)
     }
 
+    // Returns a page of the user's projects, newest first. Projects which are not searchable are
+    // only included if `include_private` is set
+    pub async fn get_projects_page<'a, E>(
+        user_id: UserId,
+        include_private: bool,
+        cursor: Option<Cursor>,
+        limit: i64,
+        exec: E,
+    ) -> Result<Vec<ProjectId>, sqlx::Error>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres> + Copy,
+    {
+        use futures::stream::TryStreamExt;
+
+        let searchable_statuses = ProjectStatus::iterator()
+            .filter(|x| x.is_searchable())
+            .map(|x| x.to_string())
+            .collect::<Vec<_>>();
+
+        let projects = sqlx::query!(
+            "
+            SELECT m.id FROM mods m
+            INNER JOIN team_members tm ON tm.team_id = m.team_id AND tm.accepted = TRUE
+            WHERE tm.user_id = $1 AND ($2 OR m.status = ANY($3))
+            AND ($4::timestamptz IS NULL OR (m.published, m.id) < ($4, $5))
+            ORDER BY m.published DESC, m.id DESC
+            LIMIT $6
+            ",
+            user_id as UserId,
+            include_private,
+            &searchable_statuses,
+            cursor.map(|x| x.created),
+            cursor.map(|x| x.id),
+            limit,
+        )
+        .fetch_many(exec)
+        .try_filter_map(|e| async { Ok(e.right().map(|m| ProjectId(m.id))) })
+        .try_collect::<Vec<ProjectId>>()
+        .await?;
+
+        Ok(projects)
+    }
+
+    // Returns a page of the projects the user follows, most recently followed first, along with
+    // the cursor position of each of them
+    pub async fn get_follows_page<'a, E>(
+        user_id: UserId,
+        cursor: Option<Cursor>,
+        limit: i64,
+        exec: E,
+    ) -> Result<Vec<(ProjectId, Cursor)>, sqlx::Error>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        use futures::stream::TryStreamExt;
+
+        sqlx::query!(
+            "
+            SELECT mf.mod_id, mf.created FROM mod_follows mf
+            WHERE mf.follower_id = $1
+            AND ($2::timestamptz IS NULL OR (mf.created, mf.mod_id) < ($2, $3))
+            ORDER BY mf.created DESC, mf.mod_id DESC
+            LIMIT $4
+            ",
+            user_id as UserId,
+            cursor.map(|x| x.created),
+            cursor.map(|x| x.id),
+            limit,
+        )
+        .fetch_many(exec)
+        .try_filter_map(|e| async {
+            Ok(e.right()
+                .map(|m| (ProjectId(m.mod_id), Cursor::new(m.created, m.mod_id))))
+        })
+        .try_collect::<Vec<(ProjectId, Cursor)>>()
+        .await
+    }
+
     pub async fn remove(
         id: UserId,
         transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
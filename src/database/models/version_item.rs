@@ -1,7 +1,9 @@
+use super::blob_item::Blob;
 use super::ids::*;
 use super::DatabaseError;
 use crate::models::ids::base62_impl::parse_base62;
-use crate::models::projects::{FileType, VersionStatus, VersionType};
+use crate::models::projects::{FileFinding, FileType, VersionStatus, VersionType};
+use crate::util::pagination::Cursor;
 use chrono::{DateTime, Utc};
 use serde::Deserialize;
 use std::cmp::Ordering;
@@ -78,6 +80,9 @@ pub struct VersionFileBuilder {
     pub primary: bool,
     pub size: u32,
     pub file_type: Option<FileType>,
+    pub findings: Vec<FileFinding>,
+    /// The SHA512 hash of the blob storing the file
+    pub blob: Option<String>,
 }
 
 impl VersionFileBuilder {
@@ -90,8 +95,8 @@ impl VersionFileBuilder {
 
         sqlx::query!(
             "
-            INSERT INTO files (id, version_id, url, filename, is_primary, size, file_type)
-            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            INSERT INTO files (id, version_id, url, filename, is_primary, size, file_type, blob)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
             ",
             file_id as FileId,
             version_id as VersionId,
@@ -100,6 +105,7 @@ impl VersionFileBuilder {
             self.primary,
             self.size as i32,
             self.file_type.map(|x| x.as_str()),
+            self.blob,
         )
         .execute(&mut *transaction)
         .await?;
@@ -118,10 +124,36 @@ impl VersionFileBuilder {
             .await?;
         }
 
+        insert_file_findings(file_id, self.findings, transaction).await?;
+
         Ok(file_id)
     }
 }
 
+pub async fn insert_file_findings(
+    file_id: FileId,
+    findings: Vec<FileFinding>,
+    transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+) -> Result<(), DatabaseError> {
+    for finding in findings {
+        sqlx::query!(
+            "
+            INSERT INTO files_findings (file_id, severity, code, message, path)
+            VALUES ($1, $2, $3, $4, $5)
+            ",
+            file_id as FileId,
+            finding.severity.as_str(),
+            finding.code.as_str(),
+            finding.message,
+            finding.path,
+        )
+        .execute(&mut *transaction)
+        .await?;
+    }
+
+    Ok(())
+}
+
 pub struct HashBuilder {
     pub algorithm: String,
     pub hash: Vec<u8>,
@@ -299,6 +331,20 @@ impl Version {
         .execute(&mut *transaction)
         .await?;
 
+        sqlx::query!(
+            "
+            DELETE FROM files_findings
+            WHERE EXISTS(
+                SELECT 1 FROM files WHERE
+                    (files.version_id = $1) AND
+                    (files_findings.file_id = files.id)
+            )
+            ",
+            id as VersionId
+        )
+        .execute(&mut *transaction)
+        .await?;
+
         sqlx::query!(
             "
             DELETE FROM hashes
@@ -313,15 +359,21 @@ impl Version {
         .execute(&mut *transaction)
         .await?;
 
-        sqlx::query!(
+        let blobs = sqlx::query!(
             "
             DELETE FROM files
             WHERE files.version_id = $1
+            RETURNING blob
             ",
             id as VersionId,
         )
-        .execute(&mut *transaction)
-        .await?;
+        .fetch_all(&mut *transaction)
+        .await?
+        .into_iter()
+        .filter_map(|x| x.blob)
+        .collect::<Vec<_>>();
+
+        Blob::release_unreferenced(&blobs, transaction).await?;
 
         // Sync dependencies
 
@@ -428,6 +480,48 @@ impl Version {
         Ok(vec)
     }
 
+    // Returns a page of the project's versions, newest first. Hidden versions are only included
+    // if `include_hidden` is set
+    pub async fn get_project_versions_page<'a, E>(
+        project_id: ProjectId,
+        include_hidden: bool,
+        cursor: Option<Cursor>,
+        limit: i64,
+        exec: E,
+    ) -> Result<Vec<VersionId>, sqlx::Error>
+    where
+        E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+    {
+        use futures::stream::TryStreamExt;
+
+        let visible_statuses = VersionStatus::iterator()
+            .filter(|x| !x.is_hidden())
+            .map(|x| x.to_string())
+            .collect::<Vec<_>>();
+
+        let vec = sqlx::query!(
+            "
+            SELECT v.id FROM versions v
+            WHERE v.mod_id = $1 AND ($2 OR v.status = ANY($3))
+            AND ($4::timestamptz IS NULL OR (v.date_published, v.id) < ($4, $5))
+            ORDER BY v.date_published DESC, v.id DESC
+            LIMIT $6
+            ",
+            project_id as ProjectId,
+            include_hidden,
+            &visible_statuses,
+            cursor.map(|x| x.created),
+            cursor.map(|x| x.id),
+            limit,
+        )
+        .fetch_many(exec)
+        .try_filter_map(|e| async { Ok(e.right().map(|v| VersionId(v.id))) })
+        .try_collect::<Vec<VersionId>>()
+        .await?;
+
+        Ok(vec)
+    }
+
     pub async fn get_projects_versions<'a, E>(
         project_ids: Vec<ProjectId>,
         game_versions: Option<Vec<String>>,
@@ -508,6 +602,7 @@ impl Version {
             JSONB_AGG(DISTINCT jsonb_build_object('version', gv.version, 'created', gv.created)) filter (where gv.version is not null) game_versions,
             ARRAY_AGG(DISTINCT l.loader) filter (where l.loader is not null) loaders,
             JSONB_AGG(DISTINCT jsonb_build_object('id', f.id, 'url', f.url, 'filename', f.filename, 'primary', f.is_primary, 'size', f.size, 'file_type', f.file_type))  filter (where f.id is not null) files,
+            JSONB_AGG(DISTINCT jsonb_build_object('id', ff.id, 'file_id', ff.file_id, 'severity', ff.severity, 'code', ff.code, 'message', ff.message, 'path', ff.path)) filter (where ff.id is not null) findings,
             JSONB_AGG(DISTINCT jsonb_build_object('algorithm', h.algorithm, 'hash', encode(h.hash, 'escape'), 'file_id', h.file_id)) filter (where h.hash is not null) hashes,
             JSONB_AGG(DISTINCT jsonb_build_object('project_id', d.mod_dependency_id, 'version_id', d.dependency_id, 'dependency_type', d.dependency_type,'file_name', dependency_file_name)) filter (where d.dependency_type is not null) dependencies
             FROM versions v
@@ -517,6 +612,7 @@ impl Version {
             LEFT OUTER JOIN loaders l on lv.loader_id = l.id
             LEFT OUTER JOIN files f on v.id = f.version_id
             LEFT OUTER JOIN hashes h on f.id = h.file_id
+            LEFT OUTER JOIN files_findings ff on f.id = ff.file_id
             LEFT OUTER JOIN dependencies d on v.id = d.dependent_id
             WHERE v.id = ANY($1)
             GROUP BY v.id
@@ -568,6 +664,21 @@ impl Version {
                                 .ok()
                                 .unwrap_or_default();
 
+                            #[derive(Deserialize)]
+                            struct Finding {
+                                pub id: i64,
+                                pub file_id: FileId,
+                                #[serde(flatten)]
+                                pub finding: FileFinding,
+                            }
+
+                            let mut findings: Vec<Finding> = serde_json::from_value(
+                                v.findings.unwrap_or_default(),
+                            )
+                                .ok()
+                                .unwrap_or_default();
+                            findings.sort_by_key(|x| x.id);
+
                             let files: Vec<File> = serde_json::from_value(
                                 v.files.unwrap_or_default(),
                             )
@@ -586,6 +697,12 @@ impl Version {
                                     }
                                 }
 
+                                let file_findings = findings
+                                    .iter()
+                                    .filter(|y| y.file_id == x.id)
+                                    .map(|y| y.finding.clone())
+                                    .collect();
+
                                 QueryFile {
                                     id: x.id,
                                     url: x.url,
@@ -594,6 +711,7 @@ impl Version {
                                     primary: x.primary,
                                     size: x.size,
                                     file_type: x.file_type,
+                                    findings: file_findings,
                                 }
                             }).collect::<Vec<_>>();
 
@@ -699,4 +817,5 @@ pub struct QueryFile {
     pub primary: bool,
     pub size: u32,
     pub file_type: Option<FileType>,
+    pub findings: Vec<FileFinding>,
 }
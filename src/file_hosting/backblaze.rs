@@ -1,4 +1,4 @@
-use super::{DeleteFileData, FileHost, FileHostingError, UploadFileData};
+use super::{DeleteFileData, FileHost, FileHostingError, SpooledFile, StoredFile, UploadFileData};
 use async_trait::async_trait;
 use bytes::Bytes;
 use reqwest::Response;
@@ -7,6 +7,8 @@ use sha2::Digest;
 
 mod authorization;
 mod delete;
+mod download;
+mod list;
 mod upload;
 
 pub struct BackblazeHost {
@@ -52,22 +54,26 @@ impl FileHost for BackblazeHost {
         })
     }
 
-    /*
-        async fn upload_file_streaming(
-            &self,
-            content_type: &str,
-            file_name: &str,
-            stream: reqwest::Body
-        ) -> Result<UploadFileData, FileHostingError> {
-            use futures::stream::StreamExt;
-
-            let mut data = Vec::new();
-            while let Some(chunk) = stream.next().await {
-                data.extend_from_slice(&chunk.map_err(|e| FileHostingError::Other(e))?);
-            }
-            self.upload_file(content_type, file_name, data).await
-        }
-    */
+    async fn upload_file_streaming(
+        &self,
+        content_type: &str,
+        file_name: &str,
+        file: &SpooledFile,
+    ) -> Result<UploadFileData, FileHostingError> {
+        let upload_data =
+            upload::upload_file_streaming(&self.upload_url_data, content_type, file_name, file)
+                .await?;
+        Ok(UploadFileData {
+            file_id: upload_data.file_id,
+            file_name: upload_data.file_name,
+            content_length: upload_data.content_length,
+            content_sha512: file.sha512.clone(),
+            content_sha1: upload_data.content_sha1,
+            content_md5: upload_data.content_md5,
+            content_type: upload_data.content_type,
+            upload_timestamp: upload_data.upload_timestamp,
+        })
+    }
 
     async fn delete_file_version(
         &self,
@@ -81,6 +87,47 @@ impl FileHost for BackblazeHost {
             file_name: delete_data.file_name,
         })
     }
+
+    async fn download_file(
+        &self,
+        file_id: Option<&str>,
+        file_name: &str,
+    ) -> Result<SpooledFile, FileHostingError> {
+        let file_id = match file_id {
+            Some(file_id) => file_id.to_string(),
+            None => {
+                list::list_file_names(
+                    &self.authorization_data,
+                    &self.upload_url_data.bucket_id,
+                    file_name,
+                )
+                .await?
+                .into_iter()
+                .find(|x| x.file_name == file_name)
+                .ok_or(FileHostingError::NotFound)?
+                .file_id
+            }
+        };
+
+        download::download_file_by_id(&self.authorization_data, &file_id).await
+    }
+
+    async fn list_files(&self, prefix: &str) -> Result<Vec<StoredFile>, FileHostingError> {
+        let files = list::list_file_names(
+            &self.authorization_data,
+            &self.upload_url_data.bucket_id,
+            prefix,
+        )
+        .await?;
+
+        Ok(files
+            .into_iter()
+            .map(|x| StoredFile {
+                file_id: x.file_id,
+                file_name: x.file_name,
+            })
+            .collect())
+    }
 }
 
 pub async fn process_response<T>(response: Response) -> Result<T, FileHostingError>
@@ -0,0 +1,33 @@
+use super::authorization::AuthorizationData;
+use crate::file_hosting::{FileHostingError, SpoolWriter, SpooledFile};
+use futures::StreamExt;
+
+pub async fn download_file_by_id(
+    authorization_data: &AuthorizationData,
+    file_id: &str,
+) -> Result<SpooledFile, FileHostingError> {
+    let response = reqwest::Client::new()
+        .get(&format!(
+            "{}/b2api/v2/b2_download_file_by_id",
+            authorization_data.download_url
+        ))
+        .query(&[("fileId", file_id)])
+        .header(
+            reqwest::header::AUTHORIZATION,
+            &authorization_data.authorization_token,
+        )
+        .send()
+        .await?;
+
+    if !response.status().is_success() {
+        return Err(FileHostingError::BackblazeError(response.json().await?));
+    }
+
+    let mut writer = SpoolWriter::new()?;
+    let mut stream = response.bytes_stream();
+    while let Some(chunk) = stream.next().await {
+        writer.write(&chunk?).await?;
+    }
+
+    Ok(writer.finish().await?)
+}
@@ -0,0 +1,64 @@
+use super::authorization::AuthorizationData;
+use crate::file_hosting::FileHostingError;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct FileName {
+    pub file_id: String,
+    pub file_name: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ListFileNamesData {
+    pub files: Vec<FileName>,
+    pub next_file_name: Option<String>,
+}
+
+// The most file names B2 returns for one request
+const MAX_FILE_COUNT: u32 = 1000;
+
+pub async fn list_file_names(
+    authorization_data: &AuthorizationData,
+    bucket_id: &str,
+    prefix: &str,
+) -> Result<Vec<FileName>, FileHostingError> {
+    let client = reqwest::Client::new();
+    let mut files = Vec::new();
+    let mut start_file_name = None;
+
+    loop {
+        let response = client
+            .post(&format!(
+                "{}/b2api/v2/b2_list_file_names",
+                authorization_data.api_url
+            ))
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(
+                reqwest::header::AUTHORIZATION,
+                &authorization_data.authorization_token,
+            )
+            .body(
+                serde_json::json!({
+                    "bucketId": bucket_id,
+                    "prefix": prefix,
+                    "startFileName": start_file_name,
+                    "maxFileCount": MAX_FILE_COUNT,
+                })
+                .to_string(),
+            )
+            .send()
+            .await?;
+
+        let data: ListFileNamesData = super::process_response(response).await?;
+        files.extend(data.files);
+
+        match data.next_file_name {
+            Some(next_file_name) => start_file_name = Some(next_file_name),
+            None => break,
+        }
+    }
+
+    Ok(files)
+}
@@ -1,5 +1,5 @@
 use super::authorization::UploadUrlData;
-use crate::file_hosting::FileHostingError;
+use crate::file_hosting::{FileHostingError, SpooledFile};
 use bytes::Bytes;
 use serde::{Deserialize, Serialize};
 
@@ -43,3 +43,26 @@ pub async fn upload_file(
 
     super::process_response(response).await
 }
+
+pub async fn upload_file_streaming(
+    url_data: &UploadUrlData,
+    content_type: &str,
+    file_name: &str,
+    file: &SpooledFile,
+) -> Result<UploadFileData, FileHostingError> {
+    let response = reqwest::Client::new()
+        .post(&url_data.upload_url)
+        .header(
+            reqwest::header::AUTHORIZATION,
+            &url_data.authorization_token,
+        )
+        .header("X-Bz-File-Name", file_name)
+        .header(reqwest::header::CONTENT_TYPE, content_type)
+        .header(reqwest::header::CONTENT_LENGTH, file.length)
+        .header("X-Bz-Content-Sha1", &file.sha1)
+        .body(file.open_async().await?)
+        .send()
+        .await?;
+
+    super::process_response(response).await
+}
@@ -0,0 +1,396 @@
+use super::{
+    file_name_from_url, DeleteFileData, FileHost, FileHostingError, SpoolWriter, SpooledFile,
+    StoredFile, UploadFileData,
+};
+use crate::models::ids::{random_base62, Base62Id};
+use async_trait::async_trait;
+use bytes::Bytes;
+use chrono::Utc;
+use hmac::{Hmac, Mac, NewMac};
+use sha2::Digest;
+use std::io::Write;
+use std::path::{Component, Path, PathBuf};
+use tokio::io::AsyncReadExt;
+
+type HmacSha256 = Hmac<sha2::Sha256>;
+
+/// Stores files in a directory on the local file system, which are served by the `/files` route.
+/// If a signing key is set, files of hidden versions are only served with a signed URL.
+pub struct LocalHost {
+    root: PathBuf,
+    signing_key: Option<String>,
+    signed_url_expiry: chrono::Duration,
+}
+
+impl LocalHost {
+    pub fn new(
+        root: &Path,
+        signing_key: Option<String>,
+        signed_url_expiry: chrono::Duration,
+    ) -> Result<Self, FileHostingError> {
+        std::fs::create_dir_all(root)?;
+
+        Ok(LocalHost {
+            root: root.canonicalize()?,
+            signing_key,
+            signed_url_expiry,
+        })
+    }
+
+    /// Whether files of hidden versions are protected by signed URLs
+    pub fn signs_urls(&self) -> bool {
+        self.signing_key.is_some()
+    }
+
+    /// The path a file is stored at. Names which are absolute or contain `..` are rejected, so a
+    /// file can never be stored outside of the root.
+    pub fn path_of(&self, file_name: &str) -> Result<PathBuf, FileHostingError> {
+        let mut path = self.root.clone();
+
+        for component in Path::new(file_name).components() {
+            match component {
+                Component::Normal(part) => path.push(part),
+                Component::CurDir => {}
+                _ => return Err(FileHostingError::InvalidFilename),
+            }
+        }
+
+        if path == self.root {
+            return Err(FileHostingError::InvalidFilename);
+        }
+
+        Ok(path)
+    }
+
+    /// The path of a stored file to serve it. Symbolic links are followed, but must not lead
+    /// outside of the root.
+    pub fn resolve(&self, file_name: &str) -> Result<PathBuf, FileHostingError> {
+        let path = self.path_of(file_name)?.canonicalize()?;
+
+        if !path.starts_with(&self.root) || !path.is_file() {
+            return Err(FileHostingError::InvalidFilename);
+        }
+
+        Ok(path)
+    }
+
+    /// Creates the directory of a file, checking it is still inside of the root once symbolic
+    /// links are followed
+    fn create_parent(&self, path: &Path) -> Result<(), FileHostingError> {
+        let parent = path.parent().ok_or(FileHostingError::InvalidFilename)?;
+        std::fs::create_dir_all(parent)?;
+
+        if !parent.canonicalize()?.starts_with(&self.root) {
+            return Err(FileHostingError::InvalidFilename);
+        }
+
+        Ok(())
+    }
+
+    fn signature(&self, key: &str, file_name: &str, expires: i64) -> HmacSha256 {
+        // HMAC accepts keys of any length
+        let mut mac = HmacSha256::new_from_slice(key.as_bytes()).unwrap();
+        mac.update(file_name.as_bytes());
+        mac.update(b"\n");
+        mac.update(expires.to_string().as_bytes());
+        mac
+    }
+
+    /// Signs the URL of a stored file on the CDN, so it can be downloaded until the URL expires.
+    /// Returns None if there is no signing key, or the URL is not on the CDN.
+    pub fn signed_url(&self, cdn_url: &str, url: &str) -> Option<String> {
+        let key = self.signing_key.as_ref()?;
+        let file_name = file_name_from_url(cdn_url, url)?;
+
+        let expires = (Utc::now() + self.signed_url_expiry).timestamp();
+        let signature = hex::encode(
+            self.signature(key, &file_name, expires)
+                .finalize()
+                .into_bytes(),
+        );
+
+        Some(format!("{url}?expires={expires}&signature={signature}"))
+    }
+
+    /// Checks the signature of a signed URL, which is only valid until it expires
+    pub fn verify(&self, file_name: &str, expires: i64, signature: &str) -> bool {
+        let key = match &self.signing_key {
+            Some(key) => key,
+            None => return false,
+        };
+
+        if expires < Utc::now().timestamp() {
+            return false;
+        }
+
+        match hex::decode(signature) {
+            Ok(signature) => self
+                .signature(key, file_name, expires)
+                .verify(&signature)
+                .is_ok(),
+            Err(_) => false,
+        }
+    }
+}
+
+// Writes to a temporary file next to the destination which replaces it once it is complete, so a
+// file is never served partially written. This blocks, so it should be run on a blocking thread.
+fn write_atomic(
+    path: &Path,
+    write: impl FnOnce(&mut std::fs::File) -> std::io::Result<()>,
+) -> std::io::Result<()> {
+    let parent = path.parent().unwrap_or_else(|| Path::new("."));
+    let mut file = tempfile::Builder::new()
+        .prefix(".upload-")
+        .tempfile_in(parent)?;
+
+    write(file.as_file_mut())?;
+    file.as_file_mut().flush()?;
+    file.as_file().sync_all()?;
+    file.persist(path).map_err(|e| e.error)?;
+
+    Ok(())
+}
+
+async fn write_atomic_async(
+    path: PathBuf,
+    write: impl FnOnce(&mut std::fs::File) -> std::io::Result<()> + Send + 'static,
+) -> Result<(), FileHostingError> {
+    tokio::task::spawn_blocking(move || write_atomic(&path, write))
+        .await
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))??;
+
+    Ok(())
+}
+
+fn new_file_id() -> String {
+    Base62Id(random_base62(11)).to_string()
+}
+
+#[async_trait]
+impl FileHost for LocalHost {
+    async fn upload_file(
+        &self,
+        content_type: &str,
+        file_name: &str,
+        file_bytes: Bytes,
+    ) -> Result<UploadFileData, FileHostingError> {
+        let path = self.path_of(file_name)?;
+        self.create_parent(&path)?;
+
+        let content_sha1 = sha1::Sha1::from(&file_bytes).hexdigest();
+        let content_sha512 = format!("{:x}", sha2::Sha512::digest(&file_bytes));
+        let content_length = file_bytes.len() as u32;
+
+        write_atomic_async(path, move |file| file.write_all(&file_bytes)).await?;
+
+        Ok(UploadFileData {
+            file_id: new_file_id(),
+            file_name: file_name.to_string(),
+            content_length,
+            content_sha512,
+            content_sha1,
+            content_md5: None,
+            content_type: content_type.to_string(),
+            upload_timestamp: Utc::now().timestamp() as u64,
+        })
+    }
+
+    async fn upload_file_streaming(
+        &self,
+        content_type: &str,
+        file_name: &str,
+        file: &SpooledFile,
+    ) -> Result<UploadFileData, FileHostingError> {
+        let path = self.path_of(file_name)?;
+        self.create_parent(&path)?;
+
+        let mut source = file.open()?;
+        write_atomic_async(path, move |file| {
+            std::io::copy(&mut source, file)?;
+            Ok(())
+        })
+        .await?;
+
+        Ok(UploadFileData {
+            file_id: new_file_id(),
+            file_name: file_name.to_string(),
+            content_length: file.length as u32,
+            content_sha512: file.sha512.clone(),
+            content_sha1: file.sha1.clone(),
+            content_md5: None,
+            content_type: content_type.to_string(),
+            upload_timestamp: Utc::now().timestamp() as u64,
+        })
+    }
+
+    async fn delete_file_version(
+        &self,
+        file_id: &str,
+        file_name: &str,
+    ) -> Result<DeleteFileData, FileHostingError> {
+        let path = self.path_of(file_name)?;
+        tokio::fs::remove_file(&path).await?;
+
+        // Directories left empty are removed, stopping at the first one which still has files
+        let mut parent = path.parent();
+        while let Some(dir) = parent {
+            if dir == self.root || tokio::fs::remove_dir(dir).await.is_err() {
+                break;
+            }
+            parent = dir.parent();
+        }
+
+        Ok(DeleteFileData {
+            file_id: file_id.to_string(),
+            file_name: file_name.to_string(),
+        })
+    }
+
+    async fn download_file(
+        &self,
+        _file_id: Option<&str>,
+        file_name: &str,
+    ) -> Result<SpooledFile, FileHostingError> {
+        let mut file = tokio::fs::File::open(self.resolve(file_name)?).await?;
+        let mut writer = SpoolWriter::new()?;
+
+        let mut buffer = vec![0; 64 * 1024];
+        loop {
+            let read = file.read(&mut buffer).await?;
+            if read == 0 {
+                break;
+            }
+            writer.write(&buffer[..read]).await?;
+        }
+
+        Ok(writer.finish().await?)
+    }
+
+    async fn list_files(&self, prefix: &str) -> Result<Vec<StoredFile>, FileHostingError> {
+        let root = self.root.clone();
+        let prefix = prefix.to_string();
+
+        let files = tokio::task::spawn_blocking(move || list_dir(&root, &root, &prefix))
+            .await
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))??;
+
+        Ok(files)
+    }
+}
+
+// Lists the files below a directory whose names relative to the root start with the prefix.
+// Temporary files of uploads in progress are skipped. This blocks, so it should be run on a
+// blocking thread.
+fn list_dir(root: &Path, dir: &Path, prefix: &str) -> std::io::Result<Vec<StoredFile>> {
+    let mut files = Vec::new();
+
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+
+        let file_name = match path.strip_prefix(root).ok().and_then(|x| x.to_str()) {
+            Some(file_name) => file_name.replace(std::path::MAIN_SEPARATOR, "/"),
+            None => continue,
+        };
+
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            // Directories which can't contain files with the prefix are not walked
+            if prefix.starts_with(&format!("{file_name}/")) || file_name.starts_with(prefix) {
+                files.extend(list_dir(root, &path, prefix)?);
+            }
+        } else if file_type.is_file()
+            && file_name.starts_with(prefix)
+            && !entry.file_name().to_string_lossy().starts_with(".upload-")
+        {
+            files.push(StoredFile {
+                file_id: String::new(),
+                file_name,
+            });
+        }
+    }
+
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn local_host(signing_key: Option<&str>) -> (tempfile::TempDir, LocalHost) {
+        let dir = tempfile::tempdir().unwrap();
+        let host = LocalHost::new(
+            dir.path(),
+            signing_key.map(String::from),
+            chrono::Duration::hours(1),
+        )
+        .unwrap();
+        (dir, host)
+    }
+
+    #[test]
+    fn names_cannot_escape_the_root() {
+        let (_dir, host) = local_host(None);
+
+        assert!(host.path_of("data/abc/icon.png").is_ok());
+        assert!(host.path_of("./data/icon.png").is_ok());
+        assert!(host.path_of("../etc/passwd").is_err());
+        assert!(host.path_of("data/../../etc/passwd").is_err());
+        assert!(host.path_of("/etc/passwd").is_err());
+        assert!(host.path_of("").is_err());
+    }
+
+    #[actix_rt::test]
+    async fn files_are_stored_and_deleted() {
+        let (_dir, host) = local_host(None);
+
+        let uploaded = host
+            .upload_file("text/plain", "data/abc/file.txt", Bytes::from("hello"))
+            .await
+            .unwrap();
+
+        let path = host.resolve("data/abc/file.txt").unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"hello");
+
+        let downloaded = host
+            .download_file(Some(&uploaded.file_id), "data/abc/file.txt")
+            .await
+            .unwrap();
+        assert_eq!(downloaded.sha1, uploaded.content_sha1);
+
+        let listed = host.list_files("data/").await.unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].file_name, "data/abc/file.txt");
+        assert!(host.list_files("data/xyz/").await.unwrap().is_empty());
+
+        host.delete_file_version(&uploaded.file_id, "data/abc/file.txt")
+            .await
+            .unwrap();
+        assert!(!path.exists());
+        assert!(!path.parent().unwrap().exists());
+    }
+
+    #[test]
+    fn signed_urls_are_verified() {
+        let (_dir, host) = local_host(Some("secret"));
+
+        let url = host
+            .signed_url(
+                "https://cdn.example.com",
+                "https://cdn.example.com/data/abc/my%20file.jar",
+            )
+            .unwrap();
+        let query = url.split_once('?').unwrap().1;
+        let (expires, signature) = query.split_once('&').unwrap();
+        let expires = expires.trim_start_matches("expires=").parse().unwrap();
+        let signature = signature.trim_start_matches("signature=");
+
+        assert!(host.verify("data/abc/my file.jar", expires, signature));
+        assert!(!host.verify("data/abc/other.jar", expires, signature));
+        assert!(!host.verify("data/abc/my file.jar", expires + 1, signature));
+
+        let (_other_dir, unsigned) = local_host(None);
+        assert!(!unsigned.verify("data/abc/my file.jar", expires, signature));
+    }
+}
@@ -2,13 +2,17 @@ use async_trait::async_trait;
 use thiserror::Error;
 
 mod backblaze;
-mod mock;
+mod local;
+mod replicated;
 mod s3_host;
+mod spool;
 
 pub use backblaze::BackblazeHost;
 use bytes::Bytes;
-pub use mock::MockHost;
+pub use local::LocalHost;
+pub use replicated::{Replica, ReplicatedHost};
 pub use s3_host::S3Host;
+pub use spool::{hash_file, open_session_at, session_path, sessions_dir, SpoolWriter, SpooledFile};
 
 #[derive(Error, Debug)]
 pub enum FileHostingError {
@@ -22,6 +26,12 @@ pub enum FileHostingError {
     FileSystemError(#[from] std::io::Error),
     #[error("Invalid Filename")]
     InvalidFilename,
+    #[error("The file is not stored on any other storage backend")]
+    NotStored,
+    #[error("The file was not found on the file host")]
+    NotFound,
+    #[error("Database error while recording file placements: {0}")]
+    Database(#[from] crate::database::models::DatabaseError),
 }
 
 #[derive(Debug, Clone)]
@@ -42,6 +52,20 @@ pub struct DeleteFileData {
     pub file_name: String,
 }
 
+/// A file stored by a file host, as listed by `list_files`
+#[derive(Debug, Clone)]
+pub struct StoredFile {
+    pub file_id: String,
+    pub file_name: String,
+}
+
+/// The name of a stored file from its URL on the CDN, or None if the URL is not on the CDN
+pub fn file_name_from_url(cdn_url: &str, url: &str) -> Option<String> {
+    let path = url.strip_prefix(cdn_url)?.strip_prefix('/')?;
+
+    urlencoding::decode(path).ok().map(|x| x.into_owned())
+}
+
 #[async_trait]
 pub trait FileHost {
     async fn upload_file(
@@ -51,9 +75,28 @@ pub trait FileHost {
         file_bytes: Bytes,
     ) -> Result<UploadFileData, FileHostingError>;
 
+    /// Uploads a spooled file from disk, without reading it into memory
+    async fn upload_file_streaming(
+        &self,
+        content_type: &str,
+        file_name: &str,
+        file: &SpooledFile,
+    ) -> Result<UploadFileData, FileHostingError>;
+
     async fn delete_file_version(
         &self,
         file_id: &str,
         file_name: &str,
     ) -> Result<DeleteFileData, FileHostingError>;
+
+    /// Downloads a stored file to a spooled file, such as to copy it to another file host. Files
+    /// whose ID is not known, such as ones uploaded before their ID was kept, are found by name.
+    async fn download_file(
+        &self,
+        file_id: Option<&str>,
+        file_name: &str,
+    ) -> Result<SpooledFile, FileHostingError>;
+
+    /// Lists the stored files whose names start with the prefix
+    async fn list_files(&self, prefix: &str) -> Result<Vec<StoredFile>, FileHostingError>;
 }
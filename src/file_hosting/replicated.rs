@@ -0,0 +1,529 @@
+use super::{
+    file_name_from_url, DeleteFileData, FileHost, FileHostingError, SpooledFile, StoredFile,
+    UploadFileData,
+};
+use crate::database::models::placement_item::{Placement, PlacementStatus};
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::future::join_all;
+use log::{info, warn};
+use sqlx::PgPool;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+// Uploads are retried once before the file is left to be repaired by the scheduler
+const UPLOAD_ATTEMPTS: usize = 2;
+// How many pending placements are repaired each time the repair task runs
+const REPAIR_BATCH: i64 = 100;
+// The file uploaded to check whether an unhealthy backend recovered
+const HEALTH_CHECK_FILE: &str = "health-check";
+
+/// A storage backend files are replicated to
+pub struct Replica {
+    /// The name of the backend, such as `s3`, which placements are recorded by
+    pub name: String,
+    pub host: Arc<dyn FileHost + Send + Sync>,
+    /// The URL files stored on the backend are served from
+    pub cdn_url: String,
+    healthy: AtomicBool,
+}
+
+impl Replica {
+    pub fn new(name: String, host: Arc<dyn FileHost + Send + Sync>, cdn_url: String) -> Self {
+        Replica {
+            name,
+            host,
+            cdn_url,
+            healthy: AtomicBool::new(true),
+        }
+    }
+
+    pub fn is_healthy(&self) -> bool {
+        self.healthy.load(Ordering::Relaxed)
+    }
+
+    fn set_healthy(&self, healthy: bool) {
+        if self.healthy.swap(healthy, Ordering::Relaxed) != healthy {
+            if healthy {
+                info!("Storage backend {} recovered", self.name);
+            } else {
+                warn!("Storage backend {} is unhealthy", self.name);
+            }
+        }
+    }
+}
+
+/// Writes files to a primary backend and its replicas, recording where each file is stored. An
+/// upload succeeds as long as one backend stored the file, and the backends which missed it are
+/// repaired from the scheduler. The URLs of uploaded files point to the primary.
+pub struct ReplicatedHost {
+    pool: PgPool,
+    /// The primary backend comes first
+    replicas: Vec<Replica>,
+}
+
+impl ReplicatedHost {
+    pub fn new(pool: PgPool, primary: Replica, replicas: Vec<Replica>) -> Self {
+        ReplicatedHost {
+            pool,
+            replicas: std::iter::once(primary).chain(replicas).collect(),
+        }
+    }
+
+    fn replica(&self, name: &str) -> Option<&Replica> {
+        self.replicas.iter().find(|x| x.name == name)
+    }
+
+    // Records the placement of a file on each backend. If no backend stored the file, nothing is
+    // recorded as there is nothing to repair it from.
+    async fn record_uploads(
+        &self,
+        content_type: &str,
+        file_name: &str,
+        results: Vec<Result<UploadFileData, FileHostingError>>,
+    ) -> Result<UploadFileData, FileHostingError> {
+        if results.iter().all(|x| x.is_err()) {
+            for replica in &self.replicas {
+                replica.set_healthy(false);
+            }
+
+            return Err(results.into_iter().find_map(|x| x.err()).unwrap());
+        }
+
+        let mut uploaded = None;
+        for (replica, result) in self.replicas.iter().zip(results) {
+            let (host_file_id, status) = match result {
+                Ok(data) => {
+                    replica.set_healthy(true);
+                    let host_file_id = data.file_id.clone();
+                    if uploaded.is_none() {
+                        uploaded = Some(data);
+                    }
+
+                    (Some(host_file_id), PlacementStatus::Stored)
+                }
+                Err(e) => {
+                    warn!(
+                        "Uploading {} to storage backend {} failed: {}",
+                        file_name, replica.name, e
+                    );
+                    replica.set_healthy(false);
+
+                    (None, PlacementStatus::Missing)
+                }
+            };
+
+            Placement {
+                path: file_name.to_string(),
+                backend: replica.name.clone(),
+                host_file_id,
+                content_type: content_type.to_string(),
+                status,
+            }
+            .upsert(&self.pool)
+            .await?;
+        }
+
+        // At least one upload succeeded
+        Ok(uploaded.unwrap())
+    }
+
+    /// The URL to download a file from. If the primary backend is unhealthy, this is the URL of
+    /// the file on a healthy replica which stores it.
+    pub async fn download_url(&self, url: &str) -> Result<String, FileHostingError> {
+        let primary = &self.replicas[0];
+
+        if primary.is_healthy() {
+            return Ok(url.to_string());
+        }
+
+        let file_name = match file_name_from_url(&primary.cdn_url, url) {
+            Some(file_name) => file_name,
+            None => return Ok(url.to_string()),
+        };
+        let placements = Placement::get_for_path(&file_name, &self.pool).await?;
+
+        let replica = self.replicas.iter().skip(1).find(|replica| {
+            replica.is_healthy()
+                && placements
+                    .iter()
+                    .any(|x| x.backend == replica.name && x.status == PlacementStatus::Stored)
+        });
+
+        Ok(match replica {
+            Some(replica) => format!("{}{}", replica.cdn_url, &url[primary.cdn_url.len()..]),
+            None => url.to_string(),
+        })
+    }
+
+    /// Checks the health of every backend by uploading a small file to it and deleting it again,
+    /// so backends which went down are noticed even while no files are uploaded, and unhealthy
+    /// ones once they recover. The file is deleted so versions of it don't pile up on backends
+    /// which keep every version, such as Backblaze.
+    pub async fn check_health(&self) {
+        join_all(self.replicas.iter().map(|replica| async move {
+            let result = async {
+                let data = replica
+                    .host
+                    .upload_file("text/plain", HEALTH_CHECK_FILE, Bytes::from_static(b"ok"))
+                    .await?;
+                replica
+                    .host
+                    .delete_file_version(&data.file_id, &data.file_name)
+                    .await
+            }
+            .await;
+
+            replica.set_healthy(result.is_ok());
+        }))
+        .await;
+    }
+
+    /// Copies files to the backends which missed them, and retries deleting files which could not
+    /// be deleted from a backend
+    pub async fn repair(&self) -> Result<(), FileHostingError> {
+        for placement in Placement::get_pending(REPAIR_BATCH, &self.pool).await? {
+            let replica = match self.replica(&placement.backend) {
+                Some(replica) => replica,
+                // The backend was removed from the configuration
+                None => {
+                    placement.remove(&self.pool).await?;
+                    continue;
+                }
+            };
+
+            match placement.status {
+                PlacementStatus::Missing => {
+                    let result = async {
+                        let file = self
+                            .download_from_others(&placement.path, &replica.name)
+                            .await?;
+                        replica
+                            .host
+                            .upload_file_streaming(&placement.content_type, &placement.path, &file)
+                            .await
+                    }
+                    .await;
+
+                    match result {
+                        Ok(data) => {
+                            replica.set_healthy(true);
+
+                            Placement {
+                                host_file_id: Some(data.file_id),
+                                status: PlacementStatus::Stored,
+                                ..placement
+                            }
+                            .upsert(&self.pool)
+                            .await?;
+                        }
+                        Err(e) => {
+                            warn!(
+                                "Repairing {} on storage backend {} failed: {}",
+                                placement.path, replica.name, e
+                            );
+                            placement.touch(&self.pool).await?;
+                        }
+                    }
+                }
+                PlacementStatus::Deleting => {
+                    let result = replica
+                        .host
+                        .delete_file_version(
+                            placement.host_file_id.as_deref().unwrap_or_default(),
+                            &placement.path,
+                        )
+                        .await;
+
+                    match result {
+                        Ok(_) => placement.remove(&self.pool).await?,
+                        Err(e) => {
+                            warn!(
+                                "Deleting {} from storage backend {} failed: {}",
+                                placement.path, replica.name, e
+                            );
+                            placement.touch(&self.pool).await?;
+                        }
+                    }
+                }
+                PlacementStatus::Stored => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    // Downloads a file from a backend other than the given one which stores it, preferring
+    // healthy backends. Fails with `NotStored` if no other backend stores it.
+    async fn download_from_others(
+        &self,
+        file_name: &str,
+        except: &str,
+    ) -> Result<SpooledFile, FileHostingError> {
+        let mut placements = Placement::get_for_path(file_name, &self.pool)
+            .await?
+            .into_iter()
+            .filter(|x| x.status == PlacementStatus::Stored && x.backend != except)
+            .filter_map(|x| Some((self.replica(&x.backend)?, x.host_file_id?)))
+            .collect::<Vec<_>>();
+        placements.sort_by_key(|(replica, _)| !replica.is_healthy());
+
+        let mut error = FileHostingError::NotStored;
+        for (replica, host_file_id) in placements {
+            match replica
+                .host
+                .download_file(Some(&host_file_id), file_name)
+                .await
+            {
+                Ok(file) => return Ok(file),
+                Err(e) => {
+                    replica.set_healthy(false);
+                    error = e;
+                }
+            }
+        }
+
+        Err(error)
+    }
+}
+
+#[async_trait]
+impl FileHost for ReplicatedHost {
+    async fn upload_file(
+        &self,
+        content_type: &str,
+        file_name: &str,
+        file_bytes: Bytes,
+    ) -> Result<UploadFileData, FileHostingError> {
+        let results = join_all(self.replicas.iter().map(|replica| {
+            let file_bytes = file_bytes.clone();
+
+            async move {
+                let mut attempts = 1;
+                loop {
+                    let result = replica
+                        .host
+                        .upload_file(content_type, file_name, file_bytes.clone())
+                        .await;
+                    if result.is_ok() || attempts == UPLOAD_ATTEMPTS {
+                        break result;
+                    }
+                    attempts += 1;
+                }
+            }
+        }))
+        .await;
+
+        self.record_uploads(content_type, file_name, results).await
+    }
+
+    async fn upload_file_streaming(
+        &self,
+        content_type: &str,
+        file_name: &str,
+        file: &SpooledFile,
+    ) -> Result<UploadFileData, FileHostingError> {
+        let results = join_all(self.replicas.iter().map(|replica| async move {
+            let mut attempts = 1;
+            loop {
+                let result = replica
+                    .host
+                    .upload_file_streaming(content_type, file_name, file)
+                    .await;
+                if result.is_ok() || attempts == UPLOAD_ATTEMPTS {
+                    break result;
+                }
+                attempts += 1;
+            }
+        }))
+        .await;
+
+        self.record_uploads(content_type, file_name, results).await
+    }
+
+    async fn delete_file_version(
+        &self,
+        file_id: &str,
+        file_name: &str,
+    ) -> Result<DeleteFileData, FileHostingError> {
+        let placements = Placement::get_for_path(file_name, &self.pool).await?;
+
+        // Files uploaded before replication was set up are only stored on the primary
+        if placements.is_empty() {
+            return self.replicas[0]
+                .host
+                .delete_file_version(file_id, file_name)
+                .await;
+        }
+
+        for placement in placements {
+            let (replica, host_file_id) =
+                match (self.replica(&placement.backend), &placement.host_file_id) {
+                    (Some(replica), Some(host_file_id)) => (replica, host_file_id),
+                    // The file was never stored on the backend, or the backend was removed
+                    _ => {
+                        placement.remove(&self.pool).await?;
+                        continue;
+                    }
+                };
+
+            match replica
+                .host
+                .delete_file_version(host_file_id, file_name)
+                .await
+            {
+                Ok(_) => placement.remove(&self.pool).await?,
+                Err(e) => {
+                    warn!(
+                        "Deleting {} from storage backend {} failed: {}",
+                        file_name, replica.name, e
+                    );
+                    replica.set_healthy(false);
+
+                    Placement {
+                        status: PlacementStatus::Deleting,
+                        ..placement
+                    }
+                    .upsert(&self.pool)
+                    .await?;
+                }
+            }
+        }
+
+        Ok(DeleteFileData {
+            file_id: file_id.to_string(),
+            file_name: file_name.to_string(),
+        })
+    }
+
+    async fn download_file(
+        &self,
+        file_id: Option<&str>,
+        file_name: &str,
+    ) -> Result<SpooledFile, FileHostingError> {
+        match self.download_from_others(file_name, "").await {
+            // Files uploaded before replication was set up are only stored on the primary
+            Err(FileHostingError::NotStored) => {
+                self.replicas[0]
+                    .host
+                    .download_file(file_id, file_name)
+                    .await
+            }
+            result => result,
+        }
+    }
+
+    async fn list_files(&self, prefix: &str) -> Result<Vec<StoredFile>, FileHostingError> {
+        self.replicas[0].host.list_files(prefix).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_hosting::LocalHost;
+
+    fn local_replica(name: &str) -> (tempfile::TempDir, Replica) {
+        let dir = tempfile::tempdir().unwrap();
+        let host = LocalHost::new(dir.path(), None, chrono::Duration::hours(1)).unwrap();
+        let replica = Replica::new(
+            name.to_string(),
+            Arc::new(host),
+            format!("https://{name}.example.com"),
+        );
+        (dir, replica)
+    }
+
+    #[sqlx::test]
+    #[ignore = "needs a Postgres database at DATABASE_URL"]
+    async fn downloads_fail_over_to_replicas(pool: PgPool) {
+        let (primary_dir, primary) = local_replica("primary");
+        let (_replica_dir, replica) = local_replica("replica");
+        let host = ReplicatedHost::new(pool, primary, vec![replica]);
+
+        let uploaded = host
+            .upload_file("text/plain", "data/abc/file.txt", Bytes::from("hello"))
+            .await
+            .unwrap();
+
+        // The primary lost the file, so it is downloaded from the replica
+        std::fs::remove_file(primary_dir.path().join("data/abc/file.txt")).unwrap();
+        let downloaded = host
+            .download_file(Some(&uploaded.file_id), "data/abc/file.txt")
+            .await
+            .unwrap();
+        assert_eq!(std::fs::read(downloaded.path()).unwrap(), b"hello");
+
+        // Downloads are redirected to the replica while the primary is unhealthy
+        host.replicas[0].set_healthy(false);
+        let url = "https://primary.example.com/data/abc/file.txt";
+        assert_eq!(
+            host.download_url(url).await.unwrap(),
+            "https://replica.example.com/data/abc/file.txt"
+        );
+    }
+
+    #[sqlx::test]
+    #[ignore = "needs a Postgres database at DATABASE_URL"]
+    async fn missing_files_are_repaired(pool: PgPool) {
+        let (_primary_dir, primary) = local_replica("primary");
+        let (replica_dir, replica) = local_replica("replica");
+        let host = ReplicatedHost::new(pool.clone(), primary, vec![replica]);
+
+        // The replica can't store files in `data` while it is a file
+        let blocker = replica_dir.path().join("data");
+        std::fs::write(&blocker, b"").unwrap();
+
+        host.upload_file("text/plain", "data/abc/file.txt", Bytes::from("hello"))
+            .await
+            .unwrap();
+        assert!(!host.replicas[1].is_healthy());
+
+        let placements = Placement::get_for_path("data/abc/file.txt", &pool)
+            .await
+            .unwrap();
+        assert!(placements
+            .iter()
+            .any(|x| x.backend == "replica" && x.status == PlacementStatus::Missing));
+
+        std::fs::remove_file(&blocker).unwrap();
+        host.repair().await.unwrap();
+
+        assert!(host.replicas[1].is_healthy());
+        assert_eq!(
+            std::fs::read(replica_dir.path().join("data/abc/file.txt")).unwrap(),
+            b"hello"
+        );
+
+        let placements = Placement::get_for_path("data/abc/file.txt", &pool)
+            .await
+            .unwrap();
+        assert_eq!(placements.len(), 2);
+        assert!(placements
+            .iter()
+            .all(|x| x.status == PlacementStatus::Stored));
+    }
+
+    #[sqlx::test]
+    #[ignore = "needs a Postgres database at DATABASE_URL"]
+    async fn health_checks_probe_every_backend(pool: PgPool) {
+        let (primary_dir, primary) = local_replica("primary");
+        let (_replica_dir, replica) = local_replica("replica");
+        let host = ReplicatedHost::new(pool, primary, vec![replica]);
+
+        // The health check file can't replace a directory
+        let blocker = primary_dir.path().join(HEALTH_CHECK_FILE);
+        std::fs::create_dir(&blocker).unwrap();
+
+        host.check_health().await;
+        assert!(!host.replicas[0].is_healthy());
+        assert!(host.replicas[1].is_healthy());
+
+        std::fs::remove_dir(&blocker).unwrap();
+        host.check_health().await;
+        assert!(host.replicas[0].is_healthy());
+
+        // The health check file is not left behind
+        assert!(!blocker.exists());
+    }
+}
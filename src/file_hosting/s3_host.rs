@@ -1,4 +1,7 @@
-use crate::file_hosting::{DeleteFileData, FileHost, FileHostingError, UploadFileData};
+use crate::file_hosting::{
+    DeleteFileData, FileHost, FileHostingError, SpoolWriter, SpooledFile, StoredFile,
+    UploadFileData,
+};
 use async_trait::async_trait;
 use bytes::Bytes;
 use chrono::Utc;
@@ -73,6 +76,40 @@ impl FileHost for S3Host {
         })
     }
 
+    async fn upload_file_streaming(
+        &self,
+        content_type: &str,
+        file_name: &str,
+        file: &SpooledFile,
+    ) -> Result<UploadFileData, FileHostingError> {
+        let mut reader = file.open_async().await?;
+
+        let status = self
+            .bucket
+            .put_object_stream_with_content_type(&mut reader, format!("/{file_name}"), content_type)
+            .await
+            .map_err(|_| {
+                FileHostingError::S3Error("Error while uploading file to S3".to_string())
+            })?;
+
+        if status != 200 {
+            return Err(FileHostingError::S3Error(format!(
+                "S3 responded with status {status} while uploading file"
+            )));
+        }
+
+        Ok(UploadFileData {
+            file_id: file_name.to_string(),
+            file_name: file_name.to_string(),
+            content_length: file.length as u32,
+            content_sha512: file.sha512.clone(),
+            content_sha1: file.sha1.clone(),
+            content_md5: None,
+            content_type: content_type.to_string(),
+            upload_timestamp: Utc::now().timestamp() as u64,
+        })
+    }
+
     async fn delete_file_version(
         &self,
         file_id: &str,
@@ -90,4 +127,47 @@ impl FileHost for S3Host {
             file_name: file_name.to_string(),
         })
     }
+
+    async fn download_file(
+        &self,
+        _file_id: Option<&str>,
+        file_name: &str,
+    ) -> Result<SpooledFile, FileHostingError> {
+        // The object is streamed to the spooled file, so large files are not held in memory
+        let mut writer = SpoolWriter::new()?;
+        let status = self
+            .bucket
+            .get_object_stream(format!("/{file_name}"), &mut writer)
+            .await
+            .map_err(|_| {
+                FileHostingError::S3Error("Error while downloading file from S3".to_string())
+            })?;
+
+        if status != 200 {
+            return Err(FileHostingError::S3Error(format!(
+                "S3 responded with status {status} while downloading file"
+            )));
+        }
+
+        Ok(writer.finish().await?)
+    }
+
+    async fn list_files(&self, prefix: &str) -> Result<Vec<StoredFile>, FileHostingError> {
+        let results = self
+            .bucket
+            .list(prefix.to_string(), None)
+            .await
+            .map_err(|_| {
+                FileHostingError::S3Error("Error while listing files in S3".to_string())
+            })?;
+
+        Ok(results
+            .into_iter()
+            .flat_map(|x| x.contents)
+            .map(|x| StoredFile {
+                file_id: String::new(),
+                file_name: x.key,
+            })
+            .collect())
+    }
 }
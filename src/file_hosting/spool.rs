@@ -0,0 +1,226 @@
+use sha2::Digest;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tempfile::TempPath;
+use tokio::io::{AsyncSeekExt, AsyncWrite, AsyncWriteExt};
+
+/// An uploaded file, written to a temporary file as it is received so it does not have to be kept
+/// in memory. Temporary files are deleted once the last reference to them is dropped.
+pub struct SpooledFile {
+    path: SpoolPath,
+    pub length: u64,
+    pub sha1: String,
+    pub sha512: String,
+}
+
+enum SpoolPath {
+    Temporary(TempPath),
+    /// The data of an upload session, which is deleted along with the session instead
+    Session(PathBuf),
+}
+
+impl SpooledFile {
+    /// The finalized data of an upload session, whose hashes were checked when it was finalized
+    pub fn from_session(path: PathBuf, length: u64, sha1: String, sha512: String) -> Self {
+        SpooledFile {
+            path: SpoolPath::Session(path),
+            length,
+            sha1,
+            sha512,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        match &self.path {
+            SpoolPath::Temporary(path) => path,
+            SpoolPath::Session(path) => path,
+        }
+    }
+
+    /// Opens the file for reading, such as for the validators
+    pub fn open(&self) -> std::io::Result<std::fs::File> {
+        std::fs::File::open(self.path())
+    }
+
+    pub async fn open_async(&self) -> std::io::Result<tokio::fs::File> {
+        tokio::fs::File::open(self.path()).await
+    }
+}
+
+/// Writes the chunks of an upload to a temporary file, hashing them on the way
+pub struct SpoolWriter {
+    file: tokio::fs::File,
+    path: TempPath,
+    length: u64,
+    sha1: sha1::Sha1,
+    sha512: sha2::Sha512,
+}
+
+impl SpoolWriter {
+    pub fn new() -> std::io::Result<Self> {
+        let (file, path) = tempfile::Builder::new()
+            .prefix("upload-")
+            .tempfile_in(spool_dir())?
+            .into_parts();
+
+        Ok(SpoolWriter {
+            file: tokio::fs::File::from_std(file),
+            path,
+            length: 0,
+            sha1: sha1::Sha1::new(),
+            sha512: sha2::Sha512::new(),
+        })
+    }
+
+    /// How many bytes were written so far
+    pub fn written(&self) -> u64 {
+        self.length
+    }
+
+    pub async fn write(&mut self, chunk: &[u8]) -> std::io::Result<()> {
+        self.file.write_all(chunk).await?;
+        self.length += chunk.len() as u64;
+        self.sha1.update(chunk);
+        self.sha512.update(chunk);
+
+        Ok(())
+    }
+
+    pub async fn finish(mut self) -> std::io::Result<SpooledFile> {
+        self.file.flush().await?;
+
+        Ok(SpooledFile {
+            path: SpoolPath::Temporary(self.path),
+            length: self.length,
+            sha1: self.sha1.digest().to_string(),
+            sha512: format!("{:x}", self.sha512.finalize()),
+        })
+    }
+}
+
+// Lets file hosts stream downloads to a spooled file, hashing them on the way like `write`
+impl AsyncWrite for SpoolWriter {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        let this = &mut *self;
+        let written = futures::ready!(Pin::new(&mut this.file).poll_write(cx, buf))?;
+
+        this.length += written as u64;
+        this.sha1.update(&buf[..written]);
+        this.sha512.update(&buf[..written]);
+
+        Poll::Ready(Ok(written))
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.file).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.file).poll_shutdown(cx)
+    }
+}
+
+/// Where the data of upload sessions is stored, in files named by their IDs. The chunks of a session
+/// may be received by any instance, so with several instances `UPLOAD_SESSION_PATH` must be set to
+/// a volume they all share. Defaults to `sessions` in the spool directory.
+pub fn sessions_dir() -> PathBuf {
+    dotenvy::var("UPLOAD_SESSION_PATH")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| spool_dir().join("sessions"))
+}
+
+pub fn session_path(id: i64) -> PathBuf {
+    sessions_dir().join(id.to_string())
+}
+
+/// Opens the data of an upload session to write a chunk at the offset, discarding anything after it
+pub async fn open_session_at(path: &Path, offset: u64) -> std::io::Result<tokio::fs::File> {
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+
+    let mut file = tokio::fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(path)
+        .await?;
+    file.set_len(offset).await?;
+    file.seek(std::io::SeekFrom::Start(offset)).await?;
+
+    Ok(file)
+}
+
+/// Computes the SHA1 and SHA512 hashes of a file, reading it in chunks. This blocks, so it should
+/// be run on a blocking thread.
+pub fn hash_file(path: &Path) -> std::io::Result<(String, String)> {
+    let mut file = std::fs::File::open(path)?;
+    let mut sha1 = sha1::Sha1::new();
+    let mut sha512 = sha2::Sha512::new();
+
+    let mut buffer = vec![0; 64 * 1024];
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        sha1.update(&buffer[..read]);
+        sha512.update(&buffer[..read]);
+    }
+
+    Ok((
+        sha1.digest().to_string(),
+        format!("{:x}", sha512.finalize()),
+    ))
+}
+
+// Uploads are spooled to `SPOOL_PATH` if set, which should have room for several of the largest
+// uploads at once
+fn spool_dir() -> PathBuf {
+    dotenvy::var("SPOOL_PATH")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| std::env::temp_dir())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[actix_rt::test]
+    async fn chunks_are_hashed_and_spooled() {
+        let mut writer = SpoolWriter::new().unwrap();
+        writer.write(b"hello ").await.unwrap();
+        writer.write(b"world").await.unwrap();
+        let file = writer.finish().await.unwrap();
+
+        assert_eq!(file.length, 11);
+        assert_eq!(file.sha1, sha1::Sha1::from("hello world").hexdigest());
+        assert_eq!(
+            file.sha512,
+            format!("{:x}", sha2::Sha512::digest(b"hello world"))
+        );
+        assert_eq!(std::fs::read(file.path()).unwrap(), b"hello world");
+
+        let path = file.path().to_path_buf();
+        drop(file);
+        assert!(!path.exists());
+    }
+
+    #[actix_rt::test]
+    async fn streamed_downloads_are_hashed() {
+        let mut writer = SpoolWriter::new().unwrap();
+        let mut source: &[u8] = b"hello world";
+        tokio::io::copy(&mut source, &mut writer).await.unwrap();
+        let file = writer.finish().await.unwrap();
+
+        assert_eq!(file.length, 11);
+        assert_eq!(file.sha1, sha1::Sha1::from("hello world").hexdigest());
+        assert_eq!(std::fs::read(file.path()).unwrap(), b"hello world");
+    }
+}
@@ -0,0 +1,251 @@
+use super::{IdentityProvider, IdentityUser};
+use crate::database::models::{generate_local_session_id, LocalSessionId};
+use crate::models::ids::base62_impl::{parse_base62, to_base62};
+use crate::models::ids::random_base62;
+use crate::util::auth::AuthenticationError;
+use actix_web::cookie::{time, Cookie, SameSite};
+use argon2::password_hash::rand_core::OsRng;
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use async_trait::async_trait;
+use chrono::{Duration, Utc};
+use hmac::{Hmac, Mac, NewMac};
+use sqlx::PgPool;
+
+pub const SESSION_COOKIE: &str = "labrinth_session";
+const SESSION_EXPIRY_DAYS: i64 = 30;
+
+type HmacSha256 = Hmac<sha2::Sha256>;
+
+/// Authenticates users with accounts and sessions stored in Postgres, so that development and
+/// tests don't need a Minos/Kratos deployment. Sessions are identified by a cookie signed with
+/// the provider's secret.
+pub struct LocalProvider {
+    pool: PgPool,
+    secret: String,
+}
+
+impl LocalProvider {
+    pub fn new(pool: PgPool, secret: String) -> Self {
+        LocalProvider { pool, secret }
+    }
+
+    // Creates a new account, returning the identity to insert the user with
+    pub async fn register(
+        &self,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+        username: String,
+        email: String,
+        name: Option<String>,
+        password: &str,
+    ) -> Result<IdentityUser, AuthenticationError> {
+        let salt = SaltString::generate(&mut OsRng);
+        let password_hash = Argon2::default()
+            .hash_password(password.as_bytes(), &salt)
+            .map_err(|_| AuthenticationError::PasswordHashing)?
+            .to_string();
+
+        let id = format!("local-{}", to_base62(random_base62(11)));
+
+        sqlx::query!(
+            "
+            INSERT INTO local_identities (id, email, username, name, password_hash)
+            VALUES ($1, $2, $3, $4, $5)
+            ",
+            id,
+            email,
+            username,
+            name,
+            password_hash
+        )
+        .execute(&mut *transaction)
+        .await?;
+
+        Ok(IdentityUser {
+            id,
+            username,
+            email,
+            name,
+            github_id: None,
+            discord_id: None,
+            google_id: None,
+            gitlab_id: None,
+            microsoft_id: None,
+            apple_id: None,
+        })
+    }
+
+    // Checks the email and password of an account, starting a new session for it
+    pub async fn login(
+        &self,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+        email: &str,
+        password: &str,
+    ) -> Result<Cookie<'static>, AuthenticationError> {
+        let identity = sqlx::query!(
+            "
+            SELECT id, password_hash FROM local_identities
+            WHERE LOWER(email) = LOWER($1)
+            ",
+            email
+        )
+        .fetch_optional(&mut *transaction)
+        .await?
+        .ok_or(AuthenticationError::InvalidCredentials)?;
+
+        let password_hash = PasswordHash::new(&identity.password_hash)
+            .map_err(|_| AuthenticationError::PasswordHashing)?;
+        Argon2::default()
+            .verify_password(password.as_bytes(), &password_hash)
+            .map_err(|_| AuthenticationError::InvalidCredentials)?;
+
+        self.create_session(transaction, identity.id).await
+    }
+
+    // Starts a new session for the identity, returning the signed session cookie
+    pub async fn create_session(
+        &self,
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+        identity_id: String,
+    ) -> Result<Cookie<'static>, AuthenticationError> {
+        let session_id = generate_local_session_id(transaction).await?;
+
+        sqlx::query!(
+            "
+            INSERT INTO local_sessions (id, identity_id, expires)
+            VALUES ($1, $2, $3)
+            ",
+            session_id as LocalSessionId,
+            identity_id,
+            Utc::now() + Duration::days(SESSION_EXPIRY_DAYS)
+        )
+        .execute(&mut *transaction)
+        .await?;
+
+        Ok(
+            Cookie::build(SESSION_COOKIE, sign_session(&self.secret, session_id))
+                .path("/")
+                .http_only(true)
+                .same_site(SameSite::Lax)
+                .max_age(time::Duration::days(SESSION_EXPIRY_DAYS))
+                .finish(),
+        )
+    }
+
+    // Ends the session of the cookies, returning a cookie which removes it
+    pub async fn logout(&self, cookies: &str) -> Result<Cookie<'static>, AuthenticationError> {
+        if let Some(session_id) = get_session(&self.secret, cookies) {
+            sqlx::query!(
+                "
+                DELETE FROM local_sessions
+                WHERE id = $1
+                ",
+                session_id as LocalSessionId
+            )
+            .execute(&self.pool)
+            .await?;
+        }
+
+        let mut cookie = Cookie::build(SESSION_COOKIE, "").path("/").finish();
+        cookie.make_removal();
+        Ok(cookie)
+    }
+}
+
+fn session_mac(secret: &str) -> HmacSha256 {
+    // HMAC accepts keys of any length
+    HmacSha256::new_from_slice(secret.as_bytes()).unwrap()
+}
+
+// The cookie value is the base62 session ID followed by its hex encoded HMAC
+fn sign_session(secret: &str, session_id: LocalSessionId) -> String {
+    let id = to_base62(session_id.0 as u64);
+
+    let mut mac = session_mac(secret);
+    mac.update(id.as_bytes());
+    let signature = hex::encode(mac.finalize().into_bytes());
+
+    format!("{id}.{signature}")
+}
+
+fn verify_session(secret: &str, value: &str) -> Option<LocalSessionId> {
+    let (id, signature) = value.split_once('.')?;
+    let signature = hex::decode(signature).ok()?;
+
+    let mut mac = session_mac(secret);
+    mac.update(id.as_bytes());
+    mac.verify(&signature).ok()?;
+
+    Some(LocalSessionId(parse_base62(id).ok()? as i64))
+}
+
+// Finds the session cookie in a cookie header, returning its session if the signature is valid
+fn get_session(secret: &str, cookies: &str) -> Option<LocalSessionId> {
+    cookies
+        .split(';')
+        .find_map(|cookie| {
+            cookie
+                .trim()
+                .strip_prefix(SESSION_COOKIE)?
+                .strip_prefix('=')
+        })
+        .and_then(|value| verify_session(secret, value))
+}
+
+#[async_trait]
+impl IdentityProvider for LocalProvider {
+    async fn get_user(&self, cookies: &str) -> Result<IdentityUser, AuthenticationError> {
+        let session_id =
+            get_session(&self.secret, cookies).ok_or(AuthenticationError::InvalidCredentials)?;
+
+        let identity = sqlx::query!(
+            "
+            SELECT li.id, li.email, li.username, li.name FROM local_sessions ls
+            INNER JOIN local_identities li ON li.id = ls.identity_id
+            WHERE ls.id = $1 AND ls.expires > NOW()
+            ",
+            session_id as LocalSessionId
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or(AuthenticationError::InvalidCredentials)?;
+
+        Ok(IdentityUser {
+            id: identity.id,
+            username: identity.username,
+            email: identity.email,
+            name: identity.name,
+            github_id: None,
+            discord_id: None,
+            google_id: None,
+            gitlab_id: None,
+            microsoft_id: None,
+            apple_id: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn session_cookies_are_signed() {
+        let value = sign_session("secret", LocalSessionId(1234567890));
+        assert_eq!(
+            verify_session("secret", &value).map(|x| x.0),
+            Some(1234567890)
+        );
+        assert!(verify_session("other secret", &value).is_none());
+
+        let (_, signature) = value.split_once('.').unwrap();
+        let forged = format!("{}.{}", to_base62(987654321), signature);
+        assert!(verify_session("secret", &forged).is_none());
+
+        let cookies = format!("ory_session=abc; {SESSION_COOKIE}={value}");
+        assert_eq!(
+            get_session("secret", &cookies).map(|x| x.0),
+            Some(1234567890)
+        );
+    }
+}
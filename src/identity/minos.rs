@@ -0,0 +1,35 @@
+use super::{IdentityProvider, IdentityUser};
+use crate::util::auth::AuthenticationError;
+use async_trait::async_trait;
+
+/// Authenticates users through Minos, which is backed by our Ory Kratos instance
+pub struct MinosProvider {
+    url: String,
+}
+
+impl MinosProvider {
+    pub fn new(url: String) -> Self {
+        MinosProvider { url }
+    }
+}
+
+#[async_trait]
+impl IdentityProvider for MinosProvider {
+    // pass the cookies to Minos to get the user.
+    async fn get_user(&self, cookies: &str) -> Result<IdentityUser, AuthenticationError> {
+        let req = reqwest::Client::new()
+            .get(format!("{}/user", self.url))
+            .header(reqwest::header::USER_AGENT, "Modrinth")
+            .header(reqwest::header::COOKIE, cookies);
+        let res = req.send().await?;
+
+        let res = match res.status() {
+            reqwest::StatusCode::OK => res,
+            reqwest::StatusCode::UNAUTHORIZED => {
+                return Err(AuthenticationError::InvalidCredentials)
+            }
+            _ => res.error_for_status()?,
+        };
+        Ok(res.json().await?)
+    }
+}
@@ -0,0 +1,30 @@
+use crate::util::auth::AuthenticationError;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+mod local;
+mod minos;
+
+pub use local::LocalProvider;
+pub use minos::MinosProvider;
+
+/// A user as known by an identity provider. The ID is stored as the `kratos_id` of the user.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct IdentityUser {
+    pub id: String,       // This is the unique generated Ory name
+    pub username: String, // unique username
+    pub email: String,
+    pub name: Option<String>, // real name
+    pub github_id: Option<i64>,
+    pub discord_id: Option<String>,
+    pub google_id: Option<String>,
+    pub gitlab_id: Option<String>,
+    pub microsoft_id: Option<String>,
+    pub apple_id: Option<String>,
+}
+
+#[async_trait]
+pub trait IdentityProvider {
+    /// Gets the user authenticated by the cookies of a request
+    async fn get_user(&self, cookies: &str) -> Result<IdentityUser, AuthenticationError>;
+}
@@ -1,10 +1,13 @@
 use crate::file_hosting::S3Host;
 use crate::queue::download::DownloadQueue;
+use crate::queue::pats::PatQueue;
 use crate::queue::payouts::PayoutsQueue;
+use crate::queue::scan::ScanQueue;
 use crate::ratelimit::errors::ARError;
 use crate::ratelimit::memory::{MemoryStore, MemoryStoreActor};
 use crate::ratelimit::middleware::RateLimiter;
 use crate::util::env::{parse_strings_from_var, parse_var};
+use crate::util::routes::get_ip_addr;
 use actix_cors::Cors;
 use actix_web::{web, App, HttpServer};
 use chrono::{DateTime, Utc};
@@ -18,12 +21,14 @@ use tokio::sync::Mutex;
 mod database;
 mod file_hosting;
 mod health;
+mod identity;
 mod models;
 mod queue;
 mod ratelimit;
 mod routes;
 mod scheduler;
 mod search;
+mod services;
 mod util;
 mod validate;
 
@@ -74,29 +79,80 @@ async fn main() -> std::io::Result<()> {
         .await
         .expect("Database connection failed");
 
+    // The local identity provider is also registered on its own, as it has its own login routes
+    let identity_backend =
+        dotenvy::var("IDENTITY_PROVIDER").unwrap_or_else(|_| "minos".to_string());
+
+    let local_identity_provider: Option<Arc<identity::LocalProvider>> =
+        match identity_backend.as_str() {
+            "local" => Some(Arc::new(identity::LocalProvider::new(
+                pool.clone(),
+                dotenvy::var("LOCAL_IDENTITY_SECRET").unwrap(),
+            ))),
+            _ => None,
+        };
+
+    let identity_provider: Arc<dyn identity::IdentityProvider + Send + Sync> =
+        match identity_backend.as_str() {
+            "minos" => Arc::new(identity::MinosProvider::new(
+                dotenvy::var("MINOS_URL").unwrap(),
+            )),
+            "local" => local_identity_provider.clone().unwrap(),
+            _ => panic!("Invalid identity provider specified. Aborting startup!"),
+        };
+
     let storage_backend = dotenvy::var("STORAGE_BACKEND").unwrap_or_else(|_| "local".to_string());
+    // Storage backends files are replicated to, besides the primary backend in STORAGE_BACKEND
+    let storage_replicas = parse_strings_from_var("STORAGE_REPLICAS").unwrap_or_default();
+
+    // The local file host is also registered on its own, as it serves its files itself
+    let local_file_host: Option<Arc<file_hosting::LocalHost>> =
+        if storage_backend == "local" || storage_replicas.iter().any(|x| x == "local") {
+            Some(Arc::new(
+                file_hosting::LocalHost::new(
+                    std::path::Path::new(&local_file_path().unwrap()),
+                    dotenvy::var("LOCAL_FILE_SIGNING_KEY").ok(),
+                    chrono::Duration::seconds(
+                        parse_var("LOCAL_FILE_SIGNED_URL_EXPIRY").unwrap_or(60 * 60),
+                    ),
+                )
+                .expect("Unable to create the local file storage directory"),
+            ))
+        } else {
+            None
+        };
+
+    let primary_file_host = create_file_host(&storage_backend, &local_file_host).await;
+
+    // The replicated file host is also registered on its own, as downloads are redirected to a
+    // healthy backend by it
+    let replicated_file_host: Option<Arc<file_hosting::ReplicatedHost>> =
+        if storage_replicas.is_empty() {
+            None
+        } else {
+            let mut replicas = Vec::new();
+            for backend in &storage_replicas {
+                replicas.push(file_hosting::Replica::new(
+                    backend.clone(),
+                    create_file_host(backend, &local_file_host).await,
+                    dotenvy::var(format!("{}_CDN_URL", backend.to_uppercase())).unwrap(),
+                ));
+            }
 
-    let file_host: Arc<dyn file_hosting::FileHost + Send + Sync> = match storage_backend.as_str() {
-        "backblaze" => Arc::new(
-            file_hosting::BackblazeHost::new(
-                &dotenvy::var("BACKBLAZE_KEY_ID").unwrap(),
-                &dotenvy::var("BACKBLAZE_KEY").unwrap(),
-                &dotenvy::var("BACKBLAZE_BUCKET_ID").unwrap(),
-            )
-            .await,
-        ),
-        "s3" => Arc::new(
-            S3Host::new(
-                &dotenvy::var("S3_BUCKET_NAME").unwrap(),
-                &dotenvy::var("S3_REGION").unwrap(),
-                &dotenvy::var("S3_URL").unwrap(),
-                &dotenvy::var("S3_ACCESS_TOKEN").unwrap(),
-                &dotenvy::var("S3_SECRET").unwrap(),
-            )
-            .unwrap(),
-        ),
-        "local" => Arc::new(file_hosting::MockHost::new()),
-        _ => panic!("Invalid storage backend specified. Aborting startup!"),
+            Some(Arc::new(file_hosting::ReplicatedHost::new(
+                pool.clone(),
+                file_hosting::Replica::new(
+                    storage_backend.clone(),
+                    primary_file_host.clone(),
+                    dotenvy::var("CDN_URL").unwrap(),
+                ),
+                replicas,
+            )))
+        };
+
+    let file_host: Arc<dyn file_hosting::FileHost + Send + Sync> = match &replicated_file_host {
+        Some(replicated_file_host) => replicated_file_host.clone(),
+        None => primary_file_host,
     };
 
     let mut scheduler = scheduler::Scheduler::new();
@@ -122,7 +178,9 @@ async fn main() -> std::io::Result<()> {
         }
     });
 
-    // Deleting old authentication states from the database every 15 minutes
+    // Deleting old authentication states and expired PATs from the database every 15 minutes
+    // Expired PATs are kept for a grace period (defaults to 30 days) so users can see them expired
+    let pat_grace_days = parse_var::<i64>("PAT_EXPIRY_GRACE_DAYS").unwrap_or(30);
     let pool_ref = pool.clone();
     scheduler.run(std::time::Duration::from_secs(15 * 60), move || {
         let pool_ref = pool_ref.clone();
@@ -146,10 +204,151 @@ async fn main() -> std::io::Result<()> {
                 );
             }
 
+            let pats_result = sqlx::query!(
+                "
+                DELETE FROM pats
+                WHERE expires_at < $1
+                ",
+                Utc::now().naive_utc() - chrono::Duration::days(pat_grace_days)
+            )
+            .execute(&pool_ref)
+            .await;
+
+            if let Err(e) = pats_result {
+                warn!("Deleting expired personal access tokens failed: {:?}", e);
+            }
+
+            let oauth_result = sqlx::query!(
+                "
+                DELETE FROM oauth_authorizations
+                WHERE refresh_expires < CURRENT_DATE
+                "
+            )
+            .execute(&pool_ref)
+            .await;
+
+            if let Err(e) = oauth_result {
+                warn!("Deleting expired OAuth authorizations failed: {:?}", e);
+            }
+
+            let sessions_result = sqlx::query!(
+                "
+                DELETE FROM local_sessions
+                WHERE expires < CURRENT_DATE
+                "
+            )
+            .execute(&pool_ref)
+            .await;
+
+            if let Err(e) = sessions_result {
+                warn!("Deleting expired local sessions failed: {:?}", e);
+            }
+
             info!("Finished deleting old records from temporary tables");
         }
     });
 
+    // Deleting abandoned upload sessions, and the data of finished ones, every hour
+    let pool_ref = pool.clone();
+    scheduler.run(std::time::Duration::from_secs(60 * 60), move || {
+        let pool_ref = pool_ref.clone();
+        info!("Deleting abandoned upload sessions");
+
+        async move {
+            let result = util::uploads::delete_abandoned_sessions(&pool_ref).await;
+
+            if let Err(e) = result {
+                warn!("Deleting abandoned upload sessions failed: {:?}", e);
+            }
+
+            info!("Finished deleting abandoned upload sessions");
+        }
+    });
+
+    // Deleting released blobs which could not be deleted from the file host before, every hour
+    let pool_ref = pool.clone();
+    let file_host_ref = file_host.clone();
+    scheduler.run(std::time::Duration::from_secs(60 * 60), move || {
+        let pool_ref = pool_ref.clone();
+        let file_host_ref = file_host_ref.clone();
+        info!("Deleting released blobs");
+
+        async move {
+            let result = util::blobs::delete_released_blobs(&pool_ref, &*file_host_ref).await;
+
+            if let Err(e) = result {
+                warn!("Deleting released blobs failed: {:?}", e);
+            }
+
+            info!("Finished deleting released blobs");
+        }
+    });
+
+    // Auditing the stored version files against their hashes and the database, and deleting
+    // orphaned objects. Defaults to once a day if unset.
+    let storage_audit_interval =
+        std::time::Duration::from_secs(parse_var("STORAGE_AUDIT_INTERVAL").unwrap_or(86400));
+    let pool_ref = pool.clone();
+    let file_host_ref = file_host.clone();
+    scheduler.run(storage_audit_interval, move || {
+        let pool_ref = pool_ref.clone();
+        let file_host_ref = file_host_ref.clone();
+        info!("Auditing stored files");
+
+        async move {
+            let result = util::storage_audit::audit_storage(&pool_ref, &*file_host_ref).await;
+
+            if let Err(e) = result {
+                warn!("Auditing stored files failed: {:?}", e);
+            }
+
+            info!("Finished auditing stored files");
+        }
+    });
+
+    // Copying files to the storage backends which missed them every 5 minutes, and checking the
+    // health of every backend
+    if let Some(replicated_file_host) = replicated_file_host.clone() {
+        scheduler.run(std::time::Duration::from_secs(5 * 60), move || {
+            let replicated_file_host = replicated_file_host.clone();
+            info!("Repairing replicated files");
+
+            async move {
+                replicated_file_host.check_health().await;
+
+                let result = replicated_file_host.repair().await;
+
+                if let Err(e) = result {
+                    warn!("Repairing replicated files failed: {:?}", e);
+                }
+
+                info!("Finished repairing replicated files");
+            }
+        });
+    }
+
+    // Warning users of PATs which are about to expire every hour
+    // The warning is sent this many days before expiry (defaults to 7 days)
+    let pat_warning_days = parse_var::<i64>("PAT_EXPIRY_WARNING_DAYS").unwrap_or(7);
+    let pool_ref = pool.clone();
+    scheduler.run(std::time::Duration::from_secs(60 * 60), move || {
+        let pool_ref = pool_ref.clone();
+        info!("Notifying users of expiring personal access tokens");
+
+        async move {
+            let result = util::pat::notify_expiring_pats(&pool_ref, pat_warning_days).await;
+
+            if let Err(e) = result {
+                warn!(
+                    "Notifying users of expiring personal access tokens failed: {:?}",
+                    e
+                );
+            }
+
+            info!("Finished notifying users of expiring personal access tokens");
+        }
+    });
+
     // Changes statuses of scheduled projects/versions
     let pool_ref = pool.clone();
     scheduler.run(std::time::Duration::from_secs(60), move || {
@@ -279,6 +478,55 @@ async fn main() -> std::io::Result<()> {
         }
     });
 
+    let pat_queue = Arc::new(PatQueue::new());
+
+    let pool_ref = pool.clone();
+    let pat_queue_ref = pat_queue.clone();
+    scheduler.run(std::time::Duration::from_secs(30), move || {
+        let pool_ref = pool_ref.clone();
+        let pat_queue_ref = pat_queue_ref.clone();
+
+        async move {
+            info!("Indexing PAT usage queue");
+            let result = pat_queue_ref.index(&pool_ref).await;
+            if let Err(e) = result {
+                warn!("Indexing PAT usage queue failed: {:?}", e);
+            }
+            info!("Done indexing PAT usage queue");
+        }
+    });
+
+    // Signatures of known malware are read from a local file, so scanning works offline
+    let scan_rules = match dotenvy::var("SCAN_RULES_PATH") {
+        Ok(path) => validate::scan::ScanRules::load(std::path::Path::new(&path))
+            .expect("Unable to load the malware scan rules"),
+        Err(_) => {
+            warn!(
+                "`SCAN_RULES_PATH` is not set, so uploads are only scanned for suspicious bytecode"
+            );
+            validate::scan::ScanRules::default()
+        }
+    };
+    let scan_queue = Arc::new(ScanQueue::new(scan_rules));
+
+    let pool_ref = pool.clone();
+    let scan_queue_ref = scan_queue.clone();
+    let file_host_ref = file_host.clone();
+    scheduler.run(std::time::Duration::from_secs(30), move || {
+        let pool_ref = pool_ref.clone();
+        let scan_queue_ref = scan_queue_ref.clone();
+        let file_host_ref = file_host_ref.clone();
+
+        async move {
+            info!("Scanning uploaded files");
+            let result = scan_queue_ref.index(&pool_ref, &*file_host_ref).await;
+            if let Err(e) = result {
+                warn!("Scanning uploaded files failed: {:?}", e);
+            }
+            info!("Done scanning uploaded files");
+        }
+    });
+
     let ip_salt = Pepper {
         pepper: models::ids::Base62Id(models::ids::random_base62(11)).to_string(),
     };
@@ -304,19 +552,7 @@ async fn main() -> std::io::Result<()> {
             .wrap(
                 RateLimiter::new(MemoryStoreActor::from(store.clone()).start())
                     .with_identifier(|req| {
-                        let connection_info = req.connection_info();
-                        let ip =
-                            String::from(if parse_var("CLOUDFLARE_INTEGRATION").unwrap_or(false) {
-                                if let Some(header) = req.headers().get("CF-Connecting-IP") {
-                                    header.to_str().map_err(|_| ARError::Identification)?
-                                } else {
-                                    connection_info.peer_addr().ok_or(ARError::Identification)?
-                                }
-                            } else {
-                                connection_info.peer_addr().ok_or(ARError::Identification)?
-                            });
-
-                        Ok(ip)
+                        get_ip_addr(req.request()).ok_or(ARError::Identification)
                     })
                     .with_interval(std::time::Duration::from_secs(60))
                     .with_max_requests(300)
@@ -344,8 +580,14 @@ async fn main() -> std::io::Result<()> {
             )
             .app_data(web::Data::new(pool.clone()))
             .app_data(web::Data::new(file_host.clone()))
+            .app_data(web::Data::new(local_file_host.clone()))
+            .app_data(web::Data::new(replicated_file_host.clone()))
+            .app_data(web::Data::new(identity_provider.clone()))
+            .app_data(web::Data::new(local_identity_provider.clone()))
             .app_data(web::Data::new(search_config.clone()))
             .app_data(web::Data::new(download_queue.clone()))
+            .app_data(web::Data::new(pat_queue.clone()))
+            .app_data(web::Data::new(scan_queue.clone()))
             .app_data(web::Data::new(payouts_queue.clone()))
             .app_data(web::Data::new(ip_salt.clone()))
             .wrap(sentry_actix::Sentry::new())
@@ -359,7 +601,45 @@ async fn main() -> std::io::Result<()> {
     .await
 }
 
+async fn create_file_host(
+    backend: &str,
+    local_file_host: &Option<Arc<file_hosting::LocalHost>>,
+) -> Arc<dyn file_hosting::FileHost + Send + Sync> {
+    match backend {
+        "backblaze" => Arc::new(
+            file_hosting::BackblazeHost::new(
+                &dotenvy::var("BACKBLAZE_KEY_ID").unwrap(),
+                &dotenvy::var("BACKBLAZE_KEY").unwrap(),
+                &dotenvy::var("BACKBLAZE_BUCKET_ID").unwrap(),
+            )
+            .await,
+        ),
+        "s3" => Arc::new(
+            S3Host::new(
+                &dotenvy::var("S3_BUCKET_NAME").unwrap(),
+                &dotenvy::var("S3_REGION").unwrap(),
+                &dotenvy::var("S3_URL").unwrap(),
+                &dotenvy::var("S3_ACCESS_TOKEN").unwrap(),
+                &dotenvy::var("S3_SECRET").unwrap(),
+            )
+            .unwrap(),
+        ),
+        "local" => local_file_host.clone().unwrap(),
+        _ => panic!("Invalid storage backend specified. Aborting startup!"),
+    }
+}
+
 // This is so that env vars not used immediately don't panic at runtime
+// The directory the local file host stores files in. It was set by `MOCK_FILE_PATH` before the
+// local file host replaced the mock one, which is still read if `LOCAL_FILE_PATH` is not set.
+fn local_file_path() -> Option<String> {
+    dotenvy::var("LOCAL_FILE_PATH").ok().or_else(|| {
+        let path = dotenvy::var("MOCK_FILE_PATH").ok()?;
+        warn!("Variable `MOCK_FILE_PATH` is deprecated, use `LOCAL_FILE_PATH` instead");
+        Some(path)
+    })
+}
+
 fn check_env_vars() -> bool {
     let mut failed = false;
 
@@ -387,8 +667,22 @@ fn check_env_vars() -> bool {
 
     failed |= check_var::<String>("SITE_URL");
     failed |= check_var::<String>("CDN_URL");
-    failed |= check_var::<String>("MINOS_URL");
-    failed |= check_var::<String>("KRATOS_URL");
+
+    let identity_backend = dotenvy::var("IDENTITY_PROVIDER").ok();
+    match identity_backend.as_deref() {
+        Some("local") => {
+            failed |= check_var::<String>("LOCAL_IDENTITY_SECRET");
+        }
+        Some("minos") | None => {
+            failed |= check_var::<String>("MINOS_URL");
+            failed |= check_var::<String>("KRATOS_URL");
+        }
+        _ => {
+            warn!("Variable `IDENTITY_PROVIDER` contains an invalid value. Expected \"minos\" or \"local\".");
+            failed |= true;
+        }
+    }
+
     failed |= check_var::<String>("LABRINTH_ADMIN_KEY");
     failed |= check_var::<String>("RATE_LIMIT_IGNORE_KEY");
     failed |= check_var::<String>("DATABASE_URL");
@@ -399,31 +693,63 @@ fn check_env_vars() -> bool {
     failed |= check_var::<String>("STORAGE_BACKEND");
 
     let storage_backend = dotenvy::var("STORAGE_BACKEND").ok();
-    match storage_backend.as_deref() {
-        Some("backblaze") => {
-            failed |= check_var::<String>("BACKBLAZE_KEY_ID");
-            failed |= check_var::<String>("BACKBLAZE_KEY");
-            failed |= check_var::<String>("BACKBLAZE_BUCKET_ID");
-        }
-        Some("s3") => {
-            failed |= check_var::<String>("S3_ACCESS_TOKEN");
-            failed |= check_var::<String>("S3_SECRET");
-            failed |= check_var::<String>("S3_URL");
-            failed |= check_var::<String>("S3_REGION");
-            failed |= check_var::<String>("S3_BUCKET_NAME");
-        }
-        Some("local") => {
-            failed |= check_var::<String>("MOCK_FILE_PATH");
-        }
-        Some(backend) => {
-            warn!("Variable `STORAGE_BACKEND` contains an invalid value: {}. Expected \"backblaze\", \"s3\", or \"local\".", backend);
-            failed |= true;
-        }
-        _ => {
-            warn!("Variable `STORAGE_BACKEND` is not set!");
-            failed |= true;
+    let storage_replicas = parse_strings_from_var("STORAGE_REPLICAS").unwrap_or_default();
+
+    if storage_backend.is_none() {
+        warn!("Variable `STORAGE_BACKEND` is not set!");
+        failed |= true;
+    }
+
+    // Replicas are served from their own CDN URL, while the primary backend uses CDN_URL
+    let backends = storage_backend
+        .iter()
+        .map(|x| (x, false))
+        .chain(storage_replicas.iter().map(|x| (x, true)));
+
+    for (backend, is_replica) in backends {
+        match backend.as_str() {
+            "backblaze" => {
+                failed |= check_var::<String>("BACKBLAZE_KEY_ID");
+                failed |= check_var::<String>("BACKBLAZE_KEY");
+                failed |= check_var::<String>("BACKBLAZE_BUCKET_ID");
+                if is_replica {
+                    failed |= check_var::<String>("BACKBLAZE_CDN_URL");
+                }
+            }
+            "s3" => {
+                failed |= check_var::<String>("S3_ACCESS_TOKEN");
+                failed |= check_var::<String>("S3_SECRET");
+                failed |= check_var::<String>("S3_URL");
+                failed |= check_var::<String>("S3_REGION");
+                failed |= check_var::<String>("S3_BUCKET_NAME");
+                if is_replica {
+                    failed |= check_var::<String>("S3_CDN_URL");
+                }
+            }
+            "local" => {
+                if local_file_path().is_none() {
+                    warn!("Variable `LOCAL_FILE_PATH` missing in dotenv or not of type `String`");
+                    failed |= true;
+                }
+                if is_replica {
+                    failed |= check_var::<String>("LOCAL_CDN_URL");
+                }
+            }
+            backend => {
+                warn!("Variable `STORAGE_BACKEND` or `STORAGE_REPLICAS` contains an invalid value: {}. Expected \"backblaze\", \"s3\", or \"local\".", backend);
+                failed |= true;
+            }
         }
     }
+
+    if storage_replicas
+        .iter()
+        .any(|x| Some(x) == storage_backend.as_ref())
+    {
+        warn!("Variable `STORAGE_REPLICAS` contains the primary storage backend");
+        failed |= true;
+    }
+
     failed |= check_var::<usize>("LOCAL_INDEX_INTERVAL");
 
     failed |= check_var::<usize>("VERSION_INDEX_INTERVAL");
@@ -431,9 +757,6 @@ fn check_env_vars() -> bool {
     failed |= check_var::<String>("GITHUB_CLIENT_ID");
     failed |= check_var::<String>("GITHUB_CLIENT_SECRET");
 
-    failed |= check_var::<String>("ARIADNE_ADMIN_KEY");
-    failed |= check_var::<String>("ARIADNE_URL");
-
     failed |= check_var::<String>("STRIPE_TOKEN");
     failed |= check_var::<String>("STRIPE_WEBHOOK_SECRET");
 
@@ -441,5 +764,12 @@ fn check_env_vars() -> bool {
     failed |= check_var::<String>("PAYPAL_CLIENT_ID");
     failed |= check_var::<String>("PAYPAL_CLIENT_SECRET");
 
+    // Ariadne is optional, but payouts can't be processed without it
+    if parse_var::<String>("ARIADNE_URL").is_none()
+        || parse_var::<String>("ARIADNE_ADMIN_KEY").is_none()
+    {
+        warn!("Variables `ARIADNE_URL` and `ARIADNE_ADMIN_KEY` are not both set, so downloads are not forwarded to Ariadne and payouts can't be processed");
+    }
+
     failed
 }
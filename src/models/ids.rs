@@ -1,11 +1,14 @@
 use thiserror::Error;
 
 pub use super::notifications::NotificationId;
+pub use super::oauth::{OAuthAuthorizationId, OAuthClientId};
+pub use super::pats::PatId;
 pub use super::projects::{ProjectId, VersionId};
 pub use super::reports::ReportId;
 pub use super::teams::TeamId;
 pub use super::threads::ThreadId;
 pub use super::threads::ThreadMessageId;
+pub use super::uploads::UploadSessionId;
 pub use super::users::UserId;
 
 /// Generates a random 64 bit integer that is exactly `n` characters
@@ -99,10 +102,32 @@ macro_rules! impl_base62_display {
 }
 impl_base62_display!(Base62Id);
 
+// IDs are documented as strings in the OpenAPI spec, the way they are serialized
+macro_rules! impl_base62_schema {
+    ($struct:ty) => {
+        impl<'s> utoipa::ToSchema<'s> for $struct {
+            fn schema() -> (
+                &'s str,
+                utoipa::openapi::RefOr<utoipa::openapi::schema::Schema>,
+            ) {
+                (
+                    stringify!($struct),
+                    utoipa::openapi::ObjectBuilder::new()
+                        .schema_type(utoipa::openapi::SchemaType::String)
+                        .description(Some("A base62 encoded ID"))
+                        .example(Some(serde_json::json!("AABBCCDD")))
+                        .into(),
+                )
+            }
+        }
+    };
+}
+
 macro_rules! base62_id_impl {
     ($struct:ty, $cons:expr) => {
         from_base62id!($struct, $cons;);
         impl_base62_display!($struct);
+        impl_base62_schema!($struct);
     }
 }
 base62_id_impl!(ProjectId, ProjectId);
@@ -113,6 +138,10 @@ base62_id_impl!(ReportId, ReportId);
 base62_id_impl!(NotificationId, NotificationId);
 base62_id_impl!(ThreadId, ThreadId);
 base62_id_impl!(ThreadMessageId, ThreadMessageId);
+base62_id_impl!(PatId, PatId);
+base62_id_impl!(OAuthClientId, OAuthClientId);
+base62_id_impl!(OAuthAuthorizationId, OAuthAuthorizationId);
+base62_id_impl!(UploadSessionId, UploadSessionId);
 
 pub mod base62_impl {
     use serde::de::{self, Deserializer, Visitor};
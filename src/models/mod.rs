@@ -1,9 +1,13 @@
 pub mod error;
 pub mod ids;
 pub mod notifications;
+pub mod oauth;
 pub mod pack;
+pub mod pats;
 pub mod projects;
 pub mod reports;
 pub mod teams;
 pub mod threads;
+pub mod uploads;
 pub mod users;
+pub mod v3;
@@ -2,17 +2,20 @@ use super::ids::Base62Id;
 use super::users::UserId;
 use crate::database::models::notification_item::Notification as DBNotification;
 use crate::database::models::notification_item::NotificationAction as DBNotificationAction;
-use crate::models::ids::{ProjectId, ReportId, TeamId, ThreadId, ThreadMessageId, VersionId};
+use crate::models::ids::{
+    PatId, ProjectId, ReportId, TeamId, ThreadId, ThreadMessageId, VersionId,
+};
 use crate::models::projects::ProjectStatus;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 
 #[derive(Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(from = "Base62Id")]
 #[serde(into = "Base62Id")]
 pub struct NotificationId(pub u64);
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, ToSchema)]
 pub struct Notification {
     pub id: NotificationId,
     pub user_id: UserId,
@@ -29,7 +32,7 @@ pub struct Notification {
     pub actions: Vec<NotificationAction>,
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum NotificationBody {
     ProjectUpdate {
@@ -54,6 +57,11 @@ pub enum NotificationBody {
         project_id: Option<ProjectId>,
         report_id: Option<ReportId>,
     },
+    PatExpiring {
+        pat_id: PatId,
+        name: String,
+        expires_at: DateTime<Utc>,
+    },
     LegacyMarkdown {
         notification_type: Option<String>,
         title: String,
@@ -137,6 +145,19 @@ impl From<DBNotification> for Notification {
                     },
                     vec![],
                 ),
+                NotificationBody::PatExpiring {
+                    name, expires_at, ..
+                } => (
+                    Some("pat_expiring".to_string()),
+                    "A personal access token is about to expire!".to_string(),
+                    format!(
+                        "Your personal access token \"{}\" expires on {}",
+                        name,
+                        expires_at.format("%Y-%m-%d")
+                    ),
+                    "/settings/pats".to_string(),
+                    vec![],
+                ),
                 NotificationBody::LegacyMarkdown {
                     notification_type,
                     title,
@@ -177,7 +198,7 @@ impl From<DBNotification> for Notification {
     }
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
 pub struct NotificationAction {
     pub title: String,
     /// The route to call when this notification action is called. Formatted HTTP Method, route
@@ -0,0 +1,54 @@
+use super::ids::Base62Id;
+use super::pats::Scopes;
+use super::users::UserId;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// The ID of an OAuth application, used as its client ID
+#[derive(Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "Base62Id")]
+#[serde(into = "Base62Id")]
+pub struct OAuthClientId(pub u64);
+
+/// The ID of a user's authorization of an OAuth application
+#[derive(Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "Base62Id")]
+#[serde(into = "Base62Id")]
+pub struct OAuthAuthorizationId(pub u64);
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct OAuthClient {
+    pub id: OAuthClientId,
+    pub name: String,
+    pub icon_url: Option<String>,
+    pub redirect_uris: Vec<String>,
+    /// The scopes this application may request from users
+    pub max_scopes: Scopes,
+    pub created_by: UserId,
+    pub created: DateTime<Utc>,
+    /// The plaintext client secret is only ever returned once, when the application is created
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_secret: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct OAuthAuthorization {
+    pub id: OAuthAuthorizationId,
+    pub client_id: OAuthClientId,
+    pub client_name: String,
+    pub client_icon_url: Option<String>,
+    pub scopes: Scopes,
+    pub created: DateTime<Utc>,
+}
+
+/// The response of the token endpoint, as described in RFC 6749 section 5.1
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct OAuthTokenResponse {
+    pub access_token: String,
+    pub token_type: String,
+    /// Seconds until the access token expires
+    pub expires_in: i64,
+    pub refresh_token: String,
+    pub scope: Scopes,
+}
@@ -0,0 +1,152 @@
+use super::ids::Base62Id;
+use serde::{Deserialize, Serialize};
+
+/// The ID of a personal access token
+#[derive(Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "Base62Id")]
+#[serde(into = "Base62Id")]
+pub struct PatId(pub u64);
+
+bitflags::bitflags! {
+    #[derive(Serialize, Deserialize)]
+    #[serde(transparent)]
+    pub struct Scopes: u64 {
+        // read a user's private data (email, follows, user data)
+        const USER_READ = 1 << 0;
+        // modify a user's profile
+        const USER_WRITE = 1 << 1;
+        // delete a user's account
+        const USER_DELETE = 1 << 2;
+
+        // read a user's notifications
+        const NOTIFICATION_READ = 1 << 3;
+        // mark notifications as read and delete them
+        const NOTIFICATION_WRITE = 1 << 4;
+
+        // read a user's payout history and balance
+        const PAYOUTS_READ = 1 << 5;
+        // withdraw money and manage payment subscriptions
+        const PAYOUTS_WRITE = 1 << 6;
+
+        // view project analytics
+        const ANALYTICS = 1 << 7;
+
+        // create new projects
+        const PROJECT_CREATE = 1 << 8;
+        // read private/hidden projects and their team members
+        const PROJECT_READ = 1 << 9;
+        // edit project data, gallery items and team members
+        const PROJECT_WRITE = 1 << 10;
+        // delete projects
+        const PROJECT_DELETE = 1 << 11;
+
+        // create new versions and upload files to them
+        const VERSION_CREATE = 1 << 12;
+        // read private/hidden versions
+        const VERSION_READ = 1 << 13;
+        // edit version data
+        const VERSION_WRITE = 1 << 14;
+        // delete versions and version files
+        const VERSION_DELETE = 1 << 15;
+
+        // create reports
+        const REPORT_CREATE = 1 << 16;
+        // read a user's reports
+        const REPORT_READ = 1 << 17;
+        // edit reports
+        const REPORT_WRITE = 1 << 18;
+        // delete reports
+        const REPORT_DELETE = 1 << 19;
+
+        // read threads
+        const THREAD_READ = 1 << 20;
+        // send messages to threads and delete them
+        const THREAD_WRITE = 1 << 21;
+
+        // create personal access tokens
+        const PAT_CREATE = 1 << 22;
+        // read a user's personal access tokens
+        const PAT_READ = 1 << 23;
+        // edit personal access tokens
+        const PAT_WRITE = 1 << 24;
+        // delete personal access tokens
+        const PAT_DELETE = 1 << 25;
+
+        // register OAuth applications
+        const OAUTH_CLIENT_CREATE = 1 << 26;
+        // read a user's OAuth applications
+        const OAUTH_CLIENT_READ = 1 << 27;
+        // edit OAuth applications
+        const OAUTH_CLIENT_WRITE = 1 << 28;
+        // delete OAuth applications
+        const OAUTH_CLIENT_DELETE = 1 << 29;
+
+        const ALL = 0b111111111111111111111111111111;
+        const NONE = 0b0;
+    }
+}
+
+impl<'s> utoipa::ToSchema<'s> for Scopes {
+    fn schema() -> (
+        &'s str,
+        utoipa::openapi::RefOr<utoipa::openapi::schema::Schema>,
+    ) {
+        (
+            "Scopes",
+            utoipa::openapi::ObjectBuilder::new()
+                .schema_type(utoipa::openapi::SchemaType::Integer)
+                .description(Some("A bitfield of scopes"))
+                .into(),
+        )
+    }
+}
+
+impl Scopes {
+    // these scopes cannot be granted to a personal access token or OAuth application
+    pub fn restricted() -> Scopes {
+        Scopes::PAT_CREATE
+            | Scopes::PAT_READ
+            | Scopes::PAT_WRITE
+            | Scopes::PAT_DELETE
+            | Scopes::OAUTH_CLIENT_CREATE
+            | Scopes::OAUTH_CLIENT_READ
+            | Scopes::OAUTH_CLIENT_WRITE
+            | Scopes::OAUTH_CLIENT_DELETE
+            | Scopes::USER_DELETE
+    }
+
+    pub fn is_restricted(&self) -> bool {
+        self.intersects(Self::restricted())
+    }
+
+    // Checks that the given bits form a non-empty set of scopes which can be granted to a
+    // personal access token
+    pub fn parse_pat_scopes(bits: u64) -> Option<Scopes> {
+        Scopes::from_bits(bits).filter(|scopes| !scopes.is_empty() && !scopes.is_restricted())
+    }
+}
+
+impl Default for Scopes {
+    fn default() -> Scopes {
+        Scopes::NONE
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pat_scopes_parsing() {
+        let scopes = Scopes::PROJECT_READ | Scopes::VERSION_CREATE;
+        assert_eq!(Scopes::parse_pat_scopes(scopes.bits()), Some(scopes));
+
+        assert_eq!(Scopes::parse_pat_scopes(0), None);
+        assert_eq!(Scopes::parse_pat_scopes(1 << 63), None);
+        assert_eq!(Scopes::parse_pat_scopes(Scopes::ALL.bits()), None);
+        assert_eq!(
+            Scopes::parse_pat_scopes((Scopes::PROJECT_READ | Scopes::PAT_CREATE).bits()),
+            None
+        );
+    }
+}
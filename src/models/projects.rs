@@ -6,22 +6,23 @@ use crate::database::models::version_item::QueryVersion;
 use crate::models::threads::ThreadId;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
 use validator::Validate;
 
 /// The ID of a specific project, encoded as base62 for usage in the API
-#[derive(Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
 #[serde(from = "Base62Id")]
 #[serde(into = "Base62Id")]
 pub struct ProjectId(pub u64);
 
 /// The ID of a specific version of a project
-#[derive(Copy, Clone, PartialEq, Eq, Serialize, Deserialize, Hash)]
+#[derive(Copy, Clone, PartialEq, Eq, Serialize, Deserialize, Hash, Debug)]
 #[serde(from = "Base62Id")]
 #[serde(into = "Base62Id")]
 pub struct VersionId(pub u64);
 
 /// A project returned from the API
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
 pub struct Project {
     /// The ID of the project, encoded as a base62 string.
     pub id: ProjectId,
@@ -88,6 +89,8 @@ pub struct Project {
     pub versions: Vec<VersionId>,
     /// The URL of the icon of the project
     pub icon_url: Option<String>,
+    /// Smaller renditions of the icon of the project
+    pub icon_thumbnails: Vec<Thumbnail>,
     /// An optional link to where to submit bugs or issues with the project.
     pub issues_url: Option<String>,
     /// An optional link to the source code for the project.
@@ -169,6 +172,7 @@ impl From<QueryProject> for Project {
             loaders: m.loaders,
             versions: data.versions.into_iter().map(|v| v.into()).collect(),
             icon_url: m.icon_url,
+            icon_thumbnails: m.icon_thumbnails,
             issues_url: m.issues_url,
             source_url: m.source_url,
             wiki_url: m.wiki_url,
@@ -193,6 +197,7 @@ impl From<QueryProject> for Project {
                     description: x.description,
                     created: x.created,
                     ordering: x.ordering,
+                    thumbnails: x.thumbnails,
                 })
                 .collect(),
             color: m.color,
@@ -202,7 +207,7 @@ impl From<QueryProject> for Project {
     }
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
 pub struct GalleryItem {
     pub url: String,
     pub featured: bool,
@@ -210,15 +215,25 @@ pub struct GalleryItem {
     pub description: Option<String>,
     pub created: DateTime<Utc>,
     pub ordering: i64,
+    /// Smaller renditions of the image
+    pub thumbnails: Vec<Thumbnail>,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+/// A smaller rendition of an image, which fits in a square of its size
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
+pub struct Thumbnail {
+    /// The width and height in pixels the thumbnail fits in
+    pub size: u32,
+    pub url: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
 pub struct ModeratorMessage {
     pub message: String,
     pub body: Option<String>,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq, ToSchema)]
 #[serde(rename_all = "kebab-case")]
 pub enum SideType {
     Required,
@@ -256,14 +271,14 @@ impl SideType {
 
 pub const DEFAULT_LICENSE_ID: &str = "LicenseRef-All-Rights-Reserved";
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
 pub struct License {
     pub id: String,
     pub name: String,
     pub url: Option<String>,
 }
 
-#[derive(Serialize, Deserialize, Validate, Clone, Eq, PartialEq)]
+#[derive(Serialize, Deserialize, Validate, Clone, Eq, PartialEq, ToSchema)]
 pub struct DonationLink {
     pub id: String,
     pub platform: String,
@@ -283,7 +298,7 @@ pub struct DonationLink {
 /// Processing - Project is not displayed on search, and not accessible by URL (Temporary state, project under review)
 /// Scheduled - Project is scheduled to be released in the future
 /// Private - Project is approved, but is not viewable to the public
-#[derive(Serialize, Deserialize, Copy, Clone, Eq, PartialEq, Debug)]
+#[derive(Serialize, Deserialize, Copy, Clone, Eq, PartialEq, Debug, ToSchema)]
 #[serde(rename_all = "lowercase")]
 pub enum ProjectStatus {
     Approved,
@@ -415,7 +430,7 @@ impl ProjectStatus {
     }
 }
 
-#[derive(Serialize, Deserialize, Copy, Clone, Debug, Eq, PartialEq)]
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, Eq, PartialEq, ToSchema)]
 #[serde(rename_all = "kebab-case")]
 pub enum MonetizationStatus {
     ForceDemonetized,
@@ -449,7 +464,7 @@ impl MonetizationStatus {
 }
 
 /// A specific version of a project
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
 pub struct Version {
     /// The ID of the version, encoded as a base62 string.
     pub id: VersionId,
@@ -525,6 +540,7 @@ impl From<QueryVersion> for Version {
                     primary: f.primary,
                     size: f.size,
                     file_type: f.file_type,
+                    findings: f.findings,
                 })
                 .collect(),
             dependencies: data
@@ -549,7 +565,8 @@ impl From<QueryVersion> for Version {
 /// Draft - Version is not displayed on project, and not accessible by URL
 /// Unlisted - Version is not displayed on project, and accessible by URL
 /// Scheduled - Version is scheduled to be released in the future
-#[derive(Serialize, Deserialize, Copy, Clone, Eq, PartialEq, Debug)]
+/// Withheld - Version is hidden until a moderator reviews the findings of the malware scan
+#[derive(Serialize, Deserialize, Copy, Clone, Eq, PartialEq, Debug, ToSchema)]
 #[serde(rename_all = "lowercase")]
 pub enum VersionStatus {
     Listed,
@@ -557,6 +574,7 @@ pub enum VersionStatus {
     Draft,
     Unlisted,
     Scheduled,
+    Withheld,
     Unknown,
 }
 
@@ -573,6 +591,7 @@ impl VersionStatus {
             "draft" => VersionStatus::Draft,
             "unlisted" => VersionStatus::Unlisted,
             "scheduled" => VersionStatus::Scheduled,
+            "withheld" => VersionStatus::Withheld,
             _ => VersionStatus::Unknown,
         }
     }
@@ -584,6 +603,7 @@ impl VersionStatus {
             VersionStatus::Unlisted => "unlisted",
             VersionStatus::Unknown => "unknown",
             VersionStatus::Scheduled => "scheduled",
+            VersionStatus::Withheld => "withheld",
         }
     }
 
@@ -594,6 +614,7 @@ impl VersionStatus {
             VersionStatus::Draft,
             VersionStatus::Unlisted,
             VersionStatus::Scheduled,
+            VersionStatus::Withheld,
             VersionStatus::Unknown,
         ]
         .iter()
@@ -609,6 +630,7 @@ impl VersionStatus {
 
             VersionStatus::Draft => true,
             VersionStatus::Scheduled => true,
+            VersionStatus::Withheld => true,
             VersionStatus::Unknown => true,
         }
     }
@@ -627,13 +649,14 @@ impl VersionStatus {
             VersionStatus::Unlisted => true,
             VersionStatus::Scheduled => false,
 
+            VersionStatus::Withheld => false,
             VersionStatus::Unknown => false,
         }
     }
 }
 
 /// A single project file, with a url for the file and the file's hash
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
 pub struct VersionFile {
     /// A map of hashes of the file.  The key is the hashing algorithm
     /// and the value is the string version of the hash.
@@ -648,11 +671,111 @@ pub struct VersionFile {
     pub size: u32,
     /// The type of the file
     pub file_type: Option<FileType>,
+    /// What the validation of the file found, such as why it was not made the primary file
+    pub findings: Vec<FileFinding>,
+}
+
+/// Something the validation of an uploaded file found
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq, ToSchema)]
+pub struct FileFinding {
+    pub severity: FindingSeverity,
+    pub code: FindingCode,
+    /// A description of the finding for the author of the file
+    pub message: String,
+    /// The path inside the archive the finding is about, if any
+    pub path: Option<String>,
+}
+
+/// How much a finding matters. Files with warnings on upload are never made primary
+/// automatically, and versions with warnings or critical findings from the malware scan are
+/// withheld until a moderator reviews them.
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, Eq, PartialEq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum FindingSeverity {
+    Info,
+    Warning,
+    Critical,
+}
+
+impl FindingSeverity {
+    // These are constant, so this can remove unnecessary allocations (`to_string`)
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FindingSeverity::Info => "info",
+            FindingSeverity::Warning => "warning",
+            FindingSeverity::Critical => "critical",
+        }
+    }
+}
+
+/// The kind of a finding, which moderators can filter the review queue by
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, Eq, PartialEq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum FindingCode {
+    /// The manifest of the loader or pack format is missing
+    MissingManifest,
+    /// A folder the format requires is missing
+    MissingFolder,
+    /// Files the format requires, other than its manifest, are missing
+    MissingContent,
+    /// The file contains source code instead of compiled classes
+    SourceFile,
+    /// The file extension does not match the project type and loaders
+    InvalidExtension,
+    /// The manifest declares support for other game versions than the version
+    GameVersionMismatch,
+    /// The file has no manifest for some of the loaders of the version
+    LoaderMismatch,
+    /// The file matches the hash of a known malicious file
+    MaliciousHash,
+    /// A class matches the signature of a known malware
+    MalwareSignature,
+    /// A class loads classes at runtime, possibly downloaded from the internet
+    RemoteClassLoading,
+    /// A class writes files and loads native libraries
+    NativeLibraryDrop,
+    /// A class calls `Runtime.exec` through reflection
+    ReflectiveExec,
+    /// Jars are nested too deep to be scanned
+    NestedJar,
+    /// The file is too large to be scanned completely
+    IncompleteScan,
+    #[serde(other)]
+    Unknown,
+}
+
+impl std::fmt::Display for FindingCode {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        fmt.write_str(self.as_str())
+    }
+}
+
+impl FindingCode {
+    // These are constant, so this can remove unnecessary allocations (`to_string`)
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FindingCode::MissingManifest => "missing_manifest",
+            FindingCode::MissingFolder => "missing_folder",
+            FindingCode::MissingContent => "missing_content",
+            FindingCode::SourceFile => "source_file",
+            FindingCode::InvalidExtension => "invalid_extension",
+            FindingCode::GameVersionMismatch => "game_version_mismatch",
+            FindingCode::LoaderMismatch => "loader_mismatch",
+            FindingCode::MaliciousHash => "malicious_hash",
+            FindingCode::MalwareSignature => "malware_signature",
+            FindingCode::RemoteClassLoading => "remote_class_loading",
+            FindingCode::NativeLibraryDrop => "native_library_drop",
+            FindingCode::ReflectiveExec => "reflective_exec",
+            FindingCode::NestedJar => "nested_jar",
+            FindingCode::IncompleteScan => "incomplete_scan",
+            FindingCode::Unknown => "unknown",
+        }
+    }
 }
 
 /// A dendency which describes what versions are required, break support, or are optional to the
 /// version's functionality
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
 pub struct Dependency {
     /// The specific version id that the dependency uses
     pub version_id: Option<VersionId>,
@@ -664,7 +787,7 @@ pub struct Dependency {
     pub dependency_type: DependencyType,
 }
 
-#[derive(Serialize, Deserialize, Copy, Clone, Eq, PartialEq)]
+#[derive(Serialize, Deserialize, Copy, Clone, Eq, PartialEq, ToSchema)]
 #[serde(rename_all = "lowercase")]
 pub enum VersionType {
     Release,
@@ -689,7 +812,7 @@ impl VersionType {
     }
 }
 
-#[derive(Serialize, Deserialize, Copy, Clone)]
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Eq, ToSchema)]
 #[serde(rename_all = "lowercase")]
 pub enum DependencyType {
     Required,
@@ -726,7 +849,7 @@ impl DependencyType {
     }
 }
 
-#[derive(Serialize, Deserialize, Copy, Clone, Debug)]
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, ToSchema)]
 #[serde(rename_all = "kebab-case")]
 pub enum FileType {
     RequiredResourcePack,
@@ -752,18 +875,18 @@ impl FileType {
 }
 
 /// A specific version of Minecraft
-#[derive(Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, ToSchema)]
 #[serde(transparent)]
 pub struct GameVersion(pub String);
 
 /// A project loader
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
 #[serde(transparent)]
 pub struct Loader(pub String);
 
 // These fields must always succeed parsing; deserialize errors aren't
 // processed correctly (don't return JSON errors)
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, ToSchema, IntoParams)]
 pub struct SearchRequest {
     pub query: Option<String>,
     pub offset: Option<String>,
@@ -2,13 +2,14 @@ use super::ids::Base62Id;
 use crate::models::ids::{ThreadId, UserId};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 
 #[derive(Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(from = "Base62Id")]
 #[serde(into = "Base62Id")]
 pub struct ReportId(pub u64);
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, ToSchema)]
 pub struct Report {
     pub id: ReportId,
     pub report_type: String,
@@ -21,7 +22,7 @@ pub struct Report {
     pub thread_id: Option<ThreadId>,
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
 #[serde(rename_all = "kebab-case")]
 pub enum ItemType {
     Project,
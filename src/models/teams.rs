@@ -3,6 +3,7 @@ use crate::database::models::team_item::QueryTeamMember;
 use crate::models::users::User;
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 
 /// The ID of a team
 #[derive(Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -15,7 +16,7 @@ pub const DEFAULT_ROLE: &str = "Member";
 
 // TODO: permissions, role names, etc
 /// A team of users who control a project
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, ToSchema)]
 pub struct Team {
     /// The id of the team
     pub id: TeamId,
@@ -41,6 +42,21 @@ bitflags::bitflags! {
     }
 }
 
+impl<'s> utoipa::ToSchema<'s> for Permissions {
+    fn schema() -> (
+        &'s str,
+        utoipa::openapi::RefOr<utoipa::openapi::schema::Schema>,
+    ) {
+        (
+            "Permissions",
+            utoipa::openapi::ObjectBuilder::new()
+                .schema_type(utoipa::openapi::SchemaType::Integer)
+                .description(Some("A bitfield of team member permissions"))
+                .into(),
+        )
+    }
+}
+
 impl Default for Permissions {
     fn default() -> Permissions {
         Permissions::UPLOAD_VERSION | Permissions::DELETE_VERSION
@@ -48,7 +64,7 @@ impl Default for Permissions {
 }
 
 /// A member of a team
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
 pub struct TeamMember {
     /// The ID of the team this team member is a member of
     pub team_id: TeamId,
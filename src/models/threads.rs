@@ -3,6 +3,7 @@ use crate::models::projects::ProjectStatus;
 use crate::models::users::{User, UserId};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 
 #[derive(Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(from = "Base62Id")]
@@ -14,7 +15,7 @@ pub struct ThreadId(pub u64);
 #[serde(into = "Base62Id")]
 pub struct ThreadMessageId(pub u64);
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, ToSchema)]
 pub struct Thread {
     pub id: ThreadId,
     #[serde(rename = "type")]
@@ -23,7 +24,7 @@ pub struct Thread {
     pub members: Vec<User>,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, ToSchema)]
 pub struct ThreadMessage {
     pub id: ThreadMessageId,
     pub author_id: Option<UserId>,
@@ -31,7 +32,7 @@ pub struct ThreadMessage {
     pub created: DateTime<Utc>,
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum MessageBody {
     Text {
@@ -49,7 +50,7 @@ pub enum MessageBody {
     Deleted,
 }
 
-#[derive(Serialize, Deserialize, Eq, PartialEq, Copy, Clone)]
+#[derive(Serialize, Deserialize, Eq, PartialEq, Copy, Clone, ToSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum ThreadType {
     Report,
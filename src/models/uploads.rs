@@ -0,0 +1,28 @@
+use super::ids::Base62Id;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use utoipa::ToSchema;
+
+/// The ID of a resumable upload of a version file
+#[derive(Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "Base62Id")]
+#[serde(into = "Base62Id")]
+pub struct UploadSessionId(pub u64);
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct UploadSession {
+    pub id: UploadSessionId,
+    pub file_name: String,
+    /// The size of the file in bytes, as declared when the session was created
+    pub size: u64,
+    /// How many bytes were received so far, which is the offset the next chunk starts at
+    pub received: u64,
+    /// Whether the upload was finalized, after which it can be referenced by a version
+    pub finalized: bool,
+    /// The hashes of the file, once the upload is finalized
+    pub hashes: HashMap<String, String>,
+    pub created: DateTime<Utc>,
+    /// When the session is deleted unless more data is received
+    pub expires: DateTime<Utc>,
+}
@@ -2,6 +2,7 @@ use super::ids::Base62Id;
 use chrono::{DateTime, Utc};
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 
 #[derive(Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(from = "Base62Id")]
@@ -28,13 +29,28 @@ bitflags::bitflags! {
     }
 }
 
+impl<'s> utoipa::ToSchema<'s> for Badges {
+    fn schema() -> (
+        &'s str,
+        utoipa::openapi::RefOr<utoipa::openapi::schema::Schema>,
+    ) {
+        (
+            "Badges",
+            utoipa::openapi::ObjectBuilder::new()
+                .schema_type(utoipa::openapi::SchemaType::Integer)
+                .description(Some("A bitfield of user badges"))
+                .into(),
+        )
+    }
+}
+
 impl Default for Badges {
     fn default() -> Badges {
         Badges::NONE
     }
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
 pub struct User {
     pub id: UserId,
     pub kratos_id: String,
@@ -50,7 +66,7 @@ pub struct User {
     pub payout_data: Option<UserPayoutData>,
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
 pub struct UserPayoutData {
     pub balance: Decimal,
     pub payout_wallet: Option<RecipientWallet>,
@@ -58,7 +74,7 @@ pub struct UserPayoutData {
     pub payout_address: Option<String>,
 }
 
-#[derive(Serialize, Deserialize, Clone, Eq, PartialEq)]
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq, ToSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum RecipientType {
     Email,
@@ -90,7 +106,7 @@ impl RecipientType {
     }
 }
 
-#[derive(Serialize, Deserialize, Clone, Eq, PartialEq)]
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq, ToSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum RecipientWallet {
     Venmo,
@@ -146,7 +162,7 @@ impl From<DBUser> for User {
     }
 }
 
-#[derive(Serialize, Deserialize, PartialEq, Eq, Clone)]
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, ToSchema)]
 #[serde(rename_all = "lowercase")]
 pub enum Role {
     Developer,
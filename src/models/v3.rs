@@ -0,0 +1,171 @@
+//! Response models of the v3 API. These mirror the v2 models, without the fields that v2 only
+//! keeps around for backwards compatibility.
+
+use super::ids::{NotificationId, ProjectId, TeamId, ThreadId, UserId, VersionId};
+use super::notifications::NotificationBody;
+use super::projects::{
+    Dependency, DonationLink, GalleryItem, GameVersion, License, Loader, MonetizationStatus,
+    ProjectStatus, SideType, Thumbnail, VersionFile, VersionStatus, VersionType,
+};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+/// A project returned from the v3 API
+#[derive(Serialize, Clone)]
+pub struct Project {
+    pub id: ProjectId,
+    pub slug: Option<String>,
+    pub project_type: String,
+    pub team: TeamId,
+    pub title: String,
+    pub description: String,
+    pub body: String,
+
+    pub published: DateTime<Utc>,
+    pub updated: DateTime<Utc>,
+    pub approved: Option<DateTime<Utc>>,
+    pub queued: Option<DateTime<Utc>>,
+
+    pub status: ProjectStatus,
+    pub requested_status: Option<ProjectStatus>,
+
+    pub license: License,
+
+    pub client_side: SideType,
+    pub server_side: SideType,
+
+    pub downloads: u32,
+    pub followers: u32,
+
+    pub categories: Vec<String>,
+    pub additional_categories: Vec<String>,
+    pub game_versions: Vec<String>,
+    pub loaders: Vec<String>,
+
+    pub versions: Vec<VersionId>,
+    pub icon_url: Option<String>,
+    pub icon_thumbnails: Vec<Thumbnail>,
+    pub issues_url: Option<String>,
+    pub source_url: Option<String>,
+    pub wiki_url: Option<String>,
+    pub discord_url: Option<String>,
+    pub donation_urls: Option<Vec<DonationLink>>,
+
+    pub gallery: Vec<GalleryItem>,
+
+    pub color: Option<u32>,
+
+    pub thread_id: Option<ThreadId>,
+
+    pub monetization_status: MonetizationStatus,
+}
+
+impl From<super::projects::Project> for Project {
+    fn from(data: super::projects::Project) -> Self {
+        Self {
+            id: data.id,
+            slug: data.slug,
+            project_type: data.project_type,
+            team: data.team,
+            title: data.title,
+            description: data.description,
+            body: data.body,
+            published: data.published,
+            updated: data.updated,
+            approved: data.approved,
+            queued: data.queued,
+            status: data.status,
+            requested_status: data.requested_status,
+            license: data.license,
+            client_side: data.client_side,
+            server_side: data.server_side,
+            downloads: data.downloads,
+            followers: data.followers,
+            categories: data.categories,
+            additional_categories: data.additional_categories,
+            game_versions: data.game_versions,
+            loaders: data.loaders,
+            versions: data.versions,
+            icon_url: data.icon_url,
+            icon_thumbnails: data.icon_thumbnails,
+            issues_url: data.issues_url,
+            source_url: data.source_url,
+            wiki_url: data.wiki_url,
+            discord_url: data.discord_url,
+            donation_urls: data.donation_urls,
+            gallery: data.gallery,
+            color: data.color,
+            thread_id: data.thread_id,
+            monetization_status: data.monetization_status,
+        }
+    }
+}
+
+/// A version returned from the v3 API
+#[derive(Serialize)]
+pub struct Version {
+    pub id: VersionId,
+    pub project_id: ProjectId,
+    pub author_id: UserId,
+    pub featured: bool,
+
+    pub name: String,
+    pub version_number: String,
+    pub changelog: String,
+
+    pub date_published: DateTime<Utc>,
+    pub downloads: u32,
+    pub version_type: VersionType,
+    pub status: VersionStatus,
+    pub requested_status: Option<VersionStatus>,
+
+    pub files: Vec<VersionFile>,
+    pub dependencies: Vec<Dependency>,
+    pub game_versions: Vec<GameVersion>,
+    pub loaders: Vec<Loader>,
+}
+
+impl From<super::projects::Version> for Version {
+    fn from(data: super::projects::Version) -> Self {
+        Self {
+            id: data.id,
+            project_id: data.project_id,
+            author_id: data.author_id,
+            featured: data.featured,
+            name: data.name,
+            version_number: data.version_number,
+            changelog: data.changelog,
+            date_published: data.date_published,
+            downloads: data.downloads,
+            version_type: data.version_type,
+            status: data.status,
+            requested_status: data.requested_status,
+            files: data.files,
+            dependencies: data.dependencies,
+            game_versions: data.game_versions,
+            loaders: data.loaders,
+        }
+    }
+}
+
+/// A notification returned from the v3 API. Its contents are only described by its body.
+#[derive(Serialize)]
+pub struct Notification {
+    pub id: NotificationId,
+    pub user_id: UserId,
+    pub read: bool,
+    pub created: DateTime<Utc>,
+    pub body: NotificationBody,
+}
+
+impl From<super::notifications::Notification> for Notification {
+    fn from(data: super::notifications::Notification) -> Self {
+        Self {
+            id: data.id,
+            user_id: data.user_id,
+            read: data.read,
+            created: data.created,
+            body: data.body,
+        }
+    }
+}
@@ -1,9 +1,92 @@
 use crate::database::models::{DatabaseError, ProjectId, VersionId};
+use chrono::{NaiveDate, Utc};
 use sqlx::PgPool;
+use std::collections::HashMap;
 use tokio::sync::Mutex;
 
+/// A counted download, with where it came from as far as its headers tell
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct Download {
+    pub project_id: ProjectId,
+    pub version_id: VersionId,
+    /// The ISO 3166-1 alpha-2 code of the country the download came from, or `XX` if unknown
+    pub country: String,
+    /// The launcher which downloaded the file, see `launcher_from_user_agent`
+    pub launcher: String,
+}
+
+impl Download {
+    /// A download with its country and launcher taken from the headers of the download request.
+    /// The country is set by Cloudflare in the `CF-IPCountry` header.
+    pub fn from_headers(
+        project_id: ProjectId,
+        version_id: VersionId,
+        headers: &HashMap<String, String>,
+    ) -> Self {
+        let header = |name: &str| {
+            headers
+                .iter()
+                .find(|(key, _)| key.eq_ignore_ascii_case(name))
+                .map(|(_, value)| value.as_str())
+        };
+
+        Download {
+            project_id,
+            version_id,
+            country: country_from_header(header("cf-ipcountry")),
+            launcher: launcher_from_user_agent(header("user-agent")).to_string(),
+        }
+    }
+}
+
+fn country_from_header(country: Option<&str>) -> String {
+    match country {
+        // Cloudflare uses XX for unknown countries and T1 for Tor
+        Some(country)
+            if country.len() == 2
+                && country.chars().all(|x| x.is_ascii_alphabetic())
+                && !country.eq_ignore_ascii_case("T1") =>
+        {
+            country.to_ascii_uppercase()
+        }
+        _ => "XX".to_string(),
+    }
+}
+
+/// The launcher a download came from by its user agent. Only well known launchers are told apart,
+/// so the breakdown stays coarse.
+pub fn launcher_from_user_agent(user_agent: Option<&str>) -> &'static str {
+    let user_agent = match user_agent {
+        Some(user_agent) if !user_agent.trim().is_empty() => user_agent.to_lowercase(),
+        _ => return "unknown",
+    };
+
+    const LAUNCHERS: &[(&str, &str)] = &[
+        ("theseus", "modrinth-app"),
+        ("modrinth-app", "modrinth-app"),
+        ("prismlauncher", "prism"),
+        ("polymc", "polymc"),
+        ("multimc", "multimc"),
+        ("atlauncher", "atlauncher"),
+        ("ferium", "ferium"),
+        ("packwiz", "packwiz"),
+    ];
+
+    LAUNCHERS
+        .iter()
+        .find(|(pattern, _)| user_agent.contains(pattern))
+        .map(|(_, launcher)| *launcher)
+        .unwrap_or_else(|| {
+            if user_agent.starts_with("mozilla/") {
+                "browser"
+            } else {
+                "other"
+            }
+        })
+}
+
 pub struct DownloadQueue {
-    queue: Mutex<Vec<(ProjectId, VersionId)>>,
+    queue: Mutex<Vec<(Download, NaiveDate)>>,
 }
 
 // Batches download transactions every thirty seconds
@@ -13,38 +96,67 @@ impl DownloadQueue {
             queue: Mutex::new(Vec::with_capacity(1000)),
         }
     }
-    pub async fn add(&self, project_id: ProjectId, version_id: VersionId) {
-        self.queue.lock().await.push((project_id, version_id));
+    pub async fn add(&self, download: Download) {
+        self.queue
+            .lock()
+            .await
+            .push((download, Utc::now().date_naive()));
     }
 
-    pub async fn take(&self) -> Vec<(ProjectId, VersionId)> {
+    pub async fn take(&self) -> Vec<(Download, NaiveDate)> {
         let mut queue = self.queue.lock().await;
         let len = queue.len();
 
         std::mem::replace(&mut queue, Vec::with_capacity(len))
     }
 
+    /// Adds the queued downloads to the download counts, and to the download analytics of the day
+    /// they were counted on
     pub async fn index(&self, pool: &PgPool) -> Result<(), DatabaseError> {
         let queue = self.take().await;
 
         if !queue.is_empty() {
+            let mut downloads: HashMap<(Download, NaiveDate), i32> = HashMap::new();
+            for download in queue {
+                *downloads.entry(download).or_default() += 1;
+            }
+
             let mut transaction = pool.begin().await?;
 
-            for (project_id, version_id) in queue {
+            for ((download, day), count) in downloads {
                 sqlx::query!(
                     "UPDATE versions
-                    SET downloads = downloads + 1
-                    WHERE (id = $1)",
-                    version_id as VersionId
+                    SET downloads = downloads + $1
+                    WHERE (id = $2)",
+                    count,
+                    download.version_id as VersionId
                 )
                 .execute(&mut *transaction)
                 .await?;
 
                 sqlx::query!(
                     "UPDATE mods
-                    SET downloads = downloads + 1
-                    WHERE (id = $1)",
-                    project_id as ProjectId
+                    SET downloads = downloads + $1
+                    WHERE (id = $2)",
+                    count,
+                    download.project_id as ProjectId
+                )
+                .execute(&mut *transaction)
+                .await?;
+
+                sqlx::query!(
+                    "
+                    INSERT INTO download_analytics (mod_id, version_id, day, country, launcher, downloads)
+                    VALUES ($1, $2, $3, $4, $5, $6)
+                    ON CONFLICT (mod_id, version_id, day, country, launcher) DO UPDATE
+                    SET downloads = download_analytics.downloads + EXCLUDED.downloads
+                    ",
+                    download.project_id as ProjectId,
+                    download.version_id as VersionId,
+                    day,
+                    download.country,
+                    download.launcher,
+                    count,
                 )
                 .execute(&mut *transaction)
                 .await?;
@@ -56,3 +168,35 @@ impl DownloadQueue {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn downloads_are_classified_from_headers() {
+        let headers = HashMap::from([
+            ("CF-IPCountry".to_string(), "de".to_string()),
+            (
+                "User-Agent".to_string(),
+                "PrismLauncher/7.1 (+https://prismlauncher.org/)".to_string(),
+            ),
+        ]);
+
+        let download = Download::from_headers(ProjectId(1), VersionId(2), &headers);
+        assert_eq!(download.country, "DE");
+        assert_eq!(download.launcher, "prism");
+
+        let download = Download::from_headers(ProjectId(1), VersionId(2), &HashMap::new());
+        assert_eq!(download.country, "XX");
+        assert_eq!(download.launcher, "unknown");
+
+        assert_eq!(country_from_header(Some("T1")), "XX");
+        assert_eq!(country_from_header(Some("USA")), "XX");
+        assert_eq!(
+            launcher_from_user_agent(Some("Mozilla/5.0 (X11; Linux x86_64)")),
+            "browser"
+        );
+        assert_eq!(launcher_from_user_agent(Some("curl/8.0")), "other");
+    }
+}
@@ -1,2 +1,4 @@
 pub mod download;
+pub mod pats;
 pub mod payouts;
+pub mod scan;
@@ -0,0 +1,74 @@
+use crate::database::models::{DatabaseError, PatId};
+use chrono::{NaiveDateTime, Utc};
+use sqlx::PgPool;
+use std::collections::HashMap;
+use tokio::sync::Mutex;
+
+pub struct PatUsage {
+    pub uses: i64,
+    pub last_used_at: NaiveDateTime,
+    pub last_used_ip: Option<String>,
+}
+
+pub struct PatQueue {
+    queue: Mutex<HashMap<PatId, PatUsage>>,
+}
+
+// Batches PAT usage updates every thirty seconds
+impl PatQueue {
+    pub fn new() -> Self {
+        PatQueue {
+            queue: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub async fn add(&self, pat_id: PatId, ip_addr: Option<String>) {
+        let now = Utc::now().naive_utc();
+        let mut queue = self.queue.lock().await;
+
+        let usage = queue.entry(pat_id).or_insert(PatUsage {
+            uses: 0,
+            last_used_at: now,
+            last_used_ip: None,
+        });
+        usage.uses += 1;
+        usage.last_used_at = now;
+        if ip_addr.is_some() {
+            usage.last_used_ip = ip_addr;
+        }
+    }
+
+    pub async fn take(&self) -> HashMap<PatId, PatUsage> {
+        let mut queue = self.queue.lock().await;
+        std::mem::take(&mut *queue)
+    }
+
+    pub async fn index(&self, pool: &PgPool) -> Result<(), DatabaseError> {
+        let queue = self.take().await;
+
+        if !queue.is_empty() {
+            let mut transaction = pool.begin().await?;
+
+            for (pat_id, usage) in queue {
+                sqlx::query!(
+                    "
+                    UPDATE pats
+                    SET usage_count = usage_count + $1, last_used_at = $2,
+                        last_used_ip = COALESCE($3, last_used_ip)
+                    WHERE id = $4
+                    ",
+                    usage.uses,
+                    usage.last_used_at,
+                    usage.last_used_ip,
+                    pat_id as PatId
+                )
+                .execute(&mut *transaction)
+                .await?;
+            }
+
+            transaction.commit().await?;
+        }
+
+        Ok(())
+    }
+}
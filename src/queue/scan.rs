@@ -0,0 +1,282 @@
+use crate::database::models::thread_item::ThreadMessageBuilder;
+use crate::database::models::version_item::insert_file_findings;
+use crate::database::models::{DatabaseError, FileId, ThreadId};
+use crate::file_hosting::{FileHost, FileHostingError, SpooledFile};
+use crate::models::projects::VersionStatus;
+use crate::models::threads::MessageBody;
+use crate::util::env::parse_var;
+use crate::util::storage_audit::version_file_path;
+use crate::validate::scan::{scan_file, ScanRules, ScanVerdict};
+use chrono::{DateTime, Duration, Utc};
+use log::warn;
+use sqlx::PgPool;
+use std::sync::Arc;
+use thiserror::Error;
+use tokio::sync::Mutex;
+
+#[derive(Error, Debug)]
+pub enum ScanningError {
+    #[error("Database error while storing scan results: {0}")]
+    Database(#[from] DatabaseError),
+    #[error("Error while managing threads")]
+    Blocking(#[from] actix_web::error::BlockingError),
+    #[error("Unable to read uploaded file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Unable to download stored file: {0}")]
+    FileHosting(#[from] FileHostingError),
+}
+
+impl From<sqlx::Error> for ScanningError {
+    fn from(error: sqlx::Error) -> Self {
+        ScanningError::Database(error.into())
+    }
+}
+
+struct PendingScan {
+    /// The SHA1 hash of the file as stored in the database, which finds it once the upload is
+    /// committed
+    sha1: Vec<u8>,
+    file: SpooledFile,
+    queued: DateTime<Utc>,
+}
+
+pub struct ScanQueue {
+    rules: Arc<ScanRules>,
+    queue: Mutex<Vec<PendingScan>>,
+    /// The ID of the last stored file which was picked up, so files which keep failing to scan do
+    /// not hold up the others
+    stored_cursor: Mutex<i64>,
+}
+
+// Scans uploaded files for malware in the background, withholding the versions of flagged files
+impl ScanQueue {
+    pub fn new(rules: ScanRules) -> Self {
+        ScanQueue {
+            rules: Arc::new(rules),
+            queue: Mutex::new(Vec::new()),
+            stored_cursor: Mutex::new(0),
+        }
+    }
+
+    pub async fn add(&self, sha1: Vec<u8>, file: SpooledFile) {
+        self.queue.lock().await.push(PendingScan {
+            sha1,
+            file,
+            queued: Utc::now(),
+        });
+    }
+
+    async fn take(&self) -> Vec<PendingScan> {
+        std::mem::take(&mut *self.queue.lock().await)
+    }
+
+    /// Scans the queued files, and a batch of the stored files which were never scanned, such as
+    /// files whose scan was lost to a restart. Scans which fail are retried on the next run.
+    pub async fn index(
+        &self,
+        pool: &PgPool,
+        file_host: &(dyn FileHost + Send + Sync),
+    ) -> Result<(), ScanningError> {
+        let mut pending = Vec::new();
+
+        for scan in self.take().await {
+            // Uploads which are not committed after ten minutes failed, and ones which keep
+            // failing to scan are left to be picked up from storage
+            let retry = match self.scan(&scan.sha1, &scan.file, pool).await {
+                Ok(true) => false,
+                Ok(false) => Utc::now() - scan.queued < Duration::minutes(10),
+                Err(e) => {
+                    warn!("Scanning an uploaded file failed: {:?}", e);
+                    Utc::now() - scan.queued < Duration::minutes(10)
+                }
+            };
+
+            if retry {
+                pending.push(scan);
+            }
+        }
+
+        let pending_hashes = pending.iter().map(|x| x.sha1.clone()).collect::<Vec<_>>();
+        self.queue.lock().await.extend(pending);
+
+        self.scan_stored(&pending_hashes, pool, file_host).await
+    }
+
+    // Downloads and scans stored files which were never scanned, other than the queued ones. Files
+    // which fail to scan are left unscanned, so they are picked up again once the cursor wraps.
+    async fn scan_stored(
+        &self,
+        queued: &[Vec<u8>],
+        pool: &PgPool,
+        file_host: &(dyn FileHost + Send + Sync),
+    ) -> Result<(), ScanningError> {
+        let mut cursor = self.stored_cursor.lock().await;
+        let batch_size = stored_batch_size();
+
+        let files = sqlx::query!(
+            "
+            SELECT f.id, f.url, h.hash sha1, b.path blob_path, b.host_file_id blob_host_file_id
+            FROM files f
+            INNER JOIN hashes h ON h.file_id = f.id AND h.algorithm = 'sha1'
+            LEFT OUTER JOIN blobs b ON b.sha512 = f.blob
+            WHERE f.scan_verdict IS NULL AND f.id > $1 AND NOT h.hash = ANY($2)
+            ORDER BY f.id
+            LIMIT $3
+            ",
+            *cursor,
+            queued,
+            batch_size,
+        )
+        .fetch_all(pool)
+        .await?;
+
+        *cursor = match files.last() {
+            Some(file) if files.len() as i64 == batch_size => file.id,
+            _ => 0,
+        };
+        drop(cursor);
+
+        for file in files {
+            // Files uploaded before blobs were introduced are stored at the path of their URL, and
+            // the ID the file host gave them was not kept
+            let (path, host_file_id) = match (file.blob_path, file.blob_host_file_id) {
+                (Some(path), host_file_id) => (path, host_file_id),
+                _ => match version_file_path(&file.url) {
+                    Some(path) => (path, None),
+                    None => continue,
+                },
+            };
+
+            let result = match file_host
+                .download_file(host_file_id.as_deref(), &path)
+                .await
+            {
+                Ok(downloaded) => self.scan(&file.sha1, &downloaded, pool).await,
+                Err(e) => Err(e.into()),
+            };
+
+            if let Err(e) = result {
+                warn!("Scanning stored file {} failed: {:?}", path, e);
+            }
+        }
+
+        Ok(())
+    }
+
+    // Scans a file and stores the verdict on the unscanned files with its hash, withholding their
+    // versions if it is flagged. Returns whether there were any such files.
+    async fn scan(
+        &self,
+        sha1: &[u8],
+        file: &SpooledFile,
+        pool: &PgPool,
+    ) -> Result<bool, ScanningError> {
+        let files = sqlx::query!(
+            "
+            SELECT f.id, f.filename, v.id version_id, v.version_number, m.thread_id
+            FROM hashes h
+            INNER JOIN files f ON h.file_id = f.id
+            INNER JOIN versions v ON f.version_id = v.id
+            INNER JOIN mods m ON v.mod_id = m.id
+            WHERE h.algorithm = 'sha1' AND h.hash = $1 AND f.scan_verdict IS NULL
+            ",
+            sha1,
+        )
+        .fetch_all(pool)
+        .await?;
+
+        if files.is_empty() {
+            return Ok(false);
+        }
+
+        let rules = self.rules.clone();
+        let (file_sha1, file_sha512) = (file.sha1.clone(), file.sha512.clone());
+        let reader = file.open()?;
+        let report =
+            actix_web::web::block(move || scan_file(reader, &file_sha1, &file_sha512, &rules))
+                .await?;
+
+        let mut transaction = pool.begin().await?;
+
+        for file in files {
+            sqlx::query!(
+                "
+                UPDATE files
+                SET scan_verdict = $1
+                WHERE id = $2
+                ",
+                report.verdict.as_str(),
+                file.id,
+            )
+            .execute(&mut *transaction)
+            .await?;
+
+            insert_file_findings(FileId(file.id), report.findings.clone(), &mut transaction)
+                .await?;
+
+            if report.verdict == ScanVerdict::Clean {
+                continue;
+            }
+
+            sqlx::query!(
+                "
+                UPDATE versions
+                SET status = $1
+                WHERE id = $2
+                ",
+                VersionStatus::Withheld.as_str(),
+                file.version_id,
+            )
+            .execute(&mut *transaction)
+            .await?;
+
+            if let Some(thread_id) = file.thread_id {
+                ThreadMessageBuilder {
+                    author_id: None,
+                    body: MessageBody::Text {
+                        body: format!(
+                            "Version {} was withheld, as the malware scan found {} to be {}:\n\n{}",
+                            file.version_number,
+                            file.filename,
+                            report.verdict.as_str(),
+                            report
+                                .findings
+                                .iter()
+                                .map(|x| match &x.path {
+                                    Some(path) => format!("- `{}` {}: {}", x.code, path, x.message),
+                                    None => format!("- `{}` {}", x.code, x.message),
+                                })
+                                .collect::<Vec<_>>()
+                                .join("\n")
+                        ),
+                        private: true,
+                        replying_to: None,
+                    },
+                    thread_id: ThreadId(thread_id),
+                }
+                .insert(&mut transaction)
+                .await?;
+
+                sqlx::query!(
+                    "
+                    UPDATE threads
+                    SET show_in_mod_inbox = TRUE
+                    WHERE id = $1
+                    ",
+                    thread_id,
+                )
+                .execute(&mut *transaction)
+                .await?;
+            }
+        }
+
+        transaction.commit().await?;
+
+        Ok(true)
+    }
+}
+
+/// How many stored files which were never scanned are scanned each run (defaults to 16)
+fn stored_batch_size() -> i64 {
+    parse_var("SCAN_STORED_BATCH_SIZE").unwrap_or(16)
+}
@@ -0,0 +1,148 @@
+use crate::file_hosting::LocalHost;
+use crate::models::ids::base62_impl::parse_base62;
+use crate::models::projects::{ProjectStatus, VersionStatus};
+use crate::routes::ApiError;
+use actix_web::http::header::{HeaderValue, CACHE_CONTROL};
+use actix_web::{get, web, HttpRequest, HttpResponse};
+use serde::Deserialize;
+use sqlx::PgPool;
+use std::sync::Arc;
+use utoipa::IntoParams;
+
+// Files are only served here when the local file host is used (STORAGE_BACKEND=local), otherwise
+// they are served by the CDN of the file host
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(file_get);
+}
+
+#[derive(Deserialize, IntoParams)]
+pub struct SignedUrl {
+    /// When the signed URL expires, as a Unix timestamp
+    pub expires: Option<i64>,
+    /// The hex encoded signature of the URL
+    pub signature: Option<String>,
+}
+
+/// Serves a file stored by the local file host, supporting range requests and conditional
+/// requests. Files of hidden versions require a signed URL if a signing key is set.
+#[utoipa::path(
+    path = "/files/{file_name}",
+    tag = "files",
+    params(
+        ("file_name" = String, Path, description = "The path of the file, relative to the CDN URL"),
+        SignedUrl,
+    ),
+    responses(
+        (status = 200, description = "The file"),
+        (status = 206, description = "The requested range of the file"),
+        (status = 304, description = "The file was not modified"),
+        (status = 404, description = "The file does not exist, or the signed URL is missing, invalid or expired"),
+    )
+)]
+#[get("{file_name:.*}")]
+pub async fn file_get(
+    req: HttpRequest,
+    info: web::Path<(String,)>,
+    query: web::Query<SignedUrl>,
+    pool: web::Data<PgPool>,
+    local_host: web::Data<Option<Arc<LocalHost>>>,
+) -> Result<HttpResponse, ApiError> {
+    let local_host = match &**local_host {
+        Some(local_host) => local_host,
+        None => return Ok(HttpResponse::NotFound().body("")),
+    };
+
+    let file_name = info.into_inner().0;
+
+    let path = match local_host.resolve(&file_name) {
+        Ok(path) => path,
+        Err(_) => return Ok(HttpResponse::NotFound().body("")),
+    };
+
+    let private = local_host.signs_urls() && is_private_file(&file_name, &pool).await?;
+
+    if private {
+        let signed = match (query.expires, &query.signature) {
+            (Some(expires), Some(signature)) => local_host.verify(&file_name, expires, signature),
+            _ => false,
+        };
+
+        // Hidden files are reported as missing, the same as for routes returning hidden versions
+        if !signed {
+            return Ok(HttpResponse::NotFound().body(""));
+        }
+    }
+
+    let file = match actix_files::NamedFile::open_async(&path).await {
+        Ok(file) => file,
+        Err(_) => return Ok(HttpResponse::NotFound().body("")),
+    };
+
+    let mut response = file
+        .use_etag(true)
+        .use_last_modified(true)
+        .into_response(&req);
+
+    // Files of hidden versions must not be kept by shared caches past the expiry of their URL
+    response.headers_mut().insert(
+        CACHE_CONTROL,
+        HeaderValue::from_static(if private {
+            "private, no-store"
+        } else {
+            "public, max-age=3600"
+        }),
+    );
+
+    Ok(response)
+}
+
+// Version files are private if they are only used by hidden versions or versions of hidden
+// projects. Files which are not version files, such as icons and gallery images, are public.
+async fn is_private_file(file_name: &str, pool: &PgPool) -> Result<bool, ApiError> {
+    // The components are compared as the file host resolves them, so `data//blobs` can't be used
+    // to get around the check
+    let parts = std::path::Path::new(file_name)
+        .components()
+        .filter_map(|x| match x {
+            std::path::Component::Normal(part) => part.to_str(),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+
+    let (blob, version_id) = match parts.as_slice() {
+        ["data", "blobs", sha512, _] => (Some(sha512.to_string()), None),
+        ["data", _, "versions", version_id, _] => match parse_base62(version_id) {
+            Ok(version_id) => (None, Some(version_id as i64)),
+            Err(_) => return Ok(false),
+        },
+        _ => return Ok(false),
+    };
+
+    let result = sqlx::query!(
+        "
+        SELECT
+            EXISTS(SELECT 1 FROM files f WHERE f.blob = $1 OR f.version_id = $2) referenced,
+            EXISTS(
+                SELECT 1 FROM files f
+                INNER JOIN versions v ON v.id = f.version_id
+                INNER JOIN mods m ON m.id = v.mod_id
+                WHERE (f.blob = $1 OR f.version_id = $2)
+                AND NOT v.status = ANY($3) AND NOT m.status = ANY($4)
+            ) visible
+        ",
+        blob,
+        version_id,
+        &*VersionStatus::iterator()
+            .filter(|x| x.is_hidden())
+            .map(|x| x.to_string())
+            .collect::<Vec<String>>(),
+        &*ProjectStatus::iterator()
+            .filter(|x| x.is_hidden())
+            .map(|x| x.to_string())
+            .collect::<Vec<String>>(),
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(result.referenced.unwrap_or(false) && !result.visible.unwrap_or(false))
+}
@@ -1,12 +1,16 @@
 use crate::database::models::project_item::QueryProject;
 use crate::database::models::version_item::{QueryFile, QueryVersion};
+use crate::file_hosting::ReplicatedHost;
+use crate::models::pats::Scopes;
 use crate::models::projects::{ProjectId, VersionId};
+use crate::queue::pats::PatQueue;
 use crate::routes::ApiError;
 use crate::util::auth::{get_user_from_headers, is_authorized_version};
 use crate::{database, util::auth::is_authorized};
 use actix_web::{get, route, web, HttpRequest, HttpResponse};
 use sqlx::PgPool;
 use std::collections::HashSet;
+use std::sync::Arc;
 use yaserde_derive::YaSerialize;
 
 pub fn config(cfg: &mut web::ServiceConfig) {
@@ -61,11 +65,22 @@ pub struct MavenPom {
     description: String,
 }
 
+#[utoipa::path(
+    path = "/maven/maven/modrinth/{id}/maven-metadata.xml",
+    tag = "maven",
+    security((), ("token" = [])),
+    params(("id" = String, Path, description = "The ID or slug of the project")),
+    responses(
+        (status = 200, description = "The maven metadata of the project", content_type = "text/xml"),
+        (status = 404, description = "The project does not exist or is not visible"),
+    )
+)]
 #[get("maven/modrinth/{id}/maven-metadata.xml")]
 pub async fn maven_metadata(
     req: HttpRequest,
     params: web::Path<(String,)>,
     pool: web::Data<PgPool>,
+    pat_queue: web::Data<Arc<PatQueue>>,
 ) -> Result<HttpResponse, ApiError> {
     let project_id = params.into_inner().0;
     let project_data =
@@ -77,7 +92,9 @@ pub async fn maven_metadata(
         return Ok(HttpResponse::NotFound().body(""));
     };
 
-    let user_option = get_user_from_headers(req.headers(), &**pool).await.ok();
+    let user_option = get_user_from_headers(&req, &**pool, &pat_queue, Scopes::VERSION_READ)
+        .await
+        .ok();
 
     if !is_authorized(&data, &user_option, &pool).await? {
         return Ok(HttpResponse::NotFound().body(""));
@@ -176,6 +193,22 @@ fn find_file<'a>(
     }
 }
 
+#[utoipa::path(
+    get,
+    path = "/maven/maven/modrinth/{id}/{versionnum}/{file}",
+    tag = "maven",
+    security((), ("token" = [])),
+    params(
+        ("id" = String, Path, description = "The ID or slug of the project"),
+        ("versionnum" = String, Path, description = "The ID or version number of the version"),
+        ("file" = String, Path, description = "The file name, or the generated pom of the version"),
+    ),
+    responses(
+        (status = 200, description = "The generated pom of the version", content_type = "text/xml"),
+        (status = 307, description = "A redirect to the file on the CDN"),
+        (status = 404, description = "The version or file does not exist or is not visible"),
+    )
+)]
 #[route(
     "maven/modrinth/{id}/{versionnum}/{file}",
     method = "GET",
@@ -185,6 +218,8 @@ pub async fn version_file(
     req: HttpRequest,
     params: web::Path<(String, String, String)>,
     pool: web::Data<PgPool>,
+    pat_queue: web::Data<Arc<PatQueue>>,
+    replicated_host: web::Data<Option<Arc<ReplicatedHost>>>,
 ) -> Result<HttpResponse, ApiError> {
     let (project_id, vnum, file) = params.into_inner();
     let project_data =
@@ -196,7 +231,9 @@ pub async fn version_file(
         return Ok(HttpResponse::NotFound().body(""));
     };
 
-    let user_option = get_user_from_headers(req.headers(), &**pool).await.ok();
+    let user_option = get_user_from_headers(&req, &**pool, &pat_queue, Scopes::VERSION_READ)
+        .await
+        .ok();
 
     if !is_authorized(&project.inner, &user_option, &pool).await? {
         return Ok(HttpResponse::NotFound().body(""));
@@ -253,19 +290,39 @@ pub async fn version_file(
             .content_type("text/xml")
             .body(yaserde::ser::to_string(&respdata).map_err(ApiError::Xml)?));
     } else if let Some(selected_file) = find_file(&project_id, &project, &version, &file) {
+        let url = match &**replicated_host {
+            Some(replicated_host) => replicated_host.download_url(&selected_file.url).await?,
+            None => selected_file.url.clone(),
+        };
+
         return Ok(HttpResponse::TemporaryRedirect()
-            .append_header(("location", &*selected_file.url))
+            .append_header(("location", &*url))
             .body(""));
     }
 
     Ok(HttpResponse::NotFound().body(""))
 }
 
+#[utoipa::path(
+    path = "/maven/maven/modrinth/{id}/{versionnum}/{file}.sha1",
+    tag = "maven",
+    security((), ("token" = [])),
+    params(
+        ("id" = String, Path, description = "The ID or slug of the project"),
+        ("versionnum" = String, Path, description = "The ID or version number of the version"),
+        ("file" = String, Path, description = "The file name"),
+    ),
+    responses(
+        (status = 200, description = "The SHA1 hash of the file", content_type = "text/plain"),
+        (status = 404, description = "The version or file does not exist or is not visible"),
+    )
+)]
 #[get("maven/modrinth/{id}/{versionnum}/{file}.sha1")]
 pub async fn version_file_sha1(
     req: HttpRequest,
     params: web::Path<(String, String, String)>,
     pool: web::Data<PgPool>,
+    pat_queue: web::Data<Arc<PatQueue>>,
 ) -> Result<HttpResponse, ApiError> {
     let (project_id, vnum, file) = params.into_inner();
     let project_data =
@@ -277,7 +334,9 @@ pub async fn version_file_sha1(
         return Ok(HttpResponse::NotFound().body(""));
     };
 
-    let user_option = get_user_from_headers(req.headers(), &**pool).await.ok();
+    let user_option = get_user_from_headers(&req, &**pool, &pat_queue, Scopes::VERSION_READ)
+        .await
+        .ok();
 
     if !is_authorized(&project.inner, &user_option, &pool).await? {
         return Ok(HttpResponse::NotFound().body(""));
@@ -316,11 +375,26 @@ pub async fn version_file_sha1(
         .unwrap_or_else(|| HttpResponse::NotFound().body("")))
 }
 
+#[utoipa::path(
+    path = "/maven/maven/modrinth/{id}/{versionnum}/{file}.sha512",
+    tag = "maven",
+    security((), ("token" = [])),
+    params(
+        ("id" = String, Path, description = "The ID or slug of the project"),
+        ("versionnum" = String, Path, description = "The ID or version number of the version"),
+        ("file" = String, Path, description = "The file name"),
+    ),
+    responses(
+        (status = 200, description = "The SHA512 hash of the file", content_type = "text/plain"),
+        (status = 404, description = "The version or file does not exist or is not visible"),
+    )
+)]
 #[get("maven/modrinth/{id}/{versionnum}/{file}.sha512")]
 pub async fn version_file_sha512(
     req: HttpRequest,
     params: web::Path<(String, String, String)>,
     pool: web::Data<PgPool>,
+    pat_queue: web::Data<Arc<PatQueue>>,
 ) -> Result<HttpResponse, ApiError> {
     let (project_id, vnum, file) = params.into_inner();
     let project_data =
@@ -332,7 +406,9 @@ pub async fn version_file_sha512(
         return Ok(HttpResponse::NotFound().body(""));
     };
 
-    let user_option = get_user_from_headers(req.headers(), &**pool).await.ok();
+    let user_option = get_user_from_headers(&req, &**pool, &pat_queue, Scopes::VERSION_READ)
+        .await
+        .ok();
 
     if !is_authorized(&project.inner, &user_option, &pool).await? {
         return Ok(HttpResponse::NotFound().body(""));
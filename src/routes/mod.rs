@@ -6,10 +6,12 @@ use futures::FutureExt;
 pub mod v2;
 pub mod v3;
 
+mod files;
 mod health;
 mod index;
 mod maven;
 mod not_found;
+mod openapi;
 mod updates;
 
 pub use self::not_found::not_found;
@@ -17,6 +19,8 @@ pub use self::not_found::not_found;
 pub fn root_config(cfg: &mut web::ServiceConfig) {
     cfg.service(index::index_get);
     cfg.service(health::health_get);
+    cfg.service(openapi::openapi_get);
+    cfg.service(web::scope("files").configure(files::config));
     cfg.service(web::scope("maven").configure(maven::config));
     cfg.service(web::scope("updates").configure(updates::config));
     cfg.service(
@@ -70,6 +74,10 @@ pub enum ApiError {
     Decoding(#[from] crate::models::ids::DecodingError),
     #[error("Image Parsing Error: {0}")]
     ImageError(#[from] image::ImageError),
+    #[error("Error while writing archive: {0}")]
+    Zip(#[from] zip::result::ZipError),
+    #[error("The requested item was not found")]
+    NotFound,
 }
 
 impl actix_web::ResponseError for ApiError {
@@ -93,6 +101,8 @@ impl actix_web::ResponseError for ApiError {
             ApiError::DiscordError(..) => StatusCode::FAILED_DEPENDENCY,
             ApiError::Decoding(..) => StatusCode::BAD_REQUEST,
             ApiError::ImageError(..) => StatusCode::BAD_REQUEST,
+            ApiError::Zip(..) => StatusCode::INTERNAL_SERVER_ERROR,
+            ApiError::NotFound => StatusCode::NOT_FOUND,
         }
     }
 
@@ -117,6 +127,8 @@ impl actix_web::ResponseError for ApiError {
                 ApiError::DiscordError(..) => "discord_error",
                 ApiError::Decoding(..) => "decoding_error",
                 ApiError::ImageError(..) => "invalid_image",
+                ApiError::Zip(..) => "archive_error",
+                ApiError::NotFound => "not_found",
             },
             description: &self.to_string(),
         })
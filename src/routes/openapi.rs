@@ -0,0 +1,146 @@
+use super::{files, maven, updates, v2};
+use actix_web::{get, HttpResponse};
+use utoipa::openapi::security::{ApiKey, ApiKeyValue, SecurityScheme};
+use utoipa::{Modify, OpenApi};
+
+/// The OpenAPI document for the routes served by labrinth, generated from the route handlers
+/// and the request and response types they use
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "Labrinth",
+        description = "The API behind Modrinth. Routes without a security requirement can be called anonymously."
+    ),
+    paths(
+        files::file_get,
+        maven::maven_metadata,
+        maven::version_file_sha1,
+        maven::version_file_sha512,
+        maven::version_file,
+        updates::forge_updates,
+    ),
+    components(schemas(crate::models::error::ApiError)),
+    modifiers(&SecuritySchemes)
+)]
+pub struct ApiDoc;
+
+// Personal access tokens, OAuth access tokens and sessions are all sent as the raw value of the
+// Authorization header, without a scheme
+struct SecuritySchemes;
+
+impl Modify for SecuritySchemes {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        if let Some(components) = openapi.components.as_mut() {
+            components.add_security_scheme(
+                "token",
+                SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::with_description(
+                    "Authorization",
+                    "A personal access token, OAuth access token or session token",
+                ))),
+            );
+        }
+    }
+}
+
+pub fn openapi() -> utoipa::openapi::OpenApi {
+    let mut openapi = ApiDoc::openapi();
+    openapi.merge(v2::ApiDoc::openapi());
+    openapi
+}
+
+#[get("openapi.json")]
+pub async fn openapi_get() -> HttpResponse {
+    HttpResponse::Ok().json(openapi())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn collect_refs(value: &serde_json::Value, refs: &mut Vec<String>) {
+        match value {
+            serde_json::Value::Object(map) => {
+                for (key, value) in map {
+                    match (key.as_str(), value) {
+                        ("$ref", serde_json::Value::String(reference)) => {
+                            refs.push(reference.clone())
+                        }
+                        _ => collect_refs(value, refs),
+                    }
+                }
+            }
+            serde_json::Value::Array(values) => {
+                values.iter().for_each(|value| collect_refs(value, refs))
+            }
+            _ => {}
+        }
+    }
+
+    #[test]
+    fn every_schema_reference_is_defined() {
+        let spec = serde_json::to_value(openapi()).unwrap();
+
+        let mut refs = Vec::new();
+        collect_refs(&spec, &mut refs);
+
+        let missing = refs
+            .into_iter()
+            .filter(|reference| {
+                let name = reference.trim_start_matches("#/components/schemas/");
+                spec["components"]["schemas"].get(name).is_none()
+            })
+            .collect::<std::collections::HashSet<_>>();
+
+        assert!(missing.is_empty(), "undefined schemas: {:?}", missing);
+    }
+
+    #[test]
+    fn every_operation_has_a_unique_id() {
+        let spec = serde_json::to_value(openapi()).unwrap();
+
+        let mut ids = std::collections::HashSet::new();
+        for (path, item) in spec["paths"].as_object().unwrap() {
+            for (method, operation) in item.as_object().unwrap() {
+                let id = operation["operationId"].as_str().unwrap().to_string();
+                assert!(
+                    ids.insert(id.clone()),
+                    "{} {} reuses operation id {}",
+                    method,
+                    path,
+                    id
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn path_parameters_match_the_path_template() {
+        let spec = serde_json::to_value(openapi()).unwrap();
+
+        for (path, item) in spec["paths"].as_object().unwrap() {
+            let mut expected = path
+                .split('{')
+                .skip(1)
+                .filter_map(|rest| rest.split('}').next())
+                .collect::<Vec<_>>();
+            expected.sort_unstable();
+
+            for (method, operation) in item.as_object().unwrap() {
+                let mut declared = operation["parameters"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter(|parameter| parameter["in"] == "path")
+                    .map(|parameter| parameter["name"].as_str().unwrap())
+                    .collect::<Vec<_>>();
+                declared.sort_unstable();
+
+                assert_eq!(
+                    expected, declared,
+                    "{} {} declares the wrong path parameters",
+                    method, path
+                );
+            }
+        }
+    }
+}
@@ -5,8 +5,11 @@ use serde::Serialize;
 use sqlx::PgPool;
 
 use crate::database;
+use crate::models::pats::Scopes;
 use crate::models::projects::VersionType;
+use crate::queue::pats::PatQueue;
 use crate::util::auth::{filter_authorized_versions, get_user_from_headers, is_authorized};
+use std::sync::Arc;
 
 use super::ApiError;
 
@@ -14,11 +17,22 @@ pub fn config(cfg: &mut web::ServiceConfig) {
     cfg.service(forge_updates);
 }
 
+#[utoipa::path(
+    path = "/updates/{id}/forge_updates.json",
+    tag = "updates",
+    security((), ("token" = [])),
+    params(("id" = String, Path, description = "The ID or slug of the project")),
+    responses(
+        (status = 200, description = "The Forge update checker JSON of the project"),
+        (status = 400, description = "The project does not exist or is not visible", body = ApiError),
+    )
+)]
 #[get("{id}/forge_updates.json")]
 pub async fn forge_updates(
     req: HttpRequest,
     info: web::Path<(String,)>,
     pool: web::Data<PgPool>,
+    pat_queue: web::Data<Arc<PatQueue>>,
 ) -> Result<HttpResponse, ApiError> {
     const ERROR: &str = "The specified project does not exist!";
 
@@ -28,7 +42,9 @@ pub async fn forge_updates(
         .await?
         .ok_or_else(|| ApiError::InvalidInput(ERROR.to_string()))?;
 
-    let user_option = get_user_from_headers(req.headers(), &**pool).await.ok();
+    let user_option = get_user_from_headers(&req, &**pool, &pat_queue, Scopes::VERSION_READ)
+        .await
+        .ok();
 
     if !is_authorized(&project, &user_option, &pool).await? {
         return Err(ApiError::InvalidInput(ERROR.to_string()));
@@ -1,9 +1,11 @@
+use crate::database::models::audit_finding_item::{AuditFinding, AuditFindingKind};
 use crate::models::ids::ProjectId;
 use crate::models::projects::MonetizationStatus;
+use crate::queue::download::Download;
 use crate::routes::ApiError;
 use crate::util::guards::admin_key_guard;
 use crate::DownloadQueue;
-use actix_web::{patch, post, web, HttpResponse};
+use actix_web::{get, patch, post, web, HttpResponse};
 use chrono::{DateTime, SecondsFormat, Utc};
 use rust_decimal::Decimal;
 use serde::Deserialize;
@@ -16,7 +18,8 @@ pub fn config(cfg: &mut web::ServiceConfig) {
     cfg.service(
         web::scope("admin")
             .service(count_download)
-            .service(process_payout),
+            .service(process_payout)
+            .service(storage_audit),
     );
 }
 
@@ -76,28 +79,36 @@ pub async fn count_download(
 
     if file_type.is_none() {
         download_queue
-            .add(
+            .add(Download::from_headers(
                 crate::database::models::ProjectId(project_id),
                 crate::database::models::VersionId(version_id),
-            )
+                &download_body.headers,
+            ))
             .await;
     }
 
-    let client = reqwest::Client::new();
-
-    client
-        .post(format!("{}download", dotenvy::var("ARIADNE_URL")?))
-        .header("Modrinth-Admin", dotenvy::var("ARIADNE_ADMIN_KEY")?)
-        .json(&json!({
-            "ip": download_body.ip,
-            "url": download_body.url,
-            "project_id": download_body.project_id,
-            "version_id": crate::models::projects::VersionId(version_id as u64).to_string(),
-            "headers": download_body.headers
-        }))
-        .send()
-        .await
-        .ok();
+    // Downloads are also forwarded to Ariadne if it is set up, as download analytics are counted
+    // without it
+    if let (Ok(ariadne_url), Ok(ariadne_admin_key)) = (
+        dotenvy::var("ARIADNE_URL"),
+        dotenvy::var("ARIADNE_ADMIN_KEY"),
+    ) {
+        let client = reqwest::Client::new();
+
+        client
+            .post(format!("{ariadne_url}download"))
+            .header("Modrinth-Admin", ariadne_admin_key)
+            .json(&json!({
+                "ip": download_body.ip,
+                "url": download_body.url,
+                "project_id": download_body.project_id,
+                "version_id": crate::models::projects::VersionId(version_id as u64).to_string(),
+                "headers": download_body.headers
+            }))
+            .send()
+            .await
+            .ok();
+    }
 
     Ok(HttpResponse::NoContent().body(""))
 }
@@ -388,3 +399,19 @@ pub async fn process_payout(
 
     Ok(HttpResponse::NoContent().body(""))
 }
+
+#[derive(Deserialize)]
+pub struct StorageAuditQuery {
+    pub kind: Option<AuditFindingKind>,
+}
+
+// Reports what the storage audit found, optionally only findings of one kind
+#[get("/storage_audit", guard = "admin_key_guard")]
+pub async fn storage_audit(
+    pool: web::Data<PgPool>,
+    query: web::Query<StorageAuditQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let findings = AuditFinding::get_all(query.kind, &**pool).await?;
+
+    Ok(HttpResponse::Ok().json(findings))
+}
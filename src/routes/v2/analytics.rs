@@ -0,0 +1,208 @@
+use crate::database;
+use crate::models::ids::VersionId;
+use crate::models::pats::Scopes;
+use crate::models::teams::Permissions;
+use crate::queue::pats::PatQueue;
+use crate::routes::ApiError;
+use crate::util::auth::get_user_from_headers;
+use actix_web::{get, web, HttpRequest, HttpResponse};
+use chrono::{Duration, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::sync::Arc;
+use utoipa::{IntoParams, ToSchema};
+
+// How many days of analytics can be requested at once
+const MAX_RANGE_DAYS: i64 = 366;
+// How many days of analytics are returned when no start date is given
+const DEFAULT_RANGE_DAYS: i64 = 30;
+
+#[derive(Deserialize, IntoParams)]
+pub struct DownloadAnalyticsQuery {
+    /// The first day to count downloads on, defaults to 30 days before the end date
+    pub start_date: Option<NaiveDate>,
+    /// The last day to count downloads on, defaults to today
+    pub end_date: Option<NaiveDate>,
+    /// Only count the downloads of this version
+    #[param(value_type = Option<String>)]
+    pub version_id: Option<VersionId>,
+}
+
+/// The downloads of a project between two days, as a time series and broken down by version,
+/// country and launcher
+#[derive(Serialize, ToSchema)]
+pub struct DownloadAnalytics {
+    pub start_date: NaiveDate,
+    pub end_date: NaiveDate,
+    pub total: i64,
+    /// The downloads of each day in the range, oldest first
+    pub days: Vec<DayDownloads>,
+    /// The downloads of each version which was downloaded in the range, most downloaded first
+    pub versions: Vec<VersionDownloads>,
+    /// The downloads from each country, by ISO 3166-1 alpha-2 code or `XX` if unknown, most
+    /// downloaded first
+    pub countries: Vec<DownloadBreakdown>,
+    /// The downloads from each launcher, such as `modrinth-app`, `prism`, `browser`, `other` or
+    /// `unknown`, most downloaded first
+    pub launchers: Vec<DownloadBreakdown>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct DayDownloads {
+    pub day: NaiveDate,
+    pub downloads: i64,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct VersionDownloads {
+    pub version_id: VersionId,
+    pub downloads: i64,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct DownloadBreakdown {
+    pub name: String,
+    pub downloads: i64,
+}
+
+#[utoipa::path(
+    path = "/v2/project/{id}/analytics/downloads",
+    tag = "projects",
+    security(("token" = [])),
+    params(
+        ("id" = String, Path, description = "The ID or slug of the project"),
+        DownloadAnalyticsQuery,
+    ),
+    responses(
+        (status = 200, description = "The downloads of the project. Downloads are counted in UTC days, and take up to a minute to be counted.", body = DownloadAnalytics),
+        (status = 400, description = "Invalid date range", body = ApiError),
+        (status = 401, description = "Missing authentication, or not permitted to view the project's analytics", body = ApiError),
+        (status = 404, description = "The project does not exist"),
+    )
+)]
+#[get("{id}/analytics/downloads")]
+pub async fn project_downloads_get(
+    req: HttpRequest,
+    info: web::Path<(String,)>,
+    web::Query(query): web::Query<DownloadAnalyticsQuery>,
+    pool: web::Data<PgPool>,
+    pat_queue: web::Data<Arc<PatQueue>>,
+) -> Result<HttpResponse, ApiError> {
+    let user = get_user_from_headers(&req, &**pool, &pat_queue, Scopes::ANALYTICS).await?;
+
+    let end_date = query.end_date.unwrap_or_else(|| Utc::now().date_naive());
+    let start_date = match query.start_date {
+        Some(start_date) => start_date,
+        None => end_date
+            .checked_sub_signed(Duration::days(DEFAULT_RANGE_DAYS - 1))
+            .ok_or_else(|| ApiError::InvalidInput("The end date is out of range!".to_string()))?,
+    };
+
+    if start_date > end_date {
+        return Err(ApiError::InvalidInput(
+            "The start date must not be after the end date!".to_string(),
+        ));
+    }
+
+    if (end_date - start_date).num_days() >= MAX_RANGE_DAYS {
+        return Err(ApiError::InvalidInput(format!(
+            "At most {MAX_RANGE_DAYS} days of analytics can be requested at once!"
+        )));
+    }
+
+    let string = info.into_inner().0;
+    let project =
+        match database::models::Project::get_from_slug_or_project_id(&string, &**pool).await? {
+            Some(project) => project,
+            None => return Ok(HttpResponse::NotFound().body("")),
+        };
+
+    if !user.role.is_mod() {
+        let team_member = database::models::TeamMember::get_from_user_id(
+            project.team_id,
+            user.id.into(),
+            &**pool,
+        )
+        .await?;
+
+        if !team_member
+            .map(|x| x.permissions.contains(Permissions::VIEW_ANALYTICS))
+            .unwrap_or(false)
+        {
+            return Err(ApiError::CustomAuthentication(
+                "You do not have permission to view this project's analytics!".to_string(),
+            ));
+        }
+    }
+
+    let rows = sqlx::query!(
+        r#"
+        SELECT day "day?", version_id "version_id?", country "country?", launcher "launcher?",
+        SUM(downloads) "downloads!"
+        FROM download_analytics
+        WHERE mod_id = $1 AND day BETWEEN $2 AND $3 AND ($4::bigint IS NULL OR version_id = $4)
+        GROUP BY GROUPING SETS ((day), (version_id), (country), (launcher), ())
+        "#,
+        project.id as database::models::ids::ProjectId,
+        start_date,
+        end_date,
+        query
+            .version_id
+            .map(|x| database::models::VersionId::from(x).0),
+    )
+    .fetch_all(&**pool)
+    .await?;
+
+    let mut total = 0;
+    let mut days = HashMap::new();
+    let mut versions = Vec::new();
+    let mut countries = Vec::new();
+    let mut launchers = Vec::new();
+
+    // Each row is the sum of one grouping set, which is told apart by the column it is grouped by
+    for row in rows {
+        match (row.day, row.version_id, row.country, row.launcher) {
+            (Some(day), _, _, _) => {
+                days.insert(day, row.downloads);
+            }
+            (_, Some(version_id), _, _) => versions.push(VersionDownloads {
+                version_id: database::models::VersionId(version_id).into(),
+                downloads: row.downloads,
+            }),
+            (_, _, Some(name), _) => countries.push(DownloadBreakdown {
+                name,
+                downloads: row.downloads,
+            }),
+            (_, _, _, Some(name)) => launchers.push(DownloadBreakdown {
+                name,
+                downloads: row.downloads,
+            }),
+            _ => total = row.downloads,
+        }
+    }
+
+    versions.sort_by(|a, b| b.downloads.cmp(&a.downloads));
+    countries.sort_by(|a, b| b.downloads.cmp(&a.downloads));
+    launchers.sort_by(|a, b| b.downloads.cmp(&a.downloads));
+
+    // Days without downloads have no rows, but are still part of the time series
+    let days = start_date
+        .iter_days()
+        .take_while(|x| *x <= end_date)
+        .map(|day| DayDownloads {
+            day,
+            downloads: days.get(&day).copied().unwrap_or(0),
+        })
+        .collect();
+
+    Ok(HttpResponse::Ok().json(DownloadAnalytics {
+        start_date,
+        end_date,
+        total,
+        days,
+        versions,
+        countries,
+        launchers,
+    }))
+}
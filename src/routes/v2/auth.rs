@@ -9,26 +9,41 @@ Just as a summary: Don't implement this flow in your application!
 */
 
 use crate::database::models::generate_state_id;
+use crate::identity::LocalProvider;
 use crate::models::error::ApiError;
 use crate::models::ids::base62_impl::{parse_base62, to_base62};
 use crate::models::ids::DecodingError;
+use crate::models::pats::Scopes;
 
 use crate::parse_strings_from_var;
 use crate::util::auth::{
-    self, get_minos_user, get_user_record_from_token_cookies, AuthenticationError,
+    self, get_identity_provider, get_user_record_from_token_cookies, AuthenticationError,
 };
+use crate::util::validate::validation_errors_to_string;
 
 use actix_web::http::StatusCode;
-use actix_web::web::{scope, Data, Query, ServiceConfig};
-use actix_web::{get, HttpRequest, HttpResponse};
+use actix_web::web::{scope, Data, Json, Query, ServiceConfig};
+use actix_web::{get, post, HttpRequest, HttpResponse};
 use chrono::Utc;
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::sync::Arc;
 
 use serde::{Deserialize, Serialize};
 use sqlx::postgres::PgPool;
 use thiserror::Error;
+use utoipa::{IntoParams, ToSchema};
+use validator::Validate;
 
 pub fn config(cfg: &mut ServiceConfig) {
-    cfg.service(scope("auth").service(auth_callback).service(init));
+    cfg.service(
+        scope("auth")
+            .service(auth_callback)
+            .service(init)
+            .service(local_register)
+            .service(local_login)
+            .service(local_logout),
+    );
 }
 
 #[derive(Error, Debug)]
@@ -53,6 +68,8 @@ pub enum AuthorizationError {
     Url,
     #[error("User is not allowed to access Modrinth services")]
     Banned,
+    #[error("Error while validating input: {0}")]
+    Validation(String),
 }
 impl actix_web::ResponseError for AuthorizationError {
     fn status_code(&self) -> StatusCode {
@@ -67,6 +84,7 @@ impl actix_web::ResponseError for AuthorizationError {
             AuthorizationError::Authentication(..) => StatusCode::UNAUTHORIZED,
             AuthorizationError::Url => StatusCode::BAD_REQUEST,
             AuthorizationError::Banned => StatusCode::FORBIDDEN,
+            AuthorizationError::Validation(..) => StatusCode::BAD_REQUEST,
         }
     }
 
@@ -83,24 +101,34 @@ impl actix_web::ResponseError for AuthorizationError {
                 AuthorizationError::Authentication(..) => "authentication_error",
                 AuthorizationError::Url => "url_error",
                 AuthorizationError::Banned => "user_banned",
+                AuthorizationError::Validation(..) => "invalid_input",
             },
             description: &self.to_string(),
         })
     }
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, IntoParams, ToSchema)]
 pub struct AuthorizationInit {
     pub url: String,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, IntoParams)]
 pub struct StateResponse {
     pub state: String,
 }
 
 // Init link takes us to Minos API and calls back to callback endpoint with a code and state
 //http://localhost:8000/api/v1/auth/init?url=https%3A%2F%2Fmodrinth.com%2Fmods
+#[utoipa::path(
+    path = "/v2/auth/init",
+    tag = "auth",
+    params(AuthorizationInit),
+    responses(
+        (status = 307, description = "A redirect to the login flow of the identity provider", body = AuthorizationInit),
+        (status = 400, description = "The callback URL is not allowed", body = ApiError),
+    )
+)]
 #[get("init")]
 pub async fn init(
     Query(info): Query<AuthorizationInit>, // callback url
@@ -144,6 +172,16 @@ pub async fn init(
         .json(AuthorizationInit { url }))
 }
 
+#[utoipa::path(
+    path = "/v2/auth/callback",
+    tag = "auth",
+    params(StateResponse),
+    responses(
+        (status = 307, description = "A redirect to the callback URL the flow was started with", body = AuthorizationInit),
+        (status = 401, description = "The state or session cookie is invalid", body = ApiError),
+        (status = 403, description = "The user is banned", body = ApiError),
+    )
+)]
 #[get("callback")]
 pub async fn auth_callback(
     req: HttpRequest,
@@ -156,7 +194,7 @@ pub async fn auth_callback(
     let result_option = sqlx::query!(
         "
             SELECT url, expires FROM states
-            WHERE id = $1
+            WHERE id = $1 AND oauth_client_id IS NULL
             ",
         state_id as i64
     )
@@ -176,7 +214,7 @@ pub async fn auth_callback(
             sqlx::query!(
                 "
                 DELETE FROM states
-                WHERE id = $1
+                WHERE id = $1 AND oauth_client_id IS NULL
                 ",
                 state_id as i64
             )
@@ -185,24 +223,31 @@ pub async fn auth_callback(
 
             // Use extracted cookie header to get authenticated user from Minos
             // TODO: check here
-            let user_result =
-                get_user_record_from_token_cookies(None, Some(cookie_header), &mut transaction)
-                    .await?;
+            let identity_provider = get_identity_provider(&req)?;
+            let user_result = get_user_record_from_token_cookies(
+                None,
+                Some(cookie_header),
+                &mut transaction,
+                identity_provider,
+                Scopes::NONE,
+            )
+            .await?;
 
             // Cookies exist, but user does not exist in database, meaning they are new, invalid, or have been banned
             if user_result.is_none() {
-                // Attempt to create a minos user from the cookie header- if this fails, the user is invalid
-                let minos_user = get_minos_user(
-                    cookie_header
-                        .to_str()
-                        .map_err(|_| AuthenticationError::InvalidCredentials)?,
-                )
-                .await?;
+                // Attempt to get the user from the identity provider with the cookie header- if this fails, the user is invalid
+                let identity_user = identity_provider
+                    .get_user(
+                        cookie_header
+                            .to_str()
+                            .map_err(|_| AuthenticationError::InvalidCredentials)?,
+                    )
+                    .await?;
 
                 // Check if user is banned
                 let banned_user = sqlx::query!(
                     "SELECT user FROM banned_users bu LEFT OUTER JOIN users u ON bu.user_id = u.id WHERE u.kratos_id = $1",
-                    minos_user.id.clone() as String
+                    identity_user.id.clone() as String
                 )
                 .fetch_optional(&mut *transaction)
                 .await?;
@@ -211,8 +256,8 @@ pub async fn auth_callback(
                     return Err(AuthorizationError::Banned);
                 }
 
-                // New user sent from Minos, we insert into Users!
-                auth::insert_new_user(&mut transaction, minos_user).await?;
+                // New user sent from the identity provider, we insert into Users!
+                auth::insert_new_user(&mut transaction, identity_user).await?;
             }
             transaction.commit().await?;
 
@@ -229,3 +274,239 @@ pub async fn auth_callback(
         Err(AuthorizationError::InvalidCredentials)
     }
 }
+
+lazy_static! {
+    static ref RE_USERNAME: Regex = Regex::new(r"^[a-zA-Z0-9_-]*$").unwrap();
+}
+
+#[derive(Deserialize, Validate, ToSchema)]
+pub struct LocalRegister {
+    #[validate(length(min = 1, max = 39), regex = "RE_USERNAME")]
+    pub username: String,
+    #[validate(email, length(max = 255))]
+    pub email: String,
+    #[validate(length(min = 1, max = 64))]
+    pub name: Option<String>,
+    #[validate(length(min = 8, max = 256))]
+    pub password: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct LocalLogin {
+    pub email: String,
+    pub password: String,
+}
+
+// The following routes are only available when the local identity provider is used (IDENTITY_PROVIDER=local),
+// replacing the Kratos login flow of init and callback.
+
+// Creates a new account and user, and logs into it
+#[utoipa::path(
+    path = "/v2/auth/local/register",
+    tag = "auth",
+    request_body = LocalRegister,
+    responses(
+        (status = 204, description = "The account was created, and the session cookie is set"),
+        (status = 400, description = "Invalid input, or the email or username is taken", body = ApiError),
+        (status = 404, description = "The local identity provider is not in use"),
+    )
+)]
+#[post("local/register")]
+pub async fn local_register(
+    Json(info): Json<LocalRegister>,
+    client: Data<PgPool>,
+    local_provider: Data<Option<Arc<LocalProvider>>>,
+) -> Result<HttpResponse, AuthorizationError> {
+    let local_provider = match &**local_provider {
+        Some(local_provider) => local_provider,
+        None => return Ok(HttpResponse::NotFound().body("")),
+    };
+
+    info.validate()
+        .map_err(|err| AuthorizationError::Validation(validation_errors_to_string(err, None)))?;
+
+    let mut transaction = client.begin().await?;
+
+    let taken = sqlx::query!(
+        "
+        SELECT EXISTS(SELECT 1 FROM local_identities WHERE LOWER(email) = LOWER($1))
+            OR EXISTS(SELECT 1 FROM users WHERE LOWER(username) = LOWER($2)) taken
+        ",
+        info.email,
+        info.username
+    )
+    .fetch_one(&mut *transaction)
+    .await?
+    .taken
+    .unwrap_or(true);
+
+    if taken {
+        return Err(AuthorizationError::Validation(
+            "An account with this email or username already exists!".to_string(),
+        ));
+    }
+
+    let identity_user = local_provider
+        .register(
+            &mut transaction,
+            info.username,
+            info.email,
+            info.name,
+            &info.password,
+        )
+        .await?;
+    let identity_id = identity_user.id.clone();
+
+    auth::insert_new_user(&mut transaction, identity_user).await?;
+    let cookie = local_provider
+        .create_session(&mut transaction, identity_id)
+        .await?;
+
+    transaction.commit().await?;
+
+    Ok(HttpResponse::NoContent().cookie(cookie).finish())
+}
+
+// Logs into an account, setting the session cookie
+#[utoipa::path(
+    path = "/v2/auth/local/login",
+    tag = "auth",
+    request_body = LocalLogin,
+    responses(
+        (status = 204, description = "The session cookie is set"),
+        (status = 401, description = "Invalid email or password", body = ApiError),
+        (status = 404, description = "The local identity provider is not in use"),
+    )
+)]
+#[post("local/login")]
+pub async fn local_login(
+    Json(info): Json<LocalLogin>,
+    client: Data<PgPool>,
+    local_provider: Data<Option<Arc<LocalProvider>>>,
+) -> Result<HttpResponse, AuthorizationError> {
+    let local_provider = match &**local_provider {
+        Some(local_provider) => local_provider,
+        None => return Ok(HttpResponse::NotFound().body("")),
+    };
+
+    let mut transaction = client.begin().await?;
+
+    let cookie = local_provider
+        .login(&mut transaction, &info.email, &info.password)
+        .await?;
+
+    transaction.commit().await?;
+
+    Ok(HttpResponse::NoContent().cookie(cookie).finish())
+}
+
+// Ends the current session, removing the session cookie
+#[utoipa::path(
+    path = "/v2/auth/local/logout",
+    tag = "auth",
+    responses(
+        (status = 204, description = "The session was ended, and the session cookie is removed"),
+        (status = 404, description = "The local identity provider is not in use"),
+    )
+)]
+#[post("local/logout")]
+pub async fn local_logout(
+    req: HttpRequest,
+    local_provider: Data<Option<Arc<LocalProvider>>>,
+) -> Result<HttpResponse, AuthorizationError> {
+    let local_provider = match &**local_provider {
+        Some(local_provider) => local_provider,
+        None => return Ok(HttpResponse::NotFound().body("")),
+    };
+
+    let cookies = req
+        .headers()
+        .get("Cookie")
+        .and_then(|x| x.to_str().ok())
+        .unwrap_or_default();
+    let cookie = local_provider.logout(cookies).await?;
+
+    Ok(HttpResponse::NoContent().cookie(cookie).finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::identity::IdentityProvider;
+    use crate::queue::pats::PatQueue;
+    use actix_web::{test, App};
+    use serde_json::json;
+
+    // Runs against a database sqlx creates from DATABASE_URL with the migrations applied. Sessions
+    // are handled by the local identity provider, so no Minos or Kratos deployment is needed.
+    #[sqlx::test]
+    #[ignore = "needs a Postgres database at DATABASE_URL"]
+    async fn local_accounts_register_log_in_and_out(pool: PgPool) {
+        let local_provider = Arc::new(LocalProvider::new(pool.clone(), "secret".to_string()));
+        let identity_provider: Arc<dyn IdentityProvider + Send + Sync> = local_provider.clone();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(pool.clone()))
+                .app_data(Data::new(Arc::new(PatQueue::new())))
+                .app_data(Data::new(identity_provider))
+                .app_data(Data::new(Some(local_provider)))
+                .service(
+                    scope("v2")
+                        .configure(config)
+                        .configure(super::super::users::config),
+                ),
+        )
+        .await;
+
+        let req = test::TestRequest::post()
+            .uri("/v2/auth/local/register")
+            .set_json(json!({
+                "username": "tester",
+                "email": "Tester@Example.com",
+                "password": "correct horse battery",
+            }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+
+        // Emails are matched regardless of case, as they are when registering
+        let login = |password: &str| {
+            test::TestRequest::post()
+                .uri("/v2/auth/local/login")
+                .set_json(json!({
+                    "email": "tester@example.com",
+                    "password": password,
+                }))
+                .to_request()
+        };
+
+        let resp = test::call_service(&app, login("wrong password")).await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+        let resp = test::call_service(&app, login("correct horse battery")).await;
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+        let cookie = resp.response().cookies().next().unwrap().into_owned();
+
+        let req = test::TestRequest::get()
+            .uri("/v2/user")
+            .cookie(cookie.clone())
+            .to_request();
+        let user: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(user["username"], "tester");
+
+        let req = test::TestRequest::post()
+            .uri("/v2/auth/local/logout")
+            .cookie(cookie.clone())
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+
+        let req = test::TestRequest::get()
+            .uri("/v2/user")
+            .cookie(cookie)
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    }
+}
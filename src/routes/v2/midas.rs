@@ -1,4 +1,6 @@
+use crate::models::pats::Scopes;
 use crate::models::users::UserId;
+use crate::queue::pats::PatQueue;
 use crate::routes::ApiError;
 use crate::util::auth::get_user_from_headers;
 use actix_web::{post, web, HttpRequest, HttpResponse};
@@ -8,6 +10,8 @@ use itertools::Itertools;
 use serde::Deserialize;
 use serde_json::{json, Value};
 use sqlx::PgPool;
+use std::sync::Arc;
+use utoipa::ToSchema;
 
 pub fn config(cfg: &mut web::ServiceConfig) {
     cfg.service(
@@ -18,18 +22,29 @@ pub fn config(cfg: &mut web::ServiceConfig) {
     );
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct CheckoutData {
     pub price_id: String,
 }
 
+#[utoipa::path(
+    path = "/v2/midas/_stripe-init-checkout",
+    tag = "midas",
+    security(("token" = [])),
+    request_body = CheckoutData,
+    responses(
+        (status = 200, description = "A JSON object with the `url` of the Stripe checkout session"),
+        (status = 401, description = "Missing or invalid authentication", body = ApiError),
+    )
+)]
 #[post("/_stripe-init-checkout")]
 pub async fn init_checkout(
     req: HttpRequest,
     pool: web::Data<PgPool>,
     data: web::Json<CheckoutData>,
+    pat_queue: web::Data<Arc<PatQueue>>,
 ) -> Result<HttpResponse, ApiError> {
-    let user = get_user_from_headers(req.headers(), &**pool).await?;
+    let user = get_user_from_headers(&req, &**pool, &pat_queue, Scopes::PAYOUTS_WRITE).await?;
 
     let client = reqwest::Client::new();
 
@@ -68,12 +83,23 @@ pub async fn init_checkout(
     )))
 }
 
+#[utoipa::path(
+    path = "/v2/midas/_stripe-init-portal",
+    tag = "midas",
+    security(("token" = [])),
+    responses(
+        (status = 200, description = "A JSON object with the `url` of the Stripe customer portal"),
+        (status = 401, description = "Missing or invalid authentication", body = ApiError),
+        (status = 400, description = "The user is not a Stripe customer", body = ApiError),
+    )
+)]
 #[post("/_stripe-init-portal")]
 pub async fn init_customer_portal(
     req: HttpRequest,
     pool: web::Data<PgPool>,
+    pat_queue: web::Data<Arc<PatQueue>>,
 ) -> Result<HttpResponse, ApiError> {
-    let user = get_user_from_headers(req.headers(), &**pool).await?;
+    let user = get_user_from_headers(&req, &**pool, &pat_queue, Scopes::PAYOUTS_WRITE).await?;
 
     let customer_id = sqlx::query!(
         "
@@ -121,6 +147,15 @@ pub async fn init_customer_portal(
     )))
 }
 
+#[utoipa::path(
+    path = "/v2/midas/_stripe-webook",
+    tag = "midas",
+    request_body(content = String, description = "A Stripe event, signed with the `Stripe-Signature` header"),
+    responses(
+        (status = 204, description = "The event was processed"),
+        (status = 403, description = "The signature of the event is invalid", body = ApiError),
+    )
+)]
 #[post("/_stripe-webook")]
 pub async fn handle_stripe_webhook(
     body: String,
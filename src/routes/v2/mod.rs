@@ -1,8 +1,10 @@
 mod admin;
+mod analytics;
 mod auth;
 mod midas;
 mod moderation;
 mod notifications;
+mod oauth;
 mod pats;
 pub(crate) mod project_creation;
 mod projects;
@@ -11,6 +13,7 @@ mod statistics;
 mod tags;
 mod teams;
 mod threads;
+mod upload_sessions;
 mod users;
 mod version_creation;
 mod version_file;
@@ -18,6 +21,262 @@ mod versions;
 
 pub use super::ApiError;
 
+/// The OpenAPI document for the v2 routes, merged into the root document.
+/// Internal routes guarded by the admin key are left out.
+#[derive(utoipa::OpenApi)]
+#[openapi(
+    paths(
+        analytics::project_downloads_get,
+        auth::init,
+        auth::auth_callback,
+        auth::local_register,
+        auth::local_login,
+        auth::local_logout,
+        midas::init_checkout,
+        midas::init_customer_portal,
+        midas::handle_stripe_webhook,
+        moderation::get_projects,
+        moderation::ban_user,
+        moderation::unban_user,
+        notifications::notifications_get,
+        notifications::notifications_read,
+        notifications::notifications_delete,
+        notifications::notification_get,
+        notifications::notification_read,
+        notifications::notification_delete,
+        oauth::authorize,
+        oauth::accept,
+        oauth::reject,
+        oauth::token,
+        oauth::authorizations_get,
+        oauth::authorization_delete,
+        oauth::clients_get,
+        oauth::client_create,
+        oauth::client_edit,
+        oauth::client_icon_edit,
+        oauth::client_delete,
+        pats::get_pats,
+        pats::create_pat,
+        pats::edit_pat,
+        pats::delete_pat,
+        projects::project_search,
+        projects::random_projects_get,
+        projects::projects_get,
+        projects::projects_edit,
+        projects::project_get,
+        projects::project_get_check,
+        projects::project_edit,
+        projects::project_delete,
+        projects::project_schedule,
+        projects::project_icon_edit,
+        projects::delete_project_icon,
+        projects::add_gallery_item,
+        projects::edit_gallery_item,
+        projects::delete_gallery_item,
+        projects::project_follow,
+        projects::project_unfollow,
+        projects::dependency_list,
+        reports::report_create,
+        reports::reports,
+        reports::report_get,
+        reports::report_edit,
+        reports::report_delete,
+        statistics::get_stats,
+        tags::category_list,
+        tags::loader_list,
+        tags::game_version_list,
+        tags::license_list,
+        tags::license_text,
+        tags::donation_platform_list,
+        tags::report_type_list,
+        tags::project_type_list,
+        tags::side_type_list,
+        teams::team_members_get_project,
+        teams::team_members_get,
+        teams::teams_get,
+        teams::join_team,
+        teams::add_team_member,
+        teams::edit_team_member,
+        teams::transfer_ownership,
+        teams::remove_team_member,
+        threads::thread_get,
+        threads::threads_get,
+        threads::thread_send_message,
+        threads::moderation_inbox,
+        threads::thread_read,
+        threads::message_delete,
+        upload_sessions::upload_session_create,
+        upload_sessions::upload_session_get,
+        upload_sessions::upload_session_chunk,
+        upload_sessions::upload_session_finalize,
+        upload_sessions::upload_session_delete,
+        users::user_auth_get,
+        users::user_data_get,
+        users::users_get,
+        users::user_get,
+        users::projects_list,
+        users::user_edit,
+        users::user_icon_edit,
+        users::user_delete,
+        users::user_follows,
+        users::user_notifications,
+        users::user_payouts,
+        users::user_payouts_request,
+        project_creation::project_create,
+        version_creation::version_create,
+        version_creation::upload_file_to_version,
+        version_creation::version_file_validate,
+        versions::version_list,
+        versions::version_project_get,
+        versions::versions_get,
+        versions::dependencies_resolve,
+        versions::modpack_export,
+        versions::version_get,
+        versions::version_edit,
+        versions::version_schedule,
+        versions::version_delete,
+        version_file::get_version_from_hash,
+        version_file::download_version,
+        version_file::delete_file,
+        version_file::get_update_from_hash,
+        version_file::get_versions_from_hashes,
+        version_file::download_files,
+        version_file::update_files,
+    ),
+    components(schemas(
+        crate::models::ids::ProjectId,
+        crate::models::ids::UserId,
+        crate::models::ids::VersionId,
+        crate::models::ids::TeamId,
+        crate::models::ids::ReportId,
+        crate::models::ids::NotificationId,
+        crate::models::ids::ThreadId,
+        crate::models::ids::ThreadMessageId,
+        crate::models::ids::PatId,
+        crate::models::ids::OAuthClientId,
+        crate::models::ids::OAuthAuthorizationId,
+        crate::models::ids::UploadSessionId,
+        crate::models::notifications::Notification,
+        crate::models::notifications::NotificationBody,
+        crate::models::notifications::NotificationAction,
+        crate::models::oauth::OAuthClient,
+        crate::models::oauth::OAuthAuthorization,
+        crate::models::oauth::OAuthTokenResponse,
+        crate::models::pats::Scopes,
+        crate::models::projects::Project,
+        crate::models::projects::GalleryItem,
+        crate::models::projects::Thumbnail,
+        crate::models::projects::ModeratorMessage,
+        crate::models::projects::SideType,
+        crate::models::projects::License,
+        crate::models::projects::DonationLink,
+        crate::models::projects::ProjectStatus,
+        crate::models::projects::MonetizationStatus,
+        crate::models::projects::Version,
+        crate::models::projects::VersionStatus,
+        crate::models::projects::VersionFile,
+        crate::models::projects::Dependency,
+        crate::models::projects::VersionType,
+        crate::models::projects::DependencyType,
+        crate::models::projects::FileType,
+        crate::models::projects::FileFinding,
+        crate::models::projects::FindingSeverity,
+        crate::models::projects::FindingCode,
+        crate::models::projects::GameVersion,
+        crate::models::projects::Loader,
+        crate::models::reports::Report,
+        crate::models::reports::ItemType,
+        crate::models::teams::Team,
+        crate::models::teams::TeamMember,
+        crate::models::teams::Permissions,
+        crate::models::threads::Thread,
+        crate::models::threads::ThreadMessage,
+        crate::models::threads::MessageBody,
+        crate::models::threads::ThreadType,
+        crate::models::uploads::UploadSession,
+        crate::models::users::User,
+        crate::models::users::Badges,
+        crate::models::users::UserPayoutData,
+        crate::models::users::RecipientType,
+        crate::models::users::RecipientWallet,
+        crate::models::users::Role,
+        crate::search::SearchResults,
+        crate::services::dependencies::ResolvedDependencies,
+        crate::services::dependencies::ResolvedVersion,
+        crate::services::dependencies::ResolutionReason,
+        crate::services::dependencies::UnresolvedDependency,
+        crate::services::dependencies::UnresolvedReason,
+        crate::services::dependencies::DependencyConflict,
+        crate::services::dependencies::ConflictReason,
+        crate::services::modpacks::ModpackReport,
+        crate::services::modpacks::PackFileReport,
+        crate::services::modpacks::PackFileStatus,
+        crate::validate::metadata::ModEnvironment,
+        crate::search::ResultSearchProject,
+        crate::util::pat::PersonalAccessToken,
+        analytics::DownloadAnalytics,
+        analytics::DayDownloads,
+        analytics::VersionDownloads,
+        analytics::DownloadBreakdown,
+        auth::AuthorizationInit,
+        auth::LocalRegister,
+        auth::LocalLogin,
+        midas::CheckoutData,
+        oauth::OAuthErrorResponse,
+        oauth::AuthorizationClient,
+        oauth::AuthorizationConsent,
+        oauth::AuthorizationFlow,
+        oauth::OAuthRedirect,
+        oauth::TokenRequest,
+        oauth::NewOAuthClient,
+        oauth::EditOAuthClient,
+        project_creation::ProjectCreateData,
+        project_creation::NewGalleryItem,
+        project_creation::ProjectCreateResponse,
+        projects::DependencyInfo,
+        projects::EditProject,
+        projects::BulkEditProject,
+        projects::SchedulingData,
+        reports::CreateReport,
+        reports::EditReport,
+        tags::CategoryData,
+        tags::LoaderData,
+        tags::GameVersionQueryData,
+        tags::LicenseData,
+        tags::LicenseText,
+        tags::DonationPlatformQueryData,
+        teams::NewTeamMember,
+        teams::EditTeamMember,
+        teams::TransferOwnership,
+        threads::NewThreadMessage,
+        upload_sessions::NewUploadSession,
+        upload_sessions::FinalizeUploadSession,
+        users::UserData,
+        users::EditUser,
+        users::EditPayoutData,
+        users::Payout,
+        users::PayoutData,
+        version_creation::InitialVersionData,
+        version_creation::InitialFileData,
+        version_creation::FileValidationData,
+        version_creation::FileValidationReport,
+        version_creation::FileMetadata,
+        version_creation::FileMetadataDependency,
+        version_creation::VersionCreateResponse,
+        version_creation::FileUploadResponse,
+        version_file::DownloadRedirect,
+        version_file::UpdateData,
+        version_file::FileHashes,
+        version_file::ManyUpdateData,
+        versions::EditVersion,
+        versions::EditVersionFileType,
+        versions::VersionSchedulingData,
+        versions::ResolveDependencies,
+        versions::ExportModpack,
+    ))
+)]
+pub struct ApiDoc;
+
 pub fn config(cfg: &mut actix_web::web::ServiceConfig) {
     cfg.service(
         actix_web::web::scope("v2")
@@ -26,6 +285,7 @@ pub fn config(cfg: &mut actix_web::web::ServiceConfig) {
             .configure(midas::config)
             .configure(moderation::config)
             .configure(notifications::config)
+            .configure(oauth::config)
             .configure(pats::config)
             .configure(project_creation::config)
             .configure(projects::config)
@@ -34,6 +294,7 @@ pub fn config(cfg: &mut actix_web::web::ServiceConfig) {
             .configure(tags::config)
             .configure(teams::config)
             .configure(threads::config)
+            .configure(upload_sessions::config)
             .configure(users::config)
             .configure(version_file::config)
             .configure(versions::config),
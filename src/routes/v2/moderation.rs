@@ -1,10 +1,15 @@
 use super::ApiError;
 use crate::database;
-use crate::models::projects::ProjectStatus;
+use crate::models::pats::Scopes;
+use crate::models::projects::{FindingCode, ProjectStatus};
+use crate::queue::pats::PatQueue;
 use crate::util::auth::check_is_moderator_from_headers;
+use crate::util::pagination::{Cursor, OptionalPagination, PaginationQuery};
 use actix_web::{delete, get, web, HttpRequest, HttpResponse};
 use serde::Deserialize;
 use sqlx::PgPool;
+use std::sync::Arc;
+use utoipa::IntoParams;
 
 pub fn config(cfg: &mut web::ServiceConfig) {
     cfg.service(
@@ -15,7 +20,7 @@ pub fn config(cfg: &mut web::ServiceConfig) {
     );
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, IntoParams)]
 pub struct ResultCount {
     #[serde(default = "default_count")]
     pub count: i16,
@@ -25,25 +30,99 @@ fn default_count() -> i16 {
     100
 }
 
+#[derive(Deserialize, IntoParams)]
+pub struct QueueFilter {
+    /// Only return projects with a version file which has a finding of this code
+    pub finding: Option<FindingCode>,
+}
+
+#[utoipa::path(
+    path = "/v2/moderation/projects",
+    tag = "moderation",
+    security(("token" = [])),
+    params(ResultCount, QueueFilter, PaginationQuery),
+    responses(
+        (status = 200, description = "The projects waiting for review, oldest first. When `cursor` or `limit` is given, `count` is ignored and a page with the `items`, `next_cursor` and `next` link is returned instead.", body = [Project]),
+        (status = 400, description = "Invalid cursor or limit", body = ApiError),
+        (status = 401, description = "The user is not a moderator", body = ApiError),
+    )
+)]
 #[get("projects")]
 pub async fn get_projects(
     req: HttpRequest,
     pool: web::Data<PgPool>,
     count: web::Query<ResultCount>,
+    filter: web::Query<QueueFilter>,
+    pagination: OptionalPagination,
+    pat_queue: web::Data<Arc<PatQueue>>,
 ) -> Result<HttpResponse, ApiError> {
-    check_is_moderator_from_headers(req.headers(), &**pool).await?;
+    check_is_moderator_from_headers(&req, &**pool, &pat_queue, Scopes::PROJECT_READ).await?;
 
     use futures::stream::TryStreamExt;
 
+    let finding = filter.finding.map(|x| x.as_str());
+
+    if let Some(pagination) = &pagination.0 {
+        // Projects queued before the queue date was tracked fall back to their creation date
+        let project_ids = sqlx::query!(
+            "
+            SELECT id FROM mods
+            WHERE status = $1
+            AND ($2::timestamptz IS NULL OR (COALESCE(queued, published), id) > ($2, $3))
+            AND ($5::varchar IS NULL OR EXISTS(
+                SELECT 1 FROM versions v
+                INNER JOIN files f ON f.version_id = v.id
+                INNER JOIN files_findings ff ON ff.file_id = f.id
+                WHERE v.mod_id = mods.id AND ff.code = $5
+            ))
+            ORDER BY COALESCE(queued, published) ASC, id ASC
+            LIMIT $4
+            ",
+            ProjectStatus::Processing.as_str(),
+            pagination.cursor.map(|x| x.created),
+            pagination.cursor.map(|x| x.id),
+            pagination.fetch_limit(),
+            finding,
+        )
+        .fetch_many(&**pool)
+        .try_filter_map(|e| async { Ok(e.right().map(|m| database::models::ProjectId(m.id))) })
+        .try_collect::<Vec<database::models::ProjectId>>()
+        .await?;
+
+        let mut projects = database::Project::get_many_full(&project_ids, &**pool).await?;
+        projects.sort_by_key(|x| (x.inner.queued.unwrap_or(x.inner.published), x.inner.id.0));
+
+        let (projects, next) = pagination.split(projects, |x| {
+            Cursor::new(x.inner.queued.unwrap_or(x.inner.published), x.inner.id.0)
+        });
+
+        return Ok(HttpResponse::Ok().json(
+            pagination.page(
+                projects
+                    .into_iter()
+                    .map(crate::models::projects::Project::from)
+                    .collect(),
+                next,
+            ),
+        ));
+    }
+
     let project_ids = sqlx::query!(
         "
         SELECT id FROM mods
         WHERE status = $1
+        AND ($3::varchar IS NULL OR EXISTS(
+            SELECT 1 FROM versions v
+            INNER JOIN files f ON f.version_id = v.id
+            INNER JOIN files_findings ff ON ff.file_id = f.id
+            WHERE v.mod_id = mods.id AND ff.code = $3
+        ))
         ORDER BY queued ASC
         LIMIT $2;
         ",
         ProjectStatus::Processing.as_str(),
-        count.count as i64
+        count.count as i64,
+        finding
     )
     .fetch_many(&**pool)
     .try_filter_map(|e| async { Ok(e.right().map(|m| database::models::ProjectId(m.id))) })
@@ -59,18 +138,29 @@ pub async fn get_projects(
     Ok(HttpResponse::Ok().json(projects))
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, IntoParams)]
 pub struct BanUser {
     pub username: String,
 }
 
+#[utoipa::path(
+    path = "/v2/moderation/ban",
+    tag = "moderation",
+    security(("token" = [])),
+    params(BanUser),
+    responses(
+        (status = 204, description = "The user was banned"),
+        (status = 401, description = "The user is not a moderator", body = ApiError),
+    )
+)]
 #[get("ban")]
 pub async fn ban_user(
     req: HttpRequest,
     pool: web::Data<PgPool>,
     username: web::Query<BanUser>,
+    pat_queue: web::Data<Arc<PatQueue>>,
 ) -> Result<HttpResponse, ApiError> {
-    check_is_moderator_from_headers(req.headers(), &**pool).await?;
+    check_is_moderator_from_headers(&req, &**pool, &pat_queue, Scopes::USER_WRITE).await?;
 
     sqlx::query!(
         "INSERT INTO banned_users (user_id) SELECT id FROM users WHERE username = $1;",
@@ -82,13 +172,24 @@ pub async fn ban_user(
     Ok(HttpResponse::NoContent().body(""))
 }
 
+#[utoipa::path(
+    path = "/v2/moderation/ban",
+    tag = "moderation",
+    security(("token" = [])),
+    params(BanUser),
+    responses(
+        (status = 204, description = "The user was unbanned"),
+        (status = 401, description = "The user is not a moderator", body = ApiError),
+    )
+)]
 #[delete("ban")]
 pub async fn unban_user(
     req: HttpRequest,
     pool: web::Data<PgPool>,
     username: web::Query<BanUser>,
+    pat_queue: web::Data<Arc<PatQueue>>,
 ) -> Result<HttpResponse, ApiError> {
-    check_is_moderator_from_headers(req.headers(), &**pool).await?;
+    check_is_moderator_from_headers(&req, &**pool, &pat_queue, Scopes::USER_WRITE).await?;
 
     sqlx::query!(
         "DELETE FROM banned_users WHERE user_id = (SELECT id FROM users WHERE username = $1);",
@@ -1,11 +1,16 @@
 use crate::database;
 use crate::models::ids::NotificationId;
 use crate::models::notifications::Notification;
+use crate::models::pats::Scopes;
+use crate::queue::pats::PatQueue;
 use crate::routes::ApiError;
+use crate::services;
 use crate::util::auth::get_user_from_headers;
 use actix_web::{delete, get, patch, web, HttpRequest, HttpResponse};
 use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
+use std::sync::Arc;
+use utoipa::IntoParams;
 
 pub fn config(cfg: &mut web::ServiceConfig) {
     cfg.service(notifications_get);
@@ -20,48 +25,57 @@ pub fn config(cfg: &mut web::ServiceConfig) {
     );
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, IntoParams)]
 pub struct NotificationIds {
     pub ids: String,
 }
 
+#[utoipa::path(
+    path = "/v2/notifications",
+    tag = "notifications",
+    security(("token" = [])),
+    params(NotificationIds),
+    responses(
+        (status = 200, description = "The notifications of the user with the given IDs", body = [Notification]),
+        (status = 401, description = "Missing or invalid authentication", body = ApiError),
+    )
+)]
 #[get("notifications")]
 pub async fn notifications_get(
     req: HttpRequest,
     web::Query(ids): web::Query<NotificationIds>,
     pool: web::Data<PgPool>,
+    pat_queue: web::Data<Arc<PatQueue>>,
 ) -> Result<HttpResponse, ApiError> {
-    let user = get_user_from_headers(req.headers(), &**pool).await?;
-
-    use database::models::notification_item::Notification as DBNotification;
-    use database::models::NotificationId as DBNotificationId;
-
-    let notification_ids: Vec<DBNotificationId> =
-        serde_json::from_str::<Vec<NotificationId>>(ids.ids.as_str())?
-            .into_iter()
-            .map(DBNotificationId::from)
-            .collect();
+    let user = get_user_from_headers(&req, &**pool, &pat_queue, Scopes::NOTIFICATION_READ).await?;
 
-    let notifications_data: Vec<DBNotification> =
-        database::models::notification_item::Notification::get_many(&notification_ids, &**pool)
-            .await?;
+    let notification_ids = serde_json::from_str::<Vec<NotificationId>>(ids.ids.as_str())?;
 
-    let notifications: Vec<Notification> = notifications_data
-        .into_iter()
-        .filter(|n| n.user_id == user.id.into() || user.role.is_admin())
-        .map(Notification::from)
-        .collect();
+    let notifications =
+        services::notifications::get_notifications(notification_ids, &user, &pool).await?;
 
     Ok(HttpResponse::Ok().json(notifications))
 }
 
+#[utoipa::path(
+    path = "/v2/notification/{id}",
+    tag = "notifications",
+    security(("token" = [])),
+    params(("id" = NotificationId, Path, description = "The ID of the notification")),
+    responses(
+        (status = 200, description = "The notification", body = Notification),
+        (status = 401, description = "Missing or invalid authentication", body = ApiError),
+        (status = 404, description = "The notification does not exist or belongs to another user"),
+    )
+)]
 #[get("{id}")]
 pub async fn notification_get(
     req: HttpRequest,
     info: web::Path<(NotificationId,)>,
     pool: web::Data<PgPool>,
+    pat_queue: web::Data<Arc<PatQueue>>,
 ) -> Result<HttpResponse, ApiError> {
-    let user = get_user_from_headers(req.headers(), &**pool).await?;
+    let user = get_user_from_headers(&req, &**pool, &pat_queue, Scopes::NOTIFICATION_READ).await?;
 
     let id = info.into_inner().0;
 
@@ -79,13 +93,25 @@ pub async fn notification_get(
     }
 }
 
+#[utoipa::path(
+    path = "/v2/notification/{id}",
+    tag = "notifications",
+    security(("token" = [])),
+    params(("id" = NotificationId, Path, description = "The ID of the notification")),
+    responses(
+        (status = 204, description = "The notification was marked as read"),
+        (status = 401, description = "The notification belongs to another user", body = ApiError),
+        (status = 404, description = "The notification does not exist"),
+    )
+)]
 #[patch("{id}")]
 pub async fn notification_read(
     req: HttpRequest,
     info: web::Path<(NotificationId,)>,
     pool: web::Data<PgPool>,
+    pat_queue: web::Data<Arc<PatQueue>>,
 ) -> Result<HttpResponse, ApiError> {
-    let user = get_user_from_headers(req.headers(), &**pool).await?;
+    let user = get_user_from_headers(&req, &**pool, &pat_queue, Scopes::NOTIFICATION_WRITE).await?;
 
     let id = info.into_inner().0;
 
@@ -112,13 +138,25 @@ pub async fn notification_read(
     }
 }
 
+#[utoipa::path(
+    path = "/v2/notification/{id}",
+    tag = "notifications",
+    security(("token" = [])),
+    params(("id" = NotificationId, Path, description = "The ID of the notification")),
+    responses(
+        (status = 204, description = "The notification was deleted"),
+        (status = 401, description = "The notification belongs to another user", body = ApiError),
+        (status = 404, description = "The notification does not exist"),
+    )
+)]
 #[delete("{id}")]
 pub async fn notification_delete(
     req: HttpRequest,
     info: web::Path<(NotificationId,)>,
     pool: web::Data<PgPool>,
+    pat_queue: web::Data<Arc<PatQueue>>,
 ) -> Result<HttpResponse, ApiError> {
-    let user = get_user_from_headers(req.headers(), &**pool).await?;
+    let user = get_user_from_headers(&req, &**pool, &pat_queue, Scopes::NOTIFICATION_WRITE).await?;
 
     let id = info.into_inner().0;
 
@@ -145,13 +183,24 @@ pub async fn notification_delete(
     }
 }
 
+#[utoipa::path(
+    path = "/v2/notifications",
+    tag = "notifications",
+    security(("token" = [])),
+    params(NotificationIds),
+    responses(
+        (status = 204, description = "The notifications were marked as read"),
+        (status = 401, description = "Missing or invalid authentication", body = ApiError),
+    )
+)]
 #[patch("notifications")]
 pub async fn notifications_read(
     req: HttpRequest,
     web::Query(ids): web::Query<NotificationIds>,
     pool: web::Data<PgPool>,
+    pat_queue: web::Data<Arc<PatQueue>>,
 ) -> Result<HttpResponse, ApiError> {
-    let user = get_user_from_headers(req.headers(), &**pool).await?;
+    let user = get_user_from_headers(&req, &**pool, &pat_queue, Scopes::NOTIFICATION_WRITE).await?;
 
     let notification_ids = serde_json::from_str::<Vec<NotificationId>>(&ids.ids)?
         .into_iter()
@@ -180,13 +229,24 @@ pub async fn notifications_read(
     Ok(HttpResponse::NoContent().body(""))
 }
 
+#[utoipa::path(
+    path = "/v2/notifications",
+    tag = "notifications",
+    security(("token" = [])),
+    params(NotificationIds),
+    responses(
+        (status = 204, description = "The notifications were deleted"),
+        (status = 401, description = "Missing or invalid authentication", body = ApiError),
+    )
+)]
 #[delete("notifications")]
 pub async fn notifications_delete(
     req: HttpRequest,
     web::Query(ids): web::Query<NotificationIds>,
     pool: web::Data<PgPool>,
+    pat_queue: web::Data<Arc<PatQueue>>,
 ) -> Result<HttpResponse, ApiError> {
-    let user = get_user_from_headers(req.headers(), &**pool).await?;
+    let user = get_user_from_headers(&req, &**pool, &pat_queue, Scopes::NOTIFICATION_WRITE).await?;
 
     let notification_ids = serde_json::from_str::<Vec<NotificationId>>(&ids.ids)?
         .into_iter()
@@ -0,0 +1,1188 @@
+/*!
+This module is Modrinth's OAuth2 provider, allowing third-party applications to act on behalf of users
+without asking them for a personal access token.
+
+Applications are registered by users and are granted a client ID and secret. The authorization code
+flow (RFC 6749 section 4.1) is used, with PKCE (RFC 7636) being required for every request:
+
+1. The application sends the user to the frontend, which calls GET /oauth/authorize with the request.
+2. The user accepts (POST /oauth/accept) or rejects (POST /oauth/reject) the request, after which they
+   are redirected back to the application with an authorization code.
+3. The application exchanges the code for an access token and refresh token at POST /oauth/token.
+
+Pending authorization requests are stored in the states table, like the requests of auth::init.
+Access tokens are stored as personal access tokens, so they are scoped and tracked in the same way.
+*/
+
+use crate::database::models::{
+    generate_oauth_authorization_id, generate_oauth_client_id, generate_pat_id, generate_state_id,
+    DatabaseError, OAuthAuthorizationId, OAuthClientId, UserId,
+};
+use crate::file_hosting::FileHost;
+use crate::models::ids::base62_impl::{parse_base62, to_base62};
+use crate::models::ids::DecodingError;
+use crate::models::oauth::{OAuthAuthorization, OAuthClient, OAuthTokenResponse};
+use crate::models::pats::Scopes;
+use crate::queue::pats::PatQueue;
+use crate::routes::ApiError;
+use crate::util::auth::{get_user_from_headers, AuthenticationError};
+use crate::util::oauth::{is_valid_code_challenge, is_valid_redirect_uri, verify_pkce};
+use crate::util::pat::{generate_pat, hash_pat, random_token, HashedPat};
+use crate::util::routes::read_from_payload;
+use crate::util::validate::validation_errors_to_string;
+
+use actix_web::http::StatusCode;
+use actix_web::web::{self, scope, Data, Query};
+use actix_web::{delete, get, patch, post, HttpRequest, HttpResponse};
+use base64::Engine;
+use chrono::{Duration, Utc};
+use constant_time_eq::constant_time_eq;
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::PgPool;
+use std::sync::Arc;
+use thiserror::Error;
+use utoipa::{IntoParams, ToSchema};
+use validator::Validate;
+
+// How long access tokens, refresh tokens, and authorization codes are valid for
+const ACCESS_TOKEN_EXPIRY_SECONDS: i64 = 60 * 60;
+const REFRESH_TOKEN_EXPIRY_DAYS: i64 = 90;
+const AUTHORIZATION_CODE_EXPIRY_MINUTES: i64 = 10;
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        scope("oauth")
+            .service(authorize)
+            .service(accept)
+            .service(reject)
+            .service(token)
+            .service(authorizations_get)
+            .service(authorization_delete)
+            .service(clients_get)
+            .service(client_create)
+            .service(client_edit)
+            .service(client_icon_edit)
+            .service(client_delete),
+    );
+}
+
+#[derive(Error, Debug)]
+pub enum OAuthError {
+    #[error("An unknown database error occured: {0}")]
+    SqlxDatabase(#[from] sqlx::Error),
+    #[error("Database Error: {0}")]
+    Database(#[from] DatabaseError),
+    #[error("Authentication Error: {0}")]
+    Authentication(#[from] AuthenticationError),
+    #[error("Error while decoding Base62")]
+    Decoding(#[from] DecodingError),
+    #[error("Invalid request: {0}")]
+    InvalidRequest(String),
+    #[error("Invalid client credentials")]
+    InvalidClient,
+    #[error("Invalid grant: {0}")]
+    InvalidGrant(String),
+    #[error("Invalid scopes requested")]
+    InvalidScope,
+    #[error("Unsupported grant type")]
+    UnsupportedGrantType,
+    #[error("Unsupported response type")]
+    UnsupportedResponseType,
+}
+
+// Error response as described in RFC 6749 section 5.2
+#[derive(Serialize, ToSchema)]
+pub struct OAuthErrorResponse<'a> {
+    error: &'a str,
+    error_description: &'a str,
+}
+
+impl actix_web::ResponseError for OAuthError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            OAuthError::SqlxDatabase(..) => StatusCode::INTERNAL_SERVER_ERROR,
+            OAuthError::Database(..) => StatusCode::INTERNAL_SERVER_ERROR,
+            OAuthError::Authentication(..) => StatusCode::UNAUTHORIZED,
+            OAuthError::Decoding(..) => StatusCode::BAD_REQUEST,
+            OAuthError::InvalidRequest(..) => StatusCode::BAD_REQUEST,
+            OAuthError::InvalidClient => StatusCode::UNAUTHORIZED,
+            OAuthError::InvalidGrant(..) => StatusCode::BAD_REQUEST,
+            OAuthError::InvalidScope => StatusCode::BAD_REQUEST,
+            OAuthError::UnsupportedGrantType => StatusCode::BAD_REQUEST,
+            OAuthError::UnsupportedResponseType => StatusCode::BAD_REQUEST,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::build(self.status_code()).json(OAuthErrorResponse {
+            error: match self {
+                OAuthError::SqlxDatabase(..) => "server_error",
+                OAuthError::Database(..) => "server_error",
+                OAuthError::Authentication(..) => "access_denied",
+                OAuthError::Decoding(..) => "invalid_request",
+                OAuthError::InvalidRequest(..) => "invalid_request",
+                OAuthError::InvalidClient => "invalid_client",
+                OAuthError::InvalidGrant(..) => "invalid_grant",
+                OAuthError::InvalidScope => "invalid_scope",
+                OAuthError::UnsupportedGrantType => "unsupported_grant_type",
+                OAuthError::UnsupportedResponseType => "unsupported_response_type",
+            },
+            error_description: &self.to_string(),
+        })
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+pub struct AuthorizationRequest {
+    pub response_type: String,
+    #[param(value_type = String)]
+    pub client_id: crate::models::ids::OAuthClientId,
+    pub redirect_uri: String,
+    pub scope: u64, // bitflag of models::pats::Scopes
+    pub state: Option<String>,
+    pub code_challenge: String,
+    pub code_challenge_method: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct AuthorizationClient {
+    #[schema(value_type = String)]
+    pub id: crate::models::ids::OAuthClientId,
+    pub name: String,
+    pub icon_url: Option<String>,
+}
+
+// Shown to the user by the frontend so they can accept or reject the request
+#[derive(Serialize, ToSchema)]
+pub struct AuthorizationConsent {
+    pub flow: String,
+    pub client: AuthorizationClient,
+    pub scopes: Scopes,
+    pub redirect_uri: String,
+    // Whether the user has already granted these scopes to the application
+    pub previously_authorized: bool,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct AuthorizationFlow {
+    pub flow: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct OAuthRedirect {
+    pub url: String,
+}
+
+// GET /oauth/authorize
+// Validates an authorization request and starts a flow for the user to accept or reject.
+// Only the user's session may authorize applications, so PATs and OAuth tokens can't be used.
+#[utoipa::path(
+    path = "/v2/oauth/authorize",
+    tag = "oauth",
+    security(("token" = [])),
+    params(AuthorizationRequest),
+    responses(
+        (status = 200, description = "The flow for the user to accept or reject", body = AuthorizationConsent),
+        (status = 400, description = "Invalid client, redirect URI, scopes or PKCE challenge", body = OAuthErrorResponse),
+        (status = 401, description = "Missing authentication, or not authenticated with a session", body = OAuthErrorResponse),
+    )
+)]
+#[get("authorize")]
+pub async fn authorize(
+    req: HttpRequest,
+    Query(info): Query<AuthorizationRequest>,
+    pool: Data<PgPool>,
+    pat_queue: Data<Arc<PatQueue>>,
+) -> Result<HttpResponse, OAuthError> {
+    let user = get_user_from_headers(&req, &**pool, &pat_queue, Scopes::restricted()).await?;
+    let user_id: UserId = user.id.into();
+    let client_id: OAuthClientId = info.client_id.into();
+
+    if info.response_type != "code" {
+        return Err(OAuthError::UnsupportedResponseType);
+    }
+
+    if info.code_challenge_method != "S256" || !is_valid_code_challenge(&info.code_challenge) {
+        return Err(OAuthError::InvalidRequest(
+            "A PKCE code challenge of 43 to 128 characters using the S256 method is required"
+                .to_string(),
+        ));
+    }
+
+    if info.state.as_ref().map(|x| x.len() > 500).unwrap_or(false) {
+        return Err(OAuthError::InvalidRequest(
+            "The state must be at most 500 characters long".to_string(),
+        ));
+    }
+
+    let client = sqlx::query!(
+        "
+        SELECT name, icon_url, redirect_uris, max_scopes
+        FROM oauth_clients
+        WHERE id = $1
+        ",
+        client_id as OAuthClientId
+    )
+    .fetch_optional(&**pool)
+    .await?
+    .ok_or(OAuthError::InvalidClient)?;
+
+    if !client.redirect_uris.contains(&info.redirect_uri) {
+        return Err(OAuthError::InvalidRequest(
+            "The redirect URI is not registered for this application".to_string(),
+        ));
+    }
+
+    let scopes = Scopes::parse_pat_scopes(info.scope)
+        .filter(|scopes| Scopes::from_bits_truncate(client.max_scopes as u64).contains(*scopes))
+        .ok_or(OAuthError::InvalidScope)?;
+
+    let previously_authorized = sqlx::query!(
+        "
+        SELECT EXISTS(
+            SELECT 1 FROM oauth_authorizations
+            WHERE client_id = $1 AND user_id = $2 AND scopes & $3 = $3
+        )
+        ",
+        client_id as OAuthClientId,
+        user_id as UserId,
+        scopes.bits() as i64
+    )
+    .fetch_one(&**pool)
+    .await?
+    .exists
+    .unwrap_or(false);
+
+    let mut transaction = pool.begin().await?;
+
+    let state = generate_state_id(&mut transaction).await?;
+
+    sqlx::query!(
+        "
+        INSERT INTO states (id, url, oauth_client_id, oauth_scopes, oauth_state, oauth_code_challenge, oauth_user_id)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        ",
+        state.0,
+        info.redirect_uri,
+        client_id as OAuthClientId,
+        scopes.bits() as i64,
+        info.state,
+        info.code_challenge,
+        user_id as UserId,
+    )
+    .execute(&mut *transaction)
+    .await?;
+
+    transaction.commit().await?;
+
+    Ok(HttpResponse::Ok().json(AuthorizationConsent {
+        flow: to_base62(state.0 as u64),
+        client: AuthorizationClient {
+            id: info.client_id,
+            name: client.name,
+            icon_url: client.icon_url,
+        },
+        scopes,
+        redirect_uri: info.redirect_uri,
+        previously_authorized,
+    }))
+}
+
+// Appends the given query parameters to a redirect URI
+fn redirect_url(redirect_uri: &str, params: &[(&str, &str)]) -> Result<String, OAuthError> {
+    let mut url = url::Url::parse(redirect_uri)
+        .map_err(|_| OAuthError::InvalidRequest("Invalid redirect URI".to_string()))?;
+
+    url.query_pairs_mut().extend_pairs(params);
+
+    Ok(url.to_string())
+}
+
+// POST /oauth/accept
+// Accepts an authorization flow, returning the URL to redirect the user to with the authorization code
+#[utoipa::path(
+    path = "/v2/oauth/accept",
+    tag = "oauth",
+    security(("token" = [])),
+    request_body = AuthorizationFlow,
+    responses(
+        (status = 200, description = "The redirect URI of the application, with the authorization code", body = OAuthRedirect),
+        (status = 400, description = "The flow does not exist or has expired", body = OAuthErrorResponse),
+        (status = 401, description = "Missing authentication, or not authenticated with a session", body = OAuthErrorResponse),
+    )
+)]
+#[post("accept")]
+pub async fn accept(
+    req: HttpRequest,
+    web::Json(info): web::Json<AuthorizationFlow>,
+    pool: Data<PgPool>,
+    pat_queue: Data<Arc<PatQueue>>,
+) -> Result<HttpResponse, OAuthError> {
+    let user = get_user_from_headers(&req, &**pool, &pat_queue, Scopes::restricted()).await?;
+    let user_id: UserId = user.id.into();
+    let state_id = parse_base62(&info.flow)? as i64;
+
+    let mut transaction = pool.begin().await?;
+
+    let flow = sqlx::query!(
+        "
+        SELECT url, oauth_state FROM states
+        WHERE id = $1 AND oauth_user_id = $2 AND oauth_code_hash IS NULL AND expires > NOW()
+        ",
+        state_id,
+        user_id as UserId
+    )
+    .fetch_optional(&mut *transaction)
+    .await?
+    .ok_or_else(|| {
+        OAuthError::InvalidRequest("Authorization request not found or expired".to_string())
+    })?;
+
+    // The code contains the ID of the flow so it can be found again, followed by a secret of
+    // which only a salted hash is stored
+    let secret = random_token("mrc_");
+    let hashed_secret = HashedPat::new(&secret);
+    let code = format!("{}.{}", info.flow, secret);
+
+    sqlx::query!(
+        "
+        UPDATE states
+        SET oauth_code_salt = $1, oauth_code_hash = $2, expires = $3
+        WHERE id = $4
+        ",
+        hashed_secret.token_salt,
+        hashed_secret.token_hash,
+        Utc::now() + Duration::minutes(AUTHORIZATION_CODE_EXPIRY_MINUTES),
+        state_id
+    )
+    .execute(&mut *transaction)
+    .await?;
+
+    transaction.commit().await?;
+
+    let mut params = vec![("code", &*code)];
+    if let Some(state) = &flow.oauth_state {
+        params.push(("state", state));
+    }
+
+    Ok(HttpResponse::Ok().json(OAuthRedirect {
+        url: redirect_url(&flow.url, &params)?,
+    }))
+}
+
+// POST /oauth/reject
+// Rejects an authorization flow, returning the URL to redirect the user back to the application with
+#[utoipa::path(
+    path = "/v2/oauth/reject",
+    tag = "oauth",
+    security(("token" = [])),
+    request_body = AuthorizationFlow,
+    responses(
+        (status = 200, description = "The redirect URI of the application, with an `access_denied` error", body = OAuthRedirect),
+        (status = 400, description = "The flow does not exist or has expired", body = OAuthErrorResponse),
+        (status = 401, description = "Missing authentication, or not authenticated with a session", body = OAuthErrorResponse),
+    )
+)]
+#[post("reject")]
+pub async fn reject(
+    req: HttpRequest,
+    web::Json(info): web::Json<AuthorizationFlow>,
+    pool: Data<PgPool>,
+    pat_queue: Data<Arc<PatQueue>>,
+) -> Result<HttpResponse, OAuthError> {
+    let user = get_user_from_headers(&req, &**pool, &pat_queue, Scopes::restricted()).await?;
+    let user_id: UserId = user.id.into();
+
+    let flow = sqlx::query!(
+        "
+        DELETE FROM states
+        WHERE id = $1 AND oauth_user_id = $2 AND oauth_code_hash IS NULL
+        RETURNING url, oauth_state
+        ",
+        parse_base62(&info.flow)? as i64,
+        user_id as UserId
+    )
+    .fetch_optional(&**pool)
+    .await?
+    .ok_or_else(|| OAuthError::InvalidRequest("Authorization request not found".to_string()))?;
+
+    let mut params = vec![("error", "access_denied")];
+    if let Some(state) = &flow.oauth_state {
+        params.push(("state", state));
+    }
+
+    Ok(HttpResponse::Ok().json(OAuthRedirect {
+        url: redirect_url(&flow.url, &params)?,
+    }))
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct TokenRequest {
+    pub grant_type: String,
+    pub code: Option<String>,
+    pub redirect_uri: Option<String>,
+    pub code_verifier: Option<String>,
+    pub refresh_token: Option<String>,
+    // Clients may authenticate either with these or with HTTP Basic authentication
+    pub client_id: Option<String>,
+    pub client_secret: Option<String>,
+}
+
+// Gets the client ID and secret from the Authorization header if present, and from the request body otherwise
+fn get_client_credentials(
+    req: &HttpRequest,
+    info: &TokenRequest,
+) -> Result<(OAuthClientId, String), OAuthError> {
+    let basic = req
+        .headers()
+        .get(actix_web::http::header::AUTHORIZATION)
+        .and_then(|x| x.to_str().ok())
+        .and_then(|x| x.strip_prefix("Basic "))
+        .and_then(|x| base64::engine::general_purpose::STANDARD.decode(x).ok())
+        .and_then(|x| String::from_utf8(x).ok());
+
+    let (client_id, client_secret) = match basic {
+        Some(credentials) => {
+            let (id, secret) = credentials
+                .split_once(':')
+                .ok_or(OAuthError::InvalidClient)?;
+            (id.to_string(), secret.to_string())
+        }
+        None => (
+            info.client_id.clone().ok_or(OAuthError::InvalidClient)?,
+            info.client_secret
+                .clone()
+                .ok_or(OAuthError::InvalidClient)?,
+        ),
+    };
+
+    let client_id = parse_base62(&client_id).map_err(|_| OAuthError::InvalidClient)?;
+
+    Ok((OAuthClientId(client_id as i64), client_secret))
+}
+
+// Checks the client's secret, returning the name of the client
+async fn authenticate_client(
+    client_id: OAuthClientId,
+    client_secret: &str,
+    transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+) -> Result<String, OAuthError> {
+    let client = sqlx::query!(
+        "
+        SELECT name, secret_salt, secret_hash FROM oauth_clients
+        WHERE id = $1
+        ",
+        client_id as OAuthClientId
+    )
+    .fetch_optional(&mut *transaction)
+    .await?
+    .ok_or(OAuthError::InvalidClient)?;
+
+    if !constant_time_eq(
+        hash_pat(&client.secret_salt, client_secret).as_bytes(),
+        client.secret_hash.as_bytes(),
+    ) {
+        return Err(OAuthError::InvalidClient);
+    }
+
+    Ok(client.name)
+}
+
+// Issues a new access token for the authorization. Access tokens are stored as PATs named after the client.
+async fn issue_access_token(
+    authorization_id: OAuthAuthorizationId,
+    client_name: &str,
+    user_id: UserId,
+    scopes: Scopes,
+    transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+) -> Result<String, OAuthError> {
+    let pat_id = generate_pat_id(transaction).await?;
+    let access_token = generate_pat(transaction, "mro_").await?;
+    let hashed_pat = HashedPat::new(&access_token);
+    let created_at = Utc::now().naive_utc();
+
+    sqlx::query!(
+        "
+        INSERT INTO pats (id, name, token_prefix, token_salt, token_hash, user_id, scopes, created_at, expires_at, oauth_authorization_id)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+        ",
+        pat_id.0,
+        client_name,
+        hashed_pat.token_prefix,
+        hashed_pat.token_salt,
+        hashed_pat.token_hash,
+        user_id as UserId,
+        scopes.bits() as i64,
+        created_at,
+        created_at + Duration::seconds(ACCESS_TOKEN_EXPIRY_SECONDS),
+        authorization_id as OAuthAuthorizationId,
+    )
+    .execute(&mut *transaction)
+    .await?;
+
+    Ok(access_token)
+}
+
+// POST /oauth/token
+// Exchanges an authorization code or refresh token for a new access token and refresh token.
+// Refresh tokens are rotated on every use, and previous access tokens of the authorization are revoked.
+#[utoipa::path(
+    path = "/v2/oauth/token",
+    tag = "oauth",
+    request_body(
+        content = TokenRequest,
+        content_type = "application/x-www-form-urlencoded",
+        description = "The client credentials may be sent with HTTP Basic authentication instead of in the body"
+    ),
+    responses(
+        (status = 200, description = "A new access token and refresh token", body = OAuthTokenResponse),
+        (status = 400, description = "Invalid grant, scopes or grant type", body = OAuthErrorResponse),
+        (status = 401, description = "Invalid client credentials", body = OAuthErrorResponse),
+    )
+)]
+#[post("token")]
+pub async fn token(
+    req: HttpRequest,
+    web::Form(info): web::Form<TokenRequest>,
+    pool: Data<PgPool>,
+) -> Result<HttpResponse, OAuthError> {
+    let (client_id, client_secret) = get_client_credentials(&req, &info)?;
+
+    let mut transaction = pool.begin().await?;
+    let client_name = authenticate_client(client_id, &client_secret, &mut transaction).await?;
+
+    let refresh_token = random_token("mrr_");
+    let hashed_refresh_token = HashedPat::new(&refresh_token);
+    let refresh_expires = Utc::now() + Duration::days(REFRESH_TOKEN_EXPIRY_DAYS);
+
+    let (authorization_id, user_id, scopes) = match &*info.grant_type {
+        "authorization_code" => {
+            let code = info
+                .code
+                .as_ref()
+                .ok_or_else(|| OAuthError::InvalidRequest("Missing code".to_string()))?;
+            let (flow, secret) = code
+                .split_once('.')
+                .ok_or_else(|| OAuthError::InvalidGrant("Invalid code".to_string()))?;
+
+            // Codes may only be used once, so the flow is deleted before the code is checked. This
+            // happens in the exchange's transaction: a concurrent exchange of the same code waits
+            // for it and then finds nothing, and a failed exchange doesn't use up the code.
+            let flow = sqlx::query!(
+                "
+                DELETE FROM states
+                WHERE id = $1 AND oauth_code_hash IS NOT NULL
+                RETURNING url, expires, oauth_client_id, oauth_scopes, oauth_code_challenge,
+                    oauth_user_id, oauth_code_salt, oauth_code_hash
+                ",
+                parse_base62(flow)? as i64
+            )
+            .fetch_optional(&mut *transaction)
+            .await?
+            .ok_or_else(|| OAuthError::InvalidGrant("Invalid code".to_string()))?;
+
+            let code_matches = match (flow.oauth_code_salt, flow.oauth_code_hash) {
+                (Some(salt), Some(hash)) => {
+                    constant_time_eq(hash_pat(&salt, secret).as_bytes(), hash.as_bytes())
+                }
+                _ => false,
+            };
+            if !code_matches
+                || flow.expires < Utc::now()
+                || flow.oauth_client_id != Some(client_id.0)
+            {
+                return Err(OAuthError::InvalidGrant("Invalid code".to_string()));
+            }
+
+            if info.redirect_uri.as_ref() != Some(&flow.url) {
+                return Err(OAuthError::InvalidGrant(
+                    "The redirect URI does not match the authorization request".to_string(),
+                ));
+            }
+
+            let pkce_matches = match (&flow.oauth_code_challenge, &info.code_verifier) {
+                (Some(challenge), Some(verifier)) => verify_pkce(challenge, verifier),
+                _ => false,
+            };
+            if !pkce_matches {
+                return Err(OAuthError::InvalidGrant(
+                    "Invalid PKCE code verifier".to_string(),
+                ));
+            }
+
+            let user_id = UserId(
+                flow.oauth_user_id
+                    .ok_or(OAuthError::InvalidGrant("Invalid code".to_string()))?,
+            );
+            let scopes = Scopes::from_bits_truncate(flow.oauth_scopes.unwrap_or(0) as u64);
+
+            let authorization_id = generate_oauth_authorization_id(&mut transaction).await?;
+
+            sqlx::query!(
+                "
+                INSERT INTO oauth_authorizations (id, client_id, user_id, scopes, refresh_token_prefix, refresh_token_salt, refresh_token_hash, refresh_expires)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+                ",
+                authorization_id as OAuthAuthorizationId,
+                client_id as OAuthClientId,
+                user_id as UserId,
+                scopes.bits() as i64,
+                hashed_refresh_token.token_prefix,
+                hashed_refresh_token.token_salt,
+                hashed_refresh_token.token_hash,
+                refresh_expires,
+            )
+            .execute(&mut *transaction)
+            .await?;
+
+            (authorization_id, user_id, scopes)
+        }
+        "refresh_token" => {
+            let refresh_token = info
+                .refresh_token
+                .as_ref()
+                .ok_or_else(|| OAuthError::InvalidRequest("Missing refresh token".to_string()))?;
+
+            let authorization = sqlx::query!(
+                "
+                SELECT id, client_id, user_id, scopes, refresh_token_salt, refresh_token_hash, refresh_expires
+                FROM oauth_authorizations
+                WHERE refresh_token_prefix = $1
+                ",
+                crate::util::pat::token_prefix(refresh_token)
+            )
+            .fetch_all(&mut *transaction)
+            .await?
+            .into_iter()
+            .find(|x| {
+                constant_time_eq(
+                    hash_pat(&x.refresh_token_salt, refresh_token).as_bytes(),
+                    x.refresh_token_hash.as_bytes(),
+                )
+            })
+            .filter(|x| x.client_id == client_id.0 && x.refresh_expires > Utc::now())
+            .ok_or_else(|| OAuthError::InvalidGrant("Invalid refresh token".to_string()))?;
+
+            sqlx::query!(
+                "
+                UPDATE oauth_authorizations
+                SET refresh_token_prefix = $1, refresh_token_salt = $2, refresh_token_hash = $3, refresh_expires = $4
+                WHERE id = $5
+                ",
+                hashed_refresh_token.token_prefix,
+                hashed_refresh_token.token_salt,
+                hashed_refresh_token.token_hash,
+                refresh_expires,
+                authorization.id,
+            )
+            .execute(&mut *transaction)
+            .await?;
+
+            sqlx::query!(
+                "
+                DELETE FROM pats
+                WHERE oauth_authorization_id = $1
+                ",
+                authorization.id
+            )
+            .execute(&mut *transaction)
+            .await?;
+
+            (
+                OAuthAuthorizationId(authorization.id),
+                UserId(authorization.user_id),
+                Scopes::from_bits_truncate(authorization.scopes as u64),
+            )
+        }
+        _ => return Err(OAuthError::UnsupportedGrantType),
+    };
+
+    let access_token = issue_access_token(
+        authorization_id,
+        &client_name,
+        user_id,
+        scopes,
+        &mut transaction,
+    )
+    .await?;
+
+    transaction.commit().await?;
+
+    Ok(HttpResponse::Ok()
+        .insert_header(("Cache-Control", "no-store"))
+        .json(OAuthTokenResponse {
+            access_token,
+            token_type: "Bearer".to_string(),
+            expires_in: ACCESS_TOKEN_EXPIRY_SECONDS,
+            refresh_token,
+            scope: scopes,
+        }))
+}
+
+// GET /oauth/authorizations
+// Get all applications the user has authorized
+#[utoipa::path(
+    path = "/v2/oauth/authorizations",
+    tag = "oauth",
+    security(("token" = [])),
+    responses(
+        (status = 200, description = "The applications the user has authorized", body = [OAuthAuthorization]),
+        (status = 401, description = "Missing or invalid authentication", body = ApiError),
+    )
+)]
+#[get("authorizations")]
+pub async fn authorizations_get(
+    req: HttpRequest,
+    pool: Data<PgPool>,
+    pat_queue: Data<Arc<PatQueue>>,
+) -> Result<HttpResponse, ApiError> {
+    let user = get_user_from_headers(&req, &**pool, &pat_queue, Scopes::USER_READ).await?;
+    let user_id: UserId = user.id.into();
+
+    let authorizations = sqlx::query!(
+        "
+        SELECT oa.id, oa.client_id, oa.scopes, oa.created, oc.name, oc.icon_url
+        FROM oauth_authorizations oa
+        INNER JOIN oauth_clients oc ON oc.id = oa.client_id
+        WHERE oa.user_id = $1
+        ORDER BY oa.created DESC
+        ",
+        user_id as UserId
+    )
+    .fetch_all(&**pool)
+    .await?
+    .into_iter()
+    .map(|x| OAuthAuthorization {
+        id: OAuthAuthorizationId(x.id).into(),
+        client_id: OAuthClientId(x.client_id).into(),
+        client_name: x.name,
+        client_icon_url: x.icon_url,
+        scopes: Scopes::from_bits_truncate(x.scopes as u64),
+        created: x.created,
+    })
+    .collect::<Vec<_>>();
+
+    Ok(HttpResponse::Ok().json(authorizations))
+}
+
+// DELETE /oauth/authorizations/{id}
+// Revokes an application's authorization, along with all of its tokens
+#[utoipa::path(
+    path = "/v2/oauth/authorizations/{id}",
+    tag = "oauth",
+    security(("token" = [])),
+    params(("id" = String, Path, description = "The ID of the authorization")),
+    responses(
+        (status = 204, description = "The authorization and its tokens were revoked"),
+        (status = 401, description = "Missing or invalid authentication", body = ApiError),
+        (status = 404, description = "The authorization does not exist or belongs to another user"),
+    )
+)]
+#[delete("authorizations/{id}")]
+pub async fn authorization_delete(
+    req: HttpRequest,
+    info: web::Path<(String,)>,
+    pool: Data<PgPool>,
+    pat_queue: Data<Arc<PatQueue>>,
+) -> Result<HttpResponse, ApiError> {
+    let user = get_user_from_headers(&req, &**pool, &pat_queue, Scopes::USER_WRITE).await?;
+    let user_id: UserId = user.id.into();
+
+    let result = sqlx::query!(
+        "
+        DELETE FROM oauth_authorizations
+        WHERE id = $1 AND user_id = $2
+        ",
+        parse_base62(&info.into_inner().0)? as i64,
+        user_id as UserId
+    )
+    .execute(&**pool)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Ok(HttpResponse::NotFound().body(""));
+    }
+
+    Ok(HttpResponse::NoContent().body(""))
+}
+
+fn validate_client_scopes(bits: u64) -> Result<Scopes, ApiError> {
+    Scopes::parse_pat_scopes(bits).ok_or_else(|| {
+        ApiError::InvalidInput(
+            "Invalid scopes! Applications require at least one scope, and cannot be granted restricted scopes.".to_string(),
+        )
+    })
+}
+
+fn validate_redirect_uris(redirect_uris: &[String]) -> Result<(), ApiError> {
+    if let Some(uri) = redirect_uris.iter().find(|x| !is_valid_redirect_uri(x)) {
+        return Err(ApiError::InvalidInput(format!(
+            "Invalid redirect URI: {uri}. Redirect URIs must use HTTPS (or HTTP for localhost) and may not contain a fragment."
+        )));
+    }
+
+    Ok(())
+}
+
+// GET /oauth/app
+// Get all applications registered by the user
+#[utoipa::path(
+    path = "/v2/oauth/app",
+    tag = "oauth",
+    security(("token" = [])),
+    responses(
+        (status = 200, description = "The applications registered by the user", body = [OAuthClient]),
+        (status = 401, description = "Missing or invalid authentication", body = ApiError),
+    )
+)]
+#[get("app")]
+pub async fn clients_get(
+    req: HttpRequest,
+    pool: Data<PgPool>,
+    pat_queue: Data<Arc<PatQueue>>,
+) -> Result<HttpResponse, ApiError> {
+    let user = get_user_from_headers(&req, &**pool, &pat_queue, Scopes::OAUTH_CLIENT_READ).await?;
+    let user_id: UserId = user.id.into();
+
+    let clients = sqlx::query!(
+        "
+        SELECT id, name, icon_url, redirect_uris, max_scopes, created_by, created
+        FROM oauth_clients
+        WHERE created_by = $1
+        ORDER BY created DESC
+        ",
+        user_id as UserId
+    )
+    .fetch_all(&**pool)
+    .await?
+    .into_iter()
+    .map(|x| OAuthClient {
+        id: OAuthClientId(x.id).into(),
+        name: x.name,
+        icon_url: x.icon_url,
+        redirect_uris: x.redirect_uris,
+        max_scopes: Scopes::from_bits_truncate(x.max_scopes as u64),
+        created_by: UserId(x.created_by).into(),
+        created: x.created,
+        client_secret: None,
+    })
+    .collect::<Vec<_>>();
+
+    Ok(HttpResponse::Ok().json(clients))
+}
+
+#[derive(Deserialize, Validate, ToSchema)]
+pub struct NewOAuthClient {
+    #[validate(length(min = 1, max = 255))]
+    pub name: String,
+    #[validate(length(min = 1, max = 16))]
+    pub redirect_uris: Vec<String>,
+    pub max_scopes: u64, // bitflag of models::pats::Scopes
+}
+
+// POST /oauth/app
+// Register a new application. Only a salted hash of the client secret is stored, so this is the only
+// time the secret is returned.
+#[utoipa::path(
+    path = "/v2/oauth/app",
+    tag = "oauth",
+    security(("token" = [])),
+    request_body = NewOAuthClient,
+    responses(
+        (status = 200, description = "The new application, including the plaintext `client_secret`", body = OAuthClient),
+        (status = 400, description = "Invalid name, redirect URIs or scopes", body = ApiError),
+        (status = 401, description = "Missing or invalid authentication", body = ApiError),
+    )
+)]
+#[post("app")]
+pub async fn client_create(
+    req: HttpRequest,
+    web::Json(info): web::Json<NewOAuthClient>,
+    pool: Data<PgPool>,
+    pat_queue: Data<Arc<PatQueue>>,
+) -> Result<HttpResponse, ApiError> {
+    let user =
+        get_user_from_headers(&req, &**pool, &pat_queue, Scopes::OAUTH_CLIENT_CREATE).await?;
+    let user_id: UserId = user.id.into();
+
+    info.validate()
+        .map_err(|err| ApiError::Validation(validation_errors_to_string(err, None)))?;
+    let max_scopes = validate_client_scopes(info.max_scopes)?;
+    validate_redirect_uris(&info.redirect_uris)?;
+
+    let mut transaction = pool.begin().await?;
+
+    let client_id = generate_oauth_client_id(&mut transaction).await?;
+    let client_secret = random_token("mrs_");
+    let hashed_secret = HashedPat::new(&client_secret);
+    let created = Utc::now();
+
+    sqlx::query!(
+        "
+        INSERT INTO oauth_clients (id, name, secret_salt, secret_hash, redirect_uris, max_scopes, created_by, created)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        ",
+        client_id as OAuthClientId,
+        info.name,
+        hashed_secret.token_salt,
+        hashed_secret.token_hash,
+        &info.redirect_uris,
+        max_scopes.bits() as i64,
+        user_id as UserId,
+        created
+    )
+    .execute(&mut *transaction)
+    .await?;
+
+    transaction.commit().await?;
+
+    Ok(HttpResponse::Ok().json(OAuthClient {
+        id: client_id.into(),
+        name: info.name,
+        icon_url: None,
+        redirect_uris: info.redirect_uris,
+        max_scopes,
+        created_by: user.id,
+        created,
+        client_secret: Some(client_secret),
+    }))
+}
+
+#[derive(Deserialize, Validate, ToSchema)]
+pub struct EditOAuthClient {
+    #[validate(length(min = 1, max = 255))]
+    pub name: Option<String>,
+    #[validate(length(min = 1, max = 16))]
+    pub redirect_uris: Option<Vec<String>>,
+    pub max_scopes: Option<u64>, // bitflag of models::pats::Scopes
+}
+
+// PATCH /oauth/app/{id}
+// Edit an application. 'None' will mean not edited. Reducing the maximum scopes also removes those
+// scopes from existing authorizations and access tokens.
+#[utoipa::path(
+    path = "/v2/oauth/app/{id}",
+    tag = "oauth",
+    security(("token" = [])),
+    params(("id" = String, Path, description = "The client ID of the application")),
+    request_body = EditOAuthClient,
+    responses(
+        (status = 204, description = "The application was edited"),
+        (status = 400, description = "Invalid name, redirect URIs or scopes", body = ApiError),
+        (status = 401, description = "Missing or invalid authentication", body = ApiError),
+        (status = 404, description = "The application does not exist or belongs to another user"),
+    )
+)]
+#[patch("app/{id}")]
+pub async fn client_edit(
+    req: HttpRequest,
+    info: web::Path<(String,)>,
+    web::Json(edit): web::Json<EditOAuthClient>,
+    pool: Data<PgPool>,
+    pat_queue: Data<Arc<PatQueue>>,
+) -> Result<HttpResponse, ApiError> {
+    let user = get_user_from_headers(&req, &**pool, &pat_queue, Scopes::OAUTH_CLIENT_WRITE).await?;
+    let user_id: UserId = user.id.into();
+    let client_id = OAuthClientId(parse_base62(&info.into_inner().0)? as i64);
+
+    edit.validate()
+        .map_err(|err| ApiError::Validation(validation_errors_to_string(err, None)))?;
+    let max_scopes = edit.max_scopes.map(validate_client_scopes).transpose()?;
+    if let Some(redirect_uris) = &edit.redirect_uris {
+        validate_redirect_uris(redirect_uris)?;
+    }
+
+    let mut transaction = pool.begin().await?;
+
+    let result = sqlx::query!(
+        "
+        UPDATE oauth_clients
+        SET name = COALESCE($1, name), redirect_uris = COALESCE($2, redirect_uris), max_scopes = COALESCE($3, max_scopes)
+        WHERE id = $4 AND created_by = $5
+        ",
+        edit.name,
+        edit.redirect_uris.as_deref(),
+        max_scopes.map(|x| x.bits() as i64),
+        client_id as OAuthClientId,
+        user_id as UserId
+    )
+    .execute(&mut *transaction)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Ok(HttpResponse::NotFound().body(""));
+    }
+
+    if let Some(max_scopes) = max_scopes {
+        sqlx::query!(
+            "
+            UPDATE oauth_authorizations
+            SET scopes = scopes & $1
+            WHERE client_id = $2
+            ",
+            max_scopes.bits() as i64,
+            client_id as OAuthClientId
+        )
+        .execute(&mut *transaction)
+        .await?;
+
+        sqlx::query!(
+            "
+            UPDATE pats
+            SET scopes = scopes & $1
+            WHERE oauth_authorization_id IN (SELECT id FROM oauth_authorizations WHERE client_id = $2)
+            ",
+            max_scopes.bits() as i64,
+            client_id as OAuthClientId
+        )
+        .execute(&mut *transaction)
+        .await?;
+    }
+
+    transaction.commit().await?;
+
+    Ok(HttpResponse::NoContent().body(""))
+}
+
+#[derive(Serialize, Deserialize, IntoParams)]
+pub struct Extension {
+    pub ext: String,
+}
+
+#[utoipa::path(
+    path = "/v2/oauth/app/{id}/icon",
+    tag = "oauth",
+    security(("token" = [])),
+    params(
+        ("id" = String, Path, description = "The client ID of the application"),
+        Extension,
+    ),
+    request_body(content = [u8], content_type = "image/*", description = "The image, in the format given by `ext`"),
+    responses(
+        (status = 204, description = "The icon was changed"),
+        (status = 400, description = "Invalid image or image format", body = ApiError),
+        (status = 401, description = "Missing or invalid authentication", body = ApiError),
+        (status = 404, description = "The application does not exist or belongs to another user"),
+    )
+)]
+#[patch("app/{id}/icon")]
+#[allow(clippy::too_many_arguments)]
+pub async fn client_icon_edit(
+    web::Query(ext): web::Query<Extension>,
+    req: HttpRequest,
+    info: web::Path<(String,)>,
+    pool: Data<PgPool>,
+    file_host: Data<Arc<dyn FileHost + Send + Sync>>,
+    mut payload: web::Payload,
+    pat_queue: Data<Arc<PatQueue>>,
+) -> Result<HttpResponse, ApiError> {
+    if let Some(content_type) = crate::util::ext::get_image_content_type(&ext.ext) {
+        let cdn_url = dotenvy::var("CDN_URL")?;
+        let user =
+            get_user_from_headers(&req, &**pool, &pat_queue, Scopes::OAUTH_CLIENT_WRITE).await?;
+        let user_id: UserId = user.id.into();
+        let client_id = OAuthClientId(parse_base62(&info.into_inner().0)? as i64);
+
+        let client = sqlx::query!(
+            "
+            SELECT icon_url FROM oauth_clients
+            WHERE id = $1 AND created_by = $2
+            ",
+            client_id as OAuthClientId,
+            user_id as UserId
+        )
+        .fetch_optional(&**pool)
+        .await?;
+
+        if let Some(client) = client {
+            if let Some(icon) = client.icon_url {
+                let name = icon.split(&format!("{cdn_url}/")).nth(1);
+
+                if let Some(icon_path) = name {
+                    file_host.delete_file_version("", icon_path).await?;
+                }
+            }
+
+            let bytes =
+                read_from_payload(&mut payload, 262144, "Icons must be smaller than 256KiB")
+                    .await?;
+
+            let hash = sha1::Sha1::from(&bytes).hexdigest();
+            let upload_data = file_host
+                .upload_file(
+                    content_type,
+                    &format!(
+                        "data/oauth/{}/{}.{}",
+                        to_base62(client_id.0 as u64),
+                        hash,
+                        ext.ext
+                    ),
+                    bytes.freeze(),
+                )
+                .await?;
+
+            sqlx::query!(
+                "
+                UPDATE oauth_clients
+                SET icon_url = $1
+                WHERE id = $2
+                ",
+                format!("{}/{}", cdn_url, upload_data.file_name),
+                client_id as OAuthClientId,
+            )
+            .execute(&**pool)
+            .await?;
+
+            Ok(HttpResponse::NoContent().body(""))
+        } else {
+            Ok(HttpResponse::NotFound().body(""))
+        }
+    } else {
+        Err(ApiError::InvalidInput(format!(
+            "Invalid format for application icon: {}",
+            ext.ext
+        )))
+    }
+}
+
+// DELETE /oauth/app/{id}
+// Delete an application, revoking all of its authorizations and tokens
+#[utoipa::path(
+    path = "/v2/oauth/app/{id}",
+    tag = "oauth",
+    security(("token" = [])),
+    params(("id" = String, Path, description = "The client ID of the application")),
+    responses(
+        (status = 204, description = "The application and its authorizations were deleted"),
+        (status = 401, description = "Missing or invalid authentication", body = ApiError),
+        (status = 404, description = "The application does not exist or belongs to another user"),
+    )
+)]
+#[delete("app/{id}")]
+pub async fn client_delete(
+    req: HttpRequest,
+    info: web::Path<(String,)>,
+    pool: Data<PgPool>,
+    pat_queue: Data<Arc<PatQueue>>,
+) -> Result<HttpResponse, ApiError> {
+    let user =
+        get_user_from_headers(&req, &**pool, &pat_queue, Scopes::OAUTH_CLIENT_DELETE).await?;
+    let user_id: UserId = user.id.into();
+
+    let result = sqlx::query!(
+        "
+        DELETE FROM oauth_clients
+        WHERE id = $1 AND created_by = $2
+        ",
+        parse_base62(&info.into_inner().0)? as i64,
+        user_id as UserId
+    )
+    .execute(&**pool)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Ok(HttpResponse::NotFound().body(""));
+    }
+
+    Ok(HttpResponse::NoContent().body(""))
+}
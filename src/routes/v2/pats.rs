@@ -7,19 +7,23 @@ Just as a summary: Don't implement this flow in your application!
 
 use crate::database;
 use crate::database::models::generate_pat_id;
-use crate::models::ids::base62_impl::{parse_base62, to_base62};
+use crate::models::ids::base62_impl::parse_base62;
 
+use crate::models::pats::{PatId, Scopes};
 use crate::models::users::UserId;
+use crate::queue::pats::PatQueue;
 use crate::routes::ApiError;
 use crate::util::auth::get_user_from_headers;
-use crate::util::pat::{generate_pat, PersonalAccessToken};
+use crate::util::pat::{generate_pat, HashedPat, PersonalAccessToken};
 
 use actix_web::web::{self, Data, Query};
 use actix_web::{delete, get, patch, post, HttpRequest, HttpResponse};
 use chrono::{Duration, Utc};
+use std::sync::Arc;
 
 use serde::Deserialize;
 use sqlx::postgres::PgPool;
+use utoipa::IntoParams;
 
 pub fn config(cfg: &mut web::ServiceConfig) {
     cfg.service(get_pats);
@@ -28,31 +32,92 @@ pub fn config(cfg: &mut web::ServiceConfig) {
     cfg.service(delete_pat);
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, IntoParams)]
 pub struct CreatePersonalAccessToken {
-    pub scope: String,
+    pub name: String,
+    pub scopes: u64,         // bitflag of models::pats::Scopes
     pub expire_in_days: i64, // resets expiry to expire_in_days days from now
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, IntoParams)]
 pub struct ModifyPersonalAccessToken {
-    pub access_token: String,
-    pub scope: Option<String>,
+    pub id: String,
+    pub name: Option<String>,
+    pub scopes: Option<u64>,
     pub expire_in_days: Option<i64>, // resets expiry to expire_in_days days from now
 }
 
+#[derive(Deserialize, IntoParams)]
+pub struct DeletePersonalAccessToken {
+    pub id: String,
+}
+
+fn validate_scopes(bits: u64) -> Result<Scopes, ApiError> {
+    Scopes::parse_pat_scopes(bits).ok_or_else(|| {
+        ApiError::InvalidInput(
+            "Invalid scopes! Personal access tokens require at least one scope, and cannot be granted restricted scopes.".to_string(),
+        )
+    })
+}
+
+fn validate_name(name: &str) -> Result<(), ApiError> {
+    if name.trim().is_empty() || name.len() > 255 {
+        return Err(ApiError::InvalidInput(
+            "Personal access token names must be between 1 and 255 characters long!".to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+// The longest personal access tokens can last, about ten years
+const MAX_EXPIRE_IN_DAYS: i64 = 3650;
+
+fn validate_expiry(expire_in_days: i64) -> Result<(), ApiError> {
+    if expire_in_days <= 0 {
+        return Err(ApiError::InvalidInput(
+            "Personal access tokens must expire in at least one day!".to_string(),
+        ));
+    }
+
+    if expire_in_days > MAX_EXPIRE_IN_DAYS {
+        return Err(ApiError::InvalidInput(format!(
+            "Personal access tokens must expire in at most {MAX_EXPIRE_IN_DAYS} days!"
+        )));
+    }
+
+    Ok(())
+}
+
 // GET /pat
 // Get all personal access tokens for the given user. Minos/Kratos cookie must be attached for it to work.
+// Access tokens issued to OAuth applications are managed through the OAuth routes instead.
+#[utoipa::path(
+    path = "/v2/pat",
+    tag = "pats",
+    security(("token" = [])),
+    responses(
+        (status = 200, description = "The user's personal access tokens", body = [PersonalAccessToken]),
+        (status = 401, description = "Missing or invalid authentication", body = ApiError),
+    )
+)]
 #[get("pat")]
-pub async fn get_pats(req: HttpRequest, pool: Data<PgPool>) -> Result<HttpResponse, ApiError> {
-    let user: crate::models::users::User = get_user_from_headers(req.headers(), &**pool).await?;
+pub async fn get_pats(
+    req: HttpRequest,
+    pool: Data<PgPool>,
+    pat_queue: Data<Arc<PatQueue>>,
+) -> Result<HttpResponse, ApiError> {
+    let user: crate::models::users::User =
+        get_user_from_headers(&req, &**pool, &pat_queue, Scopes::PAT_READ).await?;
     let db_user_id: database::models::UserId = database::models::UserId::from(user.id);
 
     let pats = sqlx::query!(
         "
-            SELECT id, access_token, user_id, scope, expires_at
+            SELECT id, name, token_prefix, user_id, scopes, created_at, expires_at,
+                last_used_at, last_used_ip, usage_count
             FROM pats
-            WHERE user_id = $1
+            WHERE user_id = $1 AND oauth_authorization_id IS NULL
+            ORDER BY created_at DESC
             ",
         db_user_id.0
     )
@@ -62,11 +127,17 @@ pub async fn get_pats(req: HttpRequest, pool: Data<PgPool>) -> Result<HttpRespon
     let pats = pats
         .into_iter()
         .map(|pat| PersonalAccessToken {
-            id: to_base62(pat.id as u64),
-            scope: pat.scope,
+            id: PatId(pat.id as u64),
+            name: pat.name,
+            scopes: Scopes::from_bits_truncate(pat.scopes as u64),
+            created_at: pat.created_at,
             expires_at: pat.expires_at,
-            access_token: pat.access_token,
+            access_token: None,
+            token_prefix: pat.token_prefix,
             user_id: UserId(pat.user_id as u64),
+            last_used_at: pat.last_used_at,
+            last_used_ip: pat.last_used_ip,
+            usage_count: pat.usage_count as u64,
         })
         .collect::<Vec<_>>();
 
@@ -76,30 +147,55 @@ pub async fn get_pats(req: HttpRequest, pool: Data<PgPool>) -> Result<HttpRespon
 // POST /pat
 // Create a new personal access token for the given user. Minos/Kratos cookie must be attached for it to work.
 // All PAT tokens are base62 encoded, and are prefixed with "mod_"
+// Only a salted hash of the token is stored, so this is the only time the token is returned
+// Scopes are a bitflag of models::pats::Scopes, and may not contain restricted scopes
+#[utoipa::path(
+    path = "/v2/pat",
+    tag = "pats",
+    security(("token" = [])),
+    params(CreatePersonalAccessToken),
+    responses(
+        (status = 200, description = "The new token, including the plaintext `access_token`", body = PersonalAccessToken),
+        (status = 400, description = "Invalid name, scopes or expiry", body = ApiError),
+        (status = 401, description = "Missing or invalid authentication", body = ApiError),
+    )
+)]
 #[post("pat")]
 pub async fn create_pat(
     req: HttpRequest,
     Query(info): Query<CreatePersonalAccessToken>, // callback url
     pool: Data<PgPool>,
+    pat_queue: Data<Arc<PatQueue>>,
 ) -> Result<HttpResponse, ApiError> {
-    let user: crate::models::users::User = get_user_from_headers(req.headers(), &**pool).await?;
+    let user: crate::models::users::User =
+        get_user_from_headers(&req, &**pool, &pat_queue, Scopes::PAT_CREATE).await?;
     let db_user_id: database::models::UserId = database::models::UserId::from(user.id);
 
+    let scopes = validate_scopes(info.scopes)?;
+    validate_name(&info.name)?;
+    validate_expiry(info.expire_in_days)?;
+
     let mut transaction: sqlx::Transaction<sqlx::Postgres> = pool.begin().await?;
 
     let pat = generate_pat_id(&mut transaction).await?;
-    let access_token = generate_pat(&mut transaction).await?;
-    let expiry = Utc::now().naive_utc() + Duration::days(info.expire_in_days);
+    let access_token = generate_pat(&mut transaction, "mod_").await?;
+    let hashed_pat = HashedPat::new(&access_token);
+    let created_at = Utc::now().naive_utc();
+    let expiry = created_at + Duration::days(info.expire_in_days);
 
     sqlx::query!(
         "
-            INSERT INTO pats (id, access_token, user_id, scope, expires_at)
-            VALUES ($1, $2, $3, $4, $5)
+            INSERT INTO pats (id, name, token_prefix, token_salt, token_hash, user_id, scopes, created_at, expires_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
             ",
         pat.0,
-        access_token,
+        info.name,
+        hashed_pat.token_prefix,
+        hashed_pat.token_salt,
+        hashed_pat.token_hash,
         db_user_id.0,
-        info.scope,
+        scopes.bits() as i64,
+        created_at,
         expiry
     )
     .execute(&mut *transaction)
@@ -108,45 +204,80 @@ pub async fn create_pat(
     transaction.commit().await?;
 
     Ok(HttpResponse::Ok().json(PersonalAccessToken {
-        id: to_base62(pat.0 as u64),
-        access_token,
-        scope: info.scope,
+        id: pat.into(),
+        name: info.name,
+        access_token: Some(access_token),
+        token_prefix: hashed_pat.token_prefix,
+        scopes,
         user_id: user.id,
+        created_at,
         expires_at: expiry,
+        last_used_at: None,
+        last_used_ip: None,
+        usage_count: 0,
     }))
 }
 
 // PATCH /pat
 // Edit an access token for the given user. 'None' will mean not edited. Minos/Kratos cookie must be attached for it to work.
+#[utoipa::path(
+    path = "/v2/pat",
+    tag = "pats",
+    security(("token" = [])),
+    params(ModifyPersonalAccessToken),
+    responses(
+        (status = 200, description = "The edited token", body = PersonalAccessToken),
+        (status = 400, description = "Invalid name, scopes or expiry", body = ApiError),
+        (status = 401, description = "Missing or invalid authentication", body = ApiError),
+    )
+)]
 #[patch("pat")]
 pub async fn edit_pat(
     req: HttpRequest,
     Query(info): Query<ModifyPersonalAccessToken>, // callback url
     pool: Data<PgPool>,
+    pat_queue: Data<Arc<PatQueue>>,
 ) -> Result<HttpResponse, ApiError> {
-    let user: crate::models::users::User = get_user_from_headers(req.headers(), &**pool).await?;
-    let access_token = &info.access_token;
+    let user: crate::models::users::User =
+        get_user_from_headers(&req, &**pool, &pat_queue, Scopes::PAT_WRITE).await?;
+    let pat_id = parse_base62(&info.id)? as i64;
     let db_user_id: database::models::UserId = database::models::UserId::from(user.id);
 
+    let scopes = info.scopes.map(validate_scopes).transpose()?;
+    if let Some(name) = &info.name {
+        validate_name(name)?;
+    }
+    if let Some(expire_in_days) = info.expire_in_days {
+        validate_expiry(expire_in_days)?;
+    }
+
     // Get the singular PAT and user combination (failing immediately if it doesn't exist)
     let mut transaction = pool.begin().await?;
     let row = sqlx::query!(
         "
-        SELECT id, access_token, scope, user_id, expires_at FROM pats
-        WHERE access_token = $1 AND user_id = $2
+        SELECT id, name, token_prefix, scopes, user_id, created_at, expires_at,
+            last_used_at, last_used_ip, usage_count
+        FROM pats
+        WHERE id = $1 AND user_id = $2 AND oauth_authorization_id IS NULL
         ",
-        access_token,
+        pat_id,
         db_user_id.0
     )
     .fetch_one(&**pool)
     .await?;
 
     let pat = PersonalAccessToken {
-        id: to_base62(row.id as u64),
-        access_token: row.access_token,
+        id: PatId(row.id as u64),
+        name: info.name.unwrap_or(row.name),
+        access_token: None,
+        token_prefix: row.token_prefix,
         user_id: UserId::from(db_user_id),
+        created_at: row.created_at,
+        last_used_at: row.last_used_at,
+        last_used_ip: row.last_used_ip,
+        usage_count: row.usage_count as u64,
 
-        scope: info.scope.unwrap_or(row.scope),
+        scopes: scopes.unwrap_or(Scopes::from_bits_truncate(row.scopes as u64)),
         expires_at: info
             .expire_in_days
             .map(|d| Utc::now().naive_utc() + Duration::days(d))
@@ -156,17 +287,18 @@ pub async fn edit_pat(
     sqlx::query!(
         "
         UPDATE pats SET
-            access_token = $1,
-            scope = $2,
-            user_id = $3,
-            expires_at = $4
+            name = $1,
+            scopes = $2,
+            expires_at = $3,
+            expiry_notified = expiry_notified AND $4
         WHERE id = $5
         ",
-        &pat.access_token,
-        pat.scope,
-        db_user_id.0,
+        pat.name,
+        pat.scopes.bits() as i64,
         pat.expires_at,
-        parse_base62(&pat.id)? as i64
+        // A new expiry means the user should be warned again before it expires
+        info.expire_in_days.is_none(),
+        row.id
     )
     .execute(&mut *transaction)
     .await?;
@@ -177,22 +309,34 @@ pub async fn edit_pat(
 
 // DELETE /pat
 // Delete a personal access token for the given user. Minos/Kratos cookie must be attached for it to work.
+#[utoipa::path(
+    path = "/v2/pat",
+    tag = "pats",
+    security(("token" = [])),
+    params(DeletePersonalAccessToken),
+    responses(
+        (status = 204, description = "The token was deleted"),
+        (status = 401, description = "Missing or invalid authentication", body = ApiError),
+    )
+)]
 #[delete("pat")]
 pub async fn delete_pat(
     req: HttpRequest,
-    Query(access_token): Query<String>, // callback url
+    Query(info): Query<DeletePersonalAccessToken>,
     pool: Data<PgPool>,
+    pat_queue: Data<Arc<PatQueue>>,
 ) -> Result<HttpResponse, ApiError> {
-    let user: crate::models::users::User = get_user_from_headers(req.headers(), &**pool).await?;
+    let user: crate::models::users::User =
+        get_user_from_headers(&req, &**pool, &pat_queue, Scopes::PAT_DELETE).await?;
     let db_user_id: database::models::UserId = database::models::UserId::from(user.id);
 
     // Get the singular PAT and user combination (failing immediately if it doesn't exist)
     let pat_id = sqlx::query!(
         "
         SELECT id FROM pats
-        WHERE access_token = $1 AND user_id = $2
+        WHERE id = $1 AND user_id = $2 AND oauth_authorization_id IS NULL
         ",
-        access_token,
+        parse_base62(&info.id)? as i64,
         db_user_id.0
     )
     .fetch_one(&**pool)
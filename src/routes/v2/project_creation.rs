@@ -3,14 +3,19 @@ use crate::database::models;
 use crate::database::models::thread_item::ThreadBuilder;
 use crate::file_hosting::{FileHost, FileHostingError};
 use crate::models::error::ApiError;
+use crate::models::pats::Scopes;
 use crate::models::projects::{
-    DonationLink, License, MonetizationStatus, ProjectId, ProjectStatus, SideType, VersionId,
-    VersionStatus,
+    DonationLink, License, MonetizationStatus, Project, ProjectId, ProjectStatus, SideType,
+    Thumbnail, VersionId, VersionStatus,
 };
 use crate::models::threads::ThreadType;
 use crate::models::users::UserId;
+use crate::queue::pats::PatQueue;
+use crate::queue::scan::ScanQueue;
 use crate::search::indexing::IndexingError;
+use crate::services::modpacks::ModpackReport;
 use crate::util::auth::{get_user_from_headers, AuthenticationError};
+use crate::util::img::{process_image_async, upload_image, ImageKind};
 use crate::util::routes::read_from_field;
 use crate::util::validate::validation_errors_to_string;
 use actix_multipart::{Field, Multipart};
@@ -25,6 +30,7 @@ use serde::{Deserialize, Serialize};
 use sqlx::postgres::PgPool;
 use std::sync::Arc;
 use thiserror::Error;
+use utoipa::ToSchema;
 use validator::Validate;
 
 pub fn config(cfg: &mut actix_web::web::ServiceConfig) {
@@ -138,8 +144,8 @@ fn default_requested_status() -> ProjectStatus {
     ProjectStatus::Approved
 }
 
-#[derive(Serialize, Deserialize, Validate, Clone)]
-struct ProjectCreateData {
+#[derive(Serialize, Deserialize, Validate, Clone, ToSchema)]
+pub struct ProjectCreateData {
     #[validate(
         length(min = 3, max = 64),
         custom(function = "crate::util::validate::validate_name")
@@ -233,7 +239,7 @@ struct ProjectCreateData {
     pub requested_status: ProjectStatus,
 }
 
-#[derive(Serialize, Deserialize, Validate, Clone)]
+#[derive(Serialize, Deserialize, Validate, Clone, ToSchema)]
 pub struct NewGalleryItem {
     /// The name of the multipart item where the gallery media is located
     pub item: String,
@@ -248,6 +254,15 @@ pub struct NewGalleryItem {
     pub ordering: i64,
 }
 
+#[derive(Serialize, ToSchema)]
+pub struct ProjectCreateResponse {
+    #[serde(flatten)]
+    pub project: Project,
+    /// The reports of the uploaded modpack files
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub modpack_reports: Vec<ModpackReport>,
+}
+
 pub struct UploadedFile {
     pub file_id: String,
     pub file_name: String,
@@ -265,12 +280,25 @@ pub async fn undo_uploads(
     Ok(())
 }
 
+#[utoipa::path(
+    path = "/v2/project",
+    tag = "projects",
+    security(("token" = [])),
+    request_body(content = ProjectCreateData, description = "A `data` part with the JSON data, followed by the file parts it names", content_type = "multipart/form-data"),
+    responses(
+        (status = 200, description = "The created project, with the reports of the modpack files of its initial versions", body = ProjectCreateResponse),
+        (status = 400, description = "Invalid input", body = ApiError),
+        (status = 401, description = "Missing authentication", body = ApiError),
+    )
+)]
 #[post("project")]
 pub async fn project_create(
     req: HttpRequest,
     mut payload: Multipart,
     client: Data<PgPool>,
     file_host: Data<Arc<dyn FileHost + Send + Sync>>,
+    pat_queue: Data<Arc<PatQueue>>,
+    scan_queue: Data<Arc<ScanQueue>>,
 ) -> Result<HttpResponse, CreateError> {
     let mut transaction = client.begin().await?;
     let mut uploaded_files = Vec::new();
@@ -282,6 +310,8 @@ pub async fn project_create(
         &***file_host,
         &mut uploaded_files,
         &client,
+        &pat_queue,
+        &scan_queue,
     )
     .await;
 
@@ -320,8 +350,8 @@ Get logged in user
         - Check for matching version
         - File size limits?
         - Check file type
-            - Eventually, malware scan
         - Upload to backblaze & create VersionFileBuilder
+        - Queue for the malware scan
     -
 
 3. Creation
@@ -329,6 +359,7 @@ Get logged in user
     - Add project data to indexing queue
 */
 
+#[allow(clippy::too_many_arguments)]
 async fn project_create_inner(
     req: HttpRequest,
     payload: &mut Multipart,
@@ -336,12 +367,15 @@ async fn project_create_inner(
     file_host: &dyn FileHost,
     uploaded_files: &mut Vec<UploadedFile>,
     pool: &PgPool,
+    pat_queue: &PatQueue,
+    scan_queue: &ScanQueue,
 ) -> Result<HttpResponse, CreateError> {
     // The base URL for files uploaded to backblaze
     let cdn_url = dotenvy::var("CDN_URL")?;
 
     // The currently logged in user
-    let current_user = get_user_from_headers(req.headers(), &mut *transaction).await?;
+    let current_user =
+        get_user_from_headers(&req, &mut *transaction, pat_queue, Scopes::PROJECT_CREATE).await?;
 
     let project_id: ProjectId = models::generate_project_id(transaction).await?.into();
 
@@ -349,6 +383,7 @@ async fn project_create_inner(
     let mut versions;
     let mut versions_map = std::collections::HashMap::new();
     let mut gallery_urls = Vec::new();
+    let mut modpack_reports = Vec::new();
 
     let all_game_versions = models::categories::GameVersion::list(&mut *transaction).await?;
     let all_loaders = models::categories::Loader::list(&mut *transaction).await?;
@@ -521,31 +556,34 @@ async fn project_create_inner(
                     )
                     .await?;
 
-                    let hash = sha1::Sha1::from(&data).hexdigest();
                     let (_, file_extension) =
                         super::version_creation::get_name_ext(&content_disposition)?;
-                    let content_type = crate::util::ext::get_image_content_type(file_extension)
-                        .ok_or_else(|| {
-                            CreateError::InvalidIconFormat(file_extension.to_string())
-                        })?;
+                    if crate::util::ext::get_image_content_type(file_extension).is_none() {
+                        return Err(CreateError::InvalidIconFormat(file_extension.to_string()));
+                    }
 
-                    let url = format!("data/{project_id}/images/{hash}.{file_extension}");
-                    let upload_data = file_host
-                        .upload_file(content_type, &url, data.freeze())
-                        .await?;
+                    let image = process_image_async(data.freeze(), ImageKind::Gallery).await?;
+                    let uploaded = upload_image(
+                        file_host,
+                        &cdn_url,
+                        &format!("data/{project_id}/images"),
+                        image,
+                    )
+                    .await?;
 
-                    uploaded_files.push(UploadedFile {
-                        file_id: upload_data.file_id,
-                        file_name: upload_data.file_name,
-                    });
+                    uploaded_files.extend(uploaded.files.into_iter().map(|x| UploadedFile {
+                        file_id: x.file_id,
+                        file_name: x.file_name,
+                    }));
 
                     gallery_urls.push(crate::models::projects::GalleryItem {
-                        url: format!("{cdn_url}/{url}"),
+                        url: uploaded.url,
                         featured: item.featured,
                         title: item.title.clone(),
                         description: item.description.clone(),
                         created: Utc::now(),
                         ordering: item.ordering,
+                        thumbnails: uploaded.thumbnails,
                     });
 
                     return Ok(());
@@ -565,17 +603,19 @@ async fn project_create_inner(
             let version_data = project_create_data.initial_versions.get(index).unwrap();
 
             // Upload the new jar file
-            super::version_creation::upload_file(
-                &mut field,
+            let file = super::version_creation::spool_version_file(&mut field).await?;
+
+            let report = super::version_creation::upload_file(
+                file,
+                file_name,
                 file_host,
+                scan_queue,
                 version_data.file_parts.len(),
                 uploaded_files,
                 &mut created_version.files,
                 &mut created_version.dependencies,
                 &cdn_url,
-                &content_disposition,
                 project_id,
-                created_version.version_id.into(),
                 &project_create_data.project_type,
                 version_data.loaders.clone(),
                 version_data.game_versions.clone(),
@@ -586,6 +626,7 @@ async fn project_create_inner(
                 transaction,
             )
             .await?;
+            modpack_reports.extend(report);
 
             Ok(())
         }
@@ -733,6 +774,7 @@ async fn project_create_inner(
             description: project_create_data.description,
             body: project_create_data.body,
             icon_url: icon_data.clone().map(|x| x.0),
+            icon_thumbnails: icon_data.clone().map(|x| x.1).unwrap_or_default(),
             issues_url: project_create_data.issues_url,
             source_url: project_create_data.source_url,
             wiki_url: project_create_data.wiki_url,
@@ -758,9 +800,10 @@ async fn project_create_inner(
                     description: x.description.clone(),
                     created: x.created,
                     ordering: x.ordering,
+                    thumbnails: x.thumbnails.clone(),
                 })
                 .collect(),
-            color: icon_data.and_then(|x| x.1),
+            color: icon_data.and_then(|x| x.2),
             thread_id,
             monetization_status: MonetizationStatus::Monetized,
         };
@@ -802,6 +845,7 @@ async fn project_create_inner(
                 .map(|v| v.version_id.into())
                 .collect::<Vec<_>>(),
             icon_url: project_builder.icon_url.clone(),
+            icon_thumbnails: project_builder.icon_thumbnails.clone(),
             issues_url: project_builder.issues_url.clone(),
             source_url: project_builder.source_url.clone(),
             wiki_url: project_builder.wiki_url.clone(),
@@ -823,7 +867,10 @@ async fn project_create_inner(
             }
         }
 
-        Ok(HttpResponse::Ok().json(response))
+        Ok(HttpResponse::Ok().json(ProjectCreateResponse {
+            project: response,
+            modpack_reports,
+        }))
     }
 }
 
@@ -842,6 +889,12 @@ async fn create_initial_version(
         )));
     }
 
+    if !version_data.upload_sessions.is_empty() {
+        return Err(CreateError::InvalidInput(String::from(
+            "Upload sessions can only be used for versions of existing projects",
+        )));
+    }
+
     version_data
         .validate()
         .map_err(|err| CreateError::ValidationError(validation_errors_to_string(err, None)))?;
@@ -915,27 +968,22 @@ async fn process_icon_upload(
     file_host: &dyn FileHost,
     mut field: Field,
     cdn_url: &str,
-) -> Result<(String, Option<u32>), CreateError> {
-    if let Some(content_type) = crate::util::ext::get_image_content_type(file_extension) {
+) -> Result<(String, Vec<Thumbnail>, Option<u32>), CreateError> {
+    if crate::util::ext::get_image_content_type(file_extension).is_some() {
         let data = read_from_field(&mut field, 262144, "Icons must be smaller than 256KiB").await?;
 
-        let color = crate::util::img::get_color_from_img(&data)?;
+        let image = process_image_async(data.freeze(), ImageKind::ProjectIcon).await?;
+        let color = image.color;
 
-        let hash = sha1::Sha1::from(&data).hexdigest();
-        let upload_data = file_host
-            .upload_file(
-                content_type,
-                &format!("data/{project_id}/{hash}.{file_extension}"),
-                data.freeze(),
-            )
-            .await?;
+        let uploaded =
+            upload_image(file_host, cdn_url, &format!("data/{project_id}"), image).await?;
 
-        uploaded_files.push(UploadedFile {
-            file_id: upload_data.file_id,
-            file_name: upload_data.file_name.clone(),
-        });
+        uploaded_files.extend(uploaded.files.into_iter().map(|x| UploadedFile {
+            file_id: x.file_id,
+            file_name: x.file_name,
+        }));
 
-        Ok((format!("{}/{}", cdn_url, upload_data.file_name), color))
+        Ok((uploaded.url, uploaded.thumbnails, color))
     } else {
         Err(CreateError::InvalidIconFormat(file_extension.to_string()))
     }
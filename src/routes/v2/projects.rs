@@ -5,14 +5,20 @@ use crate::file_hosting::FileHost;
 use crate::models;
 use crate::models::ids::base62_impl::parse_base62;
 use crate::models::notifications::NotificationBody;
+use crate::models::pats::Scopes;
 use crate::models::projects::{
     DonationLink, MonetizationStatus, Project, ProjectId, ProjectStatus, SearchRequest, SideType,
+    Thumbnail, Version,
 };
 use crate::models::teams::Permissions;
 use crate::models::threads::MessageBody;
+use crate::queue::pats::PatQueue;
 use crate::routes::ApiError;
 use crate::search::{search_for_project, SearchConfig, SearchError};
-use crate::util::auth::{filter_authorized_projects, get_user_from_headers, is_authorized};
+use crate::services;
+use crate::util::auth::{get_user_from_headers, is_authorized};
+use crate::util::blobs::delete_released_blobs;
+use crate::util::img::{delete_image, process_image_async, upload_image, ImageKind};
 use crate::util::routes::read_from_payload;
 use crate::util::validate::validation_errors_to_string;
 use actix_web::{delete, get, patch, post, web, HttpRequest, HttpResponse};
@@ -23,6 +29,7 @@ use serde::{Deserialize, Serialize};
 use serde_json::json;
 use sqlx::PgPool;
 use std::sync::Arc;
+use utoipa::{IntoParams, ToSchema};
 use validator::Validate;
 
 pub fn config(cfg: &mut web::ServiceConfig) {
@@ -46,6 +53,7 @@ pub fn config(cfg: &mut web::ServiceConfig) {
             .service(project_unfollow)
             .service(project_schedule)
             .service(super::teams::team_members_get_project)
+            .service(super::analytics::project_downloads_get)
             .service(
                 web::scope("{project_id}")
                     .service(super::versions::version_list)
@@ -55,6 +63,15 @@ pub fn config(cfg: &mut web::ServiceConfig) {
     );
 }
 
+#[utoipa::path(
+    path = "/v2/search",
+    tag = "projects",
+    params(SearchRequest),
+    responses(
+        (status = 200, description = "The projects matching the search", body = SearchResults),
+        (status = 400, description = "Invalid search parameters", body = ApiError),
+    )
+)]
 #[get("search")]
 pub async fn project_search(
     web::Query(info): web::Query<SearchRequest>,
@@ -64,12 +81,21 @@ pub async fn project_search(
     Ok(HttpResponse::Ok().json(results))
 }
 
-#[derive(Deserialize, Validate)]
+#[derive(Deserialize, Validate, IntoParams)]
 pub struct RandomProjects {
     #[validate(range(min = 1, max = 100))]
     pub count: u32,
 }
 
+#[utoipa::path(
+    path = "/v2/projects_random",
+    tag = "projects",
+    params(RandomProjects),
+    responses(
+        (status = 200, description = "Random approved projects", body = [Project]),
+        (status = 400, description = "Invalid count", body = ApiError),
+    )
+)]
 #[get("projects_random")]
 pub async fn random_projects_get(
     web::Query(count): web::Query<RandomProjects>,
@@ -103,54 +129,79 @@ pub async fn random_projects_get(
     Ok(HttpResponse::Ok().json(projects_data))
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, IntoParams)]
 pub struct ProjectIds {
     pub ids: String,
 }
 
+#[utoipa::path(
+    path = "/v2/projects",
+    tag = "projects",
+    security((), ("token" = [])),
+    params(ProjectIds),
+    responses(
+        (status = 200, description = "The visible projects with the given IDs or slugs", body = [Project]),
+        (status = 400, description = "Invalid IDs", body = ApiError),
+    )
+)]
 #[get("projects")]
 pub async fn projects_get(
     req: HttpRequest,
     web::Query(ids): web::Query<ProjectIds>,
     pool: web::Data<PgPool>,
+    pat_queue: web::Data<Arc<PatQueue>>,
 ) -> Result<HttpResponse, ApiError> {
-    let project_ids: Vec<database::models::ids::ProjectId> =
-        serde_json::from_str::<Vec<ProjectId>>(&ids.ids)?
-            .into_iter()
-            .map(|x| x.into())
-            .collect();
+    let project_ids = serde_json::from_str::<Vec<ProjectId>>(&ids.ids)?;
 
-    let projects_data = database::models::Project::get_many_full(&project_ids, &**pool).await?;
-
-    let user_option = get_user_from_headers(req.headers(), &**pool).await.ok();
+    let user_option = get_user_from_headers(&req, &**pool, &pat_queue, Scopes::PROJECT_READ)
+        .await
+        .ok();
 
-    let projects = filter_authorized_projects(projects_data, &user_option, &pool).await?;
+    let projects = services::projects::get_projects(project_ids, &user_option, &pool).await?;
 
     Ok(HttpResponse::Ok().json(projects))
 }
 
+#[utoipa::path(
+    path = "/v2/project/{id}",
+    tag = "projects",
+    security((), ("token" = [])),
+    params(("id" = String, Path, description = "The ID or slug of the project")),
+    responses(
+        (status = 200, description = "The project", body = Project),
+        (status = 404, description = "The project does not exist or is not visible"),
+    )
+)]
 #[get("{id}")]
 pub async fn project_get(
     req: HttpRequest,
     info: web::Path<(String,)>,
     pool: web::Data<PgPool>,
+    pat_queue: web::Data<Arc<PatQueue>>,
 ) -> Result<HttpResponse, ApiError> {
     let string = info.into_inner().0;
 
-    let project_data =
-        database::models::Project::get_full_from_slug_or_project_id(&string, &**pool).await?;
-
-    let user_option = get_user_from_headers(req.headers(), &**pool).await.ok();
+    let user_option = get_user_from_headers(&req, &**pool, &pat_queue, Scopes::PROJECT_READ)
+        .await
+        .ok();
 
-    if let Some(data) = project_data {
-        if is_authorized(&data.inner, &user_option, &pool).await? {
-            return Ok(HttpResponse::Ok().json(Project::from(data)));
-        }
+    if let Some(project) = services::projects::get_project(&string, &user_option, &pool).await? {
+        Ok(HttpResponse::Ok().json(project))
+    } else {
+        Ok(HttpResponse::NotFound().body(""))
     }
-    Ok(HttpResponse::NotFound().body(""))
 }
 
 //checks the validity of a project id or slug
+#[utoipa::path(
+    path = "/v2/project/{id}/check",
+    tag = "projects",
+    params(("id" = String, Path, description = "The ID or slug of the project")),
+    responses(
+        (status = 200, description = "A JSON object with the `id` of the project"),
+        (status = 404, description = "The project does not exist"),
+    )
+)]
 #[get("{id}/check")]
 pub async fn project_get_check(
     info: web::Path<(String,)>,
@@ -207,23 +258,36 @@ pub async fn project_get_check(
     }
 }
 
-#[derive(Serialize)]
-struct DependencyInfo {
+#[derive(Serialize, ToSchema)]
+pub struct DependencyInfo {
     pub projects: Vec<Project>,
-    pub versions: Vec<models::projects::Version>,
+    pub versions: Vec<Version>,
 }
 
+#[utoipa::path(
+    path = "/v2/project/{id}/dependencies",
+    tag = "projects",
+    security((), ("token" = [])),
+    params(("id" = String, Path, description = "The ID or slug of the project")),
+    responses(
+        (status = 200, description = "The projects and versions the versions of the project depend on", body = DependencyInfo),
+        (status = 404, description = "The project does not exist or is not visible"),
+    )
+)]
 #[get("dependencies")]
 pub async fn dependency_list(
     req: HttpRequest,
     info: web::Path<(String,)>,
     pool: web::Data<PgPool>,
+    pat_queue: web::Data<Arc<PatQueue>>,
 ) -> Result<HttpResponse, ApiError> {
     let string = info.into_inner().0;
 
     let result = database::models::Project::get_from_slug_or_project_id(&string, &**pool).await?;
 
-    let user_option = get_user_from_headers(req.headers(), &**pool).await.ok();
+    let user_option = get_user_from_headers(&req, &**pool, &pat_queue, Scopes::PROJECT_READ)
+        .await
+        .ok();
 
     if let Some(project) = result {
         if !is_authorized(&project, &user_option, &pool).await? {
@@ -296,7 +360,7 @@ pub async fn dependency_list(
             .collect::<Vec<_>>();
         let mut versions = versions_result
             .into_iter()
-            .map(models::projects::Version::from)
+            .map(Version::from)
             .collect::<Vec<_>>();
 
         projects.sort_by(|a, b| b.published.cmp(&a.published));
@@ -311,7 +375,7 @@ pub async fn dependency_list(
     }
 }
 
-#[derive(Deserialize, Validate)]
+#[derive(Deserialize, Validate, ToSchema)]
 pub struct EditProject {
     #[validate(
         length(min = 3, max = 64),
@@ -410,6 +474,19 @@ pub struct EditProject {
     pub monetization_status: Option<MonetizationStatus>,
 }
 
+#[utoipa::path(
+    path = "/v2/project/{id}",
+    tag = "projects",
+    security(("token" = [])),
+    params(("id" = String, Path, description = "The ID or slug of the project")),
+    request_body = EditProject,
+    responses(
+        (status = 204, description = "The project was edited"),
+        (status = 400, description = "Invalid input", body = ApiError),
+        (status = 401, description = "Missing authentication, or not permitted to edit the project", body = ApiError),
+        (status = 404, description = "The project does not exist"),
+    )
+)]
 #[patch("{id}")]
 pub async fn project_edit(
     req: HttpRequest,
@@ -417,8 +494,9 @@ pub async fn project_edit(
     pool: web::Data<PgPool>,
     config: web::Data<SearchConfig>,
     new_project: web::Json<EditProject>,
+    pat_queue: web::Data<Arc<PatQueue>>,
 ) -> Result<HttpResponse, ApiError> {
-    let user = get_user_from_headers(req.headers(), &**pool).await?;
+    let user = get_user_from_headers(&req, &**pool, &pat_queue, Scopes::PROJECT_WRITE).await?;
 
     new_project
         .validate()
@@ -1163,7 +1241,7 @@ pub async fn project_edit(
     }
 }
 
-#[derive(Deserialize, Validate)]
+#[derive(Deserialize, Validate, ToSchema)]
 pub struct BulkEditProject {
     #[validate(length(max = 3))]
     pub categories: Option<Vec<String>>,
@@ -1226,14 +1304,27 @@ pub struct BulkEditProject {
     pub discord_url: Option<Option<String>>,
 }
 
+#[utoipa::path(
+    path = "/v2/projects",
+    tag = "projects",
+    security(("token" = [])),
+    params(ProjectIds),
+    request_body = BulkEditProject,
+    responses(
+        (status = 204, description = "The projects were edited"),
+        (status = 400, description = "Invalid input", body = ApiError),
+        (status = 401, description = "Missing authentication, or not permitted to edit one of the projects", body = ApiError),
+    )
+)]
 #[patch("projects")]
 pub async fn projects_edit(
     req: HttpRequest,
     web::Query(ids): web::Query<ProjectIds>,
     pool: web::Data<PgPool>,
     bulk_edit_project: web::Json<BulkEditProject>,
+    pat_queue: web::Data<Arc<PatQueue>>,
 ) -> Result<HttpResponse, ApiError> {
-    let user = get_user_from_headers(req.headers(), &**pool).await?;
+    let user = get_user_from_headers(&req, &**pool, &pat_queue, Scopes::PROJECT_WRITE).await?;
 
     bulk_edit_project
         .validate()
@@ -1545,20 +1636,34 @@ pub async fn projects_edit(
     Ok(HttpResponse::NoContent().body(""))
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct SchedulingData {
     pub time: DateTime<Utc>,
     pub requested_status: ProjectStatus,
 }
 
+#[utoipa::path(
+    path = "/v2/project/{id}/schedule",
+    tag = "projects",
+    security(("token" = [])),
+    params(("id" = String, Path, description = "The ID or slug of the project")),
+    request_body = SchedulingData,
+    responses(
+        (status = 204, description = "The project was scheduled"),
+        (status = 400, description = "Invalid time or status", body = ApiError),
+        (status = 401, description = "Missing authentication, or not permitted to edit the project", body = ApiError),
+        (status = 404, description = "The project does not exist"),
+    )
+)]
 #[post("{id}/schedule")]
 pub async fn project_schedule(
     req: HttpRequest,
     info: web::Path<(String,)>,
     pool: web::Data<PgPool>,
     scheduling_data: web::Json<SchedulingData>,
+    pat_queue: web::Data<Arc<PatQueue>>,
 ) -> Result<HttpResponse, ApiError> {
-    let user = get_user_from_headers(req.headers(), &**pool).await?;
+    let user = get_user_from_headers(&req, &**pool, &pat_queue, Scopes::PROJECT_WRITE).await?;
 
     if scheduling_data.time < Utc::now() {
         return Err(ApiError::InvalidInput(
@@ -1612,11 +1717,26 @@ pub async fn project_schedule(
     }
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, IntoParams)]
 pub struct Extension {
     pub ext: String,
 }
 
+#[utoipa::path(
+    path = "/v2/project/{id}/icon",
+    tag = "projects",
+    security(("token" = [])),
+    params(
+        ("id" = String, Path, description = "The ID or slug of the project"),
+        Extension,
+    ),
+    request_body(content = [u8], content_type = "image/*", description = "The image, in the format given by `ext`. It is stored re-encoded as PNG, without metadata"),
+    responses(
+        (status = 204, description = "The icon was changed"),
+        (status = 400, description = "Invalid image or image format, or the project does not exist", body = ApiError),
+        (status = 401, description = "Missing authentication, or not permitted to edit the project", body = ApiError),
+    )
+)]
 #[patch("{id}/icon")]
 pub async fn project_icon_edit(
     web::Query(ext): web::Query<Extension>,
@@ -1625,10 +1745,11 @@ pub async fn project_icon_edit(
     pool: web::Data<PgPool>,
     file_host: web::Data<Arc<dyn FileHost + Send + Sync>>,
     mut payload: web::Payload,
+    pat_queue: web::Data<Arc<PatQueue>>,
 ) -> Result<HttpResponse, ApiError> {
-    if let Some(content_type) = crate::util::ext::get_image_content_type(&ext.ext) {
+    if crate::util::ext::get_image_content_type(&ext.ext).is_some() {
         let cdn_url = dotenvy::var("CDN_URL")?;
-        let user = get_user_from_headers(req.headers(), &**pool).await?;
+        let user = get_user_from_headers(&req, &**pool, &pat_queue, Scopes::PROJECT_WRITE).await?;
         let string = info.into_inner().0;
 
         let project_item = database::models::Project::get_from_slug_or_project_id(&string, &**pool)
@@ -1656,38 +1777,31 @@ pub async fn project_icon_edit(
             }
         }
 
-        if let Some(icon) = project_item.icon_url {
-            let name = icon.split(&format!("{cdn_url}/")).nth(1);
-
-            if let Some(icon_path) = name {
-                file_host.delete_file_version("", icon_path).await?;
-            }
-        }
-
         let bytes =
             read_from_payload(&mut payload, 262144, "Icons must be smaller than 256KiB").await?;
 
-        let color = crate::util::img::get_color_from_img(&bytes)?;
+        let image = process_image_async(bytes.freeze(), ImageKind::ProjectIcon).await?;
+        let color = image.color;
 
-        let hash = sha1::Sha1::from(&bytes).hexdigest();
         let project_id: ProjectId = project_item.id.into();
-        let upload_data = file_host
-            .upload_file(
-                content_type,
-                &format!("data/{}/{}.{}", project_id, hash, ext.ext),
-                bytes.freeze(),
-            )
-            .await?;
+        let icon = upload_image(
+            &***file_host,
+            &cdn_url,
+            &format!("data/{project_id}"),
+            image,
+        )
+        .await?;
 
         let mut transaction = pool.begin().await?;
 
         sqlx::query!(
             "
             UPDATE mods
-            SET icon_url = $1, color = $2
-            WHERE (id = $3)
+            SET icon_url = $1, icon_thumbnails = $2, color = $3
+            WHERE (id = $4)
             ",
-            format!("{}/{}", cdn_url, upload_data.file_name),
+            icon.url,
+            serde_json::to_value(&icon.thumbnails).unwrap_or_default(),
             color.map(|x| x as i32),
             project_item.id as database::models::ids::ProjectId,
         )
@@ -1696,6 +1810,21 @@ pub async fn project_icon_edit(
 
         transaction.commit().await?;
 
+        // The old icon is only deleted once the project uses the new one. Icons are named after
+        // their hash, so uploading the same icon again keeps its URL.
+        if let Some(old_icon) = project_item.icon_url.filter(|x| *x != icon.url) {
+            if let Err(e) = delete_image(
+                &***file_host,
+                &cdn_url,
+                &old_icon,
+                &project_item.icon_thumbnails,
+            )
+            .await
+            {
+                log::warn!("Deleting the old icon of project {project_id} failed: {e}");
+            }
+        }
+
         Ok(HttpResponse::NoContent().body(""))
     } else {
         Err(ApiError::InvalidInput(format!(
@@ -1705,14 +1834,26 @@ pub async fn project_icon_edit(
     }
 }
 
+#[utoipa::path(
+    path = "/v2/project/{id}/icon",
+    tag = "projects",
+    security(("token" = [])),
+    params(("id" = String, Path, description = "The ID or slug of the project")),
+    responses(
+        (status = 204, description = "The icon was removed"),
+        (status = 400, description = "The project does not exist", body = ApiError),
+        (status = 401, description = "Missing authentication, or not permitted to edit the project", body = ApiError),
+    )
+)]
 #[delete("{id}/icon")]
 pub async fn delete_project_icon(
     req: HttpRequest,
     info: web::Path<(String,)>,
     pool: web::Data<PgPool>,
     file_host: web::Data<Arc<dyn FileHost + Send + Sync>>,
+    pat_queue: web::Data<Arc<PatQueue>>,
 ) -> Result<HttpResponse, ApiError> {
-    let user = get_user_from_headers(req.headers(), &**pool).await?;
+    let user = get_user_from_headers(&req, &**pool, &pat_queue, Scopes::PROJECT_WRITE).await?;
     let string = info.into_inner().0;
 
     let project_item = database::models::Project::get_from_slug_or_project_id(&string, &**pool)
@@ -1741,12 +1882,8 @@ pub async fn delete_project_icon(
     }
 
     let cdn_url = dotenvy::var("CDN_URL")?;
-    if let Some(icon) = project_item.icon_url {
-        let name = icon.split(&format!("{cdn_url}/")).nth(1);
-
-        if let Some(icon_path) = name {
-            file_host.delete_file_version("", icon_path).await?;
-        }
+    if let Some(icon) = &project_item.icon_url {
+        delete_image(&***file_host, &cdn_url, icon, &project_item.icon_thumbnails).await?;
     }
 
     let mut transaction = pool.begin().await?;
@@ -1754,7 +1891,7 @@ pub async fn delete_project_icon(
     sqlx::query!(
         "
         UPDATE mods
-        SET icon_url = NULL, color = NULL
+        SET icon_url = NULL, icon_thumbnails = '[]', color = NULL
         WHERE (id = $1)
         ",
         project_item.id as database::models::ids::ProjectId,
@@ -1767,7 +1904,7 @@ pub async fn delete_project_icon(
     Ok(HttpResponse::NoContent().body(""))
 }
 
-#[derive(Serialize, Deserialize, Validate)]
+#[derive(Serialize, Deserialize, Validate, IntoParams)]
 pub struct GalleryCreateQuery {
     pub featured: bool,
     #[validate(length(min = 1, max = 255))]
@@ -1777,7 +1914,24 @@ pub struct GalleryCreateQuery {
     pub ordering: Option<i64>,
 }
 
+#[utoipa::path(
+    path = "/v2/project/{id}/gallery",
+    tag = "projects",
+    security(("token" = [])),
+    params(
+        ("id" = String, Path, description = "The ID or slug of the project"),
+        Extension,
+        GalleryCreateQuery,
+    ),
+    request_body(content = [u8], content_type = "image/*", description = "The image, in the format given by `ext`. It is stored re-encoded as PNG, without metadata"),
+    responses(
+        (status = 204, description = "The gallery image was added"),
+        (status = 400, description = "Invalid image or input, or the project does not exist", body = ApiError),
+        (status = 401, description = "Missing authentication, or not permitted to edit the project", body = ApiError),
+    )
+)]
 #[post("{id}/gallery")]
+#[allow(clippy::too_many_arguments)]
 pub async fn add_gallery_item(
     web::Query(ext): web::Query<Extension>,
     req: HttpRequest,
@@ -1786,13 +1940,14 @@ pub async fn add_gallery_item(
     pool: web::Data<PgPool>,
     file_host: web::Data<Arc<dyn FileHost + Send + Sync>>,
     mut payload: web::Payload,
+    pat_queue: web::Data<Arc<PatQueue>>,
 ) -> Result<HttpResponse, ApiError> {
-    if let Some(content_type) = crate::util::ext::get_image_content_type(&ext.ext) {
+    if crate::util::ext::get_image_content_type(&ext.ext).is_some() {
         item.validate()
             .map_err(|err| ApiError::Validation(validation_errors_to_string(err, None)))?;
 
         let cdn_url = dotenvy::var("CDN_URL")?;
-        let user = get_user_from_headers(req.headers(), &**pool).await?;
+        let user = get_user_from_headers(&req, &**pool, &pat_queue, Scopes::PROJECT_WRITE).await?;
         let string = info.into_inner().0;
 
         let project_item =
@@ -1833,12 +1988,13 @@ pub async fn add_gallery_item(
             "Gallery image exceeds the maximum of 5MiB.",
         )
         .await?;
-        let hash = sha1::Sha1::from(&bytes).hexdigest();
 
-        let id: ProjectId = project_item.inner.id.into();
-        let url = format!("data/{}/images/{}.{}", id, hash, &*ext.ext);
+        let image = process_image_async(bytes.freeze(), ImageKind::Gallery).await?;
 
-        let file_url = format!("{cdn_url}/{url}");
+        // Images are named after their hash, so a duplicate would have the URL of an existing one
+        let id: ProjectId = project_item.inner.id.into();
+        let dir = format!("data/{id}/images");
+        let file_url = image.url(&cdn_url, &dir);
         if project_item
             .gallery_items
             .iter()
@@ -1849,9 +2005,7 @@ pub async fn add_gallery_item(
             ));
         }
 
-        file_host
-            .upload_file(content_type, &url, bytes.freeze())
-            .await?;
+        let uploaded = upload_image(&***file_host, &cdn_url, &dir, image).await?;
 
         let mut transaction = pool.begin().await?;
 
@@ -1870,12 +2024,13 @@ pub async fn add_gallery_item(
         }
 
         database::models::project_item::GalleryItem {
-            image_url: file_url,
+            image_url: uploaded.url,
             featured: item.featured,
             title: item.title,
             description: item.description,
             created: Utc::now(),
             ordering: item.ordering.unwrap_or(0),
+            thumbnails: uploaded.thumbnails,
         }
         .insert(project_item.inner.id, &mut transaction)
         .await?;
@@ -1891,7 +2046,7 @@ pub async fn add_gallery_item(
     }
 }
 
-#[derive(Serialize, Deserialize, Validate)]
+#[derive(Serialize, Deserialize, Validate, IntoParams)]
 pub struct GalleryEditQuery {
     /// The url of the gallery item to edit
     pub url: String,
@@ -1913,14 +2068,29 @@ pub struct GalleryEditQuery {
     pub ordering: Option<i64>,
 }
 
+#[utoipa::path(
+    path = "/v2/project/{id}/gallery",
+    tag = "projects",
+    security(("token" = [])),
+    params(
+        ("id" = String, Path, description = "The ID or slug of the project"),
+        GalleryEditQuery,
+    ),
+    responses(
+        (status = 204, description = "The gallery image was edited"),
+        (status = 400, description = "Invalid input, or the project or gallery image does not exist", body = ApiError),
+        (status = 401, description = "Missing authentication, or not permitted to edit the project", body = ApiError),
+    )
+)]
 #[patch("{id}/gallery")]
 pub async fn edit_gallery_item(
     req: HttpRequest,
     web::Query(item): web::Query<GalleryEditQuery>,
     info: web::Path<(String,)>,
     pool: web::Data<PgPool>,
+    pat_queue: web::Data<Arc<PatQueue>>,
 ) -> Result<HttpResponse, ApiError> {
-    let user = get_user_from_headers(req.headers(), &**pool).await?;
+    let user = get_user_from_headers(&req, &**pool, &pat_queue, Scopes::PROJECT_WRITE).await?;
     let string = info.into_inner().0;
 
     item.validate()
@@ -2043,11 +2213,25 @@ pub async fn edit_gallery_item(
     Ok(HttpResponse::NoContent().body(""))
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, IntoParams)]
 pub struct GalleryDeleteQuery {
     pub url: String,
 }
 
+#[utoipa::path(
+    path = "/v2/project/{id}/gallery",
+    tag = "projects",
+    security(("token" = [])),
+    params(
+        ("id" = String, Path, description = "The ID or slug of the project"),
+        GalleryDeleteQuery,
+    ),
+    responses(
+        (status = 204, description = "The gallery image was removed"),
+        (status = 400, description = "The project or gallery image does not exist", body = ApiError),
+        (status = 401, description = "Missing authentication, or not permitted to edit the project", body = ApiError),
+    )
+)]
 #[delete("{id}/gallery")]
 pub async fn delete_gallery_item(
     req: HttpRequest,
@@ -2055,8 +2239,9 @@ pub async fn delete_gallery_item(
     info: web::Path<(String,)>,
     pool: web::Data<PgPool>,
     file_host: web::Data<Arc<dyn FileHost + Send + Sync>>,
+    pat_queue: web::Data<Arc<PatQueue>>,
 ) -> Result<HttpResponse, ApiError> {
-    let user = get_user_from_headers(req.headers(), &**pool).await?;
+    let user = get_user_from_headers(&req, &**pool, &pat_queue, Scopes::PROJECT_WRITE).await?;
     let string = info.into_inner().0;
 
     let project_item = database::models::Project::get_from_slug_or_project_id(&string, &**pool)
@@ -2085,9 +2270,9 @@ pub async fn delete_gallery_item(
     }
     let mut transaction = pool.begin().await?;
 
-    let id = sqlx::query!(
+    let gallery_item = sqlx::query!(
         "
-        SELECT id FROM mods_gallery
+        SELECT id, thumbnails FROM mods_gallery
         WHERE image_url = $1
         ",
         item.url
@@ -2099,15 +2284,17 @@ pub async fn delete_gallery_item(
             "Gallery item at URL {} is not part of the project's gallery.",
             item.url
         ))
-    })?
-    .id;
+    })?;
+    let id = gallery_item.id;
 
     let cdn_url = dotenvy::var("CDN_URL")?;
-    let name = item.url.split(&format!("{cdn_url}/")).nth(1);
-
-    if let Some(icon_path) = name {
-        file_host.delete_file_version("", icon_path).await?;
-    }
+    delete_image(
+        &***file_host,
+        &cdn_url,
+        &item.url,
+        &serde_json::from_value::<Vec<Thumbnail>>(gallery_item.thumbnails).unwrap_or_default(),
+    )
+    .await?;
 
     let mut transaction = pool.begin().await?;
 
@@ -2126,14 +2313,28 @@ pub async fn delete_gallery_item(
     Ok(HttpResponse::NoContent().body(""))
 }
 
+#[utoipa::path(
+    path = "/v2/project/{id}",
+    tag = "projects",
+    security(("token" = [])),
+    params(("id" = String, Path, description = "The ID or slug of the project")),
+    responses(
+        (status = 204, description = "The project was deleted"),
+        (status = 400, description = "The project does not exist", body = ApiError),
+        (status = 401, description = "Missing authentication, or not permitted to delete the project", body = ApiError),
+        (status = 404, description = "The project could not be deleted"),
+    )
+)]
 #[delete("{id}")]
 pub async fn project_delete(
     req: HttpRequest,
     info: web::Path<(String,)>,
     pool: web::Data<PgPool>,
     config: web::Data<SearchConfig>,
+    pat_queue: web::Data<Arc<PatQueue>>,
+    file_host: web::Data<Arc<dyn FileHost + Send + Sync>>,
 ) -> Result<HttpResponse, ApiError> {
-    let user = get_user_from_headers(req.headers(), &**pool).await?;
+    let user = get_user_from_headers(&req, &**pool, &pat_queue, Scopes::PROJECT_DELETE).await?;
     let string = info.into_inner().0;
 
     let project = database::models::Project::get_from_slug_or_project_id(&string, &**pool)
@@ -2170,6 +2371,8 @@ pub async fn project_delete(
 
     transaction.commit().await?;
 
+    delete_released_blobs(&pool, &***file_host).await?;
+
     delete_from_index(project.id.into(), config).await?;
 
     if result.is_some() {
@@ -2179,13 +2382,26 @@ pub async fn project_delete(
     }
 }
 
+#[utoipa::path(
+    path = "/v2/project/{id}/follow",
+    tag = "projects",
+    security(("token" = [])),
+    params(("id" = String, Path, description = "The ID or slug of the project")),
+    responses(
+        (status = 204, description = "The project was followed"),
+        (status = 400, description = "The project does not exist or is already followed", body = ApiError),
+        (status = 401, description = "Missing or invalid authentication", body = ApiError),
+        (status = 404, description = "The project is not visible"),
+    )
+)]
 #[post("{id}/follow")]
 pub async fn project_follow(
     req: HttpRequest,
     info: web::Path<(String,)>,
     pool: web::Data<PgPool>,
+    pat_queue: web::Data<Arc<PatQueue>>,
 ) -> Result<HttpResponse, ApiError> {
-    let user = get_user_from_headers(req.headers(), &**pool).await?;
+    let user = get_user_from_headers(&req, &**pool, &pat_queue, Scopes::USER_WRITE).await?;
     let string = info.into_inner().0;
 
     let result = database::models::Project::get_from_slug_or_project_id(&string, &**pool)
@@ -2248,13 +2464,25 @@ pub async fn project_follow(
     }
 }
 
+#[utoipa::path(
+    path = "/v2/project/{id}/follow",
+    tag = "projects",
+    security(("token" = [])),
+    params(("id" = String, Path, description = "The ID or slug of the project")),
+    responses(
+        (status = 204, description = "The project was unfollowed"),
+        (status = 400, description = "The project does not exist or is not followed", body = ApiError),
+        (status = 401, description = "Missing or invalid authentication", body = ApiError),
+    )
+)]
 #[delete("{id}/follow")]
 pub async fn project_unfollow(
     req: HttpRequest,
     info: web::Path<(String,)>,
     pool: web::Data<PgPool>,
+    pat_queue: web::Data<Arc<PatQueue>>,
 ) -> Result<HttpResponse, ApiError> {
-    let user = get_user_from_headers(req.headers(), &**pool).await?;
+    let user = get_user_from_headers(&req, &**pool, &pat_queue, Scopes::USER_WRITE).await?;
     let string = info.into_inner().0;
 
     let result = database::models::Project::get_from_slug_or_project_id(&string, &**pool)
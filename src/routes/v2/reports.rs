@@ -1,14 +1,19 @@
 use crate::database::models::thread_item::{ThreadBuilder, ThreadMessageBuilder};
 use crate::models::ids::{base62_impl::parse_base62, ProjectId, UserId, VersionId};
+use crate::models::pats::Scopes;
 use crate::models::reports::{ItemType, Report};
 use crate::models::threads::{MessageBody, ThreadType};
+use crate::queue::pats::PatQueue;
 use crate::routes::ApiError;
 use crate::util::auth::{check_is_moderator_from_headers, get_user_from_headers};
+use crate::util::pagination::{Cursor, OptionalPagination, PaginationQuery};
 use actix_web::{delete, get, patch, post, web, HttpRequest, HttpResponse};
 use chrono::Utc;
 use futures::StreamExt;
 use serde::Deserialize;
 use sqlx::PgPool;
+use std::sync::Arc;
+use utoipa::{IntoParams, ToSchema};
 use validator::Validate;
 
 pub fn config(cfg: &mut web::ServiceConfig) {
@@ -19,7 +24,7 @@ pub fn config(cfg: &mut web::ServiceConfig) {
     cfg.service(report_get);
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct CreateReport {
     pub report_type: String,
     pub item_id: String,
@@ -27,15 +32,28 @@ pub struct CreateReport {
     pub body: String,
 }
 
+#[utoipa::path(
+    path = "/v2/report",
+    tag = "reports",
+    security(("token" = [])),
+    request_body = CreateReport,
+    responses(
+        (status = 200, description = "The new report", body = Report),
+        (status = 400, description = "Invalid report type or item", body = ApiError),
+        (status = 401, description = "Missing or invalid authentication", body = ApiError),
+    )
+)]
 #[post("report")]
 pub async fn report_create(
     req: HttpRequest,
     pool: web::Data<PgPool>,
     mut body: web::Payload,
+    pat_queue: web::Data<Arc<PatQueue>>,
 ) -> Result<HttpResponse, ApiError> {
     let mut transaction = pool.begin().await?;
 
-    let current_user = get_user_from_headers(req.headers(), &mut *transaction).await?;
+    let current_user =
+        get_user_from_headers(&req, &mut *transaction, &pat_queue, Scopes::REPORT_CREATE).await?;
 
     let mut bytes = web::BytesMut::new();
     while let Some(item) = body.next().await {
@@ -157,7 +175,7 @@ pub async fn report_create(
     }))
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, IntoParams)]
 pub struct ReportsRequestOptions {
     #[serde(default = "default_count")]
     count: i16,
@@ -172,18 +190,62 @@ fn default_all() -> bool {
     true
 }
 
+#[utoipa::path(
+    path = "/v2/report",
+    tag = "reports",
+    security(("token" = [])),
+    params(ReportsRequestOptions, PaginationQuery),
+    responses(
+        (status = 200, description = "The open reports made by the user, or all open reports for moderators, oldest first. When `cursor` or `limit` is given, `count` is ignored and a page with the `items`, `next_cursor` and `next` link is returned instead.", body = [Report]),
+        (status = 400, description = "Invalid cursor or limit", body = ApiError),
+        (status = 401, description = "Missing or invalid authentication", body = ApiError),
+    )
+)]
 #[get("report")]
 pub async fn reports(
     req: HttpRequest,
     pool: web::Data<PgPool>,
     count: web::Query<ReportsRequestOptions>,
+    pagination: OptionalPagination,
+    pat_queue: web::Data<Arc<PatQueue>>,
 ) -> Result<HttpResponse, ApiError> {
-    let user = get_user_from_headers(req.headers(), &**pool).await?;
+    let user = get_user_from_headers(&req, &**pool, &pat_queue, Scopes::REPORT_READ).await?;
 
     use futures::stream::TryStreamExt;
 
-    let report_ids = if user.role.is_mod() && count.all {
-        sqlx::query!(
+    let (report_ids, next) = if let Some(pagination) = &pagination.0 {
+        let reporter = if user.role.is_mod() && count.all {
+            None
+        } else {
+            Some(user.id.0 as i64)
+        };
+
+        let rows = sqlx::query!(
+            "
+            SELECT id, created FROM reports
+            WHERE closed = FALSE AND ($1::bigint IS NULL OR reporter = $1)
+            AND ($2::timestamptz IS NULL OR (created, id) > ($2, $3))
+            ORDER BY created ASC, id ASC
+            LIMIT $4
+            ",
+            reporter,
+            pagination.cursor.map(|x| x.created),
+            pagination.cursor.map(|x| x.id),
+            pagination.fetch_limit(),
+        )
+        .fetch_all(&**pool)
+        .await?;
+
+        let (rows, next) = pagination.split(rows, |x| Cursor::new(x.created, x.id));
+
+        let report_ids = rows
+            .into_iter()
+            .map(|x| crate::database::models::ids::ReportId(x.id))
+            .collect();
+
+        (report_ids, next)
+    } else if user.role.is_mod() && count.all {
+        let report_ids = sqlx::query!(
             "
             SELECT id FROM reports
             WHERE closed = FALSE
@@ -198,9 +260,11 @@ pub async fn reports(
                 .map(|m| crate::database::models::ids::ReportId(m.id)))
         })
         .try_collect::<Vec<crate::database::models::ids::ReportId>>()
-        .await?
+        .await?;
+
+        (report_ids, None)
     } else {
-        sqlx::query!(
+        let report_ids = sqlx::query!(
             "
             SELECT id FROM reports
             WHERE closed = FALSE AND reporter = $1
@@ -216,7 +280,9 @@ pub async fn reports(
                 .map(|m| crate::database::models::ids::ReportId(m.id)))
         })
         .try_collect::<Vec<crate::database::models::ids::ReportId>>()
-        .await?
+        .await?;
+
+        (report_ids, None)
     };
 
     let query_reports =
@@ -227,17 +293,30 @@ pub async fn reports(
     for x in query_reports {
         reports.push(to_report(x)?);
     }
+    reports.sort_by_key(|x| (x.created, x.id.0));
 
-    Ok(HttpResponse::Ok().json(reports))
+    Ok(pagination.respond(reports, next))
 }
 
+#[utoipa::path(
+    path = "/v2/report/{id}",
+    tag = "reports",
+    security(("token" = [])),
+    params(("id" = ReportId, Path, description = "The ID of the report")),
+    responses(
+        (status = 200, description = "The report", body = Report),
+        (status = 401, description = "Missing or invalid authentication", body = ApiError),
+        (status = 404, description = "The report does not exist or is not visible to the user"),
+    )
+)]
 #[get("report/{id}")]
 pub async fn report_get(
     req: HttpRequest,
     pool: web::Data<PgPool>,
     info: web::Path<(crate::models::reports::ReportId,)>,
+    pat_queue: web::Data<Arc<PatQueue>>,
 ) -> Result<HttpResponse, ApiError> {
-    let user = get_user_from_headers(req.headers(), &**pool).await?;
+    let user = get_user_from_headers(&req, &**pool, &pat_queue, Scopes::REPORT_READ).await?;
     let id = info.into_inner().0.into();
 
     let report = crate::database::models::report_item::Report::get(id, &**pool).await?;
@@ -253,21 +332,35 @@ pub async fn report_get(
     }
 }
 
-#[derive(Deserialize, Validate)]
+#[derive(Deserialize, Validate, ToSchema)]
 pub struct EditReport {
     #[validate(length(max = 65536))]
     pub body: Option<String>,
     pub closed: Option<bool>,
 }
 
+#[utoipa::path(
+    path = "/v2/report/{id}",
+    tag = "reports",
+    security(("token" = [])),
+    params(("id" = ReportId, Path, description = "The ID of the report")),
+    request_body = EditReport,
+    responses(
+        (status = 204, description = "The report was edited"),
+        (status = 400, description = "Invalid input", body = ApiError),
+        (status = 401, description = "Missing or invalid authentication", body = ApiError),
+        (status = 404, description = "The report does not exist or is not visible to the user"),
+    )
+)]
 #[patch("report/{id}")]
 pub async fn report_edit(
     req: HttpRequest,
     pool: web::Data<PgPool>,
     info: web::Path<(crate::models::reports::ReportId,)>,
     edit_report: web::Json<EditReport>,
+    pat_queue: web::Data<Arc<PatQueue>>,
 ) -> Result<HttpResponse, ApiError> {
-    let user = get_user_from_headers(req.headers(), &**pool).await?;
+    let user = get_user_from_headers(&req, &**pool, &pat_queue, Scopes::REPORT_WRITE).await?;
     let id = info.into_inner().0.into();
 
     let report = crate::database::models::report_item::Report::get(id, &**pool).await?;
@@ -335,13 +428,25 @@ pub async fn report_edit(
     }
 }
 
+#[utoipa::path(
+    path = "/v2/report/{id}",
+    tag = "reports",
+    security(("token" = [])),
+    params(("id" = ReportId, Path, description = "The ID of the report")),
+    responses(
+        (status = 204, description = "The report was deleted"),
+        (status = 401, description = "The user is not a moderator", body = ApiError),
+        (status = 404, description = "The report does not exist"),
+    )
+)]
 #[delete("report/{id}")]
 pub async fn report_delete(
     req: HttpRequest,
     pool: web::Data<PgPool>,
     info: web::Path<(crate::models::reports::ReportId,)>,
+    pat_queue: web::Data<Arc<PatQueue>>,
 ) -> Result<HttpResponse, ApiError> {
-    check_is_moderator_from_headers(req.headers(), &**pool).await?;
+    check_is_moderator_from_headers(&req, &**pool, &pat_queue, Scopes::REPORT_DELETE).await?;
 
     let mut transaction = pool.begin().await?;
     let result = crate::database::models::report_item::Report::remove_full(
@@ -7,6 +7,13 @@ pub fn config(cfg: &mut web::ServiceConfig) {
     cfg.service(get_stats);
 }
 
+#[utoipa::path(
+    path = "/v2/statistics",
+    tag = "statistics",
+    responses(
+        (status = 200, description = "A JSON object with the number of `projects`, `versions`, `authors` and `files`"),
+    )
+)]
 #[get("statistics")]
 pub async fn get_stats(pool: web::Data<PgPool>) -> Result<HttpResponse, ApiError> {
     let projects = sqlx::query!(
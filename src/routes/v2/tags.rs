@@ -5,6 +5,7 @@ use actix_web::{get, web, HttpResponse};
 use chrono::{DateTime, Utc};
 use models::categories::{Category, GameVersion, Loader};
 use sqlx::PgPool;
+use utoipa::{IntoParams, ToSchema};
 
 pub fn config(cfg: &mut web::ServiceConfig) {
     cfg.service(
@@ -21,7 +22,7 @@ pub fn config(cfg: &mut web::ServiceConfig) {
     );
 }
 
-#[derive(serde::Serialize, serde::Deserialize)]
+#[derive(serde::Serialize, serde::Deserialize, ToSchema)]
 pub struct CategoryData {
     icon: String,
     name: String,
@@ -31,6 +32,13 @@ pub struct CategoryData {
 
 // TODO: searching / filtering? Could be used to implement a live
 // searching category list
+#[utoipa::path(
+    path = "/v2/tag/category",
+    tag = "tags",
+    responses(
+        (status = 200, description = "All categories", body = [CategoryData]),
+    )
+)]
 #[get("category")]
 pub async fn category_list(pool: web::Data<PgPool>) -> Result<HttpResponse, ApiError> {
     let results = Category::list(&**pool)
@@ -47,13 +55,20 @@ pub async fn category_list(pool: web::Data<PgPool>) -> Result<HttpResponse, ApiE
     Ok(HttpResponse::Ok().json(results))
 }
 
-#[derive(serde::Serialize, serde::Deserialize)]
+#[derive(serde::Serialize, serde::Deserialize, ToSchema)]
 pub struct LoaderData {
     icon: String,
     name: String,
     supported_project_types: Vec<String>,
 }
 
+#[utoipa::path(
+    path = "/v2/tag/loader",
+    tag = "tags",
+    responses(
+        (status = 200, description = "All loaders", body = [LoaderData]),
+    )
+)]
 #[get("loader")]
 pub async fn loader_list(pool: web::Data<PgPool>) -> Result<HttpResponse, ApiError> {
     let mut results = Loader::list(&**pool)
@@ -71,7 +86,7 @@ pub async fn loader_list(pool: web::Data<PgPool>) -> Result<HttpResponse, ApiErr
     Ok(HttpResponse::Ok().json(results))
 }
 
-#[derive(serde::Serialize)]
+#[derive(serde::Serialize, ToSchema)]
 pub struct GameVersionQueryData {
     pub version: String,
     pub version_type: String,
@@ -79,13 +94,21 @@ pub struct GameVersionQueryData {
     pub major: bool,
 }
 
-#[derive(serde::Deserialize)]
+#[derive(serde::Deserialize, IntoParams)]
 pub struct GameVersionQuery {
     #[serde(rename = "type")]
     type_: Option<String>,
     major: Option<bool>,
 }
 
+#[utoipa::path(
+    path = "/v2/tag/game_version",
+    tag = "tags",
+    params(GameVersionQuery),
+    responses(
+        (status = 200, description = "The game versions matching the filters", body = [GameVersionQueryData]),
+    )
+)]
 #[get("game_version")]
 pub async fn game_version_list(
     pool: web::Data<PgPool>,
@@ -108,19 +131,26 @@ pub async fn game_version_list(
     Ok(HttpResponse::Ok().json(results))
 }
 
-#[derive(serde::Serialize)]
-pub struct License {
+#[derive(serde::Serialize, ToSchema)]
+pub struct LicenseData {
     short: String,
     name: String,
 }
 
+#[utoipa::path(
+    path = "/v2/tag/license",
+    tag = "tags",
+    responses(
+        (status = 200, description = "All SPDX licenses", body = [LicenseData]),
+    )
+)]
 #[get("license")]
 pub async fn license_list() -> HttpResponse {
     let licenses = spdx::identifiers::LICENSES;
-    let mut results: Vec<License> = Vec::with_capacity(licenses.len());
+    let mut results: Vec<LicenseData> = Vec::with_capacity(licenses.len());
 
     for (short, name, _) in licenses {
-        results.push(License {
+        results.push(LicenseData {
             short: short.to_string(),
             name: name.to_string(),
         });
@@ -129,12 +159,21 @@ pub async fn license_list() -> HttpResponse {
     HttpResponse::Ok().json(results)
 }
 
-#[derive(serde::Serialize)]
+#[derive(serde::Serialize, ToSchema)]
 pub struct LicenseText {
     title: String,
     body: String,
 }
 
+#[utoipa::path(
+    path = "/v2/tag/license/{id}",
+    tag = "tags",
+    params(("id" = String, Path, description = "The SPDX identifier of the license")),
+    responses(
+        (status = 200, description = "The text of the license", body = LicenseText),
+        (status = 400, description = "Invalid SPDX identifier", body = ApiError),
+    )
+)]
 #[get("license/{id}")]
 pub async fn license_text(params: web::Path<(String,)>) -> Result<HttpResponse, ApiError> {
     let license_id = params.into_inner().0;
@@ -158,12 +197,19 @@ pub async fn license_text(params: web::Path<(String,)>) -> Result<HttpResponse,
     ))
 }
 
-#[derive(serde::Serialize)]
+#[derive(serde::Serialize, ToSchema)]
 pub struct DonationPlatformQueryData {
     short: String,
     name: String,
 }
 
+#[utoipa::path(
+    path = "/v2/tag/donation_platform",
+    tag = "tags",
+    responses(
+        (status = 200, description = "All donation platforms", body = [DonationPlatformQueryData]),
+    )
+)]
 #[get("donation_platform")]
 pub async fn donation_platform_list(pool: web::Data<PgPool>) -> Result<HttpResponse, ApiError> {
     let results: Vec<DonationPlatformQueryData> = DonationPlatform::list(&**pool)
@@ -177,18 +223,39 @@ pub async fn donation_platform_list(pool: web::Data<PgPool>) -> Result<HttpRespo
     Ok(HttpResponse::Ok().json(results))
 }
 
+#[utoipa::path(
+    path = "/v2/tag/report_type",
+    tag = "tags",
+    responses(
+        (status = 200, description = "All report types", body = [String]),
+    )
+)]
 #[get("report_type")]
 pub async fn report_type_list(pool: web::Data<PgPool>) -> Result<HttpResponse, ApiError> {
     let results = ReportType::list(&**pool).await?;
     Ok(HttpResponse::Ok().json(results))
 }
 
+#[utoipa::path(
+    path = "/v2/tag/project_type",
+    tag = "tags",
+    responses(
+        (status = 200, description = "All project types", body = [String]),
+    )
+)]
 #[get("project_type")]
 pub async fn project_type_list(pool: web::Data<PgPool>) -> Result<HttpResponse, ApiError> {
     let results = ProjectType::list(&**pool).await?;
     Ok(HttpResponse::Ok().json(results))
 }
 
+#[utoipa::path(
+    path = "/v2/tag/side_type",
+    tag = "tags",
+    responses(
+        (status = 200, description = "All side types", body = [String]),
+    )
+)]
 #[get("side_type")]
 pub async fn side_type_list(pool: web::Data<PgPool>) -> Result<HttpResponse, ApiError> {
     let results = SideType::list(&**pool).await?;
@@ -2,14 +2,19 @@ use crate::database::models::notification_item::NotificationBuilder;
 use crate::database::models::TeamMember;
 use crate::models::ids::ProjectId;
 use crate::models::notifications::NotificationBody;
+use crate::models::pats::Scopes;
 use crate::models::teams::{Permissions, TeamId};
 use crate::models::users::UserId;
+use crate::queue::pats::PatQueue;
 use crate::routes::ApiError;
+use crate::services;
 use crate::util::auth::get_user_from_headers;
 use actix_web::{delete, get, patch, post, web, HttpRequest, HttpResponse};
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
+use std::sync::Arc;
+use utoipa::{IntoParams, ToSchema};
 
 pub fn config(cfg: &mut web::ServiceConfig) {
     cfg.service(teams_get);
@@ -25,11 +30,22 @@ pub fn config(cfg: &mut web::ServiceConfig) {
     );
 }
 
+#[utoipa::path(
+    path = "/v2/project/{id}/members",
+    tag = "teams",
+    security((), ("token" = [])),
+    params(("id" = String, Path, description = "The ID or slug of the project")),
+    responses(
+        (status = 200, description = "The members of the project's team. Pending invites and payouts are only shown to members of the team.", body = [TeamMember]),
+        (status = 404, description = "The project does not exist"),
+    )
+)]
 #[get("{id}/members")]
 pub async fn team_members_get_project(
     req: HttpRequest,
     info: web::Path<(String,)>,
     pool: web::Data<PgPool>,
+    pat_queue: web::Data<Arc<PatQueue>>,
 ) -> Result<HttpResponse, ApiError> {
     let string = info.into_inner().0;
     let project_data =
@@ -38,7 +54,9 @@ pub async fn team_members_get_project(
     if let Some(project) = project_data {
         let members_data = TeamMember::get_from_team_full(project.team_id, &**pool).await?;
 
-        let current_user = get_user_from_headers(req.headers(), &**pool).await.ok();
+        let current_user = get_user_from_headers(&req, &**pool, &pat_queue, Scopes::PROJECT_READ)
+            .await
+            .ok();
 
         if let Some(user) = &current_user {
             let team_member =
@@ -74,110 +92,87 @@ pub async fn team_members_get_project(
     }
 }
 
+#[utoipa::path(
+    path = "/v2/team/{id}/members",
+    tag = "teams",
+    security((), ("token" = [])),
+    params(("id" = TeamId, Path, description = "The ID of the team")),
+    responses(
+        (status = 200, description = "The members of the team. Pending invites and payouts are only shown to members of the team.", body = [TeamMember]),
+    )
+)]
 #[get("{id}/members")]
 pub async fn team_members_get(
     req: HttpRequest,
     info: web::Path<(TeamId,)>,
     pool: web::Data<PgPool>,
+    pat_queue: web::Data<Arc<PatQueue>>,
 ) -> Result<HttpResponse, ApiError> {
     let id = info.into_inner().0;
-    let members_data = TeamMember::get_from_team_full(id.into(), &**pool).await?;
-
-    let current_user = get_user_from_headers(req.headers(), &**pool).await.ok();
-
-    if let Some(user) = &current_user {
-        let team_member = TeamMember::get_from_user_id(id.into(), user.id.into(), &**pool)
-            .await
-            .map_err(ApiError::Database)?;
-
-        if team_member.is_some() {
-            let team_members: Vec<_> = members_data
-                .into_iter()
-                .map(|data| crate::models::teams::TeamMember::from(data, false))
-                .collect();
 
-            return Ok(HttpResponse::Ok().json(team_members));
-        }
-    }
+    let current_user = get_user_from_headers(&req, &**pool, &pat_queue, Scopes::PROJECT_READ)
+        .await
+        .ok();
 
-    let user_id = current_user.map(|x| x.id.into());
-    let team_members: Vec<_> = members_data
-        .into_iter()
-        .filter(|x| {
-            x.accepted
-                || user_id
-                    .map(|y: crate::database::models::UserId| y == x.user.id)
-                    .unwrap_or(false)
-        })
-        .map(|data| crate::models::teams::TeamMember::from(data, true))
-        .collect();
+    let team_members = services::teams::get_team_members(id, &current_user, &pool).await?;
 
     Ok(HttpResponse::Ok().json(team_members))
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, IntoParams)]
 pub struct TeamIds {
     pub ids: String,
 }
 
+#[utoipa::path(
+    path = "/v2/teams",
+    tag = "teams",
+    security((), ("token" = [])),
+    params(TeamIds),
+    responses(
+        (status = 200, description = "The members of each of the teams", body = Vec<Vec<TeamMember>>),
+        (status = 400, description = "Invalid IDs", body = ApiError),
+    )
+)]
 #[get("teams")]
 pub async fn teams_get(
     req: HttpRequest,
     web::Query(ids): web::Query<TeamIds>,
     pool: web::Data<PgPool>,
+    pat_queue: web::Data<Arc<PatQueue>>,
 ) -> Result<HttpResponse, ApiError> {
-    use itertools::Itertools;
-
-    let team_ids = serde_json::from_str::<Vec<TeamId>>(&ids.ids)?
-        .into_iter()
-        .map(|x| x.into())
-        .collect::<Vec<crate::database::models::ids::TeamId>>();
-
-    let teams_data = TeamMember::get_from_team_full_many(&team_ids, &**pool).await?;
-
-    let current_user = get_user_from_headers(req.headers(), &**pool).await.ok();
-    let accepted = if let Some(user) = current_user {
-        TeamMember::get_from_user_id_many(&team_ids, user.id.into(), &**pool)
-            .await?
-            .into_iter()
-            .map(|m| m.team_id.0)
-            .collect()
-    } else {
-        std::collections::HashSet::new()
-    };
-
-    let teams_groups = teams_data.into_iter().group_by(|data| data.team_id.0);
-
-    let mut teams: Vec<Vec<crate::models::teams::TeamMember>> = vec![];
-
-    for (id, member_data) in &teams_groups {
-        if accepted.contains(&id) {
-            let team_members =
-                member_data.map(|data| crate::models::teams::TeamMember::from(data, false));
-
-            teams.push(team_members.collect());
-
-            continue;
-        }
+    let team_ids = serde_json::from_str::<Vec<TeamId>>(&ids.ids)?;
 
-        let team_members = member_data
-            .filter(|x| x.accepted)
-            .map(|data| crate::models::teams::TeamMember::from(data, true));
+    let current_user = get_user_from_headers(&req, &**pool, &pat_queue, Scopes::PROJECT_READ)
+        .await
+        .ok();
 
-        teams.push(team_members.collect());
-    }
+    let teams = services::teams::get_teams(team_ids, &current_user, &pool).await?;
 
     Ok(HttpResponse::Ok().json(teams))
 }
 
+#[utoipa::path(
+    path = "/v2/team/{id}/join",
+    tag = "teams",
+    security(("token" = [])),
+    params(("id" = TeamId, Path, description = "The ID of the team")),
+    responses(
+        (status = 204, description = "The invite to the team was accepted"),
+        (status = 400, description = "The user was not invited to the team, or is already a member", body = ApiError),
+        (status = 401, description = "Missing or invalid authentication", body = ApiError),
+    )
+)]
 #[post("{id}/join")]
 pub async fn join_team(
     req: HttpRequest,
     info: web::Path<(TeamId,)>,
     pool: web::Data<PgPool>,
+    pat_queue: web::Data<Arc<PatQueue>>,
 ) -> Result<HttpResponse, ApiError> {
     let team_id = info.into_inner().0.into();
-    let current_user = get_user_from_headers(req.headers(), &**pool).await?;
+    let current_user =
+        get_user_from_headers(&req, &**pool, &pat_queue, Scopes::PROJECT_WRITE).await?;
 
     let member =
         TeamMember::get_from_user_id_pending(team_id, current_user.id.into(), &**pool).await?;
@@ -221,7 +216,7 @@ fn default_ordering() -> i64 {
     0
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
 pub struct NewTeamMember {
     pub user_id: UserId,
     #[serde(default = "default_role")]
@@ -234,18 +229,32 @@ pub struct NewTeamMember {
     pub ordering: i64,
 }
 
+#[utoipa::path(
+    path = "/v2/team/{id}/members",
+    tag = "teams",
+    security(("token" = [])),
+    params(("id" = TeamId, Path, description = "The ID of the team")),
+    request_body = NewTeamMember,
+    responses(
+        (status = 204, description = "The user was invited to the team"),
+        (status = 400, description = "Invalid input, or the user is already a member", body = ApiError),
+        (status = 401, description = "Missing authentication, or not permitted to manage the team", body = ApiError),
+    )
+)]
 #[post("{id}/members")]
 pub async fn add_team_member(
     req: HttpRequest,
     info: web::Path<(TeamId,)>,
     pool: web::Data<PgPool>,
     new_member: web::Json<NewTeamMember>,
+    pat_queue: web::Data<Arc<PatQueue>>,
 ) -> Result<HttpResponse, ApiError> {
     let team_id = info.into_inner().0.into();
 
     let mut transaction = pool.begin().await?;
 
-    let current_user = get_user_from_headers(req.headers(), &**pool).await?;
+    let current_user =
+        get_user_from_headers(&req, &**pool, &pat_queue, Scopes::PROJECT_WRITE).await?;
     let member = TeamMember::get_from_user_id(team_id, current_user.id.into(), &**pool)
         .await?
         .ok_or_else(|| {
@@ -341,7 +350,7 @@ pub async fn add_team_member(
     Ok(HttpResponse::NoContent().body(""))
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
 pub struct EditTeamMember {
     pub permissions: Option<Permissions>,
     pub role: Option<String>,
@@ -349,18 +358,35 @@ pub struct EditTeamMember {
     pub ordering: Option<i64>,
 }
 
+#[utoipa::path(
+    path = "/v2/team/{id}/members/{user_id}",
+    tag = "teams",
+    security(("token" = [])),
+    params(
+        ("id" = TeamId, Path, description = "The ID of the team"),
+        ("user_id" = UserId, Path, description = "The ID of the team member"),
+    ),
+    request_body = EditTeamMember,
+    responses(
+        (status = 204, description = "The team member was edited"),
+        (status = 400, description = "Invalid input, or the user is not a member", body = ApiError),
+        (status = 401, description = "Missing authentication, or not permitted to manage the team", body = ApiError),
+    )
+)]
 #[patch("{id}/members/{user_id}")]
 pub async fn edit_team_member(
     req: HttpRequest,
     info: web::Path<(TeamId, UserId)>,
     pool: web::Data<PgPool>,
     edit_member: web::Json<EditTeamMember>,
+    pat_queue: web::Data<Arc<PatQueue>>,
 ) -> Result<HttpResponse, ApiError> {
     let ids = info.into_inner();
     let id = ids.0.into();
     let user_id = ids.1.into();
 
-    let current_user = get_user_from_headers(req.headers(), &**pool).await?;
+    let current_user =
+        get_user_from_headers(&req, &**pool, &pat_queue, Scopes::PROJECT_WRITE).await?;
     let member = TeamMember::get_from_user_id(id, current_user.id.into(), &**pool)
         .await?
         .ok_or_else(|| {
@@ -431,21 +457,35 @@ pub async fn edit_team_member(
     Ok(HttpResponse::NoContent().body(""))
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct TransferOwnership {
     pub user_id: UserId,
 }
 
+#[utoipa::path(
+    path = "/v2/team/{id}/owner",
+    tag = "teams",
+    security(("token" = [])),
+    params(("id" = TeamId, Path, description = "The ID of the team")),
+    request_body = TransferOwnership,
+    responses(
+        (status = 204, description = "The ownership of the team was transferred"),
+        (status = 400, description = "The new owner is not a member of the team", body = ApiError),
+        (status = 401, description = "Missing authentication, or not the owner of the team", body = ApiError),
+    )
+)]
 #[patch("{id}/owner")]
 pub async fn transfer_ownership(
     req: HttpRequest,
     info: web::Path<(TeamId,)>,
     pool: web::Data<PgPool>,
     new_owner: web::Json<TransferOwnership>,
+    pat_queue: web::Data<Arc<PatQueue>>,
 ) -> Result<HttpResponse, ApiError> {
     let id = info.into_inner().0;
 
-    let current_user = get_user_from_headers(req.headers(), &**pool).await?;
+    let current_user =
+        get_user_from_headers(&req, &**pool, &pat_queue, Scopes::PROJECT_WRITE).await?;
 
     if !current_user.role.is_admin() {
         let member = TeamMember::get_from_user_id(id.into(), current_user.id.into(), &**pool)
@@ -506,17 +546,33 @@ pub async fn transfer_ownership(
     Ok(HttpResponse::NoContent().body(""))
 }
 
+#[utoipa::path(
+    path = "/v2/team/{id}/members/{user_id}",
+    tag = "teams",
+    security(("token" = [])),
+    params(
+        ("id" = TeamId, Path, description = "The ID of the team"),
+        ("user_id" = UserId, Path, description = "The ID of the team member"),
+    ),
+    responses(
+        (status = 204, description = "The user was removed from the team, or their invite was revoked"),
+        (status = 401, description = "Missing authentication, or not permitted to manage the team", body = ApiError),
+        (status = 404, description = "The user is not a member of the team"),
+    )
+)]
 #[delete("{id}/members/{user_id}")]
 pub async fn remove_team_member(
     req: HttpRequest,
     info: web::Path<(TeamId, UserId)>,
     pool: web::Data<PgPool>,
+    pat_queue: web::Data<Arc<PatQueue>>,
 ) -> Result<HttpResponse, ApiError> {
     let ids = info.into_inner();
     let id = ids.0.into();
     let user_id = ids.1.into();
 
-    let current_user = get_user_from_headers(req.headers(), &**pool).await?;
+    let current_user =
+        get_user_from_headers(&req, &**pool, &pat_queue, Scopes::PROJECT_WRITE).await?;
     let member = TeamMember::get_from_user_id(id, current_user.id.into(), &**pool)
         .await?
         .ok_or_else(|| {
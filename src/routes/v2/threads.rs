@@ -3,15 +3,20 @@ use crate::database::models::notification_item::NotificationBuilder;
 use crate::database::models::thread_item::ThreadMessageBuilder;
 use crate::models::ids::ThreadMessageId;
 use crate::models::notifications::NotificationBody;
+use crate::models::pats::Scopes;
 use crate::models::projects::ProjectStatus;
 use crate::models::threads::{MessageBody, Thread, ThreadId, ThreadMessage, ThreadType};
 use crate::models::users::User;
+use crate::queue::pats::PatQueue;
 use crate::routes::ApiError;
 use crate::util::auth::{check_is_moderator_from_headers, get_user_from_headers};
+use crate::util::pagination::{Cursor, OptionalPagination, PaginationQuery};
 use actix_web::{delete, get, post, web, HttpRequest, HttpResponse};
 use futures::TryStreamExt;
 use serde::Deserialize;
 use sqlx::PgPool;
+use std::sync::Arc;
+use utoipa::{IntoParams, ToSchema};
 
 pub fn config(cfg: &mut web::ServiceConfig) {
     cfg.service(
@@ -243,17 +248,29 @@ fn convert_thread(data: database::models::Thread, users: Vec<User>, user: &User)
     }
 }
 
+#[utoipa::path(
+    path = "/v2/thread/{id}",
+    tag = "threads",
+    security(("token" = [])),
+    params(("id" = ThreadId, Path, description = "The ID of the thread")),
+    responses(
+        (status = 200, description = "The thread", body = Thread),
+        (status = 401, description = "Missing or invalid authentication", body = ApiError),
+        (status = 404, description = "The thread does not exist or is not visible to the user"),
+    )
+)]
 #[get("{id}")]
 pub async fn thread_get(
     req: HttpRequest,
     info: web::Path<(ThreadId,)>,
     pool: web::Data<PgPool>,
+    pat_queue: web::Data<Arc<PatQueue>>,
 ) -> Result<HttpResponse, ApiError> {
     let string = info.into_inner().0.into();
 
     let thread_data = database::models::Thread::get(string, &**pool).await?;
 
-    let user = get_user_from_headers(req.headers(), &**pool).await?;
+    let user = get_user_from_headers(&req, &**pool, &pat_queue, Scopes::THREAD_READ).await?;
 
     if let Some(mut data) = thread_data {
         if is_authorized_thread(&data, &user, &pool).await? {
@@ -279,18 +296,30 @@ pub async fn thread_get(
     Ok(HttpResponse::NotFound().body(""))
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, IntoParams)]
 pub struct ThreadIds {
     pub ids: String,
 }
 
+#[utoipa::path(
+    path = "/v2/threads",
+    tag = "threads",
+    security(("token" = [])),
+    params(ThreadIds),
+    responses(
+        (status = 200, description = "The visible threads with the given IDs", body = [Thread]),
+        (status = 400, description = "Invalid IDs", body = ApiError),
+        (status = 401, description = "Missing or invalid authentication", body = ApiError),
+    )
+)]
 #[get("threads")]
 pub async fn threads_get(
     req: HttpRequest,
     web::Query(ids): web::Query<ThreadIds>,
     pool: web::Data<PgPool>,
+    pat_queue: web::Data<Arc<PatQueue>>,
 ) -> Result<HttpResponse, ApiError> {
-    let user = get_user_from_headers(req.headers(), &**pool).await?;
+    let user = get_user_from_headers(&req, &**pool, &pat_queue, Scopes::THREAD_READ).await?;
 
     let thread_ids: Vec<database::models::ids::ThreadId> =
         serde_json::from_str::<Vec<ThreadId>>(&ids.ids)?
@@ -305,19 +334,33 @@ pub async fn threads_get(
     Ok(HttpResponse::Ok().json(threads))
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct NewThreadMessage {
     pub body: MessageBody,
 }
 
+#[utoipa::path(
+    path = "/v2/thread/{id}",
+    tag = "threads",
+    security(("token" = [])),
+    params(("id" = ThreadId, Path, description = "The ID of the thread")),
+    request_body = NewThreadMessage,
+    responses(
+        (status = 204, description = "The message was sent"),
+        (status = 400, description = "Invalid message", body = ApiError),
+        (status = 401, description = "Missing authentication, or not permitted to send this message", body = ApiError),
+        (status = 404, description = "The thread does not exist or is not visible to the user"),
+    )
+)]
 #[post("{id}")]
 pub async fn thread_send_message(
     req: HttpRequest,
     info: web::Path<(ThreadId,)>,
     pool: web::Data<PgPool>,
     new_message: web::Json<NewThreadMessage>,
+    pat_queue: web::Data<Arc<PatQueue>>,
 ) -> Result<HttpResponse, ApiError> {
-    let user = get_user_from_headers(req.headers(), &**pool).await?;
+    let user = get_user_from_headers(&req, &**pool, &pat_queue, Scopes::THREAD_WRITE).await?;
 
     let string: database::models::ThreadId = info.into_inner().0.into();
 
@@ -471,12 +514,59 @@ pub async fn thread_send_message(
     }
 }
 
+#[utoipa::path(
+    path = "/v2/thread/inbox",
+    tag = "threads",
+    security(("token" = [])),
+    params(PaginationQuery),
+    responses(
+        (status = 200, description = "The threads waiting for a moderator. Pages are sorted by the creation date of the thread, oldest first. When `cursor` or `limit` is given, a page with the `items`, `next_cursor` and `next` link is returned instead.", body = [Thread]),
+        (status = 400, description = "Invalid cursor or limit", body = ApiError),
+        (status = 401, description = "The user is not a moderator", body = ApiError),
+    )
+)]
 #[get("inbox")]
 pub async fn moderation_inbox(
     req: HttpRequest,
+    pagination: OptionalPagination,
     pool: web::Data<PgPool>,
+    pat_queue: web::Data<Arc<PatQueue>>,
 ) -> Result<HttpResponse, ApiError> {
-    let user = check_is_moderator_from_headers(req.headers(), &**pool).await?;
+    let user =
+        check_is_moderator_from_headers(&req, &**pool, &pat_queue, Scopes::THREAD_READ).await?;
+
+    if let Some(pagination) = &pagination.0 {
+        let rows = sqlx::query!(
+            "
+            SELECT id, created
+            FROM threads
+            WHERE show_in_mod_inbox = TRUE
+            AND ($1::timestamptz IS NULL OR (created, id) > ($1, $2))
+            ORDER BY created ASC, id ASC
+            LIMIT $3
+            ",
+            pagination.cursor.map(|x| x.created),
+            pagination.cursor.map(|x| x.id),
+            pagination.fetch_limit(),
+        )
+        .fetch_all(&**pool)
+        .await?;
+
+        let (rows, next) = pagination.split(rows, |x| Cursor::new(x.created, x.id));
+        let ids: Vec<_> = rows
+            .into_iter()
+            .map(|x| database::models::ThreadId(x.id))
+            .collect();
+
+        let threads_data = database::models::Thread::get_many(&ids, &**pool).await?;
+        let mut threads = filter_authorized_threads(threads_data, &user, &pool).await?;
+        threads.sort_by_key(|x| {
+            ids.iter()
+                .position(|id| *id == database::models::ThreadId::from(x.id))
+        });
+
+        return Ok(HttpResponse::Ok().json(pagination.page(threads, next)));
+    }
 
     let ids = sqlx::query!(
         "
@@ -496,13 +586,24 @@ pub async fn moderation_inbox(
     Ok(HttpResponse::Ok().json(threads))
 }
 
+#[utoipa::path(
+    path = "/v2/thread/{id}/read",
+    tag = "threads",
+    security(("token" = [])),
+    params(("id" = ThreadId, Path, description = "The ID of the thread")),
+    responses(
+        (status = 204, description = "The thread was removed from the moderation inbox"),
+        (status = 401, description = "The user is not a moderator", body = ApiError),
+    )
+)]
 #[post("{id}/read")]
 pub async fn thread_read(
     req: HttpRequest,
     info: web::Path<(ThreadId,)>,
     pool: web::Data<PgPool>,
+    pat_queue: web::Data<Arc<PatQueue>>,
 ) -> Result<HttpResponse, ApiError> {
-    check_is_moderator_from_headers(req.headers(), &**pool).await?;
+    check_is_moderator_from_headers(&req, &**pool, &pat_queue, Scopes::THREAD_WRITE).await?;
 
     let id = info.into_inner().0;
     let mut transaction = pool.begin().await?;
@@ -523,13 +624,25 @@ pub async fn thread_read(
     Ok(HttpResponse::NoContent().body(""))
 }
 
+#[utoipa::path(
+    path = "/v2/message/{id}",
+    tag = "threads",
+    security(("token" = [])),
+    params(("id" = ThreadMessageId, Path, description = "The ID of the message")),
+    responses(
+        (status = 204, description = "The message was deleted"),
+        (status = 401, description = "Missing authentication, or not the author of the message", body = ApiError),
+        (status = 404, description = "The message does not exist"),
+    )
+)]
 #[delete("{id}")]
 pub async fn message_delete(
     req: HttpRequest,
     info: web::Path<(ThreadMessageId,)>,
     pool: web::Data<PgPool>,
+    pat_queue: web::Data<Arc<PatQueue>>,
 ) -> Result<HttpResponse, ApiError> {
-    let user = get_user_from_headers(req.headers(), &**pool).await?;
+    let user = get_user_from_headers(&req, &**pool, &pat_queue, Scopes::THREAD_WRITE).await?;
 
     let result = database::models::ThreadMessage::get(info.into_inner().0.into(), &**pool).await?;
 
@@ -0,0 +1,577 @@
+use crate::database;
+use crate::database::models::generate_upload_session_id;
+use crate::file_hosting::{hash_file, open_session_at, session_path, FileHostingError};
+use crate::models::ids::UploadSessionId;
+use crate::models::pats::Scopes;
+use crate::models::uploads::UploadSession;
+use crate::queue::pats::PatQueue;
+use crate::routes::ApiError;
+use crate::util::auth::get_user_from_headers;
+use crate::util::uploads::session_expiry;
+use crate::util::validate::validation_errors_to_string;
+use actix_web::web::{self, Data};
+use actix_web::{delete, get, post, put, HttpRequest, HttpResponse};
+use chrono::{DateTime, Duration, Utc};
+use futures::StreamExt;
+use serde::Deserialize;
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::io::AsyncWriteExt;
+use utoipa::{IntoParams, ToSchema};
+use validator::Validate;
+
+// How long a session is locked for at once. Chunks taking longer extend the lock while receiving.
+const LOCK_DURATION_MINUTES: i64 = 10;
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("upload")
+            .service(upload_session_create)
+            .service(upload_session_get)
+            .service(upload_session_chunk)
+            .service(upload_session_finalize)
+            .service(upload_session_delete),
+    );
+}
+
+#[derive(Deserialize, Validate, ToSchema)]
+pub struct NewUploadSession {
+    #[validate(length(min = 1, max = 2048))]
+    pub file_name: String,
+    /// The size of the file in bytes, up to 500MiB like files uploaded in multipart requests
+    #[validate(range(min = 1, max = 524288000))]
+    pub size: u64,
+}
+
+#[derive(Deserialize, IntoParams)]
+pub struct ChunkOffset {
+    /// Where the chunk starts in the file. It may be before the received offset to resend data
+    /// which may have been corrupted, but not after it.
+    pub offset: u64,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct FinalizeUploadSession {
+    /// The expected hashes of the file, by algorithm (`sha1` or `sha512`). At least one is required.
+    pub hashes: HashMap<String, String>,
+}
+
+// Locks a session in the database while it receives a chunk, is finalized or is deleted, so it
+// is not written to concurrently by any instance. The lock expires in case the instance holding it
+// goes down, and is released when the guard is dropped.
+struct SessionGuard {
+    id: i64,
+    pool: PgPool,
+    locked_until: DateTime<Utc>,
+    released: bool,
+}
+
+impl SessionGuard {
+    /// Locks the session, returning it as read while locked
+    async fn lock(
+        id: UploadSessionId,
+        user_id: database::models::UserId,
+        pool: &PgPool,
+    ) -> Result<(Self, SessionRow), ApiError> {
+        let session_id: database::models::UploadSessionId = id.into();
+        let now = Utc::now();
+        let locked_until = now + Duration::minutes(LOCK_DURATION_MINUTES);
+
+        let session = sqlx::query_as!(
+            SessionRow,
+            "
+            UPDATE upload_sessions
+            SET locked_until = $3
+            WHERE id = $1 AND user_id = $2 AND (locked_until IS NULL OR locked_until < $4)
+            RETURNING id, file_name, size, received, sha1, sha512, created, updated
+            ",
+            session_id as database::models::UploadSessionId,
+            user_id as database::models::UserId,
+            locked_until,
+            now,
+        )
+        .fetch_optional(pool)
+        .await?;
+
+        match session {
+            Some(session) => Ok((
+                SessionGuard {
+                    id: session.id,
+                    pool: pool.clone(),
+                    locked_until,
+                    released: false,
+                },
+                session,
+            )),
+            None => {
+                // Either the session does not exist, or it is locked
+                get_session(id, user_id, pool).await?;
+
+                Err(ApiError::InvalidInput(
+                    "The upload session is busy receiving another chunk!".to_string(),
+                ))
+            }
+        }
+    }
+
+    /// Extends the lock if it is about to expire, such as while receiving a large chunk
+    async fn extend(&mut self) -> Result<(), ApiError> {
+        let now = Utc::now();
+        if self.locked_until - now > Duration::minutes(LOCK_DURATION_MINUTES / 2) {
+            return Ok(());
+        }
+
+        self.locked_until = now + Duration::minutes(LOCK_DURATION_MINUTES);
+        sqlx::query!(
+            "
+            UPDATE upload_sessions
+            SET locked_until = $2
+            WHERE id = $1
+            ",
+            self.id,
+            self.locked_until,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Releases the lock before responding, so the next chunk can be sent right away
+    async fn release(mut self) -> Result<(), ApiError> {
+        self.released = true;
+        release_session(self.id, &self.pool).await?;
+
+        Ok(())
+    }
+}
+
+impl Drop for SessionGuard {
+    fn drop(&mut self) {
+        if !self.released {
+            let (id, pool) = (self.id, self.pool.clone());
+            actix_rt::spawn(async move {
+                if let Err(e) = release_session(id, &pool).await {
+                    log::warn!("Unlocking upload session {} failed: {:?}", id, e);
+                }
+            });
+        }
+    }
+}
+
+async fn release_session(id: i64, pool: &PgPool) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "
+        UPDATE upload_sessions
+        SET locked_until = NULL
+        WHERE id = $1
+        ",
+        id,
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+struct SessionRow {
+    id: i64,
+    file_name: String,
+    size: i64,
+    received: i64,
+    sha1: Option<String>,
+    sha512: Option<String>,
+    created: DateTime<Utc>,
+    updated: DateTime<Utc>,
+}
+
+impl From<SessionRow> for UploadSession {
+    fn from(session: SessionRow) -> Self {
+        let mut hashes = HashMap::new();
+        if let Some(sha1) = session.sha1 {
+            hashes.insert("sha1".to_string(), sha1);
+        }
+        if let Some(sha512) = session.sha512 {
+            hashes.insert("sha512".to_string(), sha512);
+        }
+
+        UploadSession {
+            id: UploadSessionId(session.id as u64),
+            file_name: session.file_name,
+            size: session.size as u64,
+            received: session.received as u64,
+            finalized: !hashes.is_empty(),
+            hashes,
+            created: session.created,
+            expires: session.updated + session_expiry(),
+        }
+    }
+}
+
+async fn get_session<'a, E>(
+    id: UploadSessionId,
+    user_id: database::models::UserId,
+    exec: E,
+) -> Result<SessionRow, ApiError>
+where
+    E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+{
+    let id: database::models::UploadSessionId = id.into();
+
+    sqlx::query_as!(
+        SessionRow,
+        "
+        SELECT id, file_name, size, received, sha1, sha512, created, updated
+        FROM upload_sessions
+        WHERE id = $1 AND user_id = $2
+        ",
+        id as database::models::UploadSessionId,
+        user_id as database::models::UserId,
+    )
+    .fetch_optional(exec)
+    .await?
+    .ok_or(ApiError::NotFound)
+}
+
+/// Creates a session to upload a version file in chunks, which can be resumed if the connection
+/// fails. Once finalized, the session can be referenced in place of a file part when creating a
+/// version or adding files to it.
+#[utoipa::path(
+    path = "/v2/upload",
+    tag = "versions",
+    security(("token" = [])),
+    request_body = NewUploadSession,
+    responses(
+        (status = 200, description = "The created upload session", body = UploadSession),
+        (status = 400, description = "Invalid input", body = ApiError),
+        (status = 401, description = "Missing authentication", body = ApiError),
+    )
+)]
+#[post("")]
+pub async fn upload_session_create(
+    req: HttpRequest,
+    pool: Data<PgPool>,
+    pat_queue: Data<Arc<PatQueue>>,
+    new_session: web::Json<NewUploadSession>,
+) -> Result<HttpResponse, ApiError> {
+    let user = get_user_from_headers(&req, &**pool, &pat_queue, Scopes::VERSION_CREATE).await?;
+
+    new_session
+        .validate()
+        .map_err(|err| ApiError::Validation(validation_errors_to_string(err, None)))?;
+
+    if new_session.file_name.contains('/') {
+        return Err(ApiError::InvalidInput(
+            "File names must not contain slashes!".to_string(),
+        ));
+    }
+
+    let extension = new_session
+        .file_name
+        .rsplit_once('.')
+        .map(|(_, extension)| extension)
+        .unwrap_or_default();
+    if crate::util::ext::project_file_type(extension).is_none() {
+        return Err(ApiError::InvalidInput(format!(
+            "Invalid file type for version file: {extension}"
+        )));
+    }
+
+    let mut transaction = pool.begin().await?;
+
+    let id = generate_upload_session_id(&mut transaction).await?;
+    let user_id: database::models::UserId = user.id.into();
+    let session = sqlx::query_as!(
+        SessionRow,
+        "
+        INSERT INTO upload_sessions (id, user_id, file_name, size)
+        VALUES ($1, $2, $3, $4)
+        RETURNING id, file_name, size, received, sha1, sha512, created, updated
+        ",
+        id as database::models::UploadSessionId,
+        user_id as database::models::UserId,
+        new_session.file_name,
+        new_session.size as i64,
+    )
+    .fetch_one(&mut *transaction)
+    .await?;
+
+    transaction.commit().await?;
+
+    Ok(HttpResponse::Ok().json(UploadSession::from(session)))
+}
+
+/// Gets an upload session, whose received offset is where an interrupted upload resumes from
+#[utoipa::path(
+    path = "/v2/upload/{id}",
+    tag = "versions",
+    security(("token" = [])),
+    params(("id" = UploadSessionId, Path, description = "The ID of the upload session")),
+    responses(
+        (status = 200, description = "The upload session", body = UploadSession),
+        (status = 401, description = "Missing authentication", body = ApiError),
+        (status = 404, description = "The upload session does not exist", body = ApiError),
+    )
+)]
+#[get("{id}")]
+pub async fn upload_session_get(
+    req: HttpRequest,
+    info: web::Path<(UploadSessionId,)>,
+    pool: Data<PgPool>,
+    pat_queue: Data<Arc<PatQueue>>,
+) -> Result<HttpResponse, ApiError> {
+    let user = get_user_from_headers(&req, &**pool, &pat_queue, Scopes::VERSION_CREATE).await?;
+
+    let session = get_session(info.into_inner().0, user.id.into(), &**pool).await?;
+
+    Ok(HttpResponse::Ok().json(UploadSession::from(session)))
+}
+
+/// Writes the body of the request to the upload session at the offset. If the connection fails,
+/// the data received until then is kept, and the upload resumes from the received offset.
+#[utoipa::path(
+    path = "/v2/upload/{id}",
+    tag = "versions",
+    security(("token" = [])),
+    params(("id" = UploadSessionId, Path, description = "The ID of the upload session"), ChunkOffset),
+    request_body(content = Vec<u8>, description = "The bytes of the chunk", content_type = "application/octet-stream"),
+    responses(
+        (status = 200, description = "The upload session, with the chunk received", body = UploadSession),
+        (status = 400, description = "Invalid input", body = ApiError),
+        (status = 401, description = "Missing authentication", body = ApiError),
+        (status = 404, description = "The upload session does not exist", body = ApiError),
+    )
+)]
+#[put("{id}")]
+pub async fn upload_session_chunk(
+    req: HttpRequest,
+    info: web::Path<(UploadSessionId,)>,
+    web::Query(chunk): web::Query<ChunkOffset>,
+    mut payload: web::Payload,
+    pool: Data<PgPool>,
+    pat_queue: Data<Arc<PatQueue>>,
+) -> Result<HttpResponse, ApiError> {
+    let user = get_user_from_headers(&req, &**pool, &pat_queue, Scopes::VERSION_CREATE).await?;
+
+    let (mut guard, session) =
+        SessionGuard::lock(info.into_inner().0, user.id.into(), &pool).await?;
+
+    if session.sha1.is_some() {
+        return Err(ApiError::InvalidInput(
+            "The upload session was already finalized!".to_string(),
+        ));
+    }
+
+    if chunk.offset > session.received as u64 {
+        return Err(ApiError::InvalidInput(format!(
+            "Chunks must not start after the received offset of {}",
+            session.received
+        )));
+    }
+
+    let mut file = open_session_at(&session_path(session.id), chunk.offset)
+        .await
+        .map_err(FileHostingError::from)?;
+
+    let mut received = chunk.offset;
+    let mut error = None;
+    while let Some(item) = payload.next().await {
+        let bytes = match item {
+            Ok(bytes) => bytes,
+            Err(_) => {
+                error = Some(ApiError::InvalidInput(
+                    "The connection failed while receiving the chunk. Resume from the received offset."
+                        .to_string(),
+                ));
+                break;
+            }
+        };
+
+        if received + bytes.len() as u64 > session.size as u64 {
+            error = Some(ApiError::InvalidInput(format!(
+                "The chunk exceeds the declared size of {} bytes",
+                session.size
+            )));
+            break;
+        }
+
+        if let Err(e) = file.write_all(&bytes).await {
+            error = Some(FileHostingError::from(e).into());
+            break;
+        }
+        received += bytes.len() as u64;
+
+        if let Err(e) = guard.extend().await {
+            error = Some(e);
+            break;
+        }
+    }
+
+    // Whatever was received is kept, even if the chunk failed midway
+    file.flush().await.map_err(FileHostingError::from)?;
+    let session = sqlx::query_as!(
+        SessionRow,
+        "
+        UPDATE upload_sessions
+        SET received = $2, updated = NOW()
+        WHERE id = $1 AND sha1 IS NULL
+        RETURNING id, file_name, size, received, sha1, sha512, created, updated
+        ",
+        session.id,
+        received as i64,
+    )
+    .fetch_optional(&**pool)
+    .await?
+    .ok_or(ApiError::NotFound)?;
+    guard.release().await?;
+
+    if let Some(error) = error {
+        return Err(error);
+    }
+
+    Ok(HttpResponse::Ok().json(UploadSession::from(session)))
+}
+
+/// Checks the hashes of a fully received upload, after which it can be referenced by versions.
+/// If they do not match, the received data is discarded and the upload starts over.
+#[utoipa::path(
+    path = "/v2/upload/{id}/finalize",
+    tag = "versions",
+    security(("token" = [])),
+    params(("id" = UploadSessionId, Path, description = "The ID of the upload session")),
+    request_body = FinalizeUploadSession,
+    responses(
+        (status = 200, description = "The finalized upload session", body = UploadSession),
+        (status = 400, description = "Invalid input, or the hashes do not match", body = ApiError),
+        (status = 401, description = "Missing authentication", body = ApiError),
+        (status = 404, description = "The upload session does not exist", body = ApiError),
+    )
+)]
+#[post("{id}/finalize")]
+pub async fn upload_session_finalize(
+    req: HttpRequest,
+    info: web::Path<(UploadSessionId,)>,
+    pool: Data<PgPool>,
+    pat_queue: Data<Arc<PatQueue>>,
+    finalize: web::Json<FinalizeUploadSession>,
+) -> Result<HttpResponse, ApiError> {
+    let user = get_user_from_headers(&req, &**pool, &pat_queue, Scopes::VERSION_CREATE).await?;
+
+    let (guard, session) = SessionGuard::lock(info.into_inner().0, user.id.into(), &pool).await?;
+
+    if session.sha1.is_some() {
+        return Ok(HttpResponse::Ok().json(UploadSession::from(session)));
+    }
+
+    if finalize.hashes.is_empty() || finalize.hashes.keys().any(|x| x != "sha1" && x != "sha512") {
+        return Err(ApiError::InvalidInput(
+            "A sha1 or sha512 hash of the file is required, and no other algorithms are supported"
+                .to_string(),
+        ));
+    }
+
+    if session.received != session.size {
+        return Err(ApiError::InvalidInput(format!(
+            "Only {} of {} bytes were received",
+            session.received, session.size
+        )));
+    }
+
+    let path = session_path(session.id);
+    let (sha1, sha512) = actix_web::web::block(move || hash_file(&path))
+        .await
+        .map_err(|_| ApiError::InvalidInput("Unable to hash the upload".to_string()))?
+        .map_err(FileHostingError::from)?;
+
+    let matches = finalize.hashes.iter().all(|(algorithm, hash)| {
+        let actual = if algorithm == "sha1" { &sha1 } else { &sha512 };
+        hash.eq_ignore_ascii_case(actual)
+    });
+
+    if !matches {
+        sqlx::query!(
+            "
+            UPDATE upload_sessions
+            SET received = 0, updated = NOW()
+            WHERE id = $1
+            ",
+            session.id,
+        )
+        .execute(&**pool)
+        .await?;
+
+        return Err(ApiError::InvalidInput(
+            "The uploaded file does not match the expected hash, so it must be uploaded again"
+                .to_string(),
+        ));
+    }
+
+    let session = sqlx::query_as!(
+        SessionRow,
+        "
+        UPDATE upload_sessions
+        SET sha1 = $2, sha512 = $3, updated = NOW()
+        WHERE id = $1
+        RETURNING id, file_name, size, received, sha1, sha512, created, updated
+        ",
+        session.id,
+        sha1,
+        sha512,
+    )
+    .fetch_one(&**pool)
+    .await?;
+    guard.release().await?;
+
+    Ok(HttpResponse::Ok().json(UploadSession::from(session)))
+}
+
+/// Cancels an upload session, deleting the received data
+#[utoipa::path(
+    path = "/v2/upload/{id}",
+    tag = "versions",
+    security(("token" = [])),
+    params(("id" = UploadSessionId, Path, description = "The ID of the upload session")),
+    responses(
+        (status = 204, description = "The upload session was deleted"),
+        (status = 401, description = "Missing authentication", body = ApiError),
+        (status = 404, description = "The upload session does not exist", body = ApiError),
+    )
+)]
+#[delete("{id}")]
+pub async fn upload_session_delete(
+    req: HttpRequest,
+    info: web::Path<(UploadSessionId,)>,
+    pool: Data<PgPool>,
+    pat_queue: Data<Arc<PatQueue>>,
+) -> Result<HttpResponse, ApiError> {
+    let user = get_user_from_headers(&req, &**pool, &pat_queue, Scopes::VERSION_CREATE).await?;
+
+    let (mut guard, session) =
+        SessionGuard::lock(info.into_inner().0, user.id.into(), &pool).await?;
+
+    let deleted = sqlx::query!(
+        "
+        DELETE FROM upload_sessions
+        WHERE id = $1
+        ",
+        session.id,
+    )
+    .execute(&**pool)
+    .await?;
+    // The lock was deleted along with the session
+    guard.released = true;
+
+    // The session may have been added to a version meanwhile, whose files are still scanned
+    if deleted.rows_affected() == 0 {
+        return Err(ApiError::NotFound);
+    }
+
+    if let Err(e) = tokio::fs::remove_file(session_path(session.id)).await {
+        if e.kind() != std::io::ErrorKind::NotFound {
+            return Err(FileHostingError::from(e).into());
+        }
+    }
+
+    Ok(HttpResponse::NoContent().body(""))
+}
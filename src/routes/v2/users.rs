@@ -1,11 +1,16 @@
 use crate::database::models::User;
 use crate::file_hosting::FileHost;
-use crate::models::notifications::Notification;
-use crate::models::projects::Project;
+use crate::models::pats::Scopes;
+use crate::models::projects::{Project, ProjectId};
 use crate::models::users::{Badges, RecipientType, RecipientWallet, Role, UserId};
+use crate::queue::pats::PatQueue;
 use crate::queue::payouts::{PayoutAmount, PayoutItem, PayoutsQueue};
 use crate::routes::ApiError;
+use crate::services;
 use crate::util::auth::get_user_from_headers;
+use crate::util::blobs::delete_released_blobs;
+use crate::util::img::{delete_image, process_image_async, upload_image, ImageKind};
+use crate::util::pagination::{OptionalPagination, PaginationQuery};
 use crate::util::routes::read_from_payload;
 use crate::util::validate::validation_errors_to_string;
 use actix_web::{delete, get, patch, post, web, HttpRequest, HttpResponse};
@@ -18,6 +23,7 @@ use serde_json::json;
 use sqlx::PgPool;
 use std::sync::Arc;
 use tokio::sync::Mutex;
+use utoipa::{IntoParams, ToSchema};
 use validator::Validate;
 
 pub fn config(cfg: &mut web::ServiceConfig) {
@@ -39,26 +45,48 @@ pub fn config(cfg: &mut web::ServiceConfig) {
     );
 }
 
+#[utoipa::path(
+    path = "/v2/user",
+    tag = "users",
+    security(("token" = [])),
+    responses(
+        (status = 200, description = "The authenticated user, including private fields", body = User),
+        (status = 401, description = "Missing or invalid authentication", body = ApiError),
+    )
+)]
 #[get("user")]
 pub async fn user_auth_get(
     req: HttpRequest,
     pool: web::Data<PgPool>,
+    pat_queue: web::Data<Arc<PatQueue>>,
 ) -> Result<HttpResponse, ApiError> {
-    Ok(HttpResponse::Ok().json(get_user_from_headers(req.headers(), &**pool).await?))
+    Ok(HttpResponse::Ok()
+        .json(get_user_from_headers(&req, &**pool, &pat_queue, Scopes::USER_READ).await?))
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct UserData {
     pub notifs_count: u64,
-    pub followed_projects: Vec<crate::models::ids::ProjectId>,
+    pub followed_projects: Vec<ProjectId>,
 }
 
+#[utoipa::path(
+    path = "/v2/user_data",
+    tag = "users",
+    security(("token" = [])),
+    responses(
+        (status = 200, description = "The unread notification count and followed projects of the authenticated user", body = UserData),
+        (status = 204, description = "The user no longer exists"),
+        (status = 401, description = "Missing or invalid authentication", body = ApiError),
+    )
+)]
 #[get("user_data")]
 pub async fn user_data_get(
     req: HttpRequest,
     pool: web::Data<PgPool>,
+    pat_queue: web::Data<Arc<PatQueue>>,
 ) -> Result<HttpResponse, ApiError> {
-    let user = get_user_from_headers(req.headers(), &**pool).await?;
+    let user = get_user_from_headers(&req, &**pool, &pat_queue, Scopes::USER_READ).await?;
 
     let data = sqlx::query!(
         "
@@ -84,83 +112,91 @@ pub async fn user_data_get(
     }
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, IntoParams)]
 pub struct UserIds {
     pub ids: String,
 }
 
+#[utoipa::path(
+    path = "/v2/users",
+    tag = "users",
+    params(UserIds),
+    responses(
+        (status = 200, description = "The users with the given IDs", body = [User]),
+        (status = 400, description = "Invalid IDs", body = ApiError),
+    )
+)]
 #[get("users")]
 pub async fn users_get(
     web::Query(ids): web::Query<UserIds>,
     pool: web::Data<PgPool>,
 ) -> Result<HttpResponse, ApiError> {
-    let user_ids = serde_json::from_str::<Vec<UserId>>(&ids.ids)?
-        .into_iter()
-        .map(|x| x.into())
-        .collect::<Vec<crate::database::models::UserId>>();
+    let user_ids = serde_json::from_str::<Vec<UserId>>(&ids.ids)?;
 
-    let users_data = User::get_many(&user_ids, &**pool).await?;
-
-    let users: Vec<crate::models::users::User> = users_data.into_iter().map(From::from).collect();
+    let users = services::users::get_users(user_ids, &pool).await?;
 
     Ok(HttpResponse::Ok().json(users))
 }
 
+#[utoipa::path(
+    path = "/v2/user/{id}",
+    tag = "users",
+    params(("id" = String, Path, description = "The ID or username of the user")),
+    responses(
+        (status = 200, description = "The user", body = User),
+        (status = 404, description = "The user does not exist"),
+    )
+)]
 #[get("{id}")]
 pub async fn user_get(
     info: web::Path<(String,)>,
     pool: web::Data<PgPool>,
 ) -> Result<HttpResponse, ApiError> {
     let string = info.into_inner().0;
-    let id_option: Option<UserId> = serde_json::from_str(&format!("\"{string}\"")).ok();
-
-    let mut user_data;
-
-    if let Some(id) = id_option {
-        user_data = User::get(id.into(), &**pool).await?;
-
-        if user_data.is_none() {
-            user_data = User::get_from_username(string, &**pool).await?;
-        }
-    } else {
-        user_data = User::get_from_username(string, &**pool).await?;
-    }
 
-    if let Some(data) = user_data {
-        let response: crate::models::users::User = data.into();
-        Ok(HttpResponse::Ok().json(response))
+    if let Some(user) = services::users::get_user(&string, &pool).await? {
+        Ok(HttpResponse::Ok().json(user))
     } else {
         Ok(HttpResponse::NotFound().body(""))
     }
 }
 
+#[utoipa::path(
+    path = "/v2/user/{user_id}/projects",
+    tag = "users",
+    security((), ("token" = [])),
+    params(
+        ("user_id" = String, Path, description = "The ID or username of the user"),
+        PaginationQuery,
+    ),
+    responses(
+        (status = 200, description = "The projects of the user that are visible to the authenticated user, sorted by downloads. Pages are sorted by publication date, newest first. When `cursor` or `limit` is given, a page with the `items`, `next_cursor` and `next` link is returned instead.", body = [Project]),
+        (status = 400, description = "Invalid cursor or limit", body = ApiError),
+        (status = 404, description = "The user does not exist"),
+    )
+)]
 #[get("{user_id}/projects")]
 pub async fn projects_list(
     req: HttpRequest,
     info: web::Path<(String,)>,
+    pagination: OptionalPagination,
     pool: web::Data<PgPool>,
+    pat_queue: web::Data<Arc<PatQueue>>,
 ) -> Result<HttpResponse, ApiError> {
-    let user = get_user_from_headers(req.headers(), &**pool).await.ok();
-
-    let id_option = User::get_id_from_username_or_id(&info.into_inner().0, &**pool).await?;
-
-    if let Some(id) = id_option {
-        let user_id: UserId = id.into();
-
-        let can_view_private = user
-            .map(|y| y.role.is_mod() || y.id == user_id)
-            .unwrap_or(false);
-
-        let project_data = User::get_projects(id, &**pool).await?;
-
-        let response: Vec<_> = crate::database::Project::get_many_full(&project_data, &**pool)
-            .await?
-            .into_iter()
-            .filter(|x| can_view_private || x.inner.status.is_searchable())
-            .map(Project::from)
-            .collect();
-
-        Ok(HttpResponse::Ok().json(response))
+    let user = get_user_from_headers(&req, &**pool, &pat_queue, Scopes::PROJECT_READ)
+        .await
+        .ok();
+
+    let projects = services::projects::get_user_projects(
+        &info.into_inner().0,
+        &user,
+        &pool,
+        pagination.0.as_ref(),
+    )
+    .await?;
+
+    if let Some((projects, next)) = projects {
+        Ok(pagination.respond(projects, next))
     } else {
         Ok(HttpResponse::NotFound().body(""))
     }
@@ -170,7 +206,7 @@ lazy_static! {
     static ref RE_URL_SAFE: Regex = Regex::new(r"^[a-zA-Z0-9_-]*$").unwrap();
 }
 
-#[derive(Serialize, Deserialize, Validate)]
+#[derive(Serialize, Deserialize, Validate, ToSchema)]
 pub struct EditUser {
     #[validate(length(min = 1, max = 39), regex = "RE_URL_SAFE")]
     pub username: Option<String>,
@@ -206,7 +242,7 @@ pub struct EditUser {
     pub payout_data: Option<Option<EditPayoutData>>,
 }
 
-#[derive(Serialize, Deserialize, Validate)]
+#[derive(Serialize, Deserialize, Validate, ToSchema)]
 pub struct EditPayoutData {
     pub payout_wallet: RecipientWallet,
     pub payout_wallet_type: RecipientType,
@@ -214,14 +250,28 @@ pub struct EditPayoutData {
     pub payout_address: String,
 }
 
+#[utoipa::path(
+    path = "/v2/user/{id}",
+    tag = "users",
+    security(("token" = [])),
+    params(("id" = String, Path, description = "The ID or username of the user")),
+    request_body = EditUser,
+    responses(
+        (status = 204, description = "The user was edited"),
+        (status = 400, description = "Invalid input, or the username is taken", body = ApiError),
+        (status = 401, description = "Missing authentication, or not permitted to edit the user", body = ApiError),
+        (status = 404, description = "The user does not exist"),
+    )
+)]
 #[patch("{id}")]
 pub async fn user_edit(
     req: HttpRequest,
     info: web::Path<(String,)>,
     pool: web::Data<PgPool>,
     new_user: web::Json<EditUser>,
+    pat_queue: web::Data<Arc<PatQueue>>,
 ) -> Result<HttpResponse, ApiError> {
-    let user = get_user_from_headers(req.headers(), &**pool).await?;
+    let user = get_user_from_headers(&req, &**pool, &pat_queue, Scopes::USER_WRITE).await?;
 
     new_user
         .validate()
@@ -427,11 +477,27 @@ pub async fn user_edit(
     }
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, IntoParams)]
 pub struct Extension {
     pub ext: String,
 }
 
+#[utoipa::path(
+    path = "/v2/user/{id}/icon",
+    tag = "users",
+    security(("token" = [])),
+    params(
+        ("id" = String, Path, description = "The ID or username of the user"),
+        Extension,
+    ),
+    request_body(content = [u8], content_type = "image/*", description = "The image, in the format given by `ext`. It is stored re-encoded as PNG, without metadata"),
+    responses(
+        (status = 204, description = "The icon was changed"),
+        (status = 400, description = "Invalid image or image format", body = ApiError),
+        (status = 401, description = "Missing authentication, or not permitted to edit the user", body = ApiError),
+        (status = 404, description = "The user does not exist"),
+    )
+)]
 #[patch("{id}/icon")]
 pub async fn user_icon_edit(
     web::Query(ext): web::Query<Extension>,
@@ -440,10 +506,11 @@ pub async fn user_icon_edit(
     pool: web::Data<PgPool>,
     file_host: web::Data<Arc<dyn FileHost + Send + Sync>>,
     mut payload: web::Payload,
+    pat_queue: web::Data<Arc<PatQueue>>,
 ) -> Result<HttpResponse, ApiError> {
-    if let Some(content_type) = crate::util::ext::get_image_content_type(&ext.ext) {
+    if crate::util::ext::get_image_content_type(&ext.ext).is_some() {
         let cdn_url = dotenvy::var("CDN_URL")?;
-        let user = get_user_from_headers(req.headers(), &**pool).await?;
+        let user = get_user_from_headers(&req, &**pool, &pat_queue, Scopes::USER_WRITE).await?;
         let id_option = User::get_id_from_username_or_id(&info.into_inner().0, &**pool).await?;
 
         if let Some(id) = id_option {
@@ -467,25 +534,13 @@ pub async fn user_icon_edit(
                 }
             }
 
-            if let Some(icon) = icon_url {
-                let name = icon.split(&format!("{cdn_url}/")).nth(1);
-
-                if let Some(icon_path) = name {
-                    file_host.delete_file_version("", icon_path).await?;
-                }
-            }
-
             let bytes =
                 read_from_payload(&mut payload, 2097152, "Icons must be smaller than 2MiB").await?;
 
-            let hash = sha1::Sha1::from(&bytes).hexdigest();
-            let upload_data = file_host
-                .upload_file(
-                    content_type,
-                    &format!("user/{}/{}.{}", user_id, hash, ext.ext),
-                    bytes.freeze(),
-                )
-                .await?;
+            let image = process_image_async(bytes.freeze(), ImageKind::UserIcon).await?;
+
+            let icon =
+                upload_image(&***file_host, &cdn_url, &format!("user/{user_id}"), image).await?;
 
             sqlx::query!(
                 "
@@ -493,11 +548,20 @@ pub async fn user_icon_edit(
                 SET avatar_url = $1
                 WHERE (id = $2)
                 ",
-                format!("{}/{}", cdn_url, upload_data.file_name),
+                icon.url,
                 id as crate::database::models::ids::UserId,
             )
             .execute(&**pool)
             .await?;
+
+            // The old icon is only deleted once the user uses the new one. Icons are named after
+            // their hash, so uploading the same icon again keeps its URL.
+            if let Some(old_icon) = icon_url.filter(|x| *x != icon.url) {
+                if let Err(e) = delete_image(&***file_host, &cdn_url, &old_icon, &[]).await {
+                    log::warn!("Deleting the old icon of user {user_id} failed: {e}");
+                }
+            }
+
             Ok(HttpResponse::NoContent().body(""))
         } else {
             Ok(HttpResponse::NotFound().body(""))
@@ -510,7 +574,7 @@ pub async fn user_icon_edit(
     }
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, IntoParams)]
 pub struct RemovalType {
     #[serde(default = "default_removal")]
     removal_type: String,
@@ -520,14 +584,30 @@ fn default_removal() -> String {
     "partial".into()
 }
 
+#[utoipa::path(
+    path = "/v2/user/{id}",
+    tag = "users",
+    security(("token" = [])),
+    params(
+        ("id" = String, Path, description = "The ID or username of the user"),
+        RemovalType,
+    ),
+    responses(
+        (status = 204, description = "The user was deleted"),
+        (status = 401, description = "Missing authentication, or not permitted to delete the user", body = ApiError),
+        (status = 404, description = "The user does not exist"),
+    )
+)]
 #[delete("{id}")]
 pub async fn user_delete(
     req: HttpRequest,
     info: web::Path<(String,)>,
     pool: web::Data<PgPool>,
     removal_type: web::Query<RemovalType>,
+    pat_queue: web::Data<Arc<PatQueue>>,
+    file_host: web::Data<Arc<dyn FileHost + Send + Sync>>,
 ) -> Result<HttpResponse, ApiError> {
-    let user = get_user_from_headers(req.headers(), &**pool).await?;
+    let user = get_user_from_headers(&req, &**pool, &pat_queue, Scopes::USER_DELETE).await?;
     let id_option = User::get_id_from_username_or_id(&info.into_inner().0, &**pool).await?;
 
     if let Some(id) = id_option {
@@ -547,6 +627,8 @@ pub async fn user_delete(
 
         transaction.commit().await?;
 
+        delete_released_blobs(&pool, &***file_host).await?;
+
         if result.is_some() {
             Ok(HttpResponse::NoContent().body(""))
         } else {
@@ -557,13 +639,30 @@ pub async fn user_delete(
     }
 }
 
+#[utoipa::path(
+    path = "/v2/user/{id}/follows",
+    tag = "users",
+    security(("token" = [])),
+    params(
+        ("id" = String, Path, description = "The ID or username of the user"),
+        PaginationQuery,
+    ),
+    responses(
+        (status = 200, description = "The projects the user follows. Pages are sorted by the date the project was followed, newest first. When `cursor` or `limit` is given, a page with the `items`, `next_cursor` and `next` link is returned instead.", body = [Project]),
+        (status = 400, description = "Invalid cursor or limit", body = ApiError),
+        (status = 401, description = "Missing authentication, or not permitted to see the follows of the user", body = ApiError),
+        (status = 404, description = "The user does not exist"),
+    )
+)]
 #[get("{id}/follows")]
 pub async fn user_follows(
     req: HttpRequest,
     info: web::Path<(String,)>,
+    pagination: OptionalPagination,
     pool: web::Data<PgPool>,
+    pat_queue: web::Data<Arc<PatQueue>>,
 ) -> Result<HttpResponse, ApiError> {
-    let user = get_user_from_headers(req.headers(), &**pool).await?;
+    let user = get_user_from_headers(&req, &**pool, &pat_queue, Scopes::USER_READ).await?;
     let id_option = User::get_id_from_username_or_id(&info.into_inner().0, &**pool).await?;
 
     if let Some(id) = id_option {
@@ -575,78 +674,114 @@ pub async fn user_follows(
 
         use futures::TryStreamExt;
 
-        let project_ids = sqlx::query!(
-            "
-            SELECT mf.mod_id FROM mod_follows mf
-            WHERE mf.follower_id = $1
-            ",
-            id as crate::database::models::ids::UserId,
-        )
-        .fetch_many(&**pool)
-        .try_filter_map(|e| async {
-            Ok(e.right()
-                .map(|m| crate::database::models::ProjectId(m.mod_id)))
-        })
-        .try_collect::<Vec<crate::database::models::ProjectId>>()
-        .await?;
+        let (project_ids, next) = if let Some(pagination) = &pagination.0 {
+            let follows =
+                User::get_follows_page(id, pagination.cursor, pagination.fetch_limit(), &**pool)
+                    .await?;
+            let (follows, next) = pagination.split(follows, |(_, cursor)| *cursor);
+
+            (
+                follows
+                    .into_iter()
+                    .map(|(project_id, _)| project_id)
+                    .collect(),
+                next,
+            )
+        } else {
+            let project_ids = sqlx::query!(
+                "
+                SELECT mf.mod_id FROM mod_follows mf
+                WHERE mf.follower_id = $1
+                ",
+                id as crate::database::models::ids::UserId,
+            )
+            .fetch_many(&**pool)
+            .try_filter_map(|e| async {
+                Ok(e.right()
+                    .map(|m| crate::database::models::ProjectId(m.mod_id)))
+            })
+            .try_collect::<Vec<crate::database::models::ProjectId>>()
+            .await?;
 
-        let projects: Vec<_> = crate::database::Project::get_many_full(&project_ids, &**pool)
-            .await?
-            .into_iter()
-            .map(Project::from)
-            .collect();
+            (project_ids, None)
+        };
+
+        let mut projects = crate::database::Project::get_many_full(&project_ids, &**pool).await?;
+        if pagination.0.is_some() {
+            projects.sort_by_key(|x| project_ids.iter().position(|y| *y == x.inner.id));
+        }
 
-        Ok(HttpResponse::Ok().json(projects))
+        Ok(pagination.respond(projects.into_iter().map(Project::from).collect(), next))
     } else {
         Ok(HttpResponse::NotFound().body(""))
     }
 }
 
+#[utoipa::path(
+    path = "/v2/user/{id}/notifications",
+    tag = "users",
+    security(("token" = [])),
+    params(
+        ("id" = String, Path, description = "The ID or username of the user"),
+        PaginationQuery,
+    ),
+    responses(
+        (status = 200, description = "The notifications of the user, newest first. When `cursor` or `limit` is given, a page with the `items`, `next_cursor` and `next` link is returned instead.", body = [Notification]),
+        (status = 400, description = "Invalid cursor or limit", body = ApiError),
+        (status = 401, description = "Missing authentication, or not permitted to see the notifications of the user", body = ApiError),
+        (status = 404, description = "The user does not exist"),
+    )
+)]
 #[get("{id}/notifications")]
 pub async fn user_notifications(
     req: HttpRequest,
     info: web::Path<(String,)>,
+    pagination: OptionalPagination,
     pool: web::Data<PgPool>,
+    pat_queue: web::Data<Arc<PatQueue>>,
 ) -> Result<HttpResponse, ApiError> {
-    let user = get_user_from_headers(req.headers(), &**pool).await?;
-    let id_option = User::get_id_from_username_or_id(&info.into_inner().0, &**pool).await?;
-
-    if let Some(id) = id_option {
-        if !user.role.is_admin() && user.id != id.into() {
-            return Err(ApiError::CustomAuthentication(
-                "You do not have permission to see the notifications of this user!".to_string(),
-            ));
-        }
-
-        let mut notifications: Vec<Notification> =
-            crate::database::models::notification_item::Notification::get_many_user(id, &**pool)
-                .await?
-                .into_iter()
-                .map(Into::into)
-                .collect();
-
-        notifications.sort_by(|a, b| b.created.cmp(&a.created));
-
-        Ok(HttpResponse::Ok().json(notifications))
+    let user = get_user_from_headers(&req, &**pool, &pat_queue, Scopes::NOTIFICATION_READ).await?;
+    let notifications = services::notifications::get_user_notifications(
+        &info.into_inner().0,
+        &user,
+        &pool,
+        pagination.0.as_ref(),
+    )
+    .await?;
+
+    if let Some((notifications, next)) = notifications {
+        Ok(pagination.respond(notifications, next))
     } else {
         Ok(HttpResponse::NotFound().body(""))
     }
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct Payout {
     pub created: DateTime<Utc>,
     pub amount: Decimal,
     pub status: String,
 }
 
+#[utoipa::path(
+    path = "/v2/user/{id}/payouts",
+    tag = "users",
+    security(("token" = [])),
+    params(("id" = String, Path, description = "The ID or username of the user")),
+    responses(
+        (status = 200, description = "A JSON object with the `all_time` and `last_month` revenue of the user, and their `payouts` as a list of `Payout`"),
+        (status = 401, description = "Missing authentication, or not permitted to see the payouts of the user", body = ApiError),
+        (status = 404, description = "The user does not exist"),
+    )
+)]
 #[get("{id}/payouts")]
 pub async fn user_payouts(
     req: HttpRequest,
     info: web::Path<(String,)>,
     pool: web::Data<PgPool>,
+    pat_queue: web::Data<Arc<PatQueue>>,
 ) -> Result<HttpResponse, ApiError> {
-    let user = get_user_from_headers(req.headers(), &**pool).await?;
+    let user = get_user_from_headers(&req, &**pool, &pat_queue, Scopes::PAYOUTS_READ).await?;
     let id_option = User::get_id_from_username_or_id(&info.into_inner().0, &**pool).await?;
 
     if let Some(id) = id_option {
@@ -708,11 +843,24 @@ pub async fn user_payouts(
     }
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct PayoutData {
     amount: Decimal,
 }
 
+#[utoipa::path(
+    path = "/v2/user/{id}/payouts",
+    tag = "users",
+    security(("token" = [])),
+    params(("id" = String, Path, description = "The ID or username of the user")),
+    request_body = PayoutData,
+    responses(
+        (status = 204, description = "The payout was sent"),
+        (status = 400, description = "Invalid amount, or the user has no payout method", body = ApiError),
+        (status = 401, description = "Missing authentication, or not permitted to withdraw for the user", body = ApiError),
+        (status = 404, description = "The user does not exist"),
+    )
+)]
 #[post("{id}/payouts")]
 pub async fn user_payouts_request(
     req: HttpRequest,
@@ -720,10 +868,11 @@ pub async fn user_payouts_request(
     pool: web::Data<PgPool>,
     data: web::Json<PayoutData>,
     payouts_queue: web::Data<Arc<Mutex<PayoutsQueue>>>,
+    pat_queue: web::Data<Arc<PatQueue>>,
 ) -> Result<HttpResponse, ApiError> {
     let mut payouts_queue = payouts_queue.lock().await;
 
-    let user = get_user_from_headers(req.headers(), &**pool).await?;
+    let user = get_user_from_headers(&req, &**pool, &pat_queue, Scopes::PAYOUTS_WRITE).await?;
     let id_option = User::get_id_from_username_or_id(&info.into_inner().0, &**pool).await?;
 
     if let Some(id) = id_option {
@@ -1,20 +1,28 @@
 use super::project_creation::{CreateError, UploadedFile};
 use crate::database::models;
+use crate::database::models::blob_item::Blob;
 use crate::database::models::notification_item::NotificationBuilder;
 use crate::database::models::version_item::{
     DependencyBuilder, VersionBuilder, VersionFileBuilder,
 };
-use crate::file_hosting::FileHost;
+use crate::file_hosting::{FileHost, FileHostingError, SpooledFile};
+use crate::models::ids::UploadSessionId;
 use crate::models::notifications::NotificationBody;
-use crate::models::pack::PackFileHash;
+use crate::models::pats::Scopes;
 use crate::models::projects::{
-    Dependency, DependencyType, FileType, GameVersion, Loader, ProjectId, Version, VersionFile,
-    VersionId, VersionStatus, VersionType,
+    Dependency, DependencyType, FileFinding, FileType, FindingCode, FindingSeverity, GameVersion,
+    Loader, ProjectId, Version, VersionFile, VersionId, VersionStatus, VersionType,
 };
 use crate::models::teams::Permissions;
+use crate::queue::pats::PatQueue;
+use crate::queue::scan::ScanQueue;
+use crate::services;
+use crate::services::modpacks::ModpackReport;
 use crate::util::auth::get_user_from_headers;
-use crate::util::routes::read_from_field;
+use crate::util::routes::spool_field;
+use crate::util::uploads::take_upload_session;
 use crate::util::validate::validation_errors_to_string;
+use crate::validate::metadata::{ModEnvironment, ModMetadata};
 use crate::validate::{validate_file, ValidationResult};
 use actix_multipart::{Field, Multipart};
 use actix_web::web::Data;
@@ -25,13 +33,14 @@ use serde::{Deserialize, Serialize};
 use sqlx::postgres::PgPool;
 use std::collections::HashMap;
 use std::sync::Arc;
+use utoipa::ToSchema;
 use validator::Validate;
 
 fn default_requested_status() -> VersionStatus {
     VersionStatus::Listed
 }
 
-#[derive(Serialize, Deserialize, Validate, Clone)]
+#[derive(Serialize, Deserialize, Validate, Clone, ToSchema)]
 pub struct InitialVersionData {
     #[serde(alias = "mod_id")]
     pub project_id: Option<ProjectId>,
@@ -67,22 +76,58 @@ pub struct InitialVersionData {
     #[serde(default = "default_requested_status")]
     pub status: VersionStatus,
     #[serde(default = "HashMap::new")]
+    #[schema(value_type = HashMap<String, FileType>)]
     pub file_types: HashMap<String, Option<FileType>>,
+    /// Finalized upload sessions, by the names in `file_parts` they are used in place of
+    #[serde(default = "HashMap::new")]
+    pub upload_sessions: HashMap<String, UploadSessionId>,
 }
 
-#[derive(Serialize, Deserialize, Clone)]
-struct InitialFileData {
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub struct InitialFileData {
     #[serde(default = "HashMap::new")]
+    #[schema(value_type = HashMap<String, FileType>)]
     pub file_types: HashMap<String, Option<FileType>>,
+    /// Finalized upload sessions to add as files, by the names their file types are given by
+    #[serde(default = "HashMap::new")]
+    pub upload_sessions: HashMap<String, UploadSessionId>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct VersionCreateResponse {
+    #[serde(flatten)]
+    pub version: Version,
+    /// The reports of the uploaded modpack files
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub modpack_reports: Vec<ModpackReport>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct FileUploadResponse {
+    /// The reports of the uploaded modpack files
+    pub modpack_reports: Vec<ModpackReport>,
 }
 
 // under `/api/v1/version`
+#[utoipa::path(
+    path = "/v2/version",
+    tag = "versions",
+    security(("token" = [])),
+    request_body(content = InitialVersionData, description = "A `data` part with the JSON data, followed by the file parts it names", content_type = "multipart/form-data"),
+    responses(
+        (status = 200, description = "The created version, with the reports of its modpack files", body = VersionCreateResponse),
+        (status = 400, description = "Invalid input", body = ApiError),
+        (status = 401, description = "Missing authentication, or not permitted to upload versions to the project", body = ApiError),
+    )
+)]
 #[post("version")]
 pub async fn version_create(
     req: HttpRequest,
     mut payload: Multipart,
     client: Data<PgPool>,
     file_host: Data<Arc<dyn FileHost + Send + Sync>>,
+    pat_queue: Data<Arc<PatQueue>>,
+    scan_queue: Data<Arc<ScanQueue>>,
 ) -> Result<HttpResponse, CreateError> {
     let mut transaction = client.begin().await?;
     let mut uploaded_files = Vec::new();
@@ -93,6 +138,8 @@ pub async fn version_create(
         &mut transaction,
         &***file_host,
         &mut uploaded_files,
+        &pat_queue,
+        &scan_queue,
     )
     .await;
 
@@ -118,16 +165,20 @@ async fn version_create_inner(
     transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
     file_host: &dyn FileHost,
     uploaded_files: &mut Vec<UploadedFile>,
+    pat_queue: &PatQueue,
+    scan_queue: &ScanQueue,
 ) -> Result<HttpResponse, CreateError> {
     let cdn_url = dotenvy::var("CDN_URL")?;
 
     let mut initial_version_data = None;
     let mut version_builder = None;
+    let mut modpack_reports = Vec::new();
 
     let all_game_versions = models::categories::GameVersion::list(&mut *transaction).await?;
     let all_loaders = models::categories::Loader::list(&mut *transaction).await?;
 
-    let user = get_user_from_headers(req.headers(), &mut *transaction).await?;
+    let user =
+        get_user_from_headers(&req, &mut *transaction, pat_queue, Scopes::VERSION_CREATE).await?;
 
     let mut error = None;
     while let Some(item) = payload.next().await {
@@ -162,6 +213,16 @@ async fn version_create_inner(
                     CreateError::ValidationError(validation_errors_to_string(err, None))
                 })?;
 
+                if let Some(name) = version_create_data
+                    .upload_sessions
+                    .keys()
+                    .find(|x| !version_create_data.file_parts.contains(x))
+                {
+                    return Err(CreateError::InvalidInput(format!(
+                        "Upload session `{name}` isn't specified in `file_parts`"
+                    )));
+                }
+
                 if !version_create_data.status.can_be_requested() {
                     return Err(CreateError::InvalidInput(
                         "Status specified cannot be requested".to_string(),
@@ -298,17 +359,20 @@ async fn version_create_inner(
                 .clone()
                 .ok_or_else(|| CreateError::InvalidInput("`data` field is required".to_string()))?;
 
-            upload_file(
-                &mut field,
+            let (file_name, _) = get_name_ext(&content_disposition)?;
+            let file = spool_version_file(&mut field).await?;
+
+            let report = upload_file(
+                file,
+                file_name,
                 file_host,
+                scan_queue,
                 version_data.file_parts.len(),
                 uploaded_files,
                 &mut version.files,
                 &mut version.dependencies,
                 &cdn_url,
-                &content_disposition,
                 version.project_id.into(),
-                version.version_id.into(),
                 &project_type,
                 version_data.loaders,
                 version_data.game_versions,
@@ -319,6 +383,7 @@ async fn version_create_inner(
                 transaction,
             )
             .await?;
+            modpack_reports.extend(report);
 
             Ok(())
         }
@@ -335,9 +400,54 @@ async fn version_create_inner(
 
     let version_data = initial_version_data
         .ok_or_else(|| CreateError::InvalidInput("`data` field is required".to_string()))?;
-    let builder = version_builder
+    let mut builder = version_builder
         .ok_or_else(|| CreateError::InvalidInput("`data` field is required".to_string()))?;
 
+    let project_type = sqlx::query!(
+        "
+        SELECT name FROM project_types pt
+        INNER JOIN mods ON mods.project_type = pt.id
+        WHERE mods.id = $1
+        ",
+        builder.project_id as models::ProjectId,
+    )
+    .fetch_one(&mut *transaction)
+    .await?
+    .name;
+
+    // Files uploaded through sessions are added after the ones uploaded in the request
+    for name in &version_data.file_parts {
+        let session_id = match version_data.upload_sessions.get(name) {
+            Some(session_id) => *session_id,
+            None => continue,
+        };
+        let (file_name, file) =
+            take_upload_session(session_id.into(), user.id.into(), transaction).await?;
+
+        let report = upload_file(
+            file,
+            &file_name,
+            file_host,
+            scan_queue,
+            version_data.file_parts.len(),
+            uploaded_files,
+            &mut builder.files,
+            &mut builder.dependencies,
+            &cdn_url,
+            builder.project_id.into(),
+            &project_type,
+            version_data.loaders.clone(),
+            version_data.game_versions.clone(),
+            all_game_versions.clone(),
+            version_data.primary_file.is_some(),
+            version_data.primary_file.as_deref() == Some(name.as_str()),
+            version_data.file_types.get(name).copied().flatten(),
+            transaction,
+        )
+        .await?;
+        modpack_reports.extend(report);
+    }
+
     if builder.files.is_empty() {
         return Err(CreateError::InvalidInput(
             "Versions must have at least one file uploaded to them".to_string(),
@@ -407,9 +517,19 @@ async fn version_create_inner(
                 primary: file.primary,
                 size: file.size,
                 file_type: file.file_type,
+                findings: file.findings.clone(),
             })
             .collect::<Vec<_>>(),
-        dependencies: version_data.dependencies,
+        dependencies: builder
+            .dependencies
+            .iter()
+            .map(|x| Dependency {
+                version_id: x.version_id.map(|x| x.into()),
+                project_id: x.project_id.map(|x| x.into()),
+                file_name: x.file_name.clone(),
+                dependency_type: DependencyType::from_str(&x.dependency_type),
+            })
+            .collect(),
         game_versions: version_data.game_versions,
         loaders: version_data.loaders,
     };
@@ -420,10 +540,26 @@ async fn version_create_inner(
     models::Project::update_game_versions(project_id, &mut *transaction).await?;
     models::Project::update_loaders(project_id, &mut *transaction).await?;
 
-    Ok(HttpResponse::Ok().json(response))
+    Ok(HttpResponse::Ok().json(VersionCreateResponse {
+        version: response,
+        modpack_reports,
+    }))
 }
 
 // under /api/v1/version/{version_id}
+#[utoipa::path(
+    path = "/v2/version/{version_id}/file",
+    tag = "versions",
+    security(("token" = [])),
+    params(("version_id" = VersionId, Path, description = "The ID of the version")),
+    request_body(content = InitialFileData, description = "A `data` part with the JSON data, followed by the file parts it names", content_type = "multipart/form-data"),
+    responses(
+        (status = 200, description = "The files were added to the version, and the reports of the modpack files among them", body = FileUploadResponse),
+        (status = 204, description = "The files were added to the version"),
+        (status = 400, description = "Invalid input", body = ApiError),
+        (status = 401, description = "Missing authentication, or not permitted to upload files to the version", body = ApiError),
+    )
+)]
 #[post("{version_id}/file")]
 pub async fn upload_file_to_version(
     req: HttpRequest,
@@ -431,6 +567,8 @@ pub async fn upload_file_to_version(
     mut payload: Multipart,
     client: Data<PgPool>,
     file_host: Data<Arc<dyn FileHost + Send + Sync>>,
+    pat_queue: Data<Arc<PatQueue>>,
+    scan_queue: Data<Arc<ScanQueue>>,
 ) -> Result<HttpResponse, CreateError> {
     let mut transaction = client.begin().await?;
     let mut uploaded_files = Vec::new();
@@ -445,6 +583,8 @@ pub async fn upload_file_to_version(
         &***file_host,
         &mut uploaded_files,
         version_id,
+        &pat_queue,
+        &scan_queue,
     )
     .await;
 
@@ -473,13 +613,16 @@ async fn upload_file_to_version_inner(
     file_host: &dyn FileHost,
     uploaded_files: &mut Vec<UploadedFile>,
     version_id: models::VersionId,
+    pat_queue: &PatQueue,
+    scan_queue: &ScanQueue,
 ) -> Result<HttpResponse, CreateError> {
     let cdn_url = dotenvy::var("CDN_URL")?;
 
     let mut initial_file_data: Option<InitialFileData> = None;
     let mut file_builders: Vec<VersionFileBuilder> = Vec::new();
 
-    let user = get_user_from_headers(req.headers(), &mut *transaction).await?;
+    let user =
+        get_user_from_headers(&req, &mut *transaction, pat_queue, Scopes::VERSION_CREATE).await?;
 
     let result = models::Version::get_full(version_id, &**client).await?;
 
@@ -531,6 +674,19 @@ async fn upload_file_to_version_inner(
 
     let all_game_versions = models::categories::GameVersion::list(&mut *transaction).await?;
 
+    let mut dependencies = version
+        .dependencies
+        .iter()
+        .map(|x| DependencyBuilder {
+            project_id: x.project_id,
+            version_id: x.version_id,
+            file_name: x.file_name.clone(),
+            dependency_type: x.dependency_type.clone(),
+        })
+        .collect::<Vec<_>>();
+    let existing_dependencies = dependencies.len();
+    let mut modpack_reports = Vec::new();
+
     let mut error = None;
     while let Some(item) = payload.next().await {
         let mut field: Field = item?;
@@ -560,28 +716,20 @@ async fn upload_file_to_version_inner(
                 CreateError::InvalidInput(String::from("`data` field must come before file fields"))
             })?;
 
-            let mut dependencies = version
-                .dependencies
-                .iter()
-                .map(|x| DependencyBuilder {
-                    project_id: x.project_id,
-                    version_id: x.version_id,
-                    file_name: x.file_name.clone(),
-                    dependency_type: x.dependency_type.clone(),
-                })
-                .collect();
-
-            upload_file(
-                &mut field,
+            let (file_name, _) = get_name_ext(&content_disposition)?;
+            let file = spool_version_file(&mut field).await?;
+
+            let report = upload_file(
+                file,
+                file_name,
                 file_host,
+                scan_queue,
                 0,
                 uploaded_files,
                 &mut file_builders,
                 &mut dependencies,
                 &cdn_url,
-                &content_disposition,
                 project_id,
-                version_id.into(),
                 &project_type,
                 version.loaders.clone().into_iter().map(Loader).collect(),
                 version
@@ -597,6 +745,7 @@ async fn upload_file_to_version_inner(
                 transaction,
             )
             .await?;
+            modpack_reports.extend(report);
 
             Ok(())
         }
@@ -611,6 +760,44 @@ async fn upload_file_to_version_inner(
         return Err(error);
     }
 
+    if let Some(file_data) = &initial_file_data {
+        let mut sessions = file_data.upload_sessions.iter().collect::<Vec<_>>();
+        sessions.sort_by(|a, b| a.0.cmp(b.0));
+
+        for (name, session_id) in sessions {
+            let (file_name, file) =
+                take_upload_session((*session_id).into(), user.id.into(), transaction).await?;
+
+            let report = upload_file(
+                file,
+                &file_name,
+                file_host,
+                scan_queue,
+                0,
+                uploaded_files,
+                &mut file_builders,
+                &mut dependencies,
+                &cdn_url,
+                project_id,
+                &project_type,
+                version.loaders.clone().into_iter().map(Loader).collect(),
+                version
+                    .game_versions
+                    .clone()
+                    .into_iter()
+                    .map(GameVersion)
+                    .collect(),
+                all_game_versions.clone(),
+                true,
+                false,
+                file_data.file_types.get(name).copied().flatten(),
+                transaction,
+            )
+            .await?;
+            modpack_reports.extend(report);
+        }
+    }
+
     if file_builders.is_empty() {
         return Err(CreateError::InvalidInput(
             "At least one file must be specified".to_string(),
@@ -621,23 +808,310 @@ async fn upload_file_to_version_inner(
         }
     }
 
-    Ok(HttpResponse::NoContent().body(""))
+    for dependency in dependencies.split_off(existing_dependencies) {
+        dependency.insert(version_id, &mut *transaction).await?;
+    }
+
+    if modpack_reports.is_empty() {
+        Ok(HttpResponse::NoContent().body(""))
+    } else {
+        Ok(HttpResponse::Ok().json(FileUploadResponse { modpack_reports }))
+    }
+}
+
+#[derive(Serialize, Deserialize, Validate, Clone, ToSchema)]
+pub struct FileValidationData {
+    /// The project the file would be uploaded to. Its own files are not reported as duplicates.
+    pub project_id: Option<ProjectId>,
+    pub project_type: String,
+    #[validate(length(min = 1))]
+    pub loaders: Vec<Loader>,
+    #[validate(length(min = 1))]
+    pub game_versions: Vec<GameVersion>,
+    pub file_type: Option<FileType>,
+}
+
+/// What uploading a file to a version would result in
+#[derive(Serialize, ToSchema)]
+pub struct FileValidationReport {
+    /// Whether the file passed validation, which marks it as primary
+    pub passed: bool,
+    /// The error uploading the file as the primary file of a version would fail with
+    pub error: Option<String>,
+    pub findings: Vec<FileFinding>,
+    /// The metadata declared by the manifest of the file
+    pub metadata: Option<FileMetadata>,
+    /// The report of the pack's files if the file is a modpack
+    pub modpack_report: Option<ModpackReport>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct FileMetadata {
+    pub mod_id: String,
+    pub version: Option<String>,
+    /// The Minecraft versions the file declares support for, in the syntax of its loader
+    pub game_versions: Option<String>,
+    /// The loaders the file contains a manifest for, which the loaders of a version can be
+    /// filled in with
+    pub loaders: Vec<Loader>,
+    pub environment: Option<ModEnvironment>,
+    pub dependencies: Vec<FileMetadataDependency>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct FileMetadataDependency {
+    pub mod_id: String,
+    pub version_range: Option<String>,
+    pub dependency_type: DependencyType,
+    /// The project the dependency would be linked to, whose slug matches the mod ID
+    pub project_id: Option<ProjectId>,
+}
+
+/// Validates a file as if it were uploaded to a version, without storing anything
+#[utoipa::path(
+    path = "/v2/versions/validate",
+    tag = "versions",
+    security(("token" = [])),
+    request_body(content = FileValidationData, description = "A `data` part with the JSON data, followed by a single file part", content_type = "multipart/form-data"),
+    responses(
+        (status = 200, description = "The result of validating the file", body = FileValidationReport),
+        (status = 400, description = "Invalid input", body = ApiError),
+        (status = 401, description = "Missing authentication", body = ApiError),
+    )
+)]
+#[post("versions/validate")]
+pub async fn version_file_validate(
+    req: HttpRequest,
+    mut payload: Multipart,
+    pool: Data<PgPool>,
+    pat_queue: Data<Arc<PatQueue>>,
+) -> Result<HttpResponse, CreateError> {
+    get_user_from_headers(&req, &**pool, &pat_queue, Scopes::VERSION_CREATE).await?;
+
+    let mut validation_data = None;
+    let mut report = None;
+
+    let mut error = None;
+    while let Some(item) = payload.next().await {
+        let mut field: Field = item?;
+
+        if error.is_some() {
+            continue;
+        }
+
+        let result = async {
+            let content_disposition = field.content_disposition().clone();
+            let name = content_disposition.get_name().ok_or_else(|| {
+                CreateError::MissingValueError("Missing content name".to_string())
+            })?;
+
+            if name == "data" {
+                let mut data = Vec::new();
+                while let Some(chunk) = field.next().await {
+                    data.extend_from_slice(&chunk?);
+                }
+
+                let data: FileValidationData = serde_json::from_slice(&data)?;
+                data.validate().map_err(|err| {
+                    CreateError::ValidationError(validation_errors_to_string(err, None))
+                })?;
+
+                models::categories::ProjectType::get_id(&data.project_type, &**pool)
+                    .await?
+                    .ok_or_else(|| {
+                        CreateError::InvalidInput(format!(
+                            "Project Type {} does not exist.",
+                            data.project_type
+                        ))
+                    })?;
+
+                let all_game_versions = models::categories::GameVersion::list(&**pool).await?;
+                let all_loaders = models::categories::Loader::list(&**pool).await?;
+
+                if let Some(game_version) = data
+                    .game_versions
+                    .iter()
+                    .find(|x| !all_game_versions.iter().any(|y| y.version == x.0))
+                {
+                    return Err(CreateError::InvalidGameVersion(game_version.0.clone()));
+                }
+
+                if let Some(loader) = data.loaders.iter().find(|x| {
+                    !all_loaders.iter().any(|y| {
+                        y.loader == x.0 && y.supported_project_types.contains(&data.project_type)
+                    })
+                }) {
+                    return Err(CreateError::InvalidLoader(loader.0.clone()));
+                }
+
+                validation_data = Some((data, all_game_versions));
+
+                return Ok(());
+            }
+
+            let (data, all_game_versions) = validation_data.as_ref().ok_or_else(|| {
+                CreateError::InvalidInput(String::from("`data` field must come before file fields"))
+            })?;
+
+            if report.is_some() {
+                return Err(CreateError::InvalidInput(
+                    "Only one file can be validated at a time".to_string(),
+                ));
+            }
+
+            report = Some(
+                validate_uploaded_file(
+                    &mut field,
+                    &content_disposition,
+                    data,
+                    all_game_versions.clone(),
+                    &pool,
+                )
+                .await?,
+            );
+
+            Ok(())
+        }
+        .await;
+
+        if result.is_err() {
+            error = result.err();
+        }
+    }
+
+    if let Some(error) = error {
+        return Err(error);
+    }
+
+    let report = report.ok_or_else(|| {
+        CreateError::InvalidInput(String::from("A file must be included to be validated"))
+    })?;
+
+    Ok(HttpResponse::Ok().json(report))
+}
+
+// Runs the checks of `upload_file` on a file, reporting what they found instead of uploading it
+async fn validate_uploaded_file(
+    field: &mut Field,
+    content_disposition: &actix_web::http::header::ContentDisposition,
+    validation_data: &FileValidationData,
+    all_game_versions: Vec<models::categories::GameVersion>,
+    pool: &PgPool,
+) -> Result<FileValidationReport, CreateError> {
+    let (file_name, file_extension) = get_name_ext(content_disposition)?;
+
+    if file_name.contains('/') {
+        return Err(CreateError::InvalidInput(
+            "File names must not contain slashes!".to_string(),
+        ));
+    }
+
+    crate::util::ext::project_file_type(file_extension)
+        .ok_or_else(|| CreateError::InvalidFileType(file_extension.to_string()))?;
+
+    let file = spool_version_file(field).await?;
+
+    let mut errors = Vec::new();
+    match check_duplicate_file(&file.sha1, validation_data.project_id, pool).await {
+        Ok(()) => {}
+        Err(CreateError::InvalidInput(error)) => errors.push(error),
+        Err(err) => return Err(err),
+    }
+
+    let validation_result = validate_file(
+        file.open().map_err(FileHostingError::from)?,
+        file_extension.to_string(),
+        validation_data.project_type.clone(),
+        validation_data.loaders.clone(),
+        validation_data.game_versions.clone(),
+        all_game_versions,
+        validation_data.file_type,
+    )
+    .await?;
+
+    let mut findings = match validation_result {
+        ValidationResult::Warning(ref findings) => findings.clone(),
+        _ => Vec::new(),
+    };
+
+    if !validation_result.is_passed() {
+        errors.push(rejection_message(&findings));
+    }
+
+    let mut metadata = None;
+    let mut modpack_report = None;
+    match validation_result {
+        ValidationResult::PassWithMetadata(ref mod_metadata) => {
+            for check in [
+                check_game_versions(mod_metadata, &validation_data.game_versions),
+                check_loaders(mod_metadata, &validation_data.loaders),
+            ] {
+                match check {
+                    Ok(finding) => findings.extend(finding),
+                    Err(CreateError::InvalidInput(error)) => errors.push(error),
+                    Err(err) => return Err(err),
+                }
+            }
+
+            let projects =
+                find_dependency_projects(mod_metadata, validation_data.project_id, pool).await?;
+
+            metadata = Some(FileMetadata {
+                mod_id: mod_metadata.mod_id.clone(),
+                version: mod_metadata.version.clone(),
+                game_versions: mod_metadata.game_versions.as_ref().map(|x| x.to_string()),
+                loaders: mod_metadata.loaders.iter().cloned().map(Loader).collect(),
+                environment: mod_metadata.environment,
+                dependencies: mod_metadata
+                    .dependencies
+                    .iter()
+                    .zip(projects)
+                    .map(|(x, project_id)| FileMetadataDependency {
+                        mod_id: x.mod_id.clone(),
+                        version_range: x.version_range.clone(),
+                        dependency_type: x.dependency_type,
+                        project_id,
+                    })
+                    .collect(),
+            });
+        }
+        ValidationResult::PassWithPackDataAndFiles { ref format, .. } => {
+            modpack_report = Some(ModpackReport {
+                file_name: file_name.to_string(),
+                files: services::modpacks::check_pack_files(format, pool).await?,
+            });
+        }
+        _ => {}
+    }
+
+    Ok(FileValidationReport {
+        passed: validation_result.is_passed(),
+        error: if errors.is_empty() {
+            None
+        } else {
+            Some(errors.join(" "))
+        },
+        findings,
+        metadata,
+        modpack_report,
+    })
 }
 
 // This function is used for adding a file to a version, uploading the initial
-// files for a version, and for uploading the initial version files for a project
+// files for a version, and for uploading the initial version files for a project.
+// Returns the report of the pack's files if the file is a modpack.
 #[allow(clippy::too_many_arguments)]
 pub async fn upload_file(
-    field: &mut Field,
+    file: SpooledFile,
+    file_name: &str,
     file_host: &dyn FileHost,
+    scan_queue: &ScanQueue,
     total_files_len: usize,
     uploaded_files: &mut Vec<UploadedFile>,
     version_files: &mut Vec<VersionFileBuilder>,
     dependencies: &mut Vec<DependencyBuilder>,
     cdn_url: &str,
-    content_disposition: &actix_web::http::header::ContentDisposition,
     project_id: ProjectId,
-    version_id: VersionId,
     project_type: &str,
     loaders: Vec<Loader>,
     game_versions: Vec<GameVersion>,
@@ -646,8 +1120,8 @@ pub async fn upload_file(
     force_primary: bool,
     file_type: Option<FileType>,
     transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
-) -> Result<(), CreateError> {
-    let (file_name, file_extension) = get_name_ext(content_disposition)?;
+) -> Result<Option<ModpackReport>, CreateError> {
+    let file_extension = get_ext(file_name)?;
 
     if file_name.contains('/') {
         return Err(CreateError::InvalidInput(
@@ -658,36 +1132,10 @@ pub async fn upload_file(
     let content_type = crate::util::ext::project_file_type(file_extension)
         .ok_or_else(|| CreateError::InvalidFileType(file_extension.to_string()))?;
 
-    let data = read_from_field(
-        field, 500 * (1 << 20),
-        "Project file exceeds the maximum of 500MiB. Contact a moderator or admin to request permission to upload larger files."
-    ).await?;
-
-    let hash = sha1::Sha1::from(&data).hexdigest();
-    let exists = sqlx::query!(
-        "
-        SELECT EXISTS(SELECT 1 FROM hashes h
-        INNER JOIN files f ON f.id = h.file_id
-        INNER JOIN versions v ON v.id = f.version_id
-        WHERE h.algorithm = $2 AND h.hash = $1 AND v.mod_id != $3)
-        ",
-        hash.as_bytes(),
-        "sha1",
-        project_id.0 as i64
-    )
-    .fetch_one(&mut *transaction)
-    .await?
-    .exists
-    .unwrap_or(false);
-
-    if exists {
-        return Err(CreateError::InvalidInput(
-            "Duplicate files are not allowed to be uploaded to Modrinth!".to_string(),
-        ));
-    }
+    check_duplicate_file(&file.sha1, Some(project_id), &mut *transaction).await?;
 
     let validation_result = validate_file(
-        data.clone().into(),
+        file.open().map_err(FileHostingError::from)?,
         file_extension.to_string(),
         project_type.to_string(),
         loaders.clone(),
@@ -697,71 +1145,66 @@ pub async fn upload_file(
     )
     .await?;
 
+    let mut modpack_report = None;
     if let ValidationResult::PassWithPackDataAndFiles {
         ref format,
         ref files,
     } = validation_result
     {
-        if dependencies.is_empty() {
-            let hashes: Vec<Vec<u8>> = format
-                .files
-                .iter()
-                .filter_map(|x| x.hashes.get(&PackFileHash::Sha1))
-                .map(|x| x.as_bytes().to_vec())
-                .collect();
-
-            let res = sqlx::query!(
-                "
-                    SELECT v.id version_id, v.mod_id project_id, h.hash hash FROM hashes h
-                    INNER JOIN files f on h.file_id = f.id
-                    INNER JOIN versions v on f.version_id = v.id
-                    WHERE h.algorithm = 'sha1' AND h.hash = ANY($1)
-                    ",
-                &*hashes
-            )
-            .fetch_all(&mut *transaction)
-            .await?;
+        let pack_files = services::modpacks::check_pack_files(format, &mut *transaction).await?;
+
+        let mut detected = Vec::new();
+        for (file, report) in format.files.iter().zip(&pack_files) {
+            if report.status.is_linked() {
+                detected.push(DependencyBuilder {
+                    project_id: report.project_id.map(|x| x.into()),
+                    version_id: report.version_id.map(|x| x.into()),
+                    file_name: None,
+                    dependency_type: DependencyType::Embedded.to_string(),
+                });
+            } else if let Some(first_download) = file.downloads.first() {
+                detected.push(DependencyBuilder {
+                    project_id: None,
+                    version_id: None,
+                    file_name: Some(
+                        first_download
+                            .rsplit('/')
+                            .next()
+                            .unwrap_or(first_download)
+                            .to_string(),
+                    ),
+                    dependency_type: DependencyType::Embedded.to_string(),
+                });
+            }
+        }
 
-            for file in &format.files {
-                if let Some(dep) = res.iter().find(|x| {
-                    Some(&*x.hash) == file.hashes.get(&PackFileHash::Sha1).map(|x| x.as_bytes())
-                }) {
-                    dependencies.push(DependencyBuilder {
-                        project_id: Some(models::ProjectId(dep.project_id)),
-                        version_id: Some(models::VersionId(dep.version_id)),
-                        file_name: None,
-                        dependency_type: DependencyType::Embedded.to_string(),
-                    });
-                } else if let Some(first_download) = file.downloads.first() {
-                    dependencies.push(DependencyBuilder {
-                        project_id: None,
-                        version_id: None,
-                        file_name: Some(
-                            first_download
-                                .rsplit('/')
-                                .next()
-                                .unwrap_or(first_download)
-                                .to_string(),
-                        ),
-                        dependency_type: DependencyType::Embedded.to_string(),
-                    });
-                }
+        for file in files {
+            if !file.is_empty() {
+                detected.push(DependencyBuilder {
+                    project_id: None,
+                    version_id: None,
+                    file_name: Some(file.to_string()),
+                    dependency_type: DependencyType::Embedded.to_string(),
+                });
             }
+        }
 
-            for file in files {
-                if !file.is_empty() {
-                    dependencies.push(DependencyBuilder {
-                        project_id: None,
-                        version_id: None,
-                        file_name: Some(file.to_string()),
-                        dependency_type: DependencyType::Embedded.to_string(),
-                    });
-                }
+        // Dependencies declared by the uploader take precedence over detected ones
+        for dependency in detected {
+            if !dependencies.iter().any(|x| {
+                (dependency.project_id.is_some() && x.project_id == dependency.project_id)
+                    || (dependency.version_id.is_some() && x.version_id == dependency.version_id)
+                    || (dependency.file_name.is_some() && x.file_name == dependency.file_name)
+            }) {
+                dependencies.push(dependency);
             }
         }
-    }
 
-    let data = data.freeze();
+        modpack_report = Some(ModpackReport {
+            file_name: file_name.to_string(),
+            files: pack_files,
+        });
+    }
 
     let primary = (validation_result.is_passed()
         && version_files.iter().all(|x| !x.primary)
@@ -769,25 +1212,42 @@ pub async fn upload_file(
         || force_primary
         || total_files_len == 1;
 
-    let file_path_encode = format!(
-        "data/{}/versions/{}/{}",
-        project_id,
-        version_id,
-        urlencoding::encode(file_name)
-    );
-    let file_path = format!("data/{}/versions/{}/{}", project_id, version_id, &file_name);
-
-    let upload_data = file_host
-        .upload_file(content_type, &file_path, data)
-        .await?;
+    let mut findings = match validation_result {
+        ValidationResult::Warning(ref findings) => findings.clone(),
+        _ => Vec::new(),
+    };
 
-    uploaded_files.push(UploadedFile {
-        file_id: upload_data.file_id,
-        file_name: file_path,
-    });
+    // The manifest of the primary file must agree with the version, and fills in its
+    // dependencies if the uploader did not declare any
+    if let ValidationResult::PassWithMetadata(ref metadata) = validation_result {
+        if primary {
+            findings.extend(check_game_versions(metadata, &game_versions)?);
+            findings.extend(check_loaders(metadata, &loaders)?);
+
+            if dependencies.is_empty() {
+                let projects =
+                    find_dependency_projects(metadata, Some(project_id), &mut *transaction).await?;
+
+                for (dependency, project_id) in metadata.dependencies.iter().zip(projects) {
+                    if let Some(project_id) = project_id {
+                        let project_id = Some(project_id.into());
+
+                        if !dependencies.iter().any(|x| x.project_id == project_id) {
+                            dependencies.push(DependencyBuilder {
+                                project_id,
+                                version_id: None,
+                                file_name: None,
+                                dependency_type: dependency.dependency_type.to_string(),
+                            });
+                        }
+                    }
+                }
+            }
+        }
+    }
 
-    let sha1_bytes = upload_data.content_sha1.into_bytes();
-    let sha512_bytes = upload_data.content_sha512.into_bytes();
+    let sha1_bytes = file.sha1.clone().into_bytes();
+    let sha512_bytes = file.sha512.clone().into_bytes();
 
     if version_files.iter().any(|x| {
         x.hashes
@@ -799,15 +1259,25 @@ pub async fn upload_file(
         ));
     }
 
-    if let ValidationResult::Warning(msg) = validation_result {
-        if primary {
-            return Err(CreateError::InvalidInput(msg.to_string()));
-        }
+    if primary && !validation_result.is_passed() {
+        return Err(CreateError::InvalidInput(rejection_message(&findings)));
     }
 
+    let blob = upload_blob(
+        &file,
+        file_name,
+        content_type,
+        file_host,
+        uploaded_files,
+        transaction,
+    )
+    .await?;
+
+    scan_queue.add(sha1_bytes.clone(), file).await;
+
     version_files.push(VersionFileBuilder {
         filename: file_name.to_string(),
-        url: format!("{cdn_url}/{file_path_encode}"),
+        url: blob.url(cdn_url),
         hashes: vec![
             models::version_item::HashBuilder {
                 algorithm: "sha1".to_string(),
@@ -823,25 +1293,255 @@ pub async fn upload_file(
             },
         ],
         primary,
-        size: upload_data.content_length,
+        size: blob.size as u32,
         file_type,
+        findings,
+        blob: Some(blob.sha512),
+    });
+
+    Ok(modpack_report)
+}
+
+// Stores the file as a blob, unless one with the same content was stored already
+async fn upload_blob(
+    file: &SpooledFile,
+    file_name: &str,
+    content_type: &str,
+    file_host: &dyn FileHost,
+    uploaded_files: &mut Vec<UploadedFile>,
+    transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+) -> Result<Blob, CreateError> {
+    if let Some(blob) = Blob::reuse(&file.sha512, transaction).await? {
+        return Ok(blob);
+    }
+
+    // The blob is claimed before it is uploaded, so concurrent uploads of the content wait for it
+    // instead of uploading their own. The upload is undone before the transaction is rolled back,
+    // while it is still claimed.
+    let path = Blob::path_for(&file.sha512, file_name);
+    if !Blob::claim(&file.sha512, &path, file.length as i32, transaction).await? {
+        // The same file was uploaded concurrently
+        return Blob::reuse(&file.sha512, transaction)
+            .await?
+            .ok_or_else(|| CreateError::InvalidInput("The file could not be stored".to_string()));
+    }
+
+    let upload_data = file_host
+        .upload_file_streaming(content_type, &path, file)
+        .await?;
+    uploaded_files.push(UploadedFile {
+        file_id: upload_data.file_id.clone(),
+        file_name: path.clone(),
     });
 
+    let blob = Blob {
+        sha512: file.sha512.clone(),
+        path,
+        host_file_id: upload_data.file_id,
+        size: upload_data.content_length as i32,
+    };
+    blob.set_host_file_id(transaction).await?;
+
+    Ok(blob)
+}
+
+// Files may only be uploaded once, except to other versions of the same project
+async fn check_duplicate_file<'a, E>(
+    sha1: &str,
+    project_id: Option<ProjectId>,
+    exec: E,
+) -> Result<(), CreateError>
+where
+    E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+{
+    let exists = sqlx::query!(
+        "
+        SELECT EXISTS(SELECT 1 FROM hashes h
+        INNER JOIN files f ON f.id = h.file_id
+        INNER JOIN versions v ON v.id = f.version_id
+        WHERE h.algorithm = $2 AND h.hash = $1 AND ($3::bigint IS NULL OR v.mod_id != $3))
+        ",
+        sha1.as_bytes(),
+        "sha1",
+        project_id.map(|x| x.0 as i64),
+    )
+    .fetch_one(exec)
+    .await?
+    .exists
+    .unwrap_or(false);
+
+    if exists {
+        return Err(CreateError::InvalidInput(
+            "Duplicate files are not allowed to be uploaded to Modrinth!".to_string(),
+        ));
+    }
+
     Ok(())
 }
 
+// The Minecraft versions declared by the manifest of the primary file must include at least one
+// of the version's game versions, unless the manifest only recommends a version. The ones it does
+// not include are noted as a finding.
+fn check_game_versions(
+    metadata: &ModMetadata,
+    game_versions: &[GameVersion],
+) -> Result<Option<FileFinding>, CreateError> {
+    let range = match &metadata.game_versions {
+        Some(range) => range,
+        None => return Ok(None),
+    };
+
+    let matches = match game_versions
+        .iter()
+        .map(|x| range.matches(&x.0))
+        .collect::<Option<Vec<_>>>()
+    {
+        Some(matches) => matches,
+        None => return Ok(None),
+    };
+
+    if !matches.contains(&true) && !range.is_advisory() {
+        return Err(CreateError::InvalidInput(format!(
+            "The file declares support for Minecraft {range}, which does not include any of the version's game versions"
+        )));
+    }
+
+    let unsupported = game_versions
+        .iter()
+        .zip(matches)
+        .filter(|(_, matched)| !matched)
+        .map(|(x, _)| x.0.as_str())
+        .collect::<Vec<_>>();
+
+    if unsupported.is_empty() {
+        return Ok(None);
+    }
+
+    Ok(Some(FileFinding {
+        severity: FindingSeverity::Info,
+        code: FindingCode::GameVersionMismatch,
+        message: format!(
+            "The file declares support for Minecraft {range}, which does not include {}",
+            unsupported.join(", ")
+        ),
+        path: None,
+    }))
+}
+
+// The primary file must contain a manifest for at least one of the version's loaders. The loaders
+// it has no manifest for are noted as a finding.
+fn check_loaders(
+    metadata: &ModMetadata,
+    loaders: &[Loader],
+) -> Result<Option<FileFinding>, CreateError> {
+    if metadata.loaders.is_empty() {
+        return Ok(None);
+    }
+
+    let unsupported = loaders
+        .iter()
+        .map(|x| x.0.as_str())
+        .filter(|x| !metadata.loaders.iter().any(|y| y == x))
+        .collect::<Vec<_>>();
+
+    if unsupported.len() == loaders.len() {
+        return Err(CreateError::InvalidInput(format!(
+            "The file is for {}, which does not include any of the version's loaders",
+            metadata.loaders.join(", ")
+        )));
+    }
+
+    if unsupported.is_empty() {
+        return Ok(None);
+    }
+
+    Ok(Some(FileFinding {
+        severity: FindingSeverity::Info,
+        code: FindingCode::LoaderMismatch,
+        message: format!(
+            "The file is for {}, which does not include {}",
+            metadata.loaders.join(", "),
+            unsupported.join(", ")
+        ),
+        path: None,
+    }))
+}
+
+// The visible projects whose slugs match the mod IDs of the dependencies declared by the file,
+// in the order of the dependencies
+async fn find_dependency_projects<'a, E>(
+    metadata: &ModMetadata,
+    project_id: Option<ProjectId>,
+    exec: E,
+) -> Result<Vec<Option<ProjectId>>, CreateError>
+where
+    E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+{
+    let mod_ids = metadata
+        .dependencies
+        .iter()
+        .map(|x| x.mod_id.to_lowercase())
+        .collect::<Vec<_>>();
+
+    let projects = sqlx::query!(
+        "
+        SELECT id, LOWER(slug) \"slug!\" FROM mods
+        WHERE LOWER(slug) = ANY($1) AND ($2::bigint IS NULL OR id != $2) AND status != ANY($3)
+        ",
+        &mod_ids,
+        project_id.map(|x| x.0 as i64),
+        &*crate::models::projects::ProjectStatus::iterator()
+            .filter(|x| x.is_hidden())
+            .map(|x| x.to_string())
+            .collect::<Vec<String>>(),
+    )
+    .fetch_all(exec)
+    .await?;
+
+    Ok(mod_ids
+        .iter()
+        .map(|mod_id| {
+            projects
+                .iter()
+                .find(|x| &x.slug == mod_id)
+                .map(|x| ProjectId(x.id as u64))
+        })
+        .collect())
+}
+
+// The error a primary file which did not pass validation is rejected with
+fn rejection_message(findings: &[FileFinding]) -> String {
+    findings
+        .iter()
+        .filter(|x| x.severity == FindingSeverity::Warning)
+        .map(|x| x.message.as_str())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Spools a version file from a multipart field, up to the maximum size of version files
+pub async fn spool_version_file(field: &mut Field) -> Result<SpooledFile, CreateError> {
+    spool_field(
+        field, 500 * (1 << 20),
+        "Project file exceeds the maximum of 500MiB. Contact a moderator or admin to request permission to upload larger files."
+    ).await
+}
+
 pub fn get_name_ext(
     content_disposition: &actix_web::http::header::ContentDisposition,
 ) -> Result<(&str, &str), CreateError> {
     let file_name = content_disposition
         .get_filename()
         .ok_or_else(|| CreateError::MissingValueError("Missing content file name".to_string()))?;
-    let file_extension = if let Some(last_period) = file_name.rfind('.') {
-        file_name.get((last_period + 1)..).unwrap_or("")
+    Ok((file_name, get_ext(file_name)?))
+}
+
+pub fn get_ext(file_name: &str) -> Result<&str, CreateError> {
+    if let Some(last_period) = file_name.rfind('.') {
+        Ok(file_name.get((last_period + 1)..).unwrap_or(""))
     } else {
-        return Err(CreateError::MissingValueError(
+        Err(CreateError::MissingValueError(
             "Missing content file extension".to_string(),
-        ));
-    };
-    Ok((file_name, file_extension))
+        ))
+    }
 }
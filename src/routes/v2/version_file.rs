@@ -1,9 +1,13 @@
 use super::ApiError;
 use crate::database::models::{version_item::QueryVersion, DatabaseError};
+use crate::file_hosting::{FileHost, LocalHost, ReplicatedHost};
 use crate::models::ids::VersionId;
+use crate::models::pats::Scopes;
 use crate::models::projects::{GameVersion, Loader, Project, Version};
 use crate::models::teams::Permissions;
+use crate::queue::pats::PatQueue;
 use crate::util::auth::get_user_from_headers;
+use crate::util::blobs::delete_released_blobs;
 use crate::util::routes::ok_or_not_found;
 use crate::{database, models};
 use actix_web::{delete, get, post, web, HttpRequest, HttpResponse};
@@ -12,6 +16,8 @@ use itertools::Itertools;
 use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
 use std::collections::HashMap;
+use std::sync::Arc;
+use utoipa::{IntoParams, ToSchema};
 
 pub fn config(cfg: &mut web::ServiceConfig) {
     cfg.service(
@@ -30,7 +36,7 @@ pub fn config(cfg: &mut web::ServiceConfig) {
     );
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, IntoParams)]
 pub struct HashQuery {
     #[serde(default = "default_algorithm")]
     pub algorithm: String,
@@ -48,7 +54,19 @@ fn default_multiple() -> bool {
 }
 
 // under /api/v1/version_file/{hash}
-#[get("{version_id}")]
+#[utoipa::path(
+    path = "/v2/version_file/{hash}",
+    tag = "version files",
+    params(
+        ("hash" = String, Path, description = "The hash of the file, in the algorithm given by `algorithm`"),
+        HashQuery,
+    ),
+    responses(
+        (status = 200, description = "The version the file belongs to, or a list of all versions containing the file if `multiple` is set", body = Version),
+        (status = 404, description = "No visible version contains the file"),
+    )
+)]
+#[get("{hash}")]
 pub async fn get_version_from_hash(
     info: web::Path<(String,)>,
     pool: web::Data<PgPool>,
@@ -102,17 +120,34 @@ pub async fn get_version_from_hash(
     }
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, ToSchema)]
 pub struct DownloadRedirect {
     pub url: String,
 }
 
 // under /api/v1/version_file/{hash}/download
-#[get("{version_id}/download")]
+#[utoipa::path(
+    path = "/v2/version_file/{hash}/download",
+    tag = "version files",
+    security((), ("token" = [])),
+    params(
+        ("hash" = String, Path, description = "The hash of the file, in the algorithm given by `algorithm`"),
+        HashQuery,
+    ),
+    responses(
+        (status = 307, description = "A redirect to the file on the CDN, which is signed for files of hidden versions when using local file storage", body = DownloadRedirect),
+        (status = 404, description = "No visible version contains the file"),
+    )
+)]
+#[get("{hash}/download")]
 pub async fn download_version(
+    req: HttpRequest,
     info: web::Path<(String,)>,
     pool: web::Data<PgPool>,
     hash_query: web::Query<HashQuery>,
+    pat_queue: web::Data<Arc<PatQueue>>,
+    local_host: web::Data<Option<Arc<LocalHost>>>,
+    replicated_host: web::Data<Option<Arc<ReplicatedHost>>>,
 ) -> Result<HttpResponse, ApiError> {
     let hash = info.into_inner().0.to_lowercase();
     let mut transaction = pool.begin().await?;
@@ -143,23 +178,85 @@ pub async fn download_version(
     if let Some(id) = result {
         transaction.commit().await?;
 
-        Ok(HttpResponse::TemporaryRedirect()
-            .append_header(("Location", &*id.url))
-            .json(DownloadRedirect { url: id.url }))
-    } else {
-        Ok(HttpResponse::NotFound().body(""))
+        let url = match &**replicated_host {
+            Some(replicated_host) => replicated_host.download_url(&id.url).await?,
+            None => id.url,
+        };
+
+        return Ok(HttpResponse::TemporaryRedirect()
+            .append_header(("Location", &*url))
+            .json(DownloadRedirect { url }));
     }
+
+    // Files of hidden versions are only served by the local file host with a signed URL, which is
+    // given to moderators and members of the project's team
+    if let Some(local_host) = (**local_host).as_ref().filter(|x| x.signs_urls()) {
+        let user_option =
+            get_user_from_headers(&req, &mut *transaction, &pat_queue, Scopes::VERSION_READ)
+                .await
+                .ok();
+
+        if let Some(user) = user_option {
+            let user_id: database::models::UserId = user.id.into();
+
+            let result = sqlx::query!(
+                "
+                SELECT f.url url FROM hashes h
+                INNER JOIN files f ON h.file_id = f.id
+                INNER JOIN versions v ON v.id = f.version_id
+                INNER JOIN mods m on v.mod_id = m.id
+                WHERE h.algorithm = $2 AND h.hash = $1
+                AND ($3 OR EXISTS(SELECT 1 FROM team_members tm WHERE tm.team_id = m.team_id AND tm.user_id = $4))
+                ORDER BY v.date_published ASC
+                ",
+                hash.as_bytes(),
+                hash_query.algorithm,
+                user.role.is_mod(),
+                user_id as database::models::UserId,
+            )
+            .fetch_optional(&mut *transaction)
+            .await?;
+
+            transaction.commit().await?;
+
+            let cdn_url = dotenvy::var("CDN_URL")?;
+            if let Some(url) = result.and_then(|x| local_host.signed_url(&cdn_url, &x.url)) {
+                return Ok(HttpResponse::TemporaryRedirect()
+                    .append_header(("Location", &*url))
+                    .json(DownloadRedirect { url }));
+            }
+        }
+    }
+
+    Ok(HttpResponse::NotFound().body(""))
 }
 
 // under /api/v1/version_file/{hash}
-#[delete("{version_id}")]
+#[utoipa::path(
+    path = "/v2/version_file/{hash}",
+    tag = "version files",
+    security(("token" = [])),
+    params(
+        ("hash" = String, Path, description = "The hash of the file, in the algorithm given by `algorithm`"),
+        HashQuery,
+    ),
+    responses(
+        (status = 204, description = "The file was deleted"),
+        (status = 400, description = "The file is the only file of its version", body = ApiError),
+        (status = 401, description = "Missing authentication, or not permitted to delete the file", body = ApiError),
+        (status = 404, description = "The file does not exist"),
+    )
+)]
+#[delete("{hash}")]
 pub async fn delete_file(
     req: HttpRequest,
     info: web::Path<(String,)>,
     pool: web::Data<PgPool>,
     hash_query: web::Query<HashQuery>,
+    pat_queue: web::Data<Arc<PatQueue>>,
+    file_host: web::Data<Arc<dyn FileHost + Send + Sync>>,
 ) -> Result<HttpResponse, ApiError> {
-    let user = get_user_from_headers(req.headers(), &**pool).await?;
+    let user = get_user_from_headers(&req, &**pool, &pat_queue, Scopes::VERSION_DELETE).await?;
 
     let hash = info.into_inner().0.to_lowercase();
 
@@ -229,7 +326,7 @@ pub async fn delete_file(
 
         sqlx::query!(
             "
-            DELETE FROM hashes
+            DELETE FROM files_findings
             WHERE file_id = $1
             ",
             row.id
@@ -238,30 +335,63 @@ pub async fn delete_file(
         .await?;
 
         sqlx::query!(
+            "
+            DELETE FROM hashes
+            WHERE file_id = $1
+            ",
+            row.id
+        )
+        .execute(&mut *transaction)
+        .await?;
+
+        let blob = sqlx::query!(
             "
             DELETE FROM files
             WHERE files.id = $1
+            RETURNING blob
             ",
             row.id,
         )
-        .execute(&mut *transaction)
+        .fetch_one(&mut *transaction)
+        .await?
+        .blob;
+
+        database::models::blob_item::Blob::release_unreferenced(
+            &blob.into_iter().collect::<Vec<_>>(),
+            &mut transaction,
+        )
         .await?;
 
         transaction.commit().await?;
 
+        delete_released_blobs(&pool, &***file_host).await?;
+
         Ok(HttpResponse::NoContent().body(""))
     } else {
         Ok(HttpResponse::NotFound().body(""))
     }
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct UpdateData {
     pub loaders: Vec<Loader>,
     pub game_versions: Vec<GameVersion>,
 }
 
-#[post("{version_id}/update")]
+#[utoipa::path(
+    path = "/v2/version_file/{hash}/update",
+    tag = "version files",
+    params(
+        ("hash" = String, Path, description = "The hash of the file, in the algorithm given by `algorithm`"),
+        HashQuery,
+    ),
+    request_body = UpdateData,
+    responses(
+        (status = 200, description = "The latest version of the file's project matching the loaders and game versions", body = Version),
+        (status = 404, description = "No visible version contains the file, or no version matches"),
+    )
+)]
+#[post("{hash}/update")]
 pub async fn get_update_from_hash(
     info: web::Path<(String,)>,
     pool: web::Data<PgPool>,
@@ -336,13 +466,21 @@ pub async fn get_update_from_hash(
 }
 
 // Requests above with multiple versions below
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct FileHashes {
     pub algorithm: String,
     pub hashes: Vec<String>,
 }
 
 // under /api/v2/version_files
+#[utoipa::path(
+    path = "/v2/version_files",
+    tag = "version files",
+    request_body = FileHashes,
+    responses(
+        (status = 200, description = "The version each of the hashes belongs to, keyed by hash", body = HashMap<String, Version>),
+    )
+)]
 #[post("")]
 pub async fn get_versions_from_hashes(
     pool: web::Data<PgPool>,
@@ -465,10 +603,19 @@ pub async fn get_projects_from_hashes(
     Ok(HttpResponse::Ok().json(response?))
 }
 
+#[utoipa::path(
+    path = "/v2/version_files/download",
+    tag = "version files",
+    request_body = FileHashes,
+    responses(
+        (status = 200, description = "The download URL of each of the hashes, keyed by hash", body = HashMap<String, String>),
+    )
+)]
 #[post("download")]
 pub async fn download_files(
     pool: web::Data<PgPool>,
     file_data: web::Json<FileHashes>,
+    replicated_host: web::Data<Option<Arc<ReplicatedHost>>>,
 ) -> Result<HttpResponse, ApiError> {
     let hashes_parsed: Vec<Vec<u8>> = file_data
         .hashes
@@ -494,24 +641,27 @@ pub async fn download_files(
     .fetch_all(&mut *transaction)
     .await?;
 
-    let response = result
-        .into_iter()
-        .map(|row| {
-            if let Ok(parsed_hash) = String::from_utf8(row.hash) {
-                Ok((parsed_hash, row.url))
-            } else {
-                Err(ApiError::Database(DatabaseError::Other(format!(
-                    "Could not parse hash for version {}",
-                    row.version_id
-                ))))
-            }
-        })
-        .collect::<Result<HashMap<String, String>, ApiError>>();
+    let mut response = HashMap::new();
+    for row in result {
+        let parsed_hash = String::from_utf8(row.hash).map_err(|_| {
+            ApiError::Database(DatabaseError::Other(format!(
+                "Could not parse hash for version {}",
+                row.version_id
+            )))
+        })?;
+
+        let url = match &**replicated_host {
+            Some(replicated_host) => replicated_host.download_url(&row.url).await?,
+            None => row.url,
+        };
+
+        response.insert(parsed_hash, url);
+    }
 
-    Ok(HttpResponse::Ok().json(response?))
+    Ok(HttpResponse::Ok().json(response))
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct ManyUpdateData {
     pub algorithm: String,
     pub hashes: Vec<String>,
@@ -519,6 +669,14 @@ pub struct ManyUpdateData {
     pub game_versions: Vec<GameVersion>,
 }
 
+#[utoipa::path(
+    path = "/v2/version_files/update",
+    tag = "version files",
+    request_body = ManyUpdateData,
+    responses(
+        (status = 200, description = "The latest version matching the loaders and game versions for the project of each of the hashes, keyed by hash", body = HashMap<String, Version>),
+    )
+)]
 #[post("update")]
 pub async fn update_files(
     pool: web::Data<PgPool>,
@@ -1,21 +1,40 @@
 use super::ApiError;
 use crate::database;
+use crate::file_hosting::{FileHost, FileHostingError};
 use crate::models;
-use crate::models::projects::{Dependency, FileType, VersionStatus, VersionType};
+use crate::models::pats::Scopes;
+use crate::models::projects::{
+    Dependency, FileType, GameVersion, Loader, VersionId, VersionStatus, VersionType,
+};
 use crate::models::teams::Permissions;
+use crate::queue::pats::PatQueue;
+use crate::services;
+use crate::services::dependencies::ResolvedDependencies;
+use crate::services::modpacks::{PackOptions, MRPACK_CONTENT_TYPE};
 use crate::util::auth::{
     filter_authorized_versions, get_user_from_headers, is_authorized, is_authorized_version,
 };
+use crate::util::blobs::delete_released_blobs;
 use crate::util::validate::validation_errors_to_string;
+use actix_files::NamedFile;
+use actix_web::http::header::{
+    ContentDisposition, DispositionParam, DispositionType, HeaderValue, CONTENT_TYPE,
+};
 use actix_web::{delete, get, patch, post, web, HttpRequest, HttpResponse};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
+use std::io::Seek;
+use std::sync::Arc;
+use utoipa::{IntoParams, ToSchema};
 use validator::Validate;
 
 pub fn config(cfg: &mut web::ServiceConfig) {
     cfg.service(versions_get);
+    cfg.service(dependencies_resolve);
+    cfg.service(modpack_export);
     cfg.service(super::version_creation::version_create);
+    cfg.service(super::version_creation::version_file_validate);
 
     cfg.service(
         web::scope("version")
@@ -27,7 +46,7 @@ pub fn config(cfg: &mut web::ServiceConfig) {
     );
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, IntoParams)]
 pub struct VersionListFilters {
     pub game_versions: Option<String>,
     pub loaders: Option<String>,
@@ -37,18 +56,34 @@ pub struct VersionListFilters {
     pub offset: Option<u32>,
 }
 
+#[utoipa::path(
+    path = "/v2/project/{id}/version",
+    tag = "versions",
+    security((), ("token" = [])),
+    params(
+        ("id" = String, Path, description = "The ID or slug of the project"),
+        VersionListFilters,
+    ),
+    responses(
+        (status = 200, description = "The visible versions of the project matching the filters, newest first", body = [Version]),
+        (status = 404, description = "The project does not exist or is not visible"),
+    )
+)]
 #[get("version")]
 pub async fn version_list(
     req: HttpRequest,
     info: web::Path<(String,)>,
     web::Query(filters): web::Query<VersionListFilters>,
     pool: web::Data<PgPool>,
+    pat_queue: web::Data<Arc<PatQueue>>,
 ) -> Result<HttpResponse, ApiError> {
     let string = info.into_inner().0;
 
     let result = database::models::Project::get_from_slug_or_project_id(&string, &**pool).await?;
 
-    let user_option = get_user_from_headers(req.headers(), &**pool).await.ok();
+    let user_option = get_user_from_headers(&req, &**pool, &pat_queue, Scopes::VERSION_READ)
+        .await
+        .ok();
 
     if let Some(project) = result {
         if !is_authorized(&project, &user_option, &pool).await? {
@@ -134,17 +169,33 @@ pub async fn version_list(
 }
 
 // Given a project ID/slug and a version slug
+#[utoipa::path(
+    path = "/v2/project/{id}/version/{slug}",
+    tag = "versions",
+    security((), ("token" = [])),
+    params(
+        ("id" = String, Path, description = "The ID or slug of the project"),
+        ("slug" = String, Path, description = "The ID or version number of the version"),
+    ),
+    responses(
+        (status = 200, description = "The version", body = Version),
+        (status = 404, description = "The version does not exist or is not visible"),
+    )
+)]
 #[get("version/{slug}")]
 pub async fn version_project_get(
     req: HttpRequest,
     info: web::Path<(String, String)>,
     pool: web::Data<PgPool>,
+    pat_queue: web::Data<Arc<PatQueue>>,
 ) -> Result<HttpResponse, ApiError> {
     let id = info.into_inner();
     let version_data =
         database::models::Version::get_full_from_id_slug(&id.0, &id.1, &**pool).await?;
 
-    let user_option = get_user_from_headers(req.headers(), &**pool).await.ok();
+    let user_option = get_user_from_headers(&req, &**pool, &pat_queue, Scopes::VERSION_READ)
+        .await
+        .ok();
 
     if let Some(data) = version_data {
         if is_authorized_version(&data.inner, &user_option, &pool).await? {
@@ -155,51 +206,244 @@ pub async fn version_project_get(
     Ok(HttpResponse::NotFound().body(""))
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, IntoParams)]
 pub struct VersionIds {
     pub ids: String,
 }
 
+#[utoipa::path(
+    path = "/v2/versions",
+    tag = "versions",
+    security((), ("token" = [])),
+    params(VersionIds),
+    responses(
+        (status = 200, description = "The visible versions with the given IDs", body = [Version]),
+        (status = 400, description = "Invalid IDs", body = ApiError),
+    )
+)]
 #[get("versions")]
 pub async fn versions_get(
     req: HttpRequest,
     web::Query(ids): web::Query<VersionIds>,
     pool: web::Data<PgPool>,
+    pat_queue: web::Data<Arc<PatQueue>>,
 ) -> Result<HttpResponse, ApiError> {
-    let version_ids = serde_json::from_str::<Vec<models::ids::VersionId>>(&ids.ids)?
-        .into_iter()
-        .map(|x| x.into())
-        .collect::<Vec<database::models::VersionId>>();
-    let versions_data = database::models::Version::get_many_full(&version_ids, &**pool).await?;
+    let version_ids = serde_json::from_str::<Vec<models::ids::VersionId>>(&ids.ids)?;
 
-    let user_option = get_user_from_headers(req.headers(), &**pool).await.ok();
+    let user_option = get_user_from_headers(&req, &**pool, &pat_queue, Scopes::VERSION_READ)
+        .await
+        .ok();
 
-    let versions = filter_authorized_versions(versions_data, &user_option, &pool).await?;
+    let versions = services::versions::get_versions(version_ids, &user_option, &pool).await?;
 
     Ok(HttpResponse::Ok().json(versions))
 }
 
+#[derive(Deserialize, Validate, ToSchema)]
+pub struct ResolveDependencies {
+    /// The IDs of the versions to resolve the dependencies of
+    #[serde(default)]
+    #[validate(length(max = 256))]
+    pub version_ids: Vec<VersionId>,
+    /// Hashes of files of the versions to resolve the dependencies of
+    #[serde(default)]
+    #[validate(length(max = 256))]
+    pub hashes: Vec<String>,
+    /// The algorithm of `hashes`, either `sha1` or `sha512`
+    #[serde(default = "default_algorithm")]
+    pub algorithm: String,
+    /// The loaders dependencies must support. If empty, any loader is accepted.
+    pub loaders: Vec<Loader>,
+    /// The game versions dependencies must support. If empty, any game version is accepted.
+    pub game_versions: Vec<GameVersion>,
+    /// Whether to also resolve optional dependencies
+    #[serde(default)]
+    pub include_optional: bool,
+}
+
+fn default_algorithm() -> String {
+    "sha1".to_string()
+}
+
+#[utoipa::path(
+    path = "/v2/versions/resolve",
+    tag = "versions",
+    security((), ("token" = [])),
+    request_body = ResolveDependencies,
+    responses(
+        (status = 200, description = "The versions to install, with the reason each of them is needed, and the dependencies which could not be resolved", body = ResolvedDependencies),
+        (status = 400, description = "Invalid input, or a dependency graph which is too large", body = ApiError),
+    )
+)]
+#[post("versions/resolve")]
+pub async fn dependencies_resolve(
+    req: HttpRequest,
+    body: web::Json<ResolveDependencies>,
+    pool: web::Data<PgPool>,
+    pat_queue: web::Data<Arc<PatQueue>>,
+) -> Result<HttpResponse, ApiError> {
+    let body = body.into_inner();
+    body.validate()
+        .map_err(|err| ApiError::Validation(validation_errors_to_string(err, None)))?;
+
+    if body.version_ids.is_empty() && body.hashes.is_empty() {
+        return Err(ApiError::InvalidInput(
+            "At least one version ID or file hash is required".to_string(),
+        ));
+    }
+
+    let user_option = get_user_from_headers(&req, &**pool, &pat_queue, Scopes::VERSION_READ)
+        .await
+        .ok();
+
+    let resolved: ResolvedDependencies = services::dependencies::resolve_dependencies(
+        body.version_ids,
+        body.hashes,
+        &body.algorithm,
+        &body.loaders,
+        &body.game_versions,
+        body.include_optional,
+        &user_option,
+        &pool,
+    )
+    .await?;
+
+    Ok(HttpResponse::Ok().json(resolved))
+}
+
+#[derive(Deserialize, Validate, ToSchema)]
+pub struct ExportModpack {
+    /// The IDs of the versions whose primary files the pack installs
+    #[validate(length(min = 1, max = 256))]
+    pub version_ids: Vec<VersionId>,
+    /// Whether to also add the required dependencies of the versions, resolved as by
+    /// `POST /v2/versions/resolve`
+    #[serde(default)]
+    pub resolve_dependencies: bool,
+    /// The name of the pack
+    #[validate(length(min = 1, max = 512))]
+    pub name: String,
+    /// The version number of the pack
+    #[validate(length(min = 1, max = 512))]
+    pub version_number: String,
+    /// A short description of the pack
+    #[validate(length(max = 2048))]
+    pub summary: Option<String>,
+    /// The game version the pack is for
+    pub game_version: GameVersion,
+    /// The loader the pack is for
+    pub loader: Loader,
+    /// The version of the loader. Required for the forge, fabric and quilt loaders.
+    #[validate(length(min = 1, max = 256))]
+    pub loader_version: Option<String>,
+}
+
+#[utoipa::path(
+    path = "/v2/versions/mrpack",
+    tag = "versions",
+    security((), ("token" = [])),
+    request_body = ExportModpack,
+    responses(
+        (status = 200, description = "A `.mrpack` file installing the primary file of each version", content_type = "application/x-modrinth-modpack+zip"),
+        (status = 400, description = "Invalid input, a version which does not exist or cannot be added to a pack, or dependencies which could not be resolved", body = ApiError),
+    )
+)]
+#[post("versions/mrpack")]
+pub async fn modpack_export(
+    req: HttpRequest,
+    body: web::Json<ExportModpack>,
+    pool: web::Data<PgPool>,
+    pat_queue: web::Data<Arc<PatQueue>>,
+) -> Result<HttpResponse, ApiError> {
+    let body = body.into_inner();
+    body.validate()
+        .map_err(|err| ApiError::Validation(validation_errors_to_string(err, None)))?;
+
+    let user_option = get_user_from_headers(&req, &**pool, &pat_queue, Scopes::VERSION_READ)
+        .await
+        .ok();
+
+    let pack = services::modpacks::create_pack(
+        body.version_ids,
+        PackOptions {
+            name: body.name,
+            version_number: body.version_number,
+            summary: body.summary,
+            game_version: body.game_version,
+            loader: body.loader,
+            loader_version: body.loader_version,
+            resolve_dependencies: body.resolve_dependencies,
+        },
+        &user_option,
+        &pool,
+    )
+    .await?;
+
+    let file_name = format!("{}-{}.mrpack", pack.name, pack.version_id)
+        .chars()
+        .filter(|x| x.is_ascii_alphanumeric() || matches!(x, '-' | '_' | '.'))
+        .collect::<String>();
+
+    // The archive is written to a temporary file and streamed from there
+    let file = actix_web::web::block(move || {
+        let mut file = services::modpacks::write_mrpack(
+            &pack,
+            tempfile::tempfile().map_err(FileHostingError::from)?,
+        )?;
+        file.rewind().map_err(FileHostingError::from)?;
+
+        Ok::<_, ApiError>(file)
+    })
+    .await
+    .map_err(|_| ApiError::InvalidInput("Unable to write the pack".to_string()))??;
+
+    let mut response = NamedFile::from_file(file, &file_name)
+        .map_err(FileHostingError::from)?
+        .use_etag(false)
+        .use_last_modified(false)
+        .set_content_disposition(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename(file_name)],
+        })
+        .into_response(&req);
+    response
+        .headers_mut()
+        .insert(CONTENT_TYPE, HeaderValue::from_static(MRPACK_CONTENT_TYPE));
+
+    Ok(response)
+}
+
+#[utoipa::path(
+    path = "/v2/version/{version_id}",
+    tag = "versions",
+    security((), ("token" = [])),
+    params(("version_id" = VersionId, Path, description = "The ID of the version")),
+    responses(
+        (status = 200, description = "The version", body = Version),
+        (status = 404, description = "The version does not exist or is not visible"),
+    )
+)]
 #[get("{version_id}")]
 pub async fn version_get(
     req: HttpRequest,
     info: web::Path<(models::ids::VersionId,)>,
     pool: web::Data<PgPool>,
+    pat_queue: web::Data<Arc<PatQueue>>,
 ) -> Result<HttpResponse, ApiError> {
     let id = info.into_inner().0;
-    let version_data = database::models::Version::get_full(id.into(), &**pool).await?;
 
-    let user_option = get_user_from_headers(req.headers(), &**pool).await.ok();
+    let user_option = get_user_from_headers(&req, &**pool, &pat_queue, Scopes::VERSION_READ)
+        .await
+        .ok();
 
-    if let Some(data) = version_data {
-        if is_authorized_version(&data.inner, &user_option, &pool).await? {
-            return Ok(HttpResponse::Ok().json(models::projects::Version::from(data)));
-        }
+    if let Some(version) = services::versions::get_version(id, &user_option, &pool).await? {
+        Ok(HttpResponse::Ok().json(version))
+    } else {
+        Ok(HttpResponse::NotFound().body(""))
     }
-
-    Ok(HttpResponse::NotFound().body(""))
 }
 
-#[derive(Serialize, Deserialize, Validate)]
+#[derive(Serialize, Deserialize, Validate, ToSchema)]
 pub struct EditVersion {
     #[validate(
         length(min = 1, max = 64),
@@ -213,14 +457,14 @@ pub struct EditVersion {
     pub version_number: Option<String>,
     #[validate(length(max = 65536))]
     pub changelog: Option<String>,
-    pub version_type: Option<models::projects::VersionType>,
+    pub version_type: Option<VersionType>,
     #[validate(
         length(min = 0, max = 4096),
         custom(function = "crate::util::validate::validate_deps")
     )]
     pub dependencies: Option<Vec<Dependency>>,
-    pub game_versions: Option<Vec<models::projects::GameVersion>>,
-    pub loaders: Option<Vec<models::projects::Loader>>,
+    pub game_versions: Option<Vec<GameVersion>>,
+    pub loaders: Option<Vec<Loader>>,
     pub featured: Option<bool>,
     pub primary_file: Option<(String, String)>,
     pub downloads: Option<u32>,
@@ -228,21 +472,35 @@ pub struct EditVersion {
     pub file_types: Option<Vec<EditVersionFileType>>,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, ToSchema)]
 pub struct EditVersionFileType {
     pub algorithm: String,
     pub hash: String,
     pub file_type: Option<FileType>,
 }
 
+#[utoipa::path(
+    path = "/v2/version/{id}",
+    tag = "versions",
+    security(("token" = [])),
+    params(("id" = VersionId, Path, description = "The ID of the version")),
+    request_body = EditVersion,
+    responses(
+        (status = 204, description = "The version was edited"),
+        (status = 400, description = "Invalid input", body = ApiError),
+        (status = 401, description = "Missing authentication, or not permitted to edit the version", body = ApiError),
+        (status = 404, description = "The version does not exist"),
+    )
+)]
 #[patch("{id}")]
 pub async fn version_edit(
     req: HttpRequest,
     info: web::Path<(models::ids::VersionId,)>,
     pool: web::Data<PgPool>,
     new_version: web::Json<EditVersion>,
+    pat_queue: web::Data<Arc<PatQueue>>,
 ) -> Result<HttpResponse, ApiError> {
-    let user = get_user_from_headers(req.headers(), &**pool).await?;
+    let user = get_user_from_headers(&req, &**pool, &pat_queue, Scopes::VERSION_WRITE).await?;
 
     new_version
         .validate()
@@ -548,6 +806,13 @@ pub async fn version_edit(
                     ));
                 }
 
+                if version_item.inner.status == VersionStatus::Withheld && !user.role.is_mod() {
+                    return Err(ApiError::CustomAuthentication(
+                        "This version was withheld and must be reviewed by a moderator!"
+                            .to_string(),
+                    ));
+                }
+
                 sqlx::query!(
                     "
                     UPDATE versions
@@ -607,20 +872,34 @@ pub async fn version_edit(
     }
 }
 
-#[derive(Deserialize)]
-pub struct SchedulingData {
+#[derive(Deserialize, ToSchema)]
+pub struct VersionSchedulingData {
     pub time: DateTime<Utc>,
     pub requested_status: VersionStatus,
 }
 
+#[utoipa::path(
+    path = "/v2/version/{id}/schedule",
+    tag = "versions",
+    security(("token" = [])),
+    params(("id" = VersionId, Path, description = "The ID of the version")),
+    request_body = VersionSchedulingData,
+    responses(
+        (status = 204, description = "The version was scheduled"),
+        (status = 400, description = "Invalid time or status", body = ApiError),
+        (status = 401, description = "Missing authentication, or not permitted to edit the version", body = ApiError),
+        (status = 404, description = "The version does not exist"),
+    )
+)]
 #[post("{id}/schedule")]
 pub async fn version_schedule(
     req: HttpRequest,
     info: web::Path<(models::ids::VersionId,)>,
     pool: web::Data<PgPool>,
-    scheduling_data: web::Json<SchedulingData>,
+    scheduling_data: web::Json<VersionSchedulingData>,
+    pat_queue: web::Data<Arc<PatQueue>>,
 ) -> Result<HttpResponse, ApiError> {
-    let user = get_user_from_headers(req.headers(), &**pool).await?;
+    let user = get_user_from_headers(&req, &**pool, &pat_queue, Scopes::VERSION_WRITE).await?;
 
     if scheduling_data.time < Utc::now() {
         return Err(ApiError::InvalidInput(
@@ -655,6 +934,12 @@ pub async fn version_schedule(
             ));
         }
 
+        if version_item.inner.status == VersionStatus::Withheld && !user.role.is_mod() {
+            return Err(ApiError::CustomAuthentication(
+                "This version was withheld and must be reviewed by a moderator!".to_string(),
+            ));
+        }
+
         sqlx::query!(
             "
             UPDATE versions
@@ -674,13 +959,26 @@ pub async fn version_schedule(
     }
 }
 
+#[utoipa::path(
+    path = "/v2/version/{version_id}",
+    tag = "versions",
+    security(("token" = [])),
+    params(("version_id" = VersionId, Path, description = "The ID of the version")),
+    responses(
+        (status = 204, description = "The version was deleted"),
+        (status = 401, description = "Missing authentication, or not permitted to delete the version", body = ApiError),
+        (status = 404, description = "The version does not exist"),
+    )
+)]
 #[delete("{version_id}")]
 pub async fn version_delete(
     req: HttpRequest,
     info: web::Path<(models::ids::VersionId,)>,
     pool: web::Data<PgPool>,
+    pat_queue: web::Data<Arc<PatQueue>>,
+    file_host: web::Data<Arc<dyn FileHost + Send + Sync>>,
 ) -> Result<HttpResponse, ApiError> {
-    let user = get_user_from_headers(req.headers(), &**pool).await?;
+    let user = get_user_from_headers(&req, &**pool, &pat_queue, Scopes::VERSION_DELETE).await?;
     let id = info.into_inner().0;
 
     if !user.role.is_admin() {
@@ -713,6 +1011,8 @@ pub async fn version_delete(
 
     transaction.commit().await?;
 
+    delete_released_blobs(&pool, &***file_host).await?;
+
     if result.is_some() {
         Ok(HttpResponse::NoContent().body(""))
     } else {
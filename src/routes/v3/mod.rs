@@ -1,9 +1,37 @@
+mod notifications;
+mod projects;
+mod teams;
+mod users;
+mod versions;
+
 pub use super::ApiError;
 use actix_web::{web, HttpResponse};
+use serde::Deserialize;
 use serde_json::json;
 
 pub fn config(cfg: &mut web::ServiceConfig) {
-    cfg.service(web::scope("v3").route("", web::get().to(hello_world)));
+    cfg.service(
+        web::scope("v3")
+            // Malformed requests are reported in the same shape as every other error
+            .app_data(
+                web::QueryConfig::default()
+                    .error_handler(|err, _| ApiError::InvalidInput(err.to_string()).into()),
+            )
+            .app_data(
+                web::PathConfig::default()
+                    .error_handler(|err, _| ApiError::InvalidInput(err.to_string()).into()),
+            )
+            .app_data(
+                web::JsonConfig::default()
+                    .error_handler(|err, _| ApiError::InvalidInput(err.to_string()).into()),
+            )
+            .route("", web::get().to(hello_world))
+            .configure(notifications::config)
+            .configure(projects::config)
+            .configure(teams::config)
+            .configure(users::config)
+            .configure(versions::config),
+    );
 }
 
 pub async fn hello_world() -> Result<HttpResponse, ApiError> {
@@ -11,3 +39,9 @@ pub async fn hello_world() -> Result<HttpResponse, ApiError> {
         "hello": "world",
     })))
 }
+
+/// The query of bulk endpoints: a JSON array of IDs, for example `?ids=["AABBCCDD"]`
+#[derive(Deserialize)]
+pub struct Ids {
+    pub ids: String,
+}
@@ -0,0 +1,35 @@
+use super::{ApiError, Ids};
+use crate::models::ids::NotificationId;
+use crate::models::pats::Scopes;
+use crate::models::v3::Notification;
+use crate::queue::pats::PatQueue;
+use crate::services;
+use crate::util::auth::get_user_from_headers;
+use actix_web::{get, web, HttpRequest, HttpResponse};
+use sqlx::PgPool;
+use std::sync::Arc;
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(notifications_get);
+}
+
+#[get("notifications")]
+pub async fn notifications_get(
+    req: HttpRequest,
+    web::Query(ids): web::Query<Ids>,
+    pool: web::Data<PgPool>,
+    pat_queue: web::Data<Arc<PatQueue>>,
+) -> Result<HttpResponse, ApiError> {
+    let user = get_user_from_headers(&req, &**pool, &pat_queue, Scopes::NOTIFICATION_READ).await?;
+
+    let notification_ids = serde_json::from_str::<Vec<NotificationId>>(&ids.ids)?;
+
+    let notifications: Vec<Notification> =
+        services::notifications::get_notifications(notification_ids, &user, &pool)
+            .await?
+            .into_iter()
+            .map(Notification::from)
+            .collect();
+
+    Ok(HttpResponse::Ok().json(notifications))
+}
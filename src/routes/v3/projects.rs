@@ -0,0 +1,57 @@
+use super::{ApiError, Ids};
+use crate::models::ids::ProjectId;
+use crate::models::pats::Scopes;
+use crate::models::v3::Project;
+use crate::queue::pats::PatQueue;
+use crate::services;
+use crate::util::auth::get_user_from_headers;
+use actix_web::{get, web, HttpRequest, HttpResponse};
+use sqlx::PgPool;
+use std::sync::Arc;
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(projects_get);
+    cfg.service(project_get);
+}
+
+#[get("projects")]
+pub async fn projects_get(
+    req: HttpRequest,
+    web::Query(ids): web::Query<Ids>,
+    pool: web::Data<PgPool>,
+    pat_queue: web::Data<Arc<PatQueue>>,
+) -> Result<HttpResponse, ApiError> {
+    let project_ids = serde_json::from_str::<Vec<ProjectId>>(&ids.ids)?;
+
+    let user_option = get_user_from_headers(&req, &**pool, &pat_queue, Scopes::PROJECT_READ)
+        .await
+        .ok();
+
+    let projects: Vec<Project> = services::projects::get_projects(project_ids, &user_option, &pool)
+        .await?
+        .into_iter()
+        .map(Project::from)
+        .collect();
+
+    Ok(HttpResponse::Ok().json(projects))
+}
+
+#[get("project/{id}")]
+pub async fn project_get(
+    req: HttpRequest,
+    info: web::Path<(String,)>,
+    pool: web::Data<PgPool>,
+    pat_queue: web::Data<Arc<PatQueue>>,
+) -> Result<HttpResponse, ApiError> {
+    let string = info.into_inner().0;
+
+    let user_option = get_user_from_headers(&req, &**pool, &pat_queue, Scopes::PROJECT_READ)
+        .await
+        .ok();
+
+    let project = services::projects::get_project(&string, &user_option, &pool)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+
+    Ok(HttpResponse::Ok().json(Project::from(project)))
+}
@@ -0,0 +1,50 @@
+use super::{ApiError, Ids};
+use crate::models::pats::Scopes;
+use crate::models::teams::TeamId;
+use crate::queue::pats::PatQueue;
+use crate::services;
+use crate::util::auth::get_user_from_headers;
+use actix_web::{get, web, HttpRequest, HttpResponse};
+use sqlx::PgPool;
+use std::sync::Arc;
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(teams_get);
+    cfg.service(team_members_get);
+}
+
+#[get("teams")]
+pub async fn teams_get(
+    req: HttpRequest,
+    web::Query(ids): web::Query<Ids>,
+    pool: web::Data<PgPool>,
+    pat_queue: web::Data<Arc<PatQueue>>,
+) -> Result<HttpResponse, ApiError> {
+    let team_ids = serde_json::from_str::<Vec<TeamId>>(&ids.ids)?;
+
+    let user_option = get_user_from_headers(&req, &**pool, &pat_queue, Scopes::PROJECT_READ)
+        .await
+        .ok();
+
+    let teams = services::teams::get_teams(team_ids, &user_option, &pool).await?;
+
+    Ok(HttpResponse::Ok().json(teams))
+}
+
+#[get("team/{id}/members")]
+pub async fn team_members_get(
+    req: HttpRequest,
+    info: web::Path<(TeamId,)>,
+    pool: web::Data<PgPool>,
+    pat_queue: web::Data<Arc<PatQueue>>,
+) -> Result<HttpResponse, ApiError> {
+    let id = info.into_inner().0;
+
+    let user_option = get_user_from_headers(&req, &**pool, &pat_queue, Scopes::PROJECT_READ)
+        .await
+        .ok();
+
+    let members = services::teams::get_team_members(id, &user_option, &pool).await?;
+
+    Ok(HttpResponse::Ok().json(members))
+}
@@ -0,0 +1,94 @@
+use super::{ApiError, Ids};
+use crate::models::pats::Scopes;
+use crate::models::users::UserId;
+use crate::models::v3::{Notification, Project};
+use crate::queue::pats::PatQueue;
+use crate::services;
+use crate::util::auth::get_user_from_headers;
+use crate::util::pagination::Pagination;
+use actix_web::{get, web, HttpRequest, HttpResponse};
+use sqlx::PgPool;
+use std::sync::Arc;
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(users_get);
+    cfg.service(user_get);
+    cfg.service(user_projects_get);
+    cfg.service(user_notifications_get);
+}
+
+#[get("users")]
+pub async fn users_get(
+    web::Query(ids): web::Query<Ids>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, ApiError> {
+    let user_ids = serde_json::from_str::<Vec<UserId>>(&ids.ids)?;
+
+    let users = services::users::get_users(user_ids, &pool).await?;
+
+    Ok(HttpResponse::Ok().json(users))
+}
+
+#[get("user/{id}")]
+pub async fn user_get(
+    info: web::Path<(String,)>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, ApiError> {
+    let string = info.into_inner().0;
+
+    let user = services::users::get_user(&string, &pool)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+
+    Ok(HttpResponse::Ok().json(user))
+}
+
+#[get("user/{id}/projects")]
+pub async fn user_projects_get(
+    req: HttpRequest,
+    info: web::Path<(String,)>,
+    pagination: Pagination,
+    pool: web::Data<PgPool>,
+    pat_queue: web::Data<Arc<PatQueue>>,
+) -> Result<HttpResponse, ApiError> {
+    let user_option = get_user_from_headers(&req, &**pool, &pat_queue, Scopes::PROJECT_READ)
+        .await
+        .ok();
+
+    let (projects, next) = services::projects::get_user_projects(
+        &info.into_inner().0,
+        &user_option,
+        &pool,
+        Some(&pagination),
+    )
+    .await?
+    .ok_or(ApiError::NotFound)?;
+
+    Ok(HttpResponse::Ok()
+        .json(pagination.page(projects.into_iter().map(Project::from).collect(), next)))
+}
+
+#[get("user/{id}/notifications")]
+pub async fn user_notifications_get(
+    req: HttpRequest,
+    info: web::Path<(String,)>,
+    pagination: Pagination,
+    pool: web::Data<PgPool>,
+    pat_queue: web::Data<Arc<PatQueue>>,
+) -> Result<HttpResponse, ApiError> {
+    let user = get_user_from_headers(&req, &**pool, &pat_queue, Scopes::NOTIFICATION_READ).await?;
+
+    let (notifications, next) = services::notifications::get_user_notifications(
+        &info.into_inner().0,
+        &user,
+        &pool,
+        Some(&pagination),
+    )
+    .await?
+    .ok_or(ApiError::NotFound)?;
+
+    Ok(HttpResponse::Ok().json(pagination.page(
+        notifications.into_iter().map(Notification::from).collect(),
+        next,
+    )))
+}
@@ -0,0 +1,82 @@
+use super::{ApiError, Ids};
+use crate::models::ids::VersionId;
+use crate::models::pats::Scopes;
+use crate::models::v3::Version;
+use crate::queue::pats::PatQueue;
+use crate::services;
+use crate::util::auth::get_user_from_headers;
+use crate::util::pagination::Pagination;
+use actix_web::{get, web, HttpRequest, HttpResponse};
+use sqlx::PgPool;
+use std::sync::Arc;
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(versions_get);
+    cfg.service(version_get);
+    cfg.service(project_versions_get);
+}
+
+#[get("versions")]
+pub async fn versions_get(
+    req: HttpRequest,
+    web::Query(ids): web::Query<Ids>,
+    pool: web::Data<PgPool>,
+    pat_queue: web::Data<Arc<PatQueue>>,
+) -> Result<HttpResponse, ApiError> {
+    let version_ids = serde_json::from_str::<Vec<VersionId>>(&ids.ids)?;
+
+    let user_option = get_user_from_headers(&req, &**pool, &pat_queue, Scopes::VERSION_READ)
+        .await
+        .ok();
+
+    let versions: Vec<Version> = services::versions::get_versions(version_ids, &user_option, &pool)
+        .await?
+        .into_iter()
+        .map(Version::from)
+        .collect();
+
+    Ok(HttpResponse::Ok().json(versions))
+}
+
+#[get("version/{id}")]
+pub async fn version_get(
+    req: HttpRequest,
+    info: web::Path<(VersionId,)>,
+    pool: web::Data<PgPool>,
+    pat_queue: web::Data<Arc<PatQueue>>,
+) -> Result<HttpResponse, ApiError> {
+    let id = info.into_inner().0;
+
+    let user_option = get_user_from_headers(&req, &**pool, &pat_queue, Scopes::VERSION_READ)
+        .await
+        .ok();
+
+    let version = services::versions::get_version(id, &user_option, &pool)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+
+    Ok(HttpResponse::Ok().json(Version::from(version)))
+}
+
+#[get("project/{id}/versions")]
+pub async fn project_versions_get(
+    req: HttpRequest,
+    info: web::Path<(String,)>,
+    pagination: Pagination,
+    pool: web::Data<PgPool>,
+    pat_queue: web::Data<Arc<PatQueue>>,
+) -> Result<HttpResponse, ApiError> {
+    let string = info.into_inner().0;
+
+    let user_option = get_user_from_headers(&req, &**pool, &pat_queue, Scopes::VERSION_READ)
+        .await
+        .ok();
+
+    let (versions, next) =
+        services::versions::get_project_versions(&string, &user_option, &pool, &pagination)
+            .await?
+            .ok_or(ApiError::NotFound)?;
+
+    Ok(HttpResponse::Ok()
+        .json(pagination.page(versions.into_iter().map(Version::from).collect(), next)))
+}
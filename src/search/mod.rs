@@ -9,6 +9,7 @@ use std::borrow::Cow;
 use std::cmp::min;
 use std::fmt::Write;
 use thiserror::Error;
+use utoipa::ToSchema;
 
 pub mod indexing;
 
@@ -103,7 +104,7 @@ pub struct UploadSearchProject {
     pub dependencies: Vec<String>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
 pub struct SearchResults {
     pub hits: Vec<ResultSearchProject>,
     pub offset: usize,
@@ -111,7 +112,7 @@ pub struct SearchResults {
     pub total_hits: usize,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
 pub struct ResultSearchProject {
     pub project_id: String,
     pub project_type: String,
@@ -0,0 +1,697 @@
+use crate::database;
+use crate::models::ids::{ProjectId, VersionId};
+use crate::models::projects::{Dependency, DependencyType, GameVersion, Loader, Version};
+use crate::models::users::User;
+use crate::routes::ApiError;
+use crate::util::auth::{filter_authorized_versions, is_authorized};
+use actix_web::web;
+use serde::Serialize;
+use sqlx::PgPool;
+use std::collections::{HashMap, HashSet};
+use utoipa::ToSchema;
+
+/// The largest number of versions a resolution may contain, to bound the work done for a request
+pub const MAX_RESOLVED_VERSIONS: usize = 1024;
+
+/// A set of versions to install, resolved from the dependencies of the requested versions
+#[derive(Serialize, ToSchema)]
+pub struct ResolvedDependencies {
+    /// The versions to install, including the requested ones. Each project appears once.
+    pub versions: Vec<ResolvedVersion>,
+    /// Dependencies which could not be added to the install set
+    pub unresolved: Vec<UnresolvedDependency>,
+    /// Versions of the install set which cannot be installed together
+    pub conflicts: Vec<DependencyConflict>,
+    /// Dependency cycles, as the versions along each cycle. Cycles do not prevent resolution.
+    pub cycles: Vec<Vec<VersionId>>,
+    /// The requested file hashes which do not belong to a visible version
+    pub unknown_hashes: Vec<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ResolvedVersion {
+    pub version: Version,
+    pub reason: ResolutionReason,
+}
+
+/// Why a version is part of the install set
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, Debug, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ResolutionReason {
+    /// The version was requested by its ID or the hash of one of its files
+    Requested,
+    /// The version is a dependency of another version of the install set
+    Dependency {
+        required_by: VersionId,
+        dependency_type: DependencyType,
+    },
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct UnresolvedDependency {
+    /// The version declaring the dependency, or None for a requested version
+    pub required_by: Option<VersionId>,
+    pub dependency: Dependency,
+    pub reason: UnresolvedReason,
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, Debug, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum UnresolvedReason {
+    /// The version or project does not exist, or is not visible to the user
+    NotFound,
+    /// The project has no version for the given loaders and game versions
+    NoCompatibleVersion,
+    /// The version does not support the given loaders or game versions
+    IncompatibleVersion,
+    /// The dependency is a file hosted outside of Modrinth, which only has a file name
+    External,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct DependencyConflict {
+    /// The version declaring the dependency
+    pub version_id: VersionId,
+    /// The version of the install set the dependency conflicts with
+    pub conflicting_version_id: VersionId,
+    pub reason: ConflictReason,
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, Debug, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictReason {
+    /// The version is marked as incompatible with the conflicting version or its project
+    Incompatible,
+    /// The version requires a different version of the conflicting version's project
+    DifferentVersionRequired,
+}
+
+/// Resolves the install set of the given versions: their dependencies are walked transitively,
+/// picking the newest version compatible with the loaders and game versions for dependencies on a
+/// project. The first version added for a project wins, so requested versions take precedence over
+/// dependencies, and closer dependencies over farther ones. Optional dependencies are only
+/// followed if `include_optional` is set.
+#[allow(clippy::too_many_arguments)]
+pub async fn resolve_dependencies(
+    version_ids: Vec<VersionId>,
+    hashes: Vec<String>,
+    algorithm: &str,
+    loaders: &[Loader],
+    game_versions: &[GameVersion],
+    include_optional: bool,
+    user_option: &Option<User>,
+    pool: &web::Data<PgPool>,
+) -> Result<ResolvedDependencies, ApiError> {
+    let mut resolution = Resolution::new(include_optional);
+
+    let (hash_versions, unknown_hashes) = get_versions_from_hashes(hashes, algorithm, pool).await?;
+
+    let mut requested = version_ids;
+    for version_id in hash_versions {
+        if !requested.contains(&version_id) {
+            requested.push(version_id);
+        }
+    }
+
+    let versions = get_versions(&requested, user_option, pool).await?;
+    for version_id in requested {
+        let reason = match versions.get(&version_id) {
+            Some(version) if is_compatible(version, loaders, game_versions) => {
+                resolution.add(version.clone(), ResolutionReason::Requested);
+                continue;
+            }
+            Some(_) => UnresolvedReason::IncompatibleVersion,
+            None => UnresolvedReason::NotFound,
+        };
+
+        resolution.unresolved.push(UnresolvedDependency {
+            required_by: None,
+            dependency: Dependency {
+                version_id: Some(version_id),
+                project_id: None,
+                file_name: None,
+                dependency_type: DependencyType::Required,
+            },
+            reason,
+        });
+    }
+
+    while !resolution.frontier.is_empty() {
+        if resolution.versions.len() > MAX_RESOLVED_VERSIONS {
+            return Err(ApiError::InvalidInput(format!(
+                "The dependency graph has more than {MAX_RESOLVED_VERSIONS} versions"
+            )));
+        }
+
+        let pending = resolution.take_frontier();
+
+        // Dependencies on a specific version are added first, so that dependencies on their
+        // project resolve to them
+        let pinned_ids = pending
+            .iter()
+            .filter_map(|(_, dependency)| dependency.version_id)
+            .collect::<Vec<_>>();
+        let pinned = get_versions(&pinned_ids, user_option, pool).await?;
+
+        for (required_by, dependency) in pending
+            .iter()
+            .filter(|(_, dependency)| dependency.version_id.is_some())
+        {
+            match dependency.version_id.and_then(|x| pinned.get(&x)) {
+                Some(version) if is_compatible(version, loaders, game_versions) => {
+                    resolution.add_dependency(version.clone(), *required_by, dependency)
+                }
+                Some(_) => resolution.unresolve(
+                    *required_by,
+                    dependency,
+                    UnresolvedReason::IncompatibleVersion,
+                ),
+                None => resolution.unresolve(*required_by, dependency, UnresolvedReason::NotFound),
+            }
+        }
+
+        let project_dependencies = pending
+            .iter()
+            .filter(|(_, dependency)| dependency.version_id.is_none())
+            .collect::<Vec<_>>();
+        let candidates = get_latest_versions(
+            project_dependencies
+                .iter()
+                .filter_map(|(_, dependency)| dependency.project_id)
+                .filter(|x| !resolution.projects.contains_key(x))
+                .collect(),
+            loaders,
+            game_versions,
+            user_option,
+            pool,
+        )
+        .await?;
+
+        let mut missing = Vec::new();
+        for (required_by, dependency) in project_dependencies {
+            let project_id = match dependency.project_id {
+                Some(project_id) => project_id,
+                None => {
+                    resolution.unresolve(*required_by, dependency, UnresolvedReason::External);
+                    continue;
+                }
+            };
+
+            if let Some(version) = resolution.selected(project_id) {
+                resolution.add_dependency(version.clone(), *required_by, dependency);
+            } else if let Some(version) = candidates.get(&project_id) {
+                resolution.add_dependency(version.clone(), *required_by, dependency);
+            } else {
+                missing.push((*required_by, dependency));
+            }
+        }
+
+        // Projects without a compatible version are only reported as such if they are visible
+        let visible = get_visible_projects(
+            missing
+                .iter()
+                .filter_map(|(_, dependency)| dependency.project_id)
+                .collect(),
+            user_option,
+            pool,
+        )
+        .await?;
+        for (required_by, dependency) in missing {
+            let reason = match dependency.project_id {
+                Some(project_id) if visible.contains(&project_id) => {
+                    UnresolvedReason::NoCompatibleVersion
+                }
+                _ => UnresolvedReason::NotFound,
+            };
+            resolution.unresolve(required_by, dependency, reason);
+        }
+    }
+
+    Ok(resolution.finish(unknown_hashes))
+}
+
+/// Whether the version supports one of the loaders and one of the game versions. An empty list
+/// matches any loader or game version.
+pub fn is_compatible(version: &Version, loaders: &[Loader], game_versions: &[GameVersion]) -> bool {
+    (loaders.is_empty()
+        || version
+            .loaders
+            .iter()
+            .any(|x| loaders.iter().any(|y| x.0 == y.0)))
+        && (game_versions.is_empty()
+            || version
+                .game_versions
+                .iter()
+                .any(|x| game_versions.contains(x)))
+}
+
+async fn get_visible_projects(
+    project_ids: Vec<ProjectId>,
+    user_option: &Option<User>,
+    pool: &web::Data<PgPool>,
+) -> Result<HashSet<ProjectId>, ApiError> {
+    if project_ids.is_empty() {
+        return Ok(HashSet::new());
+    }
+
+    let project_ids = project_ids
+        .into_iter()
+        .map(|x| x.into())
+        .collect::<Vec<database::models::ProjectId>>();
+    let projects = database::models::Project::get_many(&project_ids, &***pool).await?;
+
+    let mut visible = HashSet::new();
+    for project in projects {
+        if is_authorized(&project, user_option, pool).await? {
+            visible.insert(project.id.into());
+        }
+    }
+
+    Ok(visible)
+}
+
+async fn get_versions(
+    version_ids: &[VersionId],
+    user_option: &Option<User>,
+    pool: &web::Data<PgPool>,
+) -> Result<HashMap<VersionId, Version>, ApiError> {
+    if version_ids.is_empty() {
+        return Ok(HashMap::new());
+    }
+
+    let version_ids = version_ids
+        .iter()
+        .map(|x| (*x).into())
+        .collect::<Vec<database::models::VersionId>>();
+    let versions = database::models::Version::get_many_full(&version_ids, &***pool).await?;
+
+    Ok(filter_authorized_versions(versions, user_option, pool)
+        .await?
+        .into_iter()
+        .map(|x| (x.id, x))
+        .collect())
+}
+
+// Returns the newest visible version of each project matching the loaders and game versions
+async fn get_latest_versions(
+    project_ids: Vec<ProjectId>,
+    loaders: &[Loader],
+    game_versions: &[GameVersion],
+    user_option: &Option<User>,
+    pool: &web::Data<PgPool>,
+) -> Result<HashMap<ProjectId, Version>, ApiError> {
+    if project_ids.is_empty() {
+        return Ok(HashMap::new());
+    }
+
+    let version_ids = database::models::Version::get_projects_versions(
+        project_ids.into_iter().map(|x| x.into()).collect(),
+        Some(game_versions.iter().map(|x| x.0.clone()).collect()),
+        Some(loaders.iter().map(|x| x.0.clone()).collect()),
+        None,
+        None,
+        None,
+        &***pool,
+    )
+    .await?
+    .into_values()
+    .flatten()
+    .collect::<Vec<_>>();
+
+    let versions = database::models::Version::get_many_full(&version_ids, &***pool).await?;
+
+    let mut latest: HashMap<ProjectId, Version> = HashMap::new();
+    for version in filter_authorized_versions(versions, user_option, pool).await? {
+        match latest.get(&version.project_id) {
+            Some(existing) if existing.date_published >= version.date_published => {}
+            _ => {
+                latest.insert(version.project_id, version);
+            }
+        }
+    }
+
+    Ok(latest)
+}
+
+async fn get_versions_from_hashes(
+    hashes: Vec<String>,
+    algorithm: &str,
+    pool: &web::Data<PgPool>,
+) -> Result<(Vec<VersionId>, Vec<String>), ApiError> {
+    if hashes.is_empty() {
+        return Ok((Vec::new(), Vec::new()));
+    }
+
+    let hashes = hashes
+        .into_iter()
+        .map(|x| x.to_lowercase())
+        .collect::<Vec<_>>();
+
+    let rows = sqlx::query!(
+        "
+        SELECT h.hash, f.version_id FROM hashes h
+        INNER JOIN files f ON h.file_id = f.id
+        INNER JOIN versions v ON v.id = f.version_id AND v.status != ANY($1)
+        INNER JOIN mods m on v.mod_id = m.id
+        WHERE h.algorithm = $3 AND h.hash = ANY($2::bytea[]) AND m.status != ANY($4)
+        ORDER BY v.date_published ASC
+        ",
+        &*crate::models::projects::VersionStatus::iterator()
+            .filter(|x| x.is_hidden())
+            .map(|x| x.to_string())
+            .collect::<Vec<String>>(),
+        &hashes
+            .iter()
+            .map(|x| x.as_bytes().to_vec())
+            .collect::<Vec<_>>(),
+        algorithm,
+        &*crate::models::projects::ProjectStatus::iterator()
+            .filter(|x| x.is_hidden())
+            .map(|x| x.to_string())
+            .collect::<Vec<String>>(),
+    )
+    .fetch_all(&***pool)
+    .await?;
+
+    // A file may be part of several versions, in which case the oldest is used
+    let mut versions = Vec::new();
+    let mut unknown = Vec::new();
+    for hash in hashes {
+        match rows.iter().find(|x| x.hash == hash.as_bytes()) {
+            Some(row) => versions.push(database::models::VersionId(row.version_id).into()),
+            None => unknown.push(hash),
+        }
+    }
+
+    Ok((versions, unknown))
+}
+
+/// The state of a resolution, independent of how versions are looked up
+struct Resolution {
+    include_optional: bool,
+    versions: Vec<ResolvedVersion>,
+    // The index in `versions` of the version of each project
+    projects: HashMap<ProjectId, usize>,
+    // The versions added since the last call to `take_frontier`
+    frontier: Vec<usize>,
+    incompatibilities: Vec<(VersionId, Dependency)>,
+    unresolved: Vec<UnresolvedDependency>,
+    conflicts: Vec<DependencyConflict>,
+    cycles: Vec<Vec<VersionId>>,
+}
+
+impl Resolution {
+    fn new(include_optional: bool) -> Self {
+        Resolution {
+            include_optional,
+            versions: Vec::new(),
+            projects: HashMap::new(),
+            frontier: Vec::new(),
+            incompatibilities: Vec::new(),
+            unresolved: Vec::new(),
+            conflicts: Vec::new(),
+            cycles: Vec::new(),
+        }
+    }
+
+    fn selected(&self, project_id: ProjectId) -> Option<&Version> {
+        self.projects
+            .get(&project_id)
+            .map(|x| &self.versions[*x].version)
+    }
+
+    // Adds the version unless its project already has one
+    fn add(&mut self, version: Version, reason: ResolutionReason) {
+        if self.projects.contains_key(&version.project_id) {
+            return;
+        }
+
+        self.projects
+            .insert(version.project_id, self.versions.len());
+        self.frontier.push(self.versions.len());
+        self.versions.push(ResolvedVersion { version, reason });
+    }
+
+    // Adds the dependency of `required_by` on `version`, recording a conflict if the version's
+    // project already has another version, and a cycle if `version` depends on `required_by`
+    fn add_dependency(
+        &mut self,
+        version: Version,
+        required_by: VersionId,
+        dependency: &Dependency,
+    ) {
+        let reason = ResolutionReason::Dependency {
+            required_by,
+            dependency_type: dependency.dependency_type,
+        };
+
+        let existing = match self.selected(version.project_id) {
+            Some(existing) => existing.id,
+            None => {
+                self.add(version, reason);
+                return;
+            }
+        };
+
+        if dependency.version_id.is_some() && existing != version.id {
+            self.conflicts.push(DependencyConflict {
+                version_id: required_by,
+                conflicting_version_id: existing,
+                reason: ConflictReason::DifferentVersionRequired,
+            });
+        } else if let Some(cycle) = self.path(existing, required_by) {
+            self.cycles.push(cycle);
+        }
+    }
+
+    fn unresolve(
+        &mut self,
+        required_by: VersionId,
+        dependency: &Dependency,
+        reason: UnresolvedReason,
+    ) {
+        self.unresolved.push(UnresolvedDependency {
+            required_by: Some(required_by),
+            dependency: dependency.clone(),
+            reason,
+        });
+    }
+
+    // The versions from `from` down to `to` along the versions which required them, if `from`
+    // (transitively) required `to`
+    fn path(&self, from: VersionId, to: VersionId) -> Option<Vec<VersionId>> {
+        let mut path = vec![to];
+        let mut current = to;
+
+        // Every version is added after the version which required it, so this terminates
+        while current != from {
+            let version = self.versions.iter().find(|x| x.version.id == current)?;
+
+            current = match version.reason {
+                ResolutionReason::Dependency { required_by, .. } => required_by,
+                ResolutionReason::Requested => return None,
+            };
+            path.push(current);
+        }
+
+        path.reverse();
+        Some(path)
+    }
+
+    // Returns the dependencies of the versions added since the last call which should be
+    // resolved, recording their incompatibilities
+    fn take_frontier(&mut self) -> Vec<(VersionId, Dependency)> {
+        let mut pending = Vec::new();
+
+        for index in std::mem::take(&mut self.frontier) {
+            let version = &self.versions[index].version;
+
+            for dependency in &version.dependencies {
+                match dependency.dependency_type {
+                    DependencyType::Required => {}
+                    DependencyType::Optional if self.include_optional => {}
+                    DependencyType::Incompatible => {
+                        self.incompatibilities
+                            .push((version.id, dependency.clone()));
+                        continue;
+                    }
+                    DependencyType::Optional | DependencyType::Embedded => continue,
+                }
+
+                pending.push((version.id, dependency.clone()));
+            }
+        }
+
+        pending
+    }
+
+    fn finish(mut self, unknown_hashes: Vec<String>) -> ResolvedDependencies {
+        for (version_id, dependency) in std::mem::take(&mut self.incompatibilities) {
+            let conflicting = self.versions.iter().find(|x| {
+                x.version.id != version_id
+                    && (Some(x.version.id) == dependency.version_id
+                        || (dependency.version_id.is_none()
+                            && Some(x.version.project_id) == dependency.project_id))
+            });
+
+            if let Some(conflicting) = conflicting {
+                self.conflicts.push(DependencyConflict {
+                    version_id,
+                    conflicting_version_id: conflicting.version.id,
+                    reason: ConflictReason::Incompatible,
+                });
+            }
+        }
+
+        ResolvedDependencies {
+            versions: self.versions,
+            unresolved: self.unresolved,
+            conflicts: self.conflicts,
+            cycles: self.cycles,
+            unknown_hashes,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::test_util;
+
+    fn version(id: u64, project_id: u64, dependencies: Vec<Dependency>) -> Version {
+        Version {
+            dependencies,
+            ..test_util::version(id, project_id)
+        }
+    }
+
+    fn on_project(project_id: u64, dependency_type: DependencyType) -> Dependency {
+        Dependency {
+            version_id: None,
+            project_id: Some(ProjectId(project_id)),
+            file_name: None,
+            dependency_type,
+        }
+    }
+
+    fn on_version(version_id: u64, dependency_type: DependencyType) -> Dependency {
+        Dependency {
+            version_id: Some(VersionId(version_id)),
+            project_id: None,
+            file_name: None,
+            dependency_type,
+        }
+    }
+
+    #[test]
+    fn frontier_follows_required_dependencies() {
+        let mut resolution = Resolution::new(false);
+        resolution.add(
+            version(
+                1,
+                10,
+                vec![
+                    on_project(20, DependencyType::Required),
+                    on_project(30, DependencyType::Optional),
+                    on_project(40, DependencyType::Embedded),
+                    on_project(50, DependencyType::Incompatible),
+                ],
+            ),
+            ResolutionReason::Requested,
+        );
+
+        let pending = resolution.take_frontier();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].1.project_id, Some(ProjectId(20)));
+        assert!(resolution.take_frontier().is_empty());
+
+        let mut resolution = Resolution::new(true);
+        resolution.add(
+            version(1, 10, vec![on_project(30, DependencyType::Optional)]),
+            ResolutionReason::Requested,
+        );
+        assert_eq!(resolution.take_frontier().len(), 1);
+    }
+
+    #[test]
+    fn first_version_of_a_project_wins() {
+        let mut resolution = Resolution::new(false);
+        resolution.add(version(1, 10, vec![]), ResolutionReason::Requested);
+        resolution.add(version(2, 20, vec![]), ResolutionReason::Requested);
+
+        let dependency = on_version(3, DependencyType::Required);
+        resolution.add_dependency(version(3, 10, vec![]), VersionId(2), &dependency);
+
+        let resolved = resolution.finish(Vec::new());
+        assert_eq!(resolved.versions.len(), 2);
+        assert_eq!(resolved.conflicts.len(), 1);
+        assert_eq!(resolved.conflicts[0].version_id, VersionId(2));
+        assert_eq!(resolved.conflicts[0].conflicting_version_id, VersionId(1));
+        assert_eq!(
+            resolved.conflicts[0].reason,
+            ConflictReason::DifferentVersionRequired
+        );
+    }
+
+    #[test]
+    fn cycles_are_reported() {
+        let mut resolution = Resolution::new(false);
+        resolution.add(
+            version(1, 10, vec![on_project(20, DependencyType::Required)]),
+            ResolutionReason::Requested,
+        );
+        resolution.take_frontier();
+
+        let dependency = on_project(20, DependencyType::Required);
+        resolution.add_dependency(
+            version(2, 20, vec![on_project(10, DependencyType::Required)]),
+            VersionId(1),
+            &dependency,
+        );
+        for (required_by, dependency) in resolution.take_frontier() {
+            let existing = resolution.selected(ProjectId(10)).unwrap().clone();
+            resolution.add_dependency(existing, required_by, &dependency);
+        }
+
+        let resolved = resolution.finish(Vec::new());
+        assert_eq!(resolved.versions.len(), 2);
+        assert_eq!(resolved.cycles, vec![vec![VersionId(1), VersionId(2)]]);
+    }
+
+    #[test]
+    fn incompatible_versions_conflict() {
+        let mut resolution = Resolution::new(false);
+        resolution.add(
+            version(1, 10, vec![on_project(20, DependencyType::Incompatible)]),
+            ResolutionReason::Requested,
+        );
+        resolution.add(version(2, 20, vec![]), ResolutionReason::Requested);
+        resolution.take_frontier();
+
+        let resolved = resolution.finish(Vec::new());
+        assert_eq!(resolved.conflicts.len(), 1);
+        assert_eq!(resolved.conflicts[0].conflicting_version_id, VersionId(2));
+        assert_eq!(resolved.conflicts[0].reason, ConflictReason::Incompatible);
+    }
+    #[test]
+    fn compatibility_checks_loaders_and_game_versions() {
+        let mut fabric = version(1, 10, vec![]);
+        fabric.loaders = vec![Loader("fabric".to_string())];
+        fabric.game_versions = vec![GameVersion("1.20.1".to_string())];
+
+        let loaders = [Loader("fabric".to_string())];
+        let game_versions = [GameVersion("1.20.1".to_string())];
+        assert!(is_compatible(&fabric, &loaders, &game_versions));
+        assert!(is_compatible(&fabric, &[], &[]));
+        assert!(!is_compatible(
+            &fabric,
+            &[Loader("forge".to_string())],
+            &game_versions
+        ));
+        assert!(!is_compatible(
+            &fabric,
+            &loaders,
+            &[GameVersion("1.19.2".to_string())]
+        ));
+    }
+}
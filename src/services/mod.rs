@@ -0,0 +1,41 @@
+//! Data access shared by the API versions. Route handlers authenticate and parse requests, call
+//! into these services and convert the results into the response models of their API version.
+
+pub mod dependencies;
+pub mod modpacks;
+pub mod notifications;
+pub mod projects;
+pub mod teams;
+pub mod users;
+pub mod versions;
+
+#[cfg(test)]
+pub(crate) mod test_util {
+    use crate::models::ids::{ProjectId, VersionId};
+    use crate::models::projects::{Version, VersionStatus, VersionType};
+    use crate::models::users::UserId;
+    use chrono::Utc;
+
+    /// A listed release without files, dependencies, loaders or game versions
+    pub fn version(id: u64, project_id: u64) -> Version {
+        Version {
+            id: VersionId(id),
+            project_id: ProjectId(project_id),
+            author_id: UserId(1),
+            featured: false,
+            name: String::new(),
+            version_number: String::new(),
+            changelog: String::new(),
+            changelog_url: None,
+            date_published: Utc::now(),
+            downloads: 0,
+            version_type: VersionType::Release,
+            status: VersionStatus::Listed,
+            requested_status: None,
+            files: Vec::new(),
+            dependencies: Vec::new(),
+            game_versions: Vec::new(),
+            loaders: Vec::new(),
+        }
+    }
+}
@@ -0,0 +1,558 @@
+use crate::database;
+use crate::models::ids::{ProjectId, VersionId};
+use crate::models::pack::{EnvType, PackDependency, PackFile, PackFileHash, PackFormat};
+use crate::models::projects::{
+    DependencyType, GameVersion, Loader, ProjectStatus, SideType, Version, VersionStatus,
+};
+use crate::models::users::User;
+use crate::routes::ApiError;
+use crate::services;
+use crate::services::dependencies::UnresolvedReason;
+use actix_web::web;
+use serde::Serialize;
+use sqlx::PgPool;
+use std::collections::{HashMap, HashSet};
+use std::io::{Seek, Write};
+use utoipa::ToSchema;
+use zip::write::FileOptions;
+use zip::ZipWriter;
+
+/// The content type of `.mrpack` files
+pub const MRPACK_CONTENT_TYPE: &str = "application/x-modrinth-modpack+zip";
+
+/// The files of an uploaded pack, cross-referenced against the files hosted on Modrinth
+#[derive(Serialize, ToSchema)]
+pub struct ModpackReport {
+    /// The name of the uploaded `.mrpack` file
+    pub file_name: String,
+    pub files: Vec<PackFileReport>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct PackFileReport {
+    /// The path the file is installed to
+    pub path: String,
+    pub status: PackFileStatus,
+    /// The project of the known file one of the hashes matched, if any
+    pub project_id: Option<ProjectId>,
+    /// The version of the known file one of the hashes matched, if any
+    pub version_id: Option<VersionId>,
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, Debug, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PackFileStatus {
+    /// The file is a known file of a version
+    Known,
+    /// Neither hash of the file belongs to a known file
+    Unknown,
+    /// One hash belongs to a known file, but the other hash does not match it
+    HashMismatch,
+    /// The hashes match a known file, but the declared size does not
+    SizeMismatch,
+}
+
+impl PackFileStatus {
+    /// Whether the file can be linked to the version of the known file
+    pub fn is_linked(&self) -> bool {
+        matches!(self, PackFileStatus::Known | PackFileStatus::SizeMismatch)
+    }
+}
+
+// A file hosted on Modrinth which a pack file may refer to
+struct KnownFile {
+    project_id: ProjectId,
+    version_id: VersionId,
+    size: u32,
+    sha1: Option<String>,
+    sha512: Option<String>,
+}
+
+/// Cross-references the files of a pack against the `hashes` table, returning a report of each
+/// file in the order of the pack.
+pub async fn check_pack_files<'a, E>(
+    pack: &PackFormat,
+    exec: E,
+) -> Result<Vec<PackFileReport>, sqlx::Error>
+where
+    E: sqlx::Executor<'a, Database = sqlx::Postgres>,
+{
+    let hashes = |algorithm: PackFileHash| {
+        pack.files
+            .iter()
+            .filter_map(|x| x.hashes.get(&algorithm))
+            .map(|x| x.to_lowercase().into_bytes())
+            .collect::<Vec<_>>()
+    };
+
+    // Files may be part of several versions, in which case the oldest is used. Files of hidden
+    // versions and projects are treated as unknown, so they aren't revealed.
+    let known = sqlx::query!(
+        "
+        SELECT v.mod_id, f.version_id, f.size, sha1.hash \"sha1?\", sha512.hash \"sha512?\"
+        FROM files f
+        INNER JOIN versions v ON v.id = f.version_id
+        INNER JOIN mods m ON m.id = v.mod_id
+        LEFT OUTER JOIN hashes sha1 ON sha1.file_id = f.id AND sha1.algorithm = 'sha1'
+        LEFT OUTER JOIN hashes sha512 ON sha512.file_id = f.id AND sha512.algorithm = 'sha512'
+        WHERE (sha1.hash = ANY($1) OR sha512.hash = ANY($2))
+        AND NOT v.status = ANY($3) AND NOT m.status = ANY($4)
+        ORDER BY v.date_published ASC
+        ",
+        &*hashes(PackFileHash::Sha1),
+        &*hashes(PackFileHash::Sha512),
+        &*VersionStatus::iterator()
+            .filter(|x| x.is_hidden())
+            .map(|x| x.to_string())
+            .collect::<Vec<String>>(),
+        &*ProjectStatus::iterator()
+            .filter(|x| x.is_hidden())
+            .map(|x| x.to_string())
+            .collect::<Vec<String>>(),
+    )
+    .fetch_all(exec)
+    .await?
+    .into_iter()
+    .map(|x| KnownFile {
+        project_id: database::models::ProjectId(x.mod_id).into(),
+        version_id: database::models::VersionId(x.version_id).into(),
+        size: x.size as u32,
+        sha1: x.sha1.and_then(|x| String::from_utf8(x).ok()),
+        sha512: x.sha512.and_then(|x| String::from_utf8(x).ok()),
+    })
+    .collect::<Vec<_>>();
+
+    Ok(pack
+        .files
+        .iter()
+        .map(|x| check_pack_file(x, &known))
+        .collect())
+}
+
+fn check_pack_file(file: &PackFile, known: &[KnownFile]) -> PackFileReport {
+    let sha1 = file
+        .hashes
+        .get(&PackFileHash::Sha1)
+        .map(|x| x.to_lowercase());
+    let sha512 = file
+        .hashes
+        .get(&PackFileHash::Sha512)
+        .map(|x| x.to_lowercase());
+
+    let (status, known_file) =
+        if let Some(known_file) = known.iter().find(|x| x.sha1.is_some() && x.sha1 == sha1) {
+            let status = if known_file.sha512.is_some() && known_file.sha512 != sha512 {
+                PackFileStatus::HashMismatch
+            } else if known_file.size != file.file_size {
+                PackFileStatus::SizeMismatch
+            } else {
+                PackFileStatus::Known
+            };
+
+            (status, Some(known_file))
+        } else if let Some(known_file) = known
+            .iter()
+            .find(|x| x.sha512.is_some() && x.sha512 == sha512)
+        {
+            (PackFileStatus::HashMismatch, Some(known_file))
+        } else {
+            (PackFileStatus::Unknown, None)
+        };
+
+    PackFileReport {
+        path: file.path.clone(),
+        status,
+        project_id: known_file.map(|x| x.project_id),
+        version_id: known_file.map(|x| x.version_id),
+    }
+}
+
+/// The metadata and target of an exported pack
+pub struct PackOptions {
+    pub name: String,
+    pub version_number: String,
+    pub summary: Option<String>,
+    pub game_version: GameVersion,
+    pub loader: Loader,
+    /// The version of the loader, required unless the loader is vanilla
+    pub loader_version: Option<String>,
+    /// Whether to add the required dependencies of the versions to the pack
+    pub resolve_dependencies: bool,
+}
+
+/// Builds the index of a pack installing the primary file of each version. Versions, and
+/// dependencies if they are resolved, must all be visible to the user.
+pub async fn create_pack(
+    version_ids: Vec<VersionId>,
+    options: PackOptions,
+    user_option: &Option<User>,
+    pool: &web::Data<PgPool>,
+) -> Result<PackFormat, ApiError> {
+    let mut dependencies = HashMap::new();
+    dependencies.insert(PackDependency::Minecraft, options.game_version.0.clone());
+    if let Some(loader) = pack_loader(&options.loader.0) {
+        let loader_version = options.loader_version.clone().ok_or_else(|| {
+            ApiError::InvalidInput(format!(
+                "A loader version is required for {}",
+                options.loader.0
+            ))
+        })?;
+        dependencies.insert(loader, loader_version);
+    }
+
+    let versions = if options.resolve_dependencies {
+        let resolved = services::dependencies::resolve_dependencies(
+            version_ids,
+            Vec::new(),
+            "sha1",
+            std::slice::from_ref(&options.loader),
+            std::slice::from_ref(&options.game_version),
+            false,
+            user_option,
+            pool,
+        )
+        .await?;
+
+        if let Some(conflict) = resolved.conflicts.first() {
+            return Err(ApiError::InvalidInput(format!(
+                "Version {} conflicts with version {}",
+                conflict.version_id, conflict.conflicting_version_id
+            )));
+        }
+
+        if let Some(unresolved) = resolved.unresolved.iter().find(|x| {
+            x.reason != UnresolvedReason::External
+                && x.dependency.dependency_type == DependencyType::Required
+        }) {
+            return Err(ApiError::InvalidInput(
+                match (unresolved.required_by, unresolved.dependency.version_id) {
+                    (Some(version_id), _) => format!(
+                        "A required dependency of version {version_id} could not be resolved"
+                    ),
+                    (None, Some(version_id))
+                        if unresolved.reason == UnresolvedReason::IncompatibleVersion =>
+                    {
+                        format!(
+                            "Version {version_id} does not support {} on {}",
+                            options.loader.0, options.game_version.0
+                        )
+                    }
+                    (None, _) => "One or more versions do not exist".to_string(),
+                },
+            ));
+        }
+
+        resolved
+            .versions
+            .into_iter()
+            .map(|x| x.version)
+            .collect::<Vec<_>>()
+    } else {
+        let mut seen = HashSet::new();
+        let mut requested = version_ids;
+        requested.retain(|x| seen.insert(*x));
+
+        let versions =
+            services::versions::get_versions(requested.clone(), user_option, pool).await?;
+
+        if let Some(missing) = requested
+            .iter()
+            .find(|x| !versions.iter().any(|version| version.id == **x))
+        {
+            return Err(ApiError::InvalidInput(format!(
+                "Version {missing} does not exist"
+            )));
+        }
+
+        if let Some(incompatible) = versions.iter().find(|x| {
+            !services::dependencies::is_compatible(
+                x,
+                std::slice::from_ref(&options.loader),
+                std::slice::from_ref(&options.game_version),
+            )
+        }) {
+            return Err(ApiError::InvalidInput(format!(
+                "Version {} does not support {} on {}",
+                incompatible.id, options.loader.0, options.game_version.0
+            )));
+        }
+
+        versions
+    };
+
+    let project_ids = versions
+        .iter()
+        .map(|x| x.project_id.into())
+        .collect::<HashSet<database::models::ProjectId>>()
+        .into_iter()
+        .collect::<Vec<_>>();
+    let projects = database::models::Project::get_many_full(&project_ids, &***pool)
+        .await?
+        .into_iter()
+        .map(|x| (ProjectId::from(x.inner.id), x))
+        .collect::<HashMap<_, _>>();
+
+    let mut paths = HashSet::new();
+    let mut files = Vec::new();
+    for version in &versions {
+        let project = projects
+            .get(&version.project_id)
+            .ok_or(ApiError::NotFound)?;
+
+        let file = pack_file(
+            version,
+            &project.project_type,
+            &project.client_side,
+            &project.server_side,
+        )?;
+
+        if !paths.insert(file.path.clone()) {
+            return Err(ApiError::InvalidInput(format!(
+                "Multiple files would be installed to {}",
+                file.path
+            )));
+        }
+
+        files.push(file);
+    }
+
+    Ok(PackFormat {
+        game: "minecraft".to_string(),
+        format_version: 1,
+        version_id: options.version_number,
+        name: options.name,
+        summary: options.summary,
+        files,
+        dependencies,
+    })
+}
+
+/// Writes the `.mrpack` archive of a pack, which only contains its index, to the writer. The index
+/// is serialized straight into the archive, so it is never held in memory as a whole.
+pub fn write_mrpack<W: Write + Seek>(pack: &PackFormat, writer: W) -> Result<W, ApiError> {
+    let mut zip = ZipWriter::new(writer);
+
+    zip.start_file("modrinth.index.json", FileOptions::default())?;
+    serde_json::to_writer_pretty(&mut zip, pack)?;
+
+    Ok(zip.finish()?)
+}
+
+// The pack dependency of a loader, or None for loaders packs do not declare
+fn pack_loader(loader: &str) -> Option<PackDependency> {
+    match loader {
+        "forge" => Some(PackDependency::Forge),
+        "fabric" => Some(PackDependency::FabricLoader),
+        "quilt" => Some(PackDependency::QuiltLoader),
+        _ => None,
+    }
+}
+
+// The entry of the primary file of a version, installed to the folder of its project type
+fn pack_file(
+    version: &Version,
+    project_type: &str,
+    client_side: &SideType,
+    server_side: &SideType,
+) -> Result<PackFile, ApiError> {
+    let folder = match project_type {
+        "modpack" => {
+            return Err(ApiError::InvalidInput(format!(
+                "Version {} is a modpack and cannot be added to a pack",
+                version.id
+            )))
+        }
+        "resourcepack" => "resourcepacks",
+        "shader" => "shaderpacks",
+        _ => "mods",
+    };
+
+    let file = version
+        .files
+        .iter()
+        .find(|x| x.primary)
+        .or_else(|| version.files.first())
+        .ok_or_else(|| {
+            ApiError::InvalidInput(format!("Version {} does not have any files", version.id))
+        })?;
+
+    if file.filename.contains(['/', '\\']) || file.filename.starts_with('.') {
+        return Err(ApiError::InvalidInput(format!(
+            "The file of version {} has an invalid name",
+            version.id
+        )));
+    }
+
+    let mut hashes = HashMap::new();
+    for (algorithm, hash) in [
+        (PackFileHash::Sha1, "sha1"),
+        (PackFileHash::Sha512, "sha512"),
+    ] {
+        let value = file.hashes.get(hash).ok_or_else(|| {
+            ApiError::InvalidInput(format!(
+                "The file of version {} does not have a {hash} hash",
+                version.id
+            ))
+        })?;
+        hashes.insert(algorithm, value.clone());
+    }
+
+    let env = if *client_side == SideType::Unknown || *server_side == SideType::Unknown {
+        None
+    } else {
+        let mut env = HashMap::new();
+        env.insert(EnvType::Client, client_side.clone());
+        env.insert(EnvType::Server, server_side.clone());
+        Some(env)
+    };
+
+    Ok(PackFile {
+        path: format!("{folder}/{}", file.filename),
+        hashes,
+        env,
+        downloads: vec![file.url.clone()],
+        file_size: file.size,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::projects::VersionFile;
+    use crate::services::test_util;
+    use std::io::{Cursor, Read};
+    use zip::ZipArchive;
+
+    fn version(files: Vec<VersionFile>) -> Version {
+        Version {
+            files,
+            ..test_util::version(1, 1)
+        }
+    }
+
+    fn file(filename: &str, primary: bool) -> VersionFile {
+        let mut hashes = HashMap::new();
+        hashes.insert("sha1".to_string(), format!("{filename}-sha1"));
+        hashes.insert("sha512".to_string(), format!("{filename}-sha512"));
+
+        VersionFile {
+            hashes,
+            url: format!("https://cdn.modrinth.com/data/{filename}"),
+            filename: filename.to_string(),
+            primary,
+            size: 42,
+            file_type: None,
+            findings: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn pack_file_uses_the_primary_file() {
+        let version = version(vec![file("sources.jar", false), file("mod.jar", true)]);
+
+        let entry = pack_file(&version, "mod", &SideType::Required, &SideType::Optional).unwrap();
+
+        assert_eq!(entry.path, "mods/mod.jar");
+        assert_eq!(
+            entry.downloads,
+            vec!["https://cdn.modrinth.com/data/mod.jar"]
+        );
+        assert_eq!(entry.file_size, 42);
+        assert_eq!(
+            entry.hashes.get(&PackFileHash::Sha512).map(|x| &**x),
+            Some("mod.jar-sha512")
+        );
+        let env = entry.env.unwrap();
+        assert_eq!(env.get(&EnvType::Client), Some(&SideType::Required));
+        assert_eq!(env.get(&EnvType::Server), Some(&SideType::Optional));
+
+        let shader =
+            pack_file(&version, "shader", &SideType::Unknown, &SideType::Required).unwrap();
+        assert_eq!(shader.path, "shaderpacks/mod.jar");
+        assert!(shader.env.is_none());
+
+        assert!(pack_file(
+            &version,
+            "modpack",
+            &SideType::Required,
+            &SideType::Required
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn pack_file_requires_both_hashes() {
+        let mut file = file("mod.jar", true);
+        file.hashes.remove("sha512");
+
+        let version = version(vec![file]);
+        assert!(pack_file(&version, "mod", &SideType::Required, &SideType::Required).is_err());
+    }
+
+    #[test]
+    fn pack_files_are_matched_against_known_files() {
+        let version = version(vec![file("mod.jar", true)]);
+        let entry = pack_file(&version, "mod", &SideType::Required, &SideType::Required).unwrap();
+        let known = |sha1: &str, sha512: &str, size| KnownFile {
+            project_id: ProjectId(1),
+            version_id: VersionId(2),
+            size,
+            sha1: Some(sha1.to_string()),
+            sha512: Some(sha512.to_string()),
+        };
+
+        let report = check_pack_file(&entry, &[known("mod.jar-sha1", "mod.jar-sha512", 42)]);
+        assert_eq!(report.status, PackFileStatus::Known);
+        assert_eq!(report.version_id, Some(VersionId(2)));
+
+        let report = check_pack_file(&entry, &[known("mod.jar-sha1", "mod.jar-sha512", 7)]);
+        assert_eq!(report.status, PackFileStatus::SizeMismatch);
+        assert!(report.status.is_linked());
+
+        let report = check_pack_file(&entry, &[known("mod.jar-sha1", "other", 42)]);
+        assert_eq!(report.status, PackFileStatus::HashMismatch);
+        assert!(!report.status.is_linked());
+
+        let report = check_pack_file(&entry, &[known("other", "mod.jar-sha512", 42)]);
+        assert_eq!(report.status, PackFileStatus::HashMismatch);
+        assert_eq!(report.project_id, Some(ProjectId(1)));
+
+        let report = check_pack_file(&entry, &[known("other", "other", 42)]);
+        assert_eq!(report.status, PackFileStatus::Unknown);
+        assert_eq!(report.version_id, None);
+    }
+
+    #[test]
+    fn mrpack_contains_the_index() {
+        let version = version(vec![file("mod.jar", true)]);
+        let mut dependencies = HashMap::new();
+        dependencies.insert(PackDependency::Minecraft, "1.19.4".to_string());
+        dependencies.insert(PackDependency::FabricLoader, "0.14.19".to_string());
+
+        let pack = PackFormat {
+            game: "minecraft".to_string(),
+            format_version: 1,
+            version_id: "1.0.0".to_string(),
+            name: "Pack".to_string(),
+            summary: None,
+            files: vec![
+                pack_file(&version, "mod", &SideType::Required, &SideType::Required).unwrap(),
+            ],
+            dependencies,
+        };
+
+        let mut archive =
+            ZipArchive::new(write_mrpack(&pack, Cursor::new(Vec::new())).unwrap()).unwrap();
+        let mut contents = String::new();
+        archive
+            .by_name("modrinth.index.json")
+            .unwrap()
+            .read_to_string(&mut contents)
+            .unwrap();
+
+        let parsed: PackFormat = serde_json::from_str(&contents).unwrap();
+        assert!(parsed == pack);
+        assert!(contents.contains("\"fabric-loader\": \"0.14.19\""));
+        assert!(contents.contains("\"fileSize\": 42"));
+    }
+}
@@ -0,0 +1,74 @@
+use crate::database;
+use crate::database::models::notification_item::Notification as DBNotification;
+use crate::models::ids::NotificationId;
+use crate::models::notifications::Notification;
+use crate::models::users::User;
+use crate::routes::ApiError;
+use crate::util::pagination::{Cursor, Pagination};
+use actix_web::web;
+use sqlx::PgPool;
+
+pub async fn get_notifications(
+    ids: Vec<NotificationId>,
+    user: &User,
+    pool: &web::Data<PgPool>,
+) -> Result<Vec<Notification>, ApiError> {
+    let notification_ids: Vec<database::models::NotificationId> =
+        ids.into_iter().map(|x| x.into()).collect();
+
+    let notifications_data = DBNotification::get_many(&notification_ids, &***pool).await?;
+
+    Ok(notifications_data
+        .into_iter()
+        .filter(|n| n.user_id == user.id.into() || user.role.is_admin())
+        .map(Notification::from)
+        .collect())
+}
+
+/// Returns the notifications of a user, newest first, or None if the user does not exist. Only
+/// admins can see the notifications of other users. Without pagination, all notifications are
+/// returned.
+pub async fn get_user_notifications(
+    target: &str,
+    user: &User,
+    pool: &web::Data<PgPool>,
+    pagination: Option<&Pagination>,
+) -> Result<Option<(Vec<Notification>, Option<Cursor>)>, ApiError> {
+    let id = match database::models::User::get_id_from_username_or_id(target, &***pool).await? {
+        Some(id) => id,
+        None => return Ok(None),
+    };
+
+    if !user.role.is_admin() && user.id != id.into() {
+        return Err(ApiError::CustomAuthentication(
+            "You do not have permission to see the notifications of this user!".to_string(),
+        ));
+    }
+
+    let notifications = if let Some(pagination) = pagination {
+        let notification_ids = DBNotification::get_user_page(
+            id,
+            pagination.cursor,
+            pagination.fetch_limit(),
+            &***pool,
+        )
+        .await?;
+
+        DBNotification::get_many(&notification_ids, &***pool).await?
+    } else {
+        DBNotification::get_many_user(id, &***pool).await?
+    };
+
+    let mut notifications: Vec<Notification> =
+        notifications.into_iter().map(Notification::from).collect();
+    notifications.sort_by(|a, b| (b.created, b.id.0).cmp(&(a.created, a.id.0)));
+
+    if let Some(pagination) = pagination {
+        let (notifications, next) =
+            pagination.split(notifications, |x| Cursor::new(x.created, x.id.0 as i64));
+
+        return Ok(Some((notifications, next)));
+    }
+
+    Ok(Some((notifications, None)))
+}
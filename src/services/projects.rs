@@ -0,0 +1,95 @@
+use crate::database;
+use crate::models::ids::ProjectId;
+use crate::models::projects::Project;
+use crate::models::users::{User, UserId};
+use crate::routes::ApiError;
+use crate::util::auth::{filter_authorized_projects, is_authorized};
+use crate::util::pagination::{Cursor, Pagination};
+use actix_web::web;
+use sqlx::PgPool;
+
+pub async fn get_project(
+    id_or_slug: &str,
+    user_option: &Option<User>,
+    pool: &web::Data<PgPool>,
+) -> Result<Option<Project>, ApiError> {
+    let project_data =
+        database::models::Project::get_full_from_slug_or_project_id(id_or_slug, &***pool).await?;
+
+    if let Some(data) = project_data {
+        if is_authorized(&data.inner, user_option, pool).await? {
+            return Ok(Some(Project::from(data)));
+        }
+    }
+
+    Ok(None)
+}
+
+pub async fn get_projects(
+    ids: Vec<ProjectId>,
+    user_option: &Option<User>,
+    pool: &web::Data<PgPool>,
+) -> Result<Vec<Project>, ApiError> {
+    let project_ids: Vec<database::models::ids::ProjectId> =
+        ids.into_iter().map(|x| x.into()).collect();
+
+    let projects_data = database::models::Project::get_many_full(&project_ids, &***pool).await?;
+
+    filter_authorized_projects(projects_data, user_option, pool).await
+}
+
+/// Returns the projects of a user, or None if the user does not exist. Without pagination, all
+/// projects are returned, sorted by downloads. With pagination, a page of projects sorted by
+/// their publication date is returned, along with the cursor of the next page.
+pub async fn get_user_projects(
+    user: &str,
+    user_option: &Option<User>,
+    pool: &web::Data<PgPool>,
+    pagination: Option<&Pagination>,
+) -> Result<Option<(Vec<Project>, Option<Cursor>)>, ApiError> {
+    let id = match database::models::User::get_id_from_username_or_id(user, &***pool).await? {
+        Some(id) => id,
+        None => return Ok(None),
+    };
+    let user_id: UserId = id.into();
+
+    let can_view_private = user_option
+        .as_ref()
+        .map(|y| y.role.is_mod() || y.id == user_id)
+        .unwrap_or(false);
+
+    if let Some(pagination) = pagination {
+        let project_ids = database::models::User::get_projects_page(
+            id,
+            can_view_private,
+            pagination.cursor,
+            pagination.fetch_limit(),
+            &***pool,
+        )
+        .await?;
+
+        let mut projects = database::models::Project::get_many_full(&project_ids, &***pool).await?;
+        projects.sort_by(|a, b| {
+            (b.inner.published, b.inner.id.0).cmp(&(a.inner.published, a.inner.id.0))
+        });
+
+        let (projects, next) =
+            pagination.split(projects, |x| Cursor::new(x.inner.published, x.inner.id.0));
+
+        return Ok(Some((
+            projects.into_iter().map(Project::from).collect(),
+            next,
+        )));
+    }
+
+    let project_data = database::models::User::get_projects(id, &***pool).await?;
+
+    let projects = database::models::Project::get_many_full(&project_data, &***pool)
+        .await?
+        .into_iter()
+        .filter(|x| can_view_private || x.inner.status.is_searchable())
+        .map(Project::from)
+        .collect();
+
+    Ok(Some((projects, None)))
+}
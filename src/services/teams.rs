@@ -0,0 +1,84 @@
+use crate::database::models::TeamMember as DBTeamMember;
+use crate::models::teams::{TeamId, TeamMember};
+use crate::models::users::User;
+use crate::routes::ApiError;
+use actix_web::web;
+use itertools::Itertools;
+use sqlx::PgPool;
+
+/// Returns the members of a team. Members of the team can see all members and their permissions,
+/// everyone else only sees the members who accepted their invite.
+pub async fn get_team_members(
+    id: TeamId,
+    user_option: &Option<User>,
+    pool: &web::Data<PgPool>,
+) -> Result<Vec<TeamMember>, ApiError> {
+    let members_data = DBTeamMember::get_from_team_full(id.into(), &***pool).await?;
+
+    if let Some(user) = user_option {
+        let team_member =
+            DBTeamMember::get_from_user_id(id.into(), user.id.into(), &***pool).await?;
+
+        if team_member.is_some() {
+            return Ok(members_data
+                .into_iter()
+                .map(|data| TeamMember::from(data, false))
+                .collect());
+        }
+    }
+
+    let user_id = user_option.as_ref().map(|x| x.id.into());
+    Ok(members_data
+        .into_iter()
+        .filter(|x| {
+            x.accepted
+                || user_id
+                    .map(|y: crate::database::models::UserId| y == x.user.id)
+                    .unwrap_or(false)
+        })
+        .map(|data| TeamMember::from(data, true))
+        .collect())
+}
+
+pub async fn get_teams(
+    ids: Vec<TeamId>,
+    user_option: &Option<User>,
+    pool: &web::Data<PgPool>,
+) -> Result<Vec<Vec<TeamMember>>, ApiError> {
+    let team_ids: Vec<crate::database::models::ids::TeamId> =
+        ids.into_iter().map(|x| x.into()).collect();
+
+    let teams_data = DBTeamMember::get_from_team_full_many(&team_ids, &***pool).await?;
+
+    let accepted = if let Some(user) = user_option {
+        DBTeamMember::get_from_user_id_many(&team_ids, user.id.into(), &***pool)
+            .await?
+            .into_iter()
+            .map(|m| m.team_id.0)
+            .collect()
+    } else {
+        std::collections::HashSet::new()
+    };
+
+    let teams_groups = teams_data.into_iter().group_by(|data| data.team_id.0);
+
+    let mut teams: Vec<Vec<TeamMember>> = vec![];
+
+    for (id, member_data) in &teams_groups {
+        if accepted.contains(&id) {
+            let team_members = member_data.map(|data| TeamMember::from(data, false));
+
+            teams.push(team_members.collect());
+
+            continue;
+        }
+
+        let team_members = member_data
+            .filter(|x| x.accepted)
+            .map(|data| TeamMember::from(data, true));
+
+        teams.push(team_members.collect());
+    }
+
+    Ok(teams)
+}
@@ -0,0 +1,33 @@
+use crate::database;
+use crate::models::users::{User, UserId};
+use crate::routes::ApiError;
+use actix_web::web;
+use sqlx::PgPool;
+
+pub async fn get_user(
+    id_or_username: &str,
+    pool: &web::Data<PgPool>,
+) -> Result<Option<User>, ApiError> {
+    let id_option: Option<UserId> = serde_json::from_str(&format!("\"{id_or_username}\"")).ok();
+
+    let mut user_data = None;
+
+    if let Some(id) = id_option {
+        user_data = database::models::User::get(id.into(), &***pool).await?;
+    }
+
+    if user_data.is_none() {
+        user_data =
+            database::models::User::get_from_username(id_or_username.to_string(), &***pool).await?;
+    }
+
+    Ok(user_data.map(User::from))
+}
+
+pub async fn get_users(ids: Vec<UserId>, pool: &web::Data<PgPool>) -> Result<Vec<User>, ApiError> {
+    let user_ids: Vec<database::models::UserId> = ids.into_iter().map(|x| x.into()).collect();
+
+    let users_data = database::models::User::get_many(&user_ids, &***pool).await?;
+
+    Ok(users_data.into_iter().map(User::from).collect())
+}
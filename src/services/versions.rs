@@ -0,0 +1,93 @@
+use crate::database;
+use crate::models::ids::VersionId;
+use crate::models::projects::Version;
+use crate::models::users::User;
+use crate::routes::ApiError;
+use crate::util::auth::{filter_authorized_versions, is_authorized, is_authorized_version};
+use crate::util::pagination::{Cursor, Pagination};
+use actix_web::web;
+use sqlx::PgPool;
+
+pub async fn get_version(
+    id: VersionId,
+    user_option: &Option<User>,
+    pool: &web::Data<PgPool>,
+) -> Result<Option<Version>, ApiError> {
+    let version_data = database::models::Version::get_full(id.into(), &***pool).await?;
+
+    if let Some(data) = version_data {
+        if is_authorized_version(&data.inner, user_option, pool).await? {
+            return Ok(Some(Version::from(data)));
+        }
+    }
+
+    Ok(None)
+}
+
+pub async fn get_versions(
+    ids: Vec<VersionId>,
+    user_option: &Option<User>,
+    pool: &web::Data<PgPool>,
+) -> Result<Vec<Version>, ApiError> {
+    let version_ids: Vec<database::models::VersionId> = ids.into_iter().map(|x| x.into()).collect();
+
+    let versions_data = database::models::Version::get_many_full(&version_ids, &***pool).await?;
+
+    filter_authorized_versions(versions_data, user_option, pool).await
+}
+
+/// Returns a page of the versions of a project, newest first, or None if the project does not
+/// exist or the user cannot see it. Hidden versions are only included for moderators and members
+/// of the project's team.
+pub async fn get_project_versions(
+    project: &str,
+    user_option: &Option<User>,
+    pool: &web::Data<PgPool>,
+    pagination: &Pagination,
+) -> Result<Option<(Vec<Version>, Option<Cursor>)>, ApiError> {
+    let project =
+        match database::models::Project::get_from_slug_or_project_id(project, &***pool).await? {
+            Some(project) => project,
+            None => return Ok(None),
+        };
+
+    if !is_authorized(&project, user_option, pool).await? {
+        return Ok(None);
+    }
+
+    let include_hidden = if let Some(user) = user_option {
+        user.role.is_mod()
+            || database::models::TeamMember::get_from_user_id(
+                project.team_id,
+                user.id.into(),
+                &***pool,
+            )
+            .await?
+            .is_some()
+    } else {
+        false
+    };
+
+    let version_ids = database::models::Version::get_project_versions_page(
+        project.id,
+        include_hidden,
+        pagination.cursor,
+        pagination.fetch_limit(),
+        &***pool,
+    )
+    .await?;
+
+    let mut versions = database::models::Version::get_many_full(&version_ids, &***pool).await?;
+    versions.sort_by(|a, b| {
+        (b.inner.date_published, b.inner.id.0).cmp(&(a.inner.date_published, a.inner.id.0))
+    });
+
+    let (versions, next) = pagination.split(versions, |x| {
+        Cursor::new(x.inner.date_published, x.inner.id.0)
+    });
+
+    Ok(Some((
+        versions.into_iter().map(Version::from).collect(),
+        next,
+    )))
+}
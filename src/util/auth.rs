@@ -2,17 +2,21 @@ use crate::database;
 use crate::database::models::project_item::QueryProject;
 use crate::database::models::user_item;
 use crate::database::models::version_item::QueryVersion;
+use crate::database::models::PatId;
 use crate::database::{models, Project, Version};
+use crate::identity::{IdentityProvider, IdentityUser};
+use crate::models::pats::Scopes;
 use crate::models::users::{Badges, Role, User, UserId, UserPayoutData};
+use crate::queue::pats::PatQueue;
 use crate::routes::ApiError;
+use crate::util::routes::get_ip_addr;
 use crate::Utc;
-use actix_web::http::header::HeaderMap;
 use actix_web::http::header::COOKIE;
-use actix_web::web;
+use actix_web::{web, HttpRequest};
 use reqwest::header::AUTHORIZATION;
 use rust_decimal::Decimal;
-use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
+use std::sync::Arc;
 use thiserror::Error;
 
 use super::pat::get_user_from_pat;
@@ -33,36 +37,28 @@ pub enum AuthenticationError {
     InvalidCredentials,
     #[error("Authentication method was not valid")]
     InvalidAuthMethod,
+    #[error("Token is missing the required scopes: {0:?}")]
+    MissingScopes(Scopes),
+    #[error("Error while hashing password")]
+    PasswordHashing,
+    #[error("No identity provider is configured")]
+    NoIdentityProvider,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-pub struct MinosUser {
-    pub id: String,       // This is the unique generated Ory name
-    pub username: String, // unique username
-    pub email: String,
-    pub name: Option<String>, // real name
-    pub github_id: Option<i64>,
-    pub discord_id: Option<String>,
-    pub google_id: Option<String>,
-    pub gitlab_id: Option<String>,
-    pub microsoft_id: Option<String>,
-    pub apple_id: Option<String>,
-}
-
-// Insert a new user into the database from a MinosUser without a corresponding entry
+// Insert a new user into the database from an IdentityUser without a corresponding entry
 pub async fn insert_new_user(
     transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
-    minos_user: MinosUser,
+    identity_user: IdentityUser,
 ) -> Result<(), AuthenticationError> {
     let user_id = crate::database::models::generate_user_id(transaction).await?;
 
     database::models::User {
         id: user_id,
-        github_id: minos_user.github_id,
-        kratos_id: minos_user.id,
-        username: minos_user.username,
-        name: minos_user.name,
-        email: Some(minos_user.email),
+        github_id: identity_user.github_id,
+        kratos_id: identity_user.id,
+        username: identity_user.username,
+        name: identity_user.name,
+        email: Some(identity_user.email),
         avatar_url: None,
         bio: None,
         created: Utc::now(),
@@ -79,30 +75,28 @@ pub async fn insert_new_user(
     Ok(())
 }
 
-// pass the cookies to Minos to get the user.
-pub async fn get_minos_user(cookies: &str) -> Result<MinosUser, AuthenticationError> {
-    let req = reqwest::Client::new()
-        .get(dotenvy::var("MINOS_URL").unwrap() + "/user")
-        .header(reqwest::header::USER_AGENT, "Modrinth")
-        .header(reqwest::header::COOKIE, cookies);
-    let res = req.send().await?;
-
-    let res = match res.status() {
-        reqwest::StatusCode::OK => res,
-        reqwest::StatusCode::UNAUTHORIZED => return Err(AuthenticationError::InvalidCredentials),
-        _ => res.error_for_status()?,
-    };
-    Ok(res.json().await?)
+// Get the identity provider registered as app data
+pub fn get_identity_provider(
+    req: &HttpRequest,
+) -> Result<&(dyn IdentityProvider + Send + Sync), AuthenticationError> {
+    req.app_data::<web::Data<Arc<dyn IdentityProvider + Send + Sync>>>()
+        .map(|provider| &****provider)
+        .ok_or(AuthenticationError::NoIdentityProvider)
 }
 
 // Extract database from oprtional token and cookie headers
 // If both are present, token is used
 // If neither are present, InvalidCredentials is returned
+// Tokens must have been granted all of `required_scopes`, cookies are granted every scope
+// Cookies are checked by the identity provider
+// If a PAT was used, its ID is returned alongside the user
 pub async fn get_user_record_from_token_cookies<'a, E>(
     token: Option<&reqwest::header::HeaderValue>,
     cookies: Option<&reqwest::header::HeaderValue>,
     executor: E,
-) -> Result<Option<models::User>, AuthenticationError>
+    identity_provider: &(dyn IdentityProvider + Send + Sync),
+    required_scopes: Scopes,
+) -> Result<Option<(models::User, Option<PatId>)>, AuthenticationError>
 where
     E: sqlx::Executor<'a, Database = sqlx::Postgres>,
 {
@@ -112,35 +106,57 @@ where
                 .to_str()
                 .map_err(|_| AuthenticationError::InvalidCredentials)?,
             executor,
+            required_scopes,
         )
-        .await?),
+        .await?
+        .map(|(user, pat_id)| (user, Some(pat_id)))),
         (_, Some(cookies)) => {
-            let minos_user = get_minos_user(
-                cookies
-                    .to_str()
-                    .map_err(|_| AuthenticationError::InvalidCredentials)?,
-            )
-            .await?;
+            let identity_user = identity_provider
+                .get_user(
+                    cookies
+                        .to_str()
+                        .map_err(|_| AuthenticationError::InvalidCredentials)?,
+                )
+                .await?;
 
-            Ok(models::User::get_from_minos_kratos_id(minos_user.id, executor).await?)
+            Ok(
+                models::User::get_from_minos_kratos_id(identity_user.id, executor)
+                    .await?
+                    .map(|user| (user, None)),
+            )
         }
         _ => Err(AuthenticationError::InvalidAuthMethod), // No credentials passed
     }
 }
 
+// Usage of PATs is recorded in the PAT queue
 pub async fn get_user_from_headers<'a, 'b, E>(
-    headers: &HeaderMap,
+    req: &HttpRequest,
     executor: E,
+    pat_queue: &PatQueue,
+    required_scopes: Scopes,
 ) -> Result<User, AuthenticationError>
 where
     E: sqlx::Executor<'a, Database = sqlx::Postgres>,
 {
+    let headers = req.headers();
     let token: Option<&reqwest::header::HeaderValue> = headers.get(AUTHORIZATION);
     let cookies_unparsed: Option<&reqwest::header::HeaderValue> = headers.get(COOKIE);
 
-    let db_user = get_user_record_from_token_cookies(token, cookies_unparsed, executor).await?;
+    let db_user = get_user_record_from_token_cookies(
+        token,
+        cookies_unparsed,
+        executor,
+        get_identity_provider(req)?,
+        required_scopes,
+    )
+    .await?;
 
-    match db_user {
+    if let Some((_, Some(pat_id))) = db_user {
+        pat_queue.add(pat_id, get_ip_addr(req)).await;
+    }
+
+    match db_user.map(|(user, _)| user) {
         Some(result) => Ok(User {
             id: UserId::from(result.id),
             kratos_id: result.kratos_id,
@@ -167,7 +183,8 @@ where
 pub async fn get_user_record_from_bearer_token<'a, 'b, E>(
     token: &str,
     executor: E,
-) -> Result<Option<user_item::User>, AuthenticationError>
+    required_scopes: Scopes,
+) -> Result<Option<(user_item::User, PatId)>, AuthenticationError>
 where
     E: sqlx::Executor<'a, Database = sqlx::Postgres>,
 {
@@ -176,24 +193,37 @@ where
 
         // Tokens beginning with Ory are considered to be Kratos tokens (extracted cookies) and forwarded to Minos
         let possible_user = match token.split_at(4) {
-            ("mod_", _) => get_user_from_pat(token, executor).await?,
+            // OAuth access tokens are stored as PATs, and share their scopes
+            ("mod_", _) | ("mro_", _) => get_user_from_pat(token, executor).await?,
             // TODO: forward Ory tokens directly to Minos
             _ => return Err(AuthenticationError::InvalidAuthMethod),
         };
-        Ok(possible_user)
+
+        match possible_user {
+            Some((user, scopes, pat_id)) => {
+                if !scopes.contains(required_scopes) {
+                    return Err(AuthenticationError::MissingScopes(required_scopes - scopes));
+                }
+
+                Ok(Some((user, pat_id)))
+            }
+            None => Ok(None),
+        }
     } else {
         Err(AuthenticationError::InvalidAuthMethod)
     }
 }
 
 pub async fn check_is_moderator_from_headers<'a, 'b, E>(
-    headers: &HeaderMap,
+    req: &HttpRequest,
     executor: E,
+    pat_queue: &PatQueue,
+    required_scopes: Scopes,
 ) -> Result<User, AuthenticationError>
 where
     E: sqlx::Executor<'a, Database = sqlx::Postgres>,
 {
-    let user = get_user_from_headers(headers, executor).await?;
+    let user = get_user_from_headers(req, executor, pat_queue, required_scopes).await?;
 
     if user.role.is_mod() {
         Ok(user)
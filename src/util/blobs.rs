@@ -0,0 +1,33 @@
+use crate::database::models::blob_item::Blob;
+use crate::database::models::DatabaseError;
+use crate::file_hosting::FileHost;
+use sqlx::PgPool;
+
+/// Deletes the blobs which were released by deleting the last files referencing them. This is run
+/// after deleting files, and periodically in case deleting them from the file host failed before.
+pub async fn delete_released_blobs(
+    pool: &PgPool,
+    file_host: &(dyn FileHost + Send + Sync),
+) -> Result<(), DatabaseError> {
+    // The blobs stay locked until they are deleted from the file host, as a new upload of the same
+    // content can be stored at the same path
+    let mut transaction = pool.begin().await?;
+
+    for blob in Blob::get_released(&mut transaction).await? {
+        match file_host
+            .delete_file_version(&blob.host_file_id, &blob.path)
+            .await
+        {
+            Ok(_) => Blob::remove(&blob.sha512, &mut transaction).await?,
+            Err(e) => log::warn!(
+                "Deleting blob {} from the file host failed: {}",
+                blob.sha512,
+                e
+            ),
+        }
+    }
+
+    transaction.commit().await?;
+
+    Ok(())
+}
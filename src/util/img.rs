@@ -1,15 +1,382 @@
+use crate::file_hosting::{FileHost, FileHostingError, UploadFileData};
+use crate::models::projects::Thumbnail;
+use bytes::Bytes;
 use color_thief::ColorFormat;
+use image::codecs::webp::{WebPEncoder, WebPQuality};
+use image::error::{ParameterError, ParameterErrorKind};
 use image::imageops::FilterType;
-use image::{EncodableLayout, ImageError};
+use image::{ColorType, DynamicImage, EncodableLayout, ImageError, ImageFormat, ImageOutputFormat};
+use std::io::Cursor;
 
-pub fn get_color_from_img(data: &[u8]) -> Result<Option<u32>, ImageError> {
-    let image = image::load_from_memory(data)?
+// The quality lossy images are re-encoded with
+const WEBP_QUALITY: u8 = 85;
+
+/// The format a processed image is stored in. Images uploaded in a lossless format are kept
+/// lossless, while others are stored as lossy WebP rather than growing several times in size as
+/// PNG.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum StoredFormat {
+    Png,
+    WebP,
+}
+
+impl StoredFormat {
+    fn for_image(source: Option<ImageFormat>) -> Self {
+        if source == Some(ImageFormat::Png) {
+            StoredFormat::Png
+        } else {
+            StoredFormat::WebP
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            StoredFormat::Png => "image/png",
+            StoredFormat::WebP => "image/webp",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            StoredFormat::Png => "png",
+            StoredFormat::WebP => "webp",
+        }
+    }
+}
+
+/// The kinds of uploaded images, which have their own size limits and thumbnails
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ImageKind {
+    ProjectIcon,
+    UserIcon,
+    Gallery,
+}
+
+impl ImageKind {
+    fn min_dimension(&self) -> u32 {
+        match self {
+            ImageKind::ProjectIcon | ImageKind::UserIcon => 16,
+            ImageKind::Gallery => 64,
+        }
+    }
+
+    fn max_dimension(&self) -> u32 {
+        match self {
+            ImageKind::ProjectIcon | ImageKind::UserIcon => 2048,
+            ImageKind::Gallery => 8192,
+        }
+    }
+
+    /// The sizes of the thumbnails rendered for the image, which fit in a square of that size
+    pub fn thumbnail_sizes(&self) -> &'static [u32] {
+        match self {
+            ImageKind::ProjectIcon => &[64, 128],
+            ImageKind::UserIcon => &[],
+            ImageKind::Gallery => &[256, 768],
+        }
+    }
+}
+
+/// An uploaded image which was decoded, validated and re-encoded, with its thumbnails
+pub struct ProcessedImage {
+    pub data: Bytes,
+    /// The format of the image and its thumbnails
+    pub format: StoredFormat,
+    /// The thumbnails as pairs of their size and data. Sizes larger than the image are skipped.
+    pub thumbnails: Vec<(u32, Bytes)>,
+    /// The color picked from the image, for icons
+    pub color: Option<u32>,
+}
+
+impl ProcessedImage {
+    /// The URL the image has once it is uploaded to a directory by `upload_image`
+    pub fn url(&self, cdn_url: &str, dir: &str) -> String {
+        let hash = sha1::Sha1::from(&self.data).hexdigest();
+
+        format!("{cdn_url}/{}", image_path(dir, &hash, None, self.format))
+    }
+}
+
+// Images are named after their hash, and thumbnails after the hash and their size
+fn image_path(dir: &str, hash: &str, size: Option<u32>, format: StoredFormat) -> String {
+    let extension = format.extension();
+
+    match size {
+        Some(size) => format!("{dir}/{hash}_{size}.{extension}"),
+        None => format!("{dir}/{hash}.{extension}"),
+    }
+}
+
+/// Decodes an uploaded image and re-encodes it, so only the pixels are kept and metadata such as
+/// EXIF is stripped. The EXIF orientation is applied first, so photos are not stored sideways.
+/// Images outside of the dimensions allowed for their kind are rejected before they are decoded.
+/// Only the first frame of animated images is kept.
+pub fn process_image(data: &[u8], kind: ImageKind) -> Result<ProcessedImage, ImageError> {
+    let mut limits = image::io::Limits::default();
+    limits.max_image_width = Some(kind.max_dimension());
+    limits.max_image_height = Some(kind.max_dimension());
+
+    let mut reader = image::io::Reader::new(Cursor::new(data)).with_guessed_format()?;
+    reader.limits(limits);
+    let source = reader.format();
+    let image = apply_orientation(reader.decode()?, orientation(data));
+    let format = StoredFormat::for_image(source);
+
+    if image.width() < kind.min_dimension() || image.height() < kind.min_dimension() {
+        return Err(ImageError::Parameter(ParameterError::from_kind(
+            ParameterErrorKind::Generic(format!(
+                "Images must be at least {0}x{0} pixels",
+                kind.min_dimension()
+            )),
+        )));
+    }
+
+    let mut thumbnails = Vec::new();
+    for size in kind.thumbnail_sizes() {
+        if image.width() > *size || image.height() > *size {
+            thumbnails.push((*size, encode(&image.thumbnail(*size, *size), format)?));
+        }
+    }
+
+    let color = match kind {
+        ImageKind::ProjectIcon => get_color(&image),
+        _ => None,
+    };
+
+    Ok(ProcessedImage {
+        data: encode(&image, format)?,
+        format,
+        thumbnails,
+        color,
+    })
+}
+
+// The EXIF orientation of an image, from 1 to 8, or 1 if it has none
+fn orientation(data: &[u8]) -> u32 {
+    exif::Reader::new()
+        .read_from_container(&mut Cursor::new(data))
+        .ok()
+        .and_then(|exif| {
+            exif.get_field(exif::Tag::Orientation, exif::In::PRIMARY)?
+                .value
+                .get_uint(0)
+        })
+        .unwrap_or(1)
+}
+
+// Rotates and flips an image as its EXIF orientation says it should be displayed
+fn apply_orientation(image: DynamicImage, orientation: u32) -> DynamicImage {
+    match orientation {
+        2 => image.fliph(),
+        3 => image.rotate180(),
+        4 => image.flipv(),
+        5 => image.rotate90().fliph(),
+        6 => image.rotate90(),
+        7 => image.rotate270().fliph(),
+        8 => image.rotate270(),
+        _ => image,
+    }
+}
+
+/// Processes an image on a blocking thread, as decoding and resizing large images takes a while
+pub async fn process_image_async(
+    data: Bytes,
+    kind: ImageKind,
+) -> Result<ProcessedImage, ImageError> {
+    tokio::task::spawn_blocking(move || process_image(&data, kind))
+        .await
+        .map_err(|e| ImageError::IoError(std::io::Error::new(std::io::ErrorKind::Other, e)))?
+}
+
+fn encode(image: &DynamicImage, format: StoredFormat) -> Result<Bytes, ImageError> {
+    let mut data = Cursor::new(Vec::new());
+    match format {
+        StoredFormat::Png => image.write_to(&mut data, ImageOutputFormat::Png)?,
+        // Only 8-bit images can be encoded as WebP
+        StoredFormat::WebP => {
+            let encoder =
+                WebPEncoder::new_with_quality(&mut data, WebPQuality::lossy(WEBP_QUALITY));
+            if image.color().has_alpha() {
+                let image = image.to_rgba8();
+                encoder.encode(&image, image.width(), image.height(), ColorType::Rgba8)?;
+            } else {
+                let image = image.to_rgb8();
+                encoder.encode(&image, image.width(), image.height(), ColorType::Rgb8)?;
+            }
+        }
+    }
+
+    Ok(Bytes::from(data.into_inner()))
+}
+
+/// A processed image stored on the file host
+pub struct UploadedImage {
+    pub url: String,
+    pub thumbnails: Vec<Thumbnail>,
+    /// The image and its thumbnails, so they can be deleted if what uses them fails to be saved
+    pub files: Vec<UploadFileData>,
+}
+
+/// Uploads a processed image and its thumbnails to a directory, named after the hash of the image
+pub async fn upload_image(
+    file_host: &dyn FileHost,
+    cdn_url: &str,
+    dir: &str,
+    image: ProcessedImage,
+) -> Result<UploadedImage, FileHostingError> {
+    let hash = sha1::Sha1::from(&image.data).hexdigest();
+    let content_type = image.format.content_type();
+
+    let mut files = Vec::new();
+    let mut thumbnails = Vec::new();
+    for (size, data) in image.thumbnails {
+        let upload_data = file_host
+            .upload_file(
+                content_type,
+                &image_path(dir, &hash, Some(size), image.format),
+                data,
+            )
+            .await?;
+
+        thumbnails.push(Thumbnail {
+            size,
+            url: format!("{}/{}", cdn_url, upload_data.file_name),
+        });
+        files.push(upload_data);
+    }
+
+    let upload_data = file_host
+        .upload_file(
+            content_type,
+            &image_path(dir, &hash, None, image.format),
+            image.data,
+        )
+        .await?;
+
+    let url = format!("{}/{}", cdn_url, upload_data.file_name);
+    files.push(upload_data);
+
+    Ok(UploadedImage {
+        url,
+        thumbnails,
+        files,
+    })
+}
+
+/// Deletes a stored image and its thumbnails. Images which are not on the CDN are skipped.
+pub async fn delete_image(
+    file_host: &dyn FileHost,
+    cdn_url: &str,
+    url: &str,
+    thumbnails: &[Thumbnail],
+) -> Result<(), FileHostingError> {
+    let prefix = format!("{cdn_url}/");
+
+    for url in thumbnails
+        .iter()
+        .map(|x| &*x.url)
+        .chain(std::iter::once(url))
+    {
+        if let Some(path) = url.strip_prefix(&prefix) {
+            file_host.delete_file_version("", path).await?;
+        }
+    }
+
+    Ok(())
+}
+
+fn get_color(image: &DynamicImage) -> Option<u32> {
+    let image = image
         .resize(256, 256, FilterType::Nearest)
         .crop_imm(128, 128, 64, 64);
-    let color = color_thief::get_palette(image.to_rgb8().as_bytes(), ColorFormat::Rgb, 10, 2)
+
+    color_thief::get_palette(image.to_rgb8().as_bytes(), ColorFormat::Rgb, 10, 2)
         .ok()
         .and_then(|x| x.get(0).copied())
-        .map(|x| (x.r as u32) << 16 | (x.g as u32) << 8 | (x.b as u32));
+        .map(|x| (x.r as u32) << 16 | (x.g as u32) << 8 | (x.b as u32))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage};
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let image =
+            DynamicImage::ImageRgb8(RgbImage::from_pixel(width, height, Rgb([200, 30, 30])));
+        encode(&image, StoredFormat::Png).unwrap().to_vec()
+    }
+
+    // A JPEG with an EXIF orientation, which is in an APP1 segment after the start of the image
+    fn jpeg(width: u32, height: u32, orientation: u8) -> Vec<u8> {
+        let image =
+            DynamicImage::ImageRgb8(RgbImage::from_pixel(width, height, Rgb([200, 30, 30])));
+        let mut data = Vec::new();
+        image
+            .write_to(&mut Cursor::new(&mut data), ImageOutputFormat::Jpeg(85))
+            .unwrap();
+
+        let mut exif = b"\xff\xe1\x00\x22Exif\x00\x00MM\x00\x2a\x00\x00\x00\x08".to_vec();
+        exif.extend_from_slice(b"\x00\x01\x01\x12\x00\x03\x00\x00\x00\x01\x00");
+        exif.extend_from_slice(&[orientation, 0, 0, 0, 0, 0, 0]);
+
+        [&data[..2], &exif, &data[2..]].concat()
+    }
+
+    #[test]
+    fn images_are_reencoded_with_thumbnails() {
+        let processed = process_image(&png(300, 150), ImageKind::Gallery).unwrap();
+
+        let image = image::load_from_memory(&processed.data).unwrap();
+        assert_eq!((image.width(), image.height()), (300, 150));
+        assert_eq!(processed.thumbnails.len(), 1);
+
+        let (size, thumbnail) = &processed.thumbnails[0];
+        let thumbnail = image::load_from_memory(thumbnail).unwrap();
+        assert_eq!(*size, 256);
+        assert_eq!((thumbnail.width(), thumbnail.height()), (256, 128));
+    }
+
+    #[test]
+    fn image_dimensions_are_validated() {
+        assert!(process_image(&png(8, 8), ImageKind::ProjectIcon).is_err());
+        assert!(process_image(&png(4096, 16), ImageKind::ProjectIcon).is_err());
+        assert!(process_image(b"not an image", ImageKind::ProjectIcon).is_err());
+
+        let processed = process_image(&png(100, 100), ImageKind::ProjectIcon).unwrap();
+        assert_eq!(processed.thumbnails.len(), 1);
+    }
+
+    #[test]
+    fn lossy_images_are_kept_lossy() {
+        let processed = process_image(&png(100, 100), ImageKind::Gallery).unwrap();
+        assert_eq!(processed.format, StoredFormat::Png);
+        assert_eq!(
+            image::guess_format(&processed.data).unwrap(),
+            ImageFormat::Png
+        );
+
+        let processed = process_image(&jpeg(300, 150, 1), ImageKind::Gallery).unwrap();
+        assert_eq!(processed.format, StoredFormat::WebP);
+        assert_eq!(
+            image::guess_format(&processed.data).unwrap(),
+            ImageFormat::WebP
+        );
+        assert_eq!(
+            image::guess_format(&processed.thumbnails[0].1).unwrap(),
+            ImageFormat::WebP
+        );
+    }
+
+    #[test]
+    fn exif_orientation_is_applied() {
+        assert_eq!(orientation(&jpeg(300, 150, 6)), 6);
+        assert_eq!(orientation(&png(300, 150)), 1);
 
-    Ok(color)
+        // Rotated a quarter turn, so it is displayed upright
+        let processed = process_image(&jpeg(300, 150, 6), ImageKind::Gallery).unwrap();
+        let image = image::load_from_memory(&processed.data).unwrap();
+        assert_eq!((image.width(), image.height()), (150, 300));
+        assert_eq!(orientation(&processed.data), 1);
+    }
 }
@@ -1,9 +1,14 @@
 pub mod auth;
+pub mod blobs;
 pub mod env;
 pub mod ext;
 pub mod guards;
 pub mod img;
+pub mod oauth;
+pub mod pagination;
 pub mod pat;
 pub mod routes;
+pub mod storage_audit;
+pub mod uploads;
 pub mod validate;
 pub mod webhook;
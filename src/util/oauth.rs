@@ -0,0 +1,70 @@
+use base64::Engine;
+use sha2::Digest;
+
+// Checks a PKCE code verifier against the code challenge sent when authorization was requested.
+// Only the S256 challenge method is supported (RFC 7636 section 4.2).
+pub fn verify_pkce(code_challenge: &str, code_verifier: &str) -> bool {
+    // RFC 7636 section 4.1: verifiers must be between 43 and 128 characters long
+    if code_verifier.len() < 43 || code_verifier.len() > 128 {
+        return false;
+    }
+
+    let digest = sha2::Sha256::digest(code_verifier.as_bytes());
+    base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(digest) == code_challenge
+}
+
+// Checks the code challenge of an authorization request, which must be between 43 and 128
+// characters long like verifiers (RFC 7636 section 4.2)
+pub fn is_valid_code_challenge(code_challenge: &str) -> bool {
+    (43..=128).contains(&code_challenge.len())
+}
+
+// Checks that a redirect URI can be registered for an OAuth application. Fragments are not
+// allowed by RFC 6749 section 3.1.2, and redirects must use HTTPS unless they point back to the
+// user's own machine (as native applications do).
+pub fn is_valid_redirect_uri(uri: &str) -> bool {
+    let url = match url::Url::parse(uri) {
+        Ok(url) => url,
+        Err(_) => return false,
+    };
+
+    if url.fragment().is_some() || uri.len() > 2048 {
+        return false;
+    }
+
+    match url.scheme() {
+        "https" => true,
+        "http" => matches!(url.host_str(), Some("localhost") | Some("127.0.0.1")),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pkce_rfc_example() {
+        // Example from RFC 7636 appendix B
+        let verifier = "dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk";
+        let challenge = "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM";
+
+        assert!(verify_pkce(challenge, verifier));
+        assert!(!verify_pkce(challenge, &verifier.replace('d', "e")));
+        assert!(!verify_pkce("short", "short"));
+
+        assert!(is_valid_code_challenge(challenge));
+        assert!(!is_valid_code_challenge(""));
+        assert!(!is_valid_code_challenge(&challenge[..42]));
+        assert!(!is_valid_code_challenge(&"a".repeat(129)));
+    }
+
+    #[test]
+    fn redirect_uris() {
+        assert!(is_valid_redirect_uri("https://example.com/callback"));
+        assert!(is_valid_redirect_uri("http://localhost:8080/callback"));
+        assert!(!is_valid_redirect_uri("http://example.com/callback"));
+        assert!(!is_valid_redirect_uri("https://example.com/callback#token"));
+        assert!(!is_valid_redirect_uri("not a url"));
+    }
+}
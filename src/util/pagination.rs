@@ -0,0 +1,264 @@
+use crate::routes::ApiError;
+use actix_web::dev::Payload;
+use actix_web::{web, FromRequest, HttpRequest, HttpResponse};
+use base64::Engine;
+use chrono::{DateTime, TimeZone, Utc};
+use futures::future::{ready, Ready};
+use serde::{Deserialize, Serialize};
+use utoipa::IntoParams;
+
+pub const DEFAULT_PAGE_SIZE: i64 = 50;
+pub const MAX_PAGE_SIZE: i64 = 500;
+
+/// The position of the last item of a page. Items are ordered by a timestamp, usually their
+/// creation date (newest first unless the endpoint says otherwise), and then by their ID, so that
+/// the order stays stable while new items are added.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Cursor {
+    pub created: DateTime<Utc>,
+    pub id: i64,
+}
+
+impl Cursor {
+    pub fn new(created: DateTime<Utc>, id: i64) -> Self {
+        Cursor { created, id }
+    }
+
+    // Cursors are opaque to clients: the base64 encoded timestamp (in microseconds) and ID
+    pub fn encode(&self) -> String {
+        base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(format!(
+            "{}_{}",
+            self.created.timestamp_micros(),
+            self.id
+        ))
+    }
+
+    pub fn decode(cursor: &str) -> Option<Self> {
+        let decoded = base64::engine::general_purpose::URL_SAFE_NO_PAD
+            .decode(cursor)
+            .ok()?;
+        let (created, id) = std::str::from_utf8(&decoded).ok()?.split_once('_')?;
+
+        let created: i64 = created.parse().ok()?;
+        Some(Cursor {
+            created: Utc
+                .timestamp_opt(
+                    created.div_euclid(1_000_000),
+                    (created.rem_euclid(1_000_000) * 1000) as u32,
+                )
+                .single()?,
+            id: id.parse().ok()?,
+        })
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct PaginationQuery {
+    /// The `next_cursor` of the previous page
+    cursor: Option<String>,
+    /// The number of items per page, between 1 and 500. Defaults to 50.
+    limit: Option<i64>,
+}
+
+/// Extracts the `cursor` and `limit` query parameters of paginated list endpoints.
+///
+/// Queries should select the items after the cursor in the order of `Cursor` and fetch
+/// `fetch_limit()` rows, which are then passed to `split` to find the cursor of the next page:
+///
+/// ```sql
+/// WHERE ($2::timestamptz IS NULL OR (created, id) < ($2, $3))
+/// ORDER BY created DESC, id DESC
+/// LIMIT $4
+/// ```
+///
+/// Endpoints listing items in ascending order select the items after the cursor with
+/// `(created, id) > ($2, $3)` instead.
+pub struct Pagination {
+    pub cursor: Option<Cursor>,
+    pub limit: i64,
+    path: String,
+    query: Vec<(String, String)>,
+}
+
+impl Pagination {
+    // One more row than the page size is fetched to know whether there is a next page
+    pub fn fetch_limit(&self) -> i64 {
+        self.limit + 1
+    }
+
+    // Cuts the fetched rows down to the page size, returning the cursor of the next page if
+    // there are more rows
+    pub fn split<T>(
+        &self,
+        mut rows: Vec<T>,
+        cursor: impl Fn(&T) -> Cursor,
+    ) -> (Vec<T>, Option<Cursor>) {
+        if rows.len() as i64 > self.limit {
+            rows.truncate(self.limit as usize);
+            let next = rows.last().map(cursor);
+            (rows, next)
+        } else {
+            (rows, None)
+        }
+    }
+
+    pub fn page<T>(&self, items: Vec<T>, next: Option<Cursor>) -> Page<T> {
+        let next_cursor = next.map(|x| x.encode());
+
+        let next = next_cursor.as_ref().map(|cursor| {
+            let query = url::form_urlencoded::Serializer::new(String::new())
+                .extend_pairs(self.query.iter().filter(|(key, _)| key != "cursor"))
+                .append_pair("cursor", cursor)
+                .finish();
+
+            format!("{}?{}", self.path, query)
+        });
+
+        Page {
+            items,
+            next_cursor,
+            next,
+        }
+    }
+}
+
+impl FromRequest for Pagination {
+    type Error = ApiError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        ready(parse_query(req).and_then(|query| parse_pagination(req, query)))
+    }
+}
+
+/// Pagination for v2 list endpoints, which predate it. They keep returning every item as a bare
+/// list unless a `cursor` or `limit` is given, in which case they return a `Page`.
+pub struct OptionalPagination(pub Option<Pagination>);
+
+impl OptionalPagination {
+    // Responds with a page if pagination was requested, and with the bare list otherwise
+    pub fn respond<T: Serialize>(&self, items: Vec<T>, next: Option<Cursor>) -> HttpResponse {
+        match &self.0 {
+            Some(pagination) => HttpResponse::Ok().json(pagination.page(items, next)),
+            None => HttpResponse::Ok().json(items),
+        }
+    }
+}
+
+impl FromRequest for OptionalPagination {
+    type Error = ApiError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        ready(parse_query(req).and_then(|query| {
+            if query.cursor.is_none() && query.limit.is_none() {
+                Ok(OptionalPagination(None))
+            } else {
+                parse_pagination(req, query).map(|x| OptionalPagination(Some(x)))
+            }
+        }))
+    }
+}
+
+fn parse_query(req: &HttpRequest) -> Result<PaginationQuery, ApiError> {
+    Ok(
+        web::Query::<PaginationQuery>::from_query(req.query_string())
+            .map_err(|err| ApiError::InvalidInput(err.to_string()))?
+            .into_inner(),
+    )
+}
+
+fn parse_pagination(req: &HttpRequest, query: PaginationQuery) -> Result<Pagination, ApiError> {
+    let cursor = query
+        .cursor
+        .map(|cursor| {
+            Cursor::decode(&cursor)
+                .ok_or_else(|| ApiError::InvalidInput("Invalid pagination cursor".to_string()))
+        })
+        .transpose()?;
+
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE);
+    if !(1..=MAX_PAGE_SIZE).contains(&limit) {
+        return Err(ApiError::InvalidInput(format!(
+            "The page limit must be between 1 and {MAX_PAGE_SIZE}"
+        )));
+    }
+
+    Ok(Pagination {
+        cursor,
+        limit,
+        path: req.path().to_string(),
+        query: url::form_urlencoded::parse(req.query_string().as_bytes())
+            .into_owned()
+            .collect(),
+    })
+}
+
+/// A page of a paginated list. `next` is the link to the next page, if there is one.
+#[derive(Serialize)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub next_cursor: Option<String>,
+    pub next: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cursor_round_trip() {
+        let cursor = Cursor::new(Utc.timestamp_opt(1684000000, 123456000).unwrap(), 42);
+
+        assert_eq!(Cursor::decode(&cursor.encode()), Some(cursor));
+        assert_eq!(Cursor::decode("not a cursor"), None);
+    }
+
+    #[test]
+    fn split_returns_next_cursor() {
+        let pagination = Pagination {
+            cursor: None,
+            limit: 2,
+            path: "/v3/user/abc/projects".to_string(),
+            query: vec![("limit".to_string(), "2".to_string())],
+        };
+        let cursor = |x: &i64| Cursor::new(Utc.timestamp_opt(*x, 0).unwrap(), *x);
+
+        let (rows, next) = pagination.split(vec![3, 2, 1], cursor);
+        assert_eq!(rows, vec![3, 2]);
+        assert_eq!(next, Some(cursor(&2)));
+
+        let page = pagination.page(rows, next);
+        assert_eq!(
+            page.next,
+            Some(format!(
+                "/v3/user/abc/projects?limit=2&cursor={}",
+                cursor(&2).encode()
+            ))
+        );
+
+        let (rows, next) = pagination.split(vec![2, 1], cursor);
+        assert_eq!(rows, vec![2, 1]);
+        assert_eq!(next, None);
+    }
+
+    #[actix_rt::test]
+    async fn optional_pagination_is_opt_in() {
+        let extract = |uri: &str| {
+            let req = actix_web::test::TestRequest::get()
+                .uri(uri)
+                .to_http_request();
+            OptionalPagination::extract(&req).into_inner()
+        };
+
+        assert!(extract("/v2/report?count=10").unwrap().0.is_none());
+
+        let pagination = extract("/v2/report?limit=10").unwrap().0.unwrap();
+        assert_eq!(pagination.limit, 10);
+        assert_eq!(pagination.cursor, None);
+
+        assert!(extract("/v2/report?cursor=invalid").is_err());
+        assert!(extract("/v2/report?limit=0").is_err());
+    }
+}
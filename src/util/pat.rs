@@ -7,51 +7,114 @@ Just as a summary: Don't implement this flow in your application!
 
 use super::auth::AuthenticationError;
 use crate::database;
-use crate::database::models::{DatabaseError, UserId};
+use crate::database::models::notification_item::NotificationBuilder;
+use crate::database::models::{DatabaseError, PatId, UserId};
+use crate::models::notifications::NotificationBody;
+use crate::models::pats::{self, Scopes};
 use crate::models::users::{self, Badges, RecipientType, RecipientWallet};
 use censor::Censor;
-use chrono::{NaiveDateTime, Utc};
+use chrono::{DateTime, Duration, NaiveDateTime, Utc};
+use constant_time_eq::constant_time_eq;
 use rand::Rng;
 use serde::{Deserialize, Serialize};
+use sha2::Digest;
+use sqlx::PgPool;
+use utoipa::ToSchema;
 
-#[derive(Serialize, Deserialize)]
+// Length of the non-secret token prefix (including 'mod_') stored alongside the hash
+pub const PAT_PREFIX_LENGTH: usize = 12;
+
+#[derive(Serialize, Deserialize, ToSchema)]
 pub struct PersonalAccessToken {
-    pub id: String,
-    pub access_token: String,
-    pub scope: String,
+    #[schema(value_type = String)]
+    pub id: pats::PatId,
+    pub name: String,
+    // The plaintext token is only ever returned once, when the token is created
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub access_token: Option<String>,
+    pub token_prefix: String,
+    pub scopes: Scopes,
+    #[schema(value_type = String)]
     pub user_id: users::UserId,
+    pub created_at: NaiveDateTime,
     pub expires_at: NaiveDateTime,
+    pub last_used_at: Option<NaiveDateTime>,
+    pub last_used_ip: Option<String>,
+    pub usage_count: u64,
+}
+
+// A PAT as it is stored in the database. The plaintext token is never stored.
+pub struct HashedPat {
+    pub token_prefix: String,
+    pub token_salt: String,
+    pub token_hash: String,
+}
+
+impl HashedPat {
+    pub fn new(access_token: &str) -> HashedPat {
+        let mut salt = [0u8; 16];
+        rand::thread_rng().fill(&mut salt);
+        let token_salt = hex::encode(salt);
+
+        HashedPat {
+            token_prefix: token_prefix(access_token).to_string(),
+            token_hash: hash_pat(&token_salt, access_token),
+            token_salt,
+        }
+    }
+}
+
+pub fn token_prefix(access_token: &str) -> &str {
+    access_token
+        .get(..PAT_PREFIX_LENGTH)
+        .unwrap_or(access_token)
 }
-// Find user from PAT token
+
+// Hex encoded sha512 of the salt followed by the token
+pub fn hash_pat(salt: &str, access_token: &str) -> String {
+    format!(
+        "{:x}",
+        sha2::Sha512::digest(format!("{salt}{access_token}").as_bytes())
+    )
+}
+// Find user from PAT token, along with the scopes the token was granted and the token's ID
 // Separate to user_items as it may yet include further behaviour.
 pub async fn get_user_from_pat<'a, E>(
     access_token: &str,
     executor: E,
-) -> Result<Option<database::models::User>, AuthenticationError>
+) -> Result<Option<(database::models::User, Scopes, PatId)>, AuthenticationError>
 where
     E: sqlx::Executor<'a, Database = sqlx::Postgres>,
 {
     let row = sqlx::query!(
         "
-                SELECT pats.expires_at,
+                SELECT pats.id pat_id, pats.expires_at, pats.scopes, pats.token_salt, pats.token_hash,
                     u.id, u.name, u.kratos_id, u.email,
                     u.avatar_url, u.username, u.bio,
                     u.created, u.role, u.badges,
                     u.balance, u.payout_wallet, u.payout_wallet_type,
                     u.payout_address, u.github_id
                 FROM pats LEFT OUTER JOIN users u ON pats.user_id = u.id
-                WHERE access_token = $1
+                WHERE pats.token_prefix = $1
                 ",
-        access_token
+        token_prefix(access_token)
     )
-    .fetch_optional(executor)
+    .fetch_all(executor)
     .await?;
+
+    let row = row.into_iter().find(|row| {
+        constant_time_eq(
+            hash_pat(&row.token_salt, access_token).as_bytes(),
+            row.token_hash.as_bytes(),
+        )
+    });
+
     if let Some(row) = row {
         if row.expires_at < Utc::now().naive_utc() {
             return Ok(None);
         }
 
-        return Ok(Some(database::models::User {
+        let user = database::models::User {
             id: UserId(row.id),
             kratos_id: row.kratos_id,
             github_id: row.github_id,
@@ -69,38 +132,90 @@ where
                 .payout_wallet_type
                 .map(|x| RecipientType::from_string(&x)),
             payout_address: row.payout_address,
-        }));
+        };
+
+        return Ok(Some((
+            user,
+            Scopes::from_bits_truncate(row.scopes as u64),
+            PatId(row.pat_id),
+        )));
     }
     Ok(None)
 }
 
-// Generate a new 128 char PAT token starting with 'mod_'
+// Notify users of their PATs expiring within the given number of days. Each token is only
+// notified about once per expiry date.
+pub async fn notify_expiring_pats(pool: &PgPool, days: i64) -> Result<(), DatabaseError> {
+    let mut transaction = pool.begin().await?;
+    let now = Utc::now().naive_utc();
+
+    let pats = sqlx::query!(
+        "
+        UPDATE pats
+        SET expiry_notified = TRUE
+        WHERE expiry_notified = FALSE AND expires_at > $1 AND expires_at < $2
+            AND oauth_authorization_id IS NULL
+        RETURNING id, user_id, name, expires_at
+        ",
+        now,
+        now + Duration::days(days)
+    )
+    .fetch_all(&mut *transaction)
+    .await?;
+
+    for pat in pats {
+        NotificationBuilder {
+            body: NotificationBody::PatExpiring {
+                pat_id: PatId(pat.id).into(),
+                name: pat.name,
+                expires_at: DateTime::from_utc(pat.expires_at, Utc),
+            },
+        }
+        .insert(UserId(pat.user_id), &mut transaction)
+        .await?;
+    }
+
+    transaction.commit().await?;
+
+    Ok(())
+}
+
+// Generate a random 64 char token starting with the given 4 char prefix. Apart from the prefix,
+// this may include uppercase and lowercase and numbers only.
+pub fn random_token(prefix: &str) -> String {
+    let mut rng = rand::thread_rng();
+
+    let mut token = String::with_capacity(64);
+    token.push_str(prefix);
+    for _ in 0..60 {
+        let c = rng.gen_range(0..60);
+        if c < 10 {
+            token.push(char::from_u32(c + 48).unwrap()); // 0-9
+        } else if c < 36 {
+            token.push(char::from_u32(c + 55).unwrap()); // A-Z
+        } else {
+            token.push(char::from_u32(c + 61).unwrap()); // a-z
+        }
+    }
+    token
+}
+
+// Generate a new 64 char PAT token with a unique prefix. PATs created by users start with 'mod_',
+// OAuth access tokens start with 'mro_'.
 pub async fn generate_pat(
     con: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    prefix: &str,
 ) -> Result<String, DatabaseError> {
-    let mut rng = rand::thread_rng();
     let mut retry_count = 0;
     let censor = Censor::Standard + Censor::Sex;
 
-    // First generate the PAT token as a random 128 char string. This may include uppercase and lowercase and numbers only.
     loop {
-        let mut access_token = String::with_capacity(63);
-        access_token.push_str("mod_");
-        for _ in 0..60 {
-            let c = rng.gen_range(0..60);
-            if c < 10 {
-                access_token.push(char::from_u32(c + 48).unwrap()); // 0-9
-            } else if c < 36 {
-                access_token.push(char::from_u32(c + 55).unwrap()); // A-Z
-            } else {
-                access_token.push(char::from_u32(c + 61).unwrap()); // a-z
-            }
-        }
+        let access_token = random_token(prefix);
         let results = sqlx::query!(
             "
-            SELECT EXISTS(SELECT 1 FROM pats WHERE access_token=$1)
+            SELECT EXISTS(SELECT 1 FROM pats WHERE token_prefix=$1)
         ",
-            access_token
+            token_prefix(&access_token)
         )
         .fetch_one(&mut *con)
         .await?;
@@ -115,3 +230,32 @@ pub async fn generate_pat(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pat_hash_matches_migration() {
+        // Value computed by postgres as in the pat-hashing migration:
+        // encode(sha512(convert_to(token_salt || access_token, 'UTF8')), 'hex')
+        assert_eq!(
+            hash_pat(
+                "0123456789abcdef0123456789abcdef",
+                "mod_abcdefghijklmnopqrstuvwxyz"
+            ),
+            "7819ef75463ed6127902fa08ecd81ef9c1cba7843046c1db1f8e789809d6165f2353046c0e206f05403005ee967af57aa8ac2516f690e3810ef357485db14bbc"
+        );
+    }
+
+    #[test]
+    fn hashed_pat_is_salted() {
+        let token = "mod_abcdefghijklmnopqrstuvwxyz";
+        let first = HashedPat::new(token);
+        let second = HashedPat::new(token);
+
+        assert_eq!(first.token_prefix, "mod_abcdefgh");
+        assert_ne!(first.token_hash, second.token_hash);
+        assert_eq!(hash_pat(&first.token_salt, token), first.token_hash);
+    }
+}
@@ -1,8 +1,10 @@
+use crate::file_hosting::{FileHostingError, SpoolWriter, SpooledFile};
 use crate::routes::v2::project_creation::CreateError;
 use crate::routes::ApiError;
+use crate::util::env::parse_var;
 use actix_multipart::Field;
 use actix_web::web::Payload;
-use actix_web::HttpResponse;
+use actix_web::{HttpRequest, HttpResponse};
 use bytes::BytesMut;
 use futures::StreamExt;
 use serde::Serialize;
@@ -41,6 +43,39 @@ pub async fn read_from_field(
     Ok(bytes)
 }
 
+/// Writes a field to a temporary file as it is received, instead of reading it into memory
+pub async fn spool_field(
+    field: &mut Field,
+    cap: u64,
+    err_msg: &'static str,
+) -> Result<SpooledFile, CreateError> {
+    let mut writer = SpoolWriter::new().map_err(FileHostingError::from)?;
+    while let Some(chunk) = field.next().await {
+        if writer.written() >= cap {
+            return Err(CreateError::InvalidInput(String::from(err_msg)));
+        } else {
+            writer
+                .write(&chunk?)
+                .await
+                .map_err(FileHostingError::from)?;
+        }
+    }
+    Ok(writer.finish().await.map_err(FileHostingError::from)?)
+}
+
+// Gets the IP address of the client, using the header set by Cloudflare if the integration is enabled
+pub fn get_ip_addr(req: &HttpRequest) -> Option<String> {
+    let connection_info = req.connection_info();
+
+    if parse_var("CLOUDFLARE_INTEGRATION").unwrap_or(false) {
+        if let Some(header) = req.headers().get("CF-Connecting-IP") {
+            return header.to_str().ok().map(String::from);
+        }
+    }
+
+    connection_info.peer_addr().map(String::from)
+}
+
 pub(crate) fn ok_or_not_found<T, U>(version_data: Option<T>) -> Result<HttpResponse, ApiError>
 where
     U: From<T> + Serialize,
@@ -0,0 +1,323 @@
+use crate::database::models::audit_finding_item::{AuditFinding, AuditFindingKind};
+use crate::database::models::DatabaseError;
+use crate::file_hosting::{FileHost, FileHostingError};
+use crate::util::env::parse_var;
+use chrono::{Duration, Utc};
+use log::{info, warn};
+use sqlx::PgPool;
+use std::collections::{HashMap, HashSet};
+
+/// How many files are downloaded to check their hashes each audit (defaults to 100)
+pub fn sample_size() -> i64 {
+    parse_var("STORAGE_AUDIT_SAMPLE_SIZE").unwrap_or(100)
+}
+
+/// How long a blob is kept after it was first found orphaned (defaults to 7 days), so blobs
+/// of uploads which have not been committed yet are never deleted
+pub fn orphan_grace() -> Duration {
+    Duration::days(parse_var::<i64>("STORAGE_ORPHAN_GRACE_DAYS").unwrap_or(7))
+}
+
+/// Whether an object on the file host is a version file: either a blob, or a file uploaded
+/// before blobs were introduced. Other objects, such as icons and gallery images, aren't audited.
+pub fn is_version_file(path: &str) -> bool {
+    let parts = path.split('/').collect::<Vec<_>>();
+
+    matches!(
+        parts.as_slice(),
+        ["data", "blobs", sha512, name] if !sha512.is_empty() && !name.is_empty()
+    ) || matches!(
+        parts.as_slice(),
+        ["data", project_id, "versions", version_id, name]
+            if !project_id.is_empty() && !version_id.is_empty() && !name.is_empty()
+    )
+}
+
+/// The path of a version file on the file host from its URL. The host, and any path before the
+/// `data` directory, are ignored, as files uploaded before the CDN URL changed keep their old URL.
+pub fn version_file_path(url: &str) -> Option<String> {
+    let path = url.split_once("://").map(|(_, x)| x).unwrap_or(url);
+    let path = path.split_once('/')?.1;
+    let path = path.split(|x| x == '?' || x == '#').next()?;
+    let path = urlencoding::decode(path).ok()?;
+
+    let parts = path.split('/').collect::<Vec<_>>();
+    (0..parts.len())
+        .filter(|x| parts[*x] == "data")
+        .map(|x| parts[x..].join("/"))
+        .find(|x| is_version_file(x))
+}
+
+/// Audits the stored version files against the database. A random sample of files is downloaded
+/// to check their content still matches their hash, the objects on the file host are compared
+/// with the files referencing them, and orphaned blobs are deleted after a grace period. What is
+/// found is recorded as findings, which are removed once they no longer apply.
+pub async fn audit_storage(
+    pool: &PgPool,
+    file_host: &(dyn FileHost + Send + Sync),
+) -> Result<(), FileHostingError> {
+    let started = Utc::now();
+
+    check_hashes(pool, file_host).await?;
+
+    // The expected objects are found before listing the file host, so an object uploaded in
+    // between is at worst reported as orphaned until the next audit
+    let expected = expected_objects(pool).await?;
+    let stored = file_host
+        .list_files("data/")
+        .await?
+        .into_iter()
+        .filter(|x| is_version_file(&x.file_name))
+        .map(|x| (x.file_name, x.file_id))
+        .collect::<HashMap<_, _>>();
+
+    for (path, host_file_id) in &expected {
+        if !stored.contains_key(path) {
+            warn!("Stored file {} is missing from the file host", path);
+
+            new_finding(path, AuditFindingKind::MissingObject, host_file_id.clone())
+                .upsert(pool)
+                .await?;
+        }
+    }
+
+    for (path, host_file_id) in &stored {
+        if !expected.contains_key(path) {
+            new_finding(
+                path,
+                AuditFindingKind::OrphanedObject,
+                Some(host_file_id.clone()).filter(|x| !x.is_empty()),
+            )
+            .upsert(pool)
+            .await?;
+        }
+    }
+
+    AuditFinding::remove_stale(AuditFindingKind::MissingObject, started, pool).await?;
+    AuditFinding::remove_stale(AuditFindingKind::OrphanedObject, started, pool).await?;
+
+    // Mismatches of files which were deleted since are removed, as they are only found again
+    // when the file is sampled
+    for finding in AuditFinding::get_all(Some(AuditFindingKind::HashMismatch), pool).await? {
+        if !expected.contains_key(&finding.path) {
+            AuditFinding::remove(&finding.path, finding.kind, pool).await?;
+        }
+    }
+
+    delete_orphans(pool, file_host).await?;
+
+    Ok(())
+}
+
+fn new_finding(path: &str, kind: AuditFindingKind, host_file_id: Option<String>) -> AuditFinding {
+    AuditFinding {
+        path: path.to_string(),
+        kind,
+        host_file_id,
+        expected_sha512: None,
+        actual_sha512: None,
+        first_found: Utc::now(),
+        last_found: Utc::now(),
+    }
+}
+
+// Downloads a random sample of files and compares their content with their SHA512 hash
+async fn check_hashes(
+    pool: &PgPool,
+    file_host: &(dyn FileHost + Send + Sync),
+) -> Result<(), FileHostingError> {
+    let files = sqlx::query!(
+        "
+        SELECT f.url, h.hash, b.path blob_path, b.host_file_id blob_host_file_id
+        FROM files f
+        INNER JOIN hashes h ON h.file_id = f.id AND h.algorithm = 'sha512'
+        LEFT OUTER JOIN blobs b ON b.sha512 = f.blob
+        ORDER BY RANDOM()
+        LIMIT $1
+        ",
+        sample_size(),
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(DatabaseError::from)?;
+
+    let mut checked = 0;
+    for file in files {
+        // Files uploaded before blobs were introduced are stored at the path of their URL, and
+        // the ID the file host gave them was not kept
+        let (path, host_file_id) = match (file.blob_path, file.blob_host_file_id) {
+            (Some(path), host_file_id) => (path, host_file_id),
+            _ => match version_file_path(&file.url) {
+                Some(path) => (path, None),
+                None => continue,
+            },
+        };
+
+        let expected_sha512 = match String::from_utf8(file.hash) {
+            Ok(hash) => hash,
+            Err(_) => continue,
+        };
+
+        let downloaded = match file_host
+            .download_file(host_file_id.as_deref(), &path)
+            .await
+        {
+            Ok(downloaded) => downloaded,
+            Err(e) => {
+                // Missing objects are found by comparing the objects on the file host instead
+                warn!("Downloading {} to check its hash failed: {}", path, e);
+                continue;
+            }
+        };
+        checked += 1;
+
+        if downloaded.sha512 == expected_sha512 {
+            AuditFinding::remove(&path, AuditFindingKind::HashMismatch, pool).await?;
+        } else {
+            warn!(
+                "Stored file {} has SHA512 hash {}, but {} was expected",
+                path, downloaded.sha512, expected_sha512
+            );
+
+            AuditFinding {
+                expected_sha512: Some(expected_sha512),
+                actual_sha512: Some(downloaded.sha512.clone()),
+                ..new_finding(&path, AuditFindingKind::HashMismatch, host_file_id)
+            }
+            .upsert(pool)
+            .await?;
+        }
+    }
+
+    info!("Checked the hashes of {} stored files", checked);
+
+    Ok(())
+}
+
+// The objects referenced by version files, with the ID the file host gave them if it is known
+async fn expected_objects(pool: &PgPool) -> Result<HashMap<String, Option<String>>, DatabaseError> {
+    let mut objects = HashMap::new();
+
+    // Released blobs are still expected, as they are deleted from the file host separately
+    let blobs = sqlx::query!(
+        "
+        SELECT path, host_file_id
+        FROM blobs
+        "
+    )
+    .fetch_all(pool)
+    .await?;
+
+    for blob in blobs {
+        objects.insert(blob.path, Some(blob.host_file_id));
+    }
+
+    let files = sqlx::query!(
+        "
+        SELECT url
+        FROM files
+        WHERE blob IS NULL
+        "
+    )
+    .fetch_all(pool)
+    .await?;
+
+    for file in files {
+        if let Some(path) = version_file_path(&file.url) {
+            objects.entry(path).or_insert(None);
+        }
+    }
+
+    Ok(objects)
+}
+
+// Deletes the blobs which have been orphaned for longer than the grace period. Orphaned files
+// uploaded before blobs were introduced are only reported, as they can't be told apart from files
+// whose URL no longer points to them, and are left for an admin to review. The referenced objects
+// are found again first, in case a file referenced one of them since the audit started.
+async fn delete_orphans(
+    pool: &PgPool,
+    file_host: &(dyn FileHost + Send + Sync),
+) -> Result<(), FileHostingError> {
+    let orphans = AuditFinding::get_all(Some(AuditFindingKind::OrphanedObject), pool)
+        .await?
+        .into_iter()
+        .filter(|x| {
+            x.path.starts_with("data/blobs/") && x.first_found < Utc::now() - orphan_grace()
+        })
+        .collect::<Vec<_>>();
+
+    if orphans.is_empty() {
+        return Ok(());
+    }
+
+    let referenced = expected_objects(pool)
+        .await?
+        .into_keys()
+        .collect::<HashSet<_>>();
+
+    for orphan in orphans {
+        if referenced.contains(&orphan.path) {
+            AuditFinding::remove(&orphan.path, orphan.kind, pool).await?;
+            continue;
+        }
+
+        match file_host
+            .delete_file_version(
+                orphan.host_file_id.as_deref().unwrap_or_default(),
+                &orphan.path,
+            )
+            .await
+        {
+            Ok(_) => {
+                info!("Deleted orphaned object {}", orphan.path);
+                AuditFinding::remove(&orphan.path, orphan.kind, pool).await?;
+            }
+            Err(e) => warn!("Deleting orphaned object {} failed: {}", orphan.path, e),
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_version_files_are_audited() {
+        assert!(is_version_file("data/blobs/abc123/mod.jar"));
+        assert!(is_version_file("data/AABBCCDD/versions/EEFFGGHH/mod.jar"));
+
+        assert!(!is_version_file("data/AABBCCDD/icon.png"));
+        assert!(!is_version_file("data/AABBCCDD/images/gallery.png"));
+        assert!(!is_version_file("data/blobs/abc123"));
+        assert!(!is_version_file("data/blobs//mod.jar"));
+        assert!(!is_version_file("user/AABBCCDD/icon.png"));
+    }
+
+    #[test]
+    fn version_files_are_found_on_any_cdn() {
+        // A file uploaded before the CDN URL changed
+        assert_eq!(
+            version_file_path(
+                "https://old-cdn.example.com/data/AABBCCDD/versions/EEFFGGHH/My%20Mod.jar"
+            )
+            .as_deref(),
+            Some("data/AABBCCDD/versions/EEFFGGHH/My Mod.jar")
+        );
+        assert_eq!(
+            version_file_path("http://localhost:8000/files/data/blobs/abc123/mod.jar").as_deref(),
+            Some("data/blobs/abc123/mod.jar")
+        );
+
+        assert_eq!(
+            version_file_path("https://cdn.example.com/data/AABBCCDD/icon.png"),
+            None
+        );
+        assert_eq!(
+            version_file_path("https://github.com/user/repo/mod.jar"),
+            None
+        );
+    }
+}
@@ -0,0 +1,115 @@
+use crate::database::models::{DatabaseError, UploadSessionId, UserId};
+use crate::file_hosting::{session_path, sessions_dir, SpooledFile};
+use crate::routes::v2::project_creation::CreateError;
+use crate::util::env::parse_var;
+use chrono::{Duration, Utc};
+use sqlx::PgPool;
+
+/// How long an upload session is kept without receiving data (defaults to 24 hours)
+pub fn session_expiry() -> Duration {
+    Duration::hours(parse_var::<i64>("UPLOAD_SESSION_EXPIRY_HOURS").unwrap_or(24))
+}
+
+/// Takes the finalized upload of a session to be added to a version, returning the name of the
+/// file. The session is deleted with the transaction, and its data by `delete_abandoned_sessions`,
+/// so the upload can be referenced again if the transaction fails.
+pub async fn take_upload_session(
+    id: UploadSessionId,
+    user_id: UserId,
+    transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+) -> Result<(String, SpooledFile), CreateError> {
+    let session = sqlx::query!(
+        "
+        DELETE FROM upload_sessions
+        WHERE id = $1 AND user_id = $2 AND sha1 IS NOT NULL AND sha512 IS NOT NULL
+        RETURNING file_name, size, sha1 \"sha1!\", sha512 \"sha512!\"
+        ",
+        id as UploadSessionId,
+        user_id as UserId,
+    )
+    .fetch_optional(&mut *transaction)
+    .await?
+    .ok_or_else(|| {
+        CreateError::InvalidInput(format!(
+            "Upload session {} does not exist or was not finalized",
+            crate::models::ids::UploadSessionId::from(id)
+        ))
+    })?;
+
+    Ok((
+        session.file_name,
+        SpooledFile::from_session(
+            session_path(id.0),
+            session.size as u64,
+            session.sha1,
+            session.sha512,
+        ),
+    ))
+}
+
+/// Deletes upload sessions which stopped receiving data, and the data of sessions which were
+/// deleted or added to versions. Their data is kept for an hour after, as it may still be scanned.
+pub async fn delete_abandoned_sessions(pool: &PgPool) -> Result<(), DatabaseError> {
+    let deleted = sqlx::query!(
+        "
+        DELETE FROM upload_sessions
+        WHERE updated < $1
+        RETURNING id
+        ",
+        Utc::now() - session_expiry(),
+    )
+    .fetch_all(pool)
+    .await?;
+
+    for session in deleted {
+        if let Err(e) = tokio::fs::remove_file(session_path(session.id)).await {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                log::warn!(
+                    "Deleting data of upload session {} failed: {}",
+                    session.id,
+                    e
+                );
+            }
+        }
+    }
+
+    let sessions = sqlx::query!(
+        "
+        SELECT id FROM upload_sessions
+        "
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|x| x.id)
+    .collect::<std::collections::HashSet<_>>();
+
+    let mut entries = match tokio::fs::read_dir(sessions_dir()).await {
+        Ok(entries) => entries,
+        Err(_) => return Ok(()),
+    };
+
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        let orphaned = entry
+            .file_name()
+            .to_str()
+            .and_then(|x| x.parse::<i64>().ok())
+            .map(|id| !sessions.contains(&id))
+            .unwrap_or(false);
+
+        let stale = match entry.metadata().await.and_then(|x| x.modified()) {
+            Ok(modified) => {
+                modified.elapsed().unwrap_or_default() > std::time::Duration::from_secs(60 * 60)
+            }
+            Err(_) => false,
+        };
+
+        if orphaned && stale {
+            if let Err(e) = tokio::fs::remove_file(entry.path()).await {
+                log::warn!("Deleting orphaned upload session data failed: {}", e);
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -1,5 +1,6 @@
+use crate::models::projects::FindingCode;
 use crate::validate::{SupportedGameVersions, ValidationError, ValidationResult};
-use std::io::Cursor;
+use std::fs::File;
 use zip::ZipArchive;
 
 pub struct DataPackValidator;
@@ -23,11 +24,13 @@ impl super::Validator for DataPackValidator {
 
     fn validate(
         &self,
-        archive: &mut ZipArchive<Cursor<bytes::Bytes>>,
+        archive: &mut ZipArchive<File>,
     ) -> Result<ValidationResult, ValidationError> {
         if archive.by_name("pack.mcmeta").is_err() {
-            return Ok(ValidationResult::Warning(
+            return Ok(ValidationResult::warning(
+                FindingCode::MissingManifest,
                 "No pack.mcmeta present for datapack file. Tip: Make sure pack.mcmeta is in the root directory of your datapack!",
+                Some("pack.mcmeta"),
             ));
         }
 
@@ -1,6 +1,14 @@
-use crate::validate::{SupportedGameVersions, ValidationError, ValidationResult};
+use crate::models::projects::{DependencyType, FindingCode};
+use crate::validate::metadata::{
+    read_manifest, GameVersionRange, ModEnvironment, ModMetadata, VersionPredicates,
+};
+use crate::validate::{
+    check_compiled, warning, SupportedGameVersions, ValidationError, ValidationResult,
+};
 use chrono::{DateTime, NaiveDateTime, Utc};
-use std::io::Cursor;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs::File;
 use zip::ZipArchive;
 
 pub struct FabricValidator;
@@ -28,23 +36,166 @@ impl super::Validator for FabricValidator {
 
     fn validate(
         &self,
-        archive: &mut ZipArchive<Cursor<bytes::Bytes>>,
+        archive: &mut ZipArchive<File>,
     ) -> Result<ValidationResult, ValidationError> {
-        if archive.by_name("fabric.mod.json").is_err() {
-            return Ok(ValidationResult::Warning(
-                "No fabric.mod.json present for Fabric file.",
-            ));
-        }
+        let mut findings = Vec::new();
+        let manifest = match archive.by_name("fabric.mod.json") {
+            Ok(file) => Some(read_manifest(file)?),
+            Err(_) => {
+                findings.push(warning(
+                    FindingCode::MissingManifest,
+                    "No fabric.mod.json present for Fabric file.",
+                    Some("fabric.mod.json"),
+                ));
+                None
+            }
+        };
+        findings.extend(check_compiled(
+            archive,
+            |name| name.ends_with("refmap.json") || name.ends_with(".class"),
+            "Fabric mod file is a source file!",
+        ));
+
+        let manifest = match manifest {
+            Some(manifest) if findings.is_empty() => manifest,
+            _ => return Ok(ValidationResult::Warning(findings)),
+        };
+
+        // The loader accepts manifests which are not strictly valid JSON, so these still pass
+        Ok(match serde_json::from_slice::<FabricModJson>(&manifest) {
+            Ok(manifest) => ValidationResult::PassWithMetadata(manifest.into()),
+            Err(_) => ValidationResult::Pass,
+        })
+    }
+}
 
-        if !archive
-            .file_names()
-            .any(|name| name.ends_with("refmap.json") || name.ends_with(".class"))
-        {
-            return Ok(ValidationResult::Warning(
-                "Fabric mod file is a source file!",
-            ));
+#[derive(Deserialize)]
+struct FabricModJson {
+    id: String,
+    version: Option<String>,
+    environment: Option<String>,
+    #[serde(default)]
+    depends: BTreeMap<String, VersionPredicates>,
+    #[serde(default)]
+    recommends: BTreeMap<String, VersionPredicates>,
+    #[serde(default)]
+    suggests: BTreeMap<String, VersionPredicates>,
+    #[serde(default)]
+    breaks: BTreeMap<String, VersionPredicates>,
+}
+
+impl From<FabricModJson> for ModMetadata {
+    fn from(manifest: FabricModJson) -> Self {
+        let mut metadata = ModMetadata {
+            mod_id: manifest.id,
+            version: manifest.version,
+            environment: match manifest.environment.as_deref() {
+                Some("client") => Some(ModEnvironment::Client),
+                Some("server") => Some(ModEnvironment::Server),
+                Some("*") | None => Some(ModEnvironment::Both),
+                Some(_) => None,
+            },
+            ..Default::default()
+        };
+
+        let dependencies = [
+            (manifest.depends, DependencyType::Required),
+            (manifest.recommends, DependencyType::Optional),
+            (manifest.suggests, DependencyType::Optional),
+            (manifest.breaks, DependencyType::Incompatible),
+        ];
+        for (mods, dependency_type) in dependencies {
+            for (mod_id, predicates) in mods {
+                let predicates = predicates.into_vec();
+
+                if mod_id == "minecraft" && dependency_type == DependencyType::Required {
+                    metadata.game_versions = predicates.clone().map(GameVersionRange::Predicates);
+                }
+
+                metadata.add_dependency(
+                    &mod_id,
+                    predicates.map(|x| x.join(" || ")),
+                    dependency_type,
+                );
+            }
         }
 
-        Ok(ValidationResult::Pass)
+        metadata
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fabric_mod_json_metadata() {
+        let manifest = r#"{
+            "schemaVersion": 1,
+            "id": "examplemod",
+            "version": "1.2.0",
+            "environment": "client",
+            "depends": {
+                "fabricloader": ">=0.14",
+                "fabric-api": "*",
+                "minecraft": ["1.19.4", "1.20.x"]
+            },
+            "breaks": { "optifabric": "*" }
+        }"#;
+
+        let metadata = ModMetadata::from(serde_json::from_str::<FabricModJson>(manifest).unwrap());
+
+        assert_eq!(metadata.mod_id, "examplemod");
+        assert_eq!(metadata.version.as_deref(), Some("1.2.0"));
+        assert_eq!(metadata.environment, Some(ModEnvironment::Client));
+        assert_eq!(
+            metadata.game_versions,
+            Some(GameVersionRange::Predicates(vec![
+                "1.19.4".to_string(),
+                "1.20.x".to_string()
+            ]))
+        );
+        assert_eq!(
+            metadata
+                .dependencies
+                .iter()
+                .map(|x| (&*x.mod_id, x.dependency_type))
+                .collect::<Vec<_>>(),
+            vec![
+                ("fabric-api", DependencyType::Required),
+                ("optifabric", DependencyType::Incompatible)
+            ]
+        );
+    }
+
+    #[test]
+    fn source_files_are_reported() {
+        use crate::validate::Validator;
+        use std::io::Write;
+
+        let mut writer = zip::ZipWriter::new(tempfile::tempfile().unwrap());
+        let options = zip::write::FileOptions::default();
+        writer
+            .start_file("com/example/ExampleMod.java", options)
+            .unwrap();
+        writer.write_all(b"class ExampleMod {}").unwrap();
+        let file = writer.finish().unwrap();
+
+        let mut archive = ZipArchive::new(file).unwrap();
+        let findings = match FabricValidator.validate(&mut archive).unwrap() {
+            ValidationResult::Warning(findings) => findings,
+            _ => panic!("a source file passed validation"),
+        };
+
+        assert_eq!(
+            findings
+                .iter()
+                .map(|x| (x.code, x.path.as_deref()))
+                .collect::<Vec<_>>(),
+            vec![
+                (FindingCode::MissingManifest, Some("fabric.mod.json")),
+                (FindingCode::SourceFile, Some("com/example/ExampleMod.java"))
+            ]
+        );
     }
 }
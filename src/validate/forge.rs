@@ -1,6 +1,12 @@
-use crate::validate::{SupportedGameVersions, ValidationError, ValidationResult};
+use crate::models::projects::{DependencyType, FindingCode};
+use crate::validate::metadata::{read_manifest, GameVersionRange, ModMetadata};
+use crate::validate::{
+    check_compiled, warning, SupportedGameVersions, ValidationError, ValidationResult,
+};
 use chrono::{DateTime, NaiveDateTime, Utc};
-use std::io::Cursor;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs::File;
 use zip::ZipArchive;
 
 pub struct ForgeValidator;
@@ -28,23 +34,110 @@ impl super::Validator for ForgeValidator {
 
     fn validate(
         &self,
-        archive: &mut ZipArchive<Cursor<bytes::Bytes>>,
+        archive: &mut ZipArchive<File>,
     ) -> Result<ValidationResult, ValidationError> {
-        if archive.by_name("META-INF/mods.toml").is_err() {
-            return Ok(ValidationResult::Warning(
-                "No mods.toml present for Forge file.",
-            ));
-        }
+        let mut findings = Vec::new();
+        let manifest = match archive.by_name("META-INF/mods.toml") {
+            Ok(file) => Some(read_manifest(file)?),
+            Err(_) => {
+                findings.push(warning(
+                    FindingCode::MissingManifest,
+                    "No mods.toml present for Forge file.",
+                    Some("META-INF/mods.toml"),
+                ));
+                None
+            }
+        };
+        findings.extend(check_compiled(
+            archive,
+            |name| name.ends_with(".class"),
+            "Forge mod file is a source file!",
+        ));
 
-        if !archive.file_names().any(|name| name.ends_with(".class")) {
-            return Ok(ValidationResult::Warning(
-                "Forge mod file is a source file!",
-            ));
-        }
+        let manifest = match manifest {
+            Some(manifest) if findings.is_empty() => manifest,
+            _ => return Ok(ValidationResult::Warning(findings)),
+        };
 
         //TODO: Check if file is a dev JAR?
 
-        Ok(ValidationResult::Pass)
+        Ok(
+            match toml::from_slice::<ModsToml>(&manifest)
+                .ok()
+                .and_then(ModsToml::into_metadata)
+            {
+                Some(metadata) => ValidationResult::PassWithMetadata(metadata),
+                None => ValidationResult::Pass,
+            },
+        )
+    }
+}
+
+pub struct NeoForgeValidator;
+
+impl super::Validator for NeoForgeValidator {
+    fn get_file_extensions(&self) -> &[&str] {
+        &["jar", "zip"]
+    }
+
+    fn get_project_types(&self) -> &[&str] {
+        &["mod"]
+    }
+
+    fn get_supported_loaders(&self) -> &[&str] {
+        &["neoforge"]
+    }
+
+    fn get_supported_game_versions(&self) -> SupportedGameVersions {
+        // Time since release of 1.20.1, the first NeoForge version
+        SupportedGameVersions::PastDate(DateTime::from_utc(
+            NaiveDateTime::from_timestamp_opt(1686576350, 0).unwrap(),
+            Utc,
+        ))
+    }
+
+    fn validate(
+        &self,
+        archive: &mut ZipArchive<File>,
+    ) -> Result<ValidationResult, ValidationError> {
+        // Versions before 1.20.5 use the manifest of Forge
+        let manifest_path = ["META-INF/neoforge.mods.toml", "META-INF/mods.toml"]
+            .iter()
+            .copied()
+            .find(|x| archive.file_names().any(|name| name == *x));
+
+        let mut findings = Vec::new();
+        let manifest = match manifest_path {
+            Some(path) => Some(read_manifest(archive.by_name(path)?)?),
+            None => {
+                findings.push(warning(
+                    FindingCode::MissingManifest,
+                    "No neoforge.mods.toml present for NeoForge file.",
+                    Some("META-INF/neoforge.mods.toml"),
+                ));
+                None
+            }
+        };
+        findings.extend(check_compiled(
+            archive,
+            |name| name.ends_with(".class"),
+            "NeoForge mod file is a source file!",
+        ));
+
+        let manifest = match manifest {
+            Some(manifest) if findings.is_empty() => manifest,
+            _ => return Ok(ValidationResult::Warning(findings)),
+        };
+
+        Ok(
+            match toml::from_slice::<ModsToml>(&manifest)
+                .ok()
+                .and_then(ModsToml::into_metadata)
+            {
+                Some(metadata) => ValidationResult::PassWithMetadata(metadata),
+                None => ValidationResult::Pass,
+            },
+        )
     }
 }
 
@@ -79,22 +172,237 @@ impl super::Validator for LegacyForgeValidator {
 
     fn validate(
         &self,
-        archive: &mut ZipArchive<Cursor<bytes::Bytes>>,
+        archive: &mut ZipArchive<File>,
     ) -> Result<ValidationResult, ValidationError> {
-        if archive.by_name("mcmod.info").is_err() {
-            return Ok(ValidationResult::Warning(
-                "Forge mod file does not contain mcmod.info!",
-            ));
+        let mut findings = Vec::new();
+        let manifest = match archive.by_name("mcmod.info") {
+            Ok(file) => Some(read_manifest(file)?),
+            Err(_) => {
+                findings.push(warning(
+                    FindingCode::MissingManifest,
+                    "Forge mod file does not contain mcmod.info!",
+                    Some("mcmod.info"),
+                ));
+                None
+            }
         };
+        findings.extend(check_compiled(
+            archive,
+            |name| name.ends_with(".class"),
+            "Forge mod file is a source file!",
+        ));
 
-        if !archive.file_names().any(|name| name.ends_with(".class")) {
-            return Ok(ValidationResult::Warning(
-                "Forge mod file is a source file!",
-            ));
-        }
+        let manifest = match manifest {
+            Some(manifest) if findings.is_empty() => manifest,
+            _ => return Ok(ValidationResult::Warning(findings)),
+        };
 
         //TODO: Check if file is a dev JAR?
 
-        Ok(ValidationResult::Pass)
+        Ok(
+            match serde_json::from_slice::<McModInfo>(&manifest)
+                .ok()
+                .and_then(McModInfo::into_metadata)
+            {
+                Some(metadata) => ValidationResult::PassWithMetadata(metadata),
+                None => ValidationResult::Pass,
+            },
+        )
+    }
+}
+
+#[derive(Deserialize)]
+struct ModsToml {
+    mods: Vec<ModsTomlMod>,
+    #[serde(default)]
+    dependencies: HashMap<String, Vec<ModsTomlDependency>>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ModsTomlMod {
+    mod_id: String,
+    version: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ModsTomlDependency {
+    mod_id: String,
+    mandatory: Option<bool>,
+    #[serde(rename = "type")]
+    type_: Option<String>,
+    version_range: Option<String>,
+}
+
+impl ModsToml {
+    // The metadata of the first mod of the file
+    fn into_metadata(mut self) -> Option<ModMetadata> {
+        let first = self.mods.into_iter().next()?;
+
+        let mut metadata = ModMetadata {
+            // Versions are usually substituted from the build, as in `${file.jarVersion}`
+            version: first.version.filter(|x| !x.contains("${")),
+            ..Default::default()
+        };
+
+        for dependency in self.dependencies.remove(&first.mod_id).unwrap_or_default() {
+            let dependency_type = match (dependency.type_.as_deref(), dependency.mandatory) {
+                (Some("required"), _) | (None, Some(true)) => DependencyType::Required,
+                (Some("optional"), _) | (None, Some(false) | None) => DependencyType::Optional,
+                (Some("incompatible"), _) => DependencyType::Incompatible,
+                _ => continue,
+            };
+
+            if dependency.mod_id == "minecraft" && dependency_type == DependencyType::Required {
+                metadata.game_versions = dependency
+                    .version_range
+                    .clone()
+                    .map(GameVersionRange::Maven);
+            }
+
+            metadata.add_dependency(
+                &dependency.mod_id,
+                dependency.version_range,
+                dependency_type,
+            );
+        }
+
+        metadata.mod_id = first.mod_id;
+        Some(metadata)
+    }
+}
+
+/// `mcmod.info` is either a list of mods, or an object with a `modList`
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum McModInfo {
+    List(Vec<McMod>),
+    Object {
+        #[serde(rename = "modList")]
+        mod_list: Vec<McMod>,
+    },
+}
+
+#[derive(Deserialize)]
+struct McMod {
+    modid: String,
+    version: Option<String>,
+    mcversion: Option<String>,
+    #[serde(rename = "requiredMods", default)]
+    required_mods: Vec<String>,
+}
+
+impl McModInfo {
+    // The metadata of the first mod of the file
+    fn into_metadata(self) -> Option<ModMetadata> {
+        let first = match self {
+            McModInfo::List(mods) => mods,
+            McModInfo::Object { mod_list } => mod_list,
+        }
+        .into_iter()
+        .next()?;
+
+        let mut metadata = ModMetadata {
+            mod_id: first.modid,
+            version: first.version.filter(|x| !x.contains("${")),
+            // `mcversion` is usually the single version the mod was built for, but may be a range
+            game_versions: first.mcversion.filter(|x| !x.contains("${")).map(|x| {
+                if x.starts_with(['[', '(']) {
+                    GameVersionRange::Maven(x)
+                } else {
+                    GameVersionRange::Recommended(x)
+                }
+            }),
+            ..Default::default()
+        };
+
+        // Required mods are written as `modid@versionrange`
+        for required in first.required_mods {
+            let (mod_id, version_range) = match required.split_once('@') {
+                Some((mod_id, range)) => (mod_id.to_string(), Some(range.to_string())),
+                None => (required, None),
+            };
+
+            metadata.add_dependency(
+                &mod_id.to_lowercase(),
+                version_range,
+                DependencyType::Required,
+            );
+        }
+
+        Some(metadata)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mods_toml_metadata() {
+        let manifest = r#"
+            modLoader = "javafml"
+            loaderVersion = "[44,)"
+
+            [[mods]]
+            modId = "examplemod"
+            version = "${file.jarVersion}"
+
+            [[dependencies.examplemod]]
+            modId = "forge"
+            mandatory = true
+            versionRange = "[44,)"
+
+            [[dependencies.examplemod]]
+            modId = "minecraft"
+            mandatory = true
+            versionRange = "[1.19.3,1.20)"
+
+            [[dependencies.examplemod]]
+            modId = "jei"
+            mandatory = false
+            versionRange = "[12,)"
+        "#;
+
+        let metadata = toml::from_str::<ModsToml>(manifest)
+            .unwrap()
+            .into_metadata()
+            .unwrap();
+
+        assert_eq!(metadata.mod_id, "examplemod");
+        assert_eq!(metadata.version, None);
+        assert_eq!(
+            metadata.game_versions,
+            Some(GameVersionRange::Maven("[1.19.3,1.20)".to_string()))
+        );
+        assert_eq!(metadata.dependencies.len(), 1);
+        assert_eq!(metadata.dependencies[0].mod_id, "jei");
+        assert_eq!(
+            metadata.dependencies[0].dependency_type,
+            DependencyType::Optional
+        );
+    }
+    #[test]
+    fn mcmod_info_metadata() {
+        let manifest = r#"[{
+            "modid": "examplemod",
+            "version": "1.0",
+            "mcversion": "1.12.2",
+            "requiredMods": ["Forge", "jei@[4.8,)"]
+        }]"#;
+
+        let metadata = serde_json::from_str::<McModInfo>(manifest)
+            .unwrap()
+            .into_metadata()
+            .unwrap();
+
+        assert_eq!(metadata.mod_id, "examplemod");
+        assert_eq!(
+            metadata.game_versions,
+            Some(GameVersionRange::Recommended("1.12.2".to_string()))
+        );
+        assert_eq!(metadata.dependencies.len(), 1);
+        assert_eq!(metadata.dependencies[0].mod_id, "jei");
     }
 }
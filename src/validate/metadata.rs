@@ -0,0 +1,421 @@
+use crate::models::projects::DependencyType;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::io::Read;
+use utoipa::ToSchema;
+use zip::read::ZipFile;
+
+/// Mod IDs of Minecraft, Java and the loaders, which are not dependencies on other mods
+const PLATFORM_MOD_IDS: &[&str] = &[
+    "minecraft",
+    "java",
+    "fabricloader",
+    "quilt_loader",
+    "forge",
+    "neoforge",
+];
+
+/// The manifests of the loaders, and the loaders which load files containing them
+const LOADER_MANIFESTS: &[(&str, &[&str])] = &[
+    // Quilt also loads Fabric mods
+    ("fabric.mod.json", &["fabric", "quilt"]),
+    ("quilt.mod.json", &["quilt"]),
+    // NeoForge versions before 1.20.5 use the manifest of Forge
+    ("META-INF/mods.toml", &["forge", "neoforge"]),
+    ("META-INF/neoforge.mods.toml", &["neoforge"]),
+    ("mcmod.info", &["forge", "sponge"]),
+    ("riftmod.json", &["rift"]),
+    ("litemod.json", &["liteloader"]),
+    // BungeeCord falls back to the manifest of Bukkit
+    (
+        "plugin.yml",
+        &[
+            "bukkit",
+            "spigot",
+            "paper",
+            "purpur",
+            "folia",
+            "bungeecord",
+            "waterfall",
+        ],
+    ),
+    ("paper-plugin.yml", &["paper", "purpur", "folia"]),
+    ("bungee.yml", &["bungeecord", "waterfall"]),
+    ("velocity-plugin.json", &["velocity"]),
+    ("sponge_plugins.json", &["sponge"]),
+];
+
+/// Metadata declared by the manifest of a mod or plugin file
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ModMetadata {
+    /// The ID the mod declares, which other mods use to depend on it
+    pub mod_id: String,
+    /// The version the mod declares, which may not match the version number on Modrinth
+    pub version: Option<String>,
+    /// The mods the mod depends on, excluding Minecraft and its loader
+    pub dependencies: Vec<ModDependency>,
+    /// The Minecraft versions the mod declares support for
+    pub game_versions: Option<GameVersionRange>,
+    pub environment: Option<ModEnvironment>,
+    /// The loaders the file contains a manifest for, see `loaders_of_manifests`
+    pub loaders: Vec<String>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ModDependency {
+    pub mod_id: String,
+    /// The versions of the dependency the mod supports, in the syntax of its loader
+    pub version_range: Option<String>,
+    pub dependency_type: DependencyType,
+}
+
+/// The side a mod runs on
+#[derive(Serialize, Clone, Copy, Debug, Eq, PartialEq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ModEnvironment {
+    Client,
+    Server,
+    Both,
+}
+
+/// A range of Minecraft versions, in the syntax of the loader which declared it
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum GameVersionRange {
+    /// Fabric and Quilt version predicates, such as `>=1.19.2 <1.20` or `1.19.x`. Any of the
+    /// predicates must match.
+    Predicates(Vec<String>),
+    /// A Maven version range, such as `[1.19,1.20)`, as used by Forge
+    Maven(String),
+    /// The lowest supported version, such as the `api-version` of Bukkit plugins
+    AtLeast(String),
+    /// The version the mod was built for, such as the `mcversion` of `mcmod.info`. This is only a
+    /// recommendation, so versions starting with it match, and others are not rejected.
+    Recommended(String),
+}
+
+impl std::fmt::Display for GameVersionRange {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            GameVersionRange::Predicates(predicates) => write!(fmt, "{}", predicates.join(" || ")),
+            GameVersionRange::Maven(range) => write!(fmt, "{range}"),
+            GameVersionRange::AtLeast(version) => write!(fmt, ">={version}"),
+            GameVersionRange::Recommended(version) => write!(fmt, "{version}"),
+        }
+    }
+}
+
+impl GameVersionRange {
+    /// Whether the range includes the game version, or None if the range or version cannot be
+    /// compared, such as for snapshots.
+    pub fn matches(&self, game_version: &str) -> Option<bool> {
+        let version = parse_version(game_version)?;
+
+        match self {
+            GameVersionRange::Predicates(predicates) => {
+                let mut matched = false;
+                for predicate in predicates {
+                    matched |= predicate_matches(predicate, &version)?;
+                }
+                Some(matched)
+            }
+            GameVersionRange::Maven(range) => maven_range_matches(range, &version),
+            GameVersionRange::AtLeast(minimum) => {
+                Some(compare(&version, &parse_version(minimum)?) != Ordering::Less)
+            }
+            GameVersionRange::Recommended(recommended) => Some(
+                parse_version(recommended)?
+                    .iter()
+                    .enumerate()
+                    .all(|(i, x)| version.get(i).unwrap_or(&0) == x),
+            ),
+        }
+    }
+
+    /// Whether game versions outside of the range should only be noted, instead of rejected
+    pub fn is_advisory(&self) -> bool {
+        matches!(self, GameVersionRange::Recommended(_))
+    }
+}
+
+impl ModMetadata {
+    // Adds a dependency, unless it is on Minecraft, Java or a loader
+    pub(super) fn add_dependency(
+        &mut self,
+        mod_id: &str,
+        version_range: Option<String>,
+        dependency_type: DependencyType,
+    ) {
+        if !PLATFORM_MOD_IDS.contains(&mod_id) {
+            self.dependencies.push(ModDependency {
+                mod_id: mod_id.to_string(),
+                version_range,
+                dependency_type,
+            });
+        }
+    }
+}
+
+/// Fabric and Quilt version predicates, which are either a single predicate or a list of them
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub(super) enum VersionPredicates {
+    One(String),
+    Many(Vec<String>),
+    /// Other forms, such as Quilt's `any` and `all` objects, which are not compared
+    #[allow(dead_code)]
+    Other(serde_json::Value),
+}
+
+impl VersionPredicates {
+    pub(super) fn into_vec(self) -> Option<Vec<String>> {
+        match self {
+            VersionPredicates::One(predicate) => Some(vec![predicate]),
+            VersionPredicates::Many(predicates) => Some(predicates),
+            VersionPredicates::Other(_) => None,
+        }
+    }
+}
+
+/// The loaders which load a file with the given file names, by the manifests among them, in the
+/// order of `LOADER_MANIFESTS`
+pub fn loaders_of_manifests<'a>(file_names: impl Iterator<Item = &'a str>) -> Vec<String> {
+    let file_names = file_names.collect::<Vec<_>>();
+
+    let mut loaders = Vec::new();
+    for (manifest, manifest_loaders) in LOADER_MANIFESTS {
+        if file_names.contains(manifest) {
+            for loader in *manifest_loaders {
+                if !loaders.iter().any(|x| x == *loader) {
+                    loaders.push(loader.to_string());
+                }
+            }
+        }
+    }
+
+    loaders
+}
+
+/// Reads a manifest, which may not be valid UTF-8
+pub(super) fn read_manifest(mut file: ZipFile) -> Result<Vec<u8>, std::io::Error> {
+    let mut contents = Vec::new();
+    file.read_to_end(&mut contents)?;
+    Ok(contents)
+}
+
+// Release versions, such as `1.19.4`. Snapshots and pre-releases are not comparable.
+fn parse_version(version: &str) -> Option<Vec<u64>> {
+    version.split('.').map(|x| x.parse().ok()).collect()
+}
+
+fn compare(a: &[u64], b: &[u64]) -> Ordering {
+    for i in 0..a.len().max(b.len()) {
+        match a.get(i).unwrap_or(&0).cmp(b.get(i).unwrap_or(&0)) {
+            Ordering::Equal => {}
+            ordering => return ordering,
+        }
+    }
+
+    Ordering::Equal
+}
+
+// A comparison operator, and whether it accepts the ordering of a version to its operand
+type Operator = (&'static str, fn(Ordering) -> bool);
+
+// A space separated list of comparisons, all of which must match
+fn predicate_matches(predicate: &str, version: &[u64]) -> Option<bool> {
+    let mut matched = true;
+
+    for comparison in predicate.split_whitespace() {
+        matched &= comparison_matches(comparison, version)?;
+    }
+
+    Some(matched)
+}
+
+fn comparison_matches(comparison: &str, version: &[u64]) -> Option<bool> {
+    if comparison == "*" {
+        return Some(true);
+    }
+
+    let operators: &[Operator] = &[
+        (">=", |x| x != Ordering::Less),
+        ("<=", |x| x != Ordering::Greater),
+        (">", |x| x == Ordering::Greater),
+        ("<", |x| x == Ordering::Less),
+        ("=", |x| x == Ordering::Equal),
+    ];
+    for (operator, accepts) in operators {
+        if let Some(other) = comparison.strip_prefix(operator) {
+            return Some(accepts(compare(version, &parse_version(other)?)));
+        }
+    }
+
+    // `~1.19.2` allows patch updates, and `^1.19.2` minor updates
+    for (operator, kept) in [("~", 2), ("^", 1)] {
+        if let Some(other) = comparison.strip_prefix(operator) {
+            let lower = parse_version(other)?;
+            let mut upper = lower.iter().copied().take(kept).collect::<Vec<_>>();
+            upper.resize(kept, 0);
+            let last = upper.last_mut()?;
+            *last = last.checked_add(1)?;
+
+            return Some(
+                compare(version, &lower) != Ordering::Less
+                    && compare(version, &upper) == Ordering::Less,
+            );
+        }
+    }
+
+    // `1.19.x` matches any version starting with `1.19`
+    if let Some(prefix) = comparison
+        .strip_suffix(".x")
+        .or_else(|| comparison.strip_suffix(".X"))
+        .or_else(|| comparison.strip_suffix(".*"))
+    {
+        let prefix = parse_version(prefix)?;
+        return Some(
+            prefix
+                .iter()
+                .enumerate()
+                .all(|(i, x)| version.get(i).unwrap_or(&0) == x),
+        );
+    }
+
+    Some(compare(version, &parse_version(comparison)?) == Ordering::Equal)
+}
+
+// A comma separated list of ranges such as `[1.18,1.19)` or `[1.19.2]`, any of which must match.
+// A bare version is only a recommendation, so it matches any version.
+fn maven_range_matches(range: &str, version: &[u64]) -> Option<bool> {
+    let range = range.trim();
+    if range.is_empty() || !range.starts_with(['[', '(']) {
+        return Some(true);
+    }
+
+    let mut matched = false;
+    let mut rest = range;
+    while !rest.is_empty() {
+        let end = rest.find([']', ')'])?;
+        let (bounds, remaining) = rest.split_at(end + 1);
+        rest = remaining.trim_start_matches(',').trim();
+
+        // Each range must be opened, such as `)` after another range
+        if !bounds.starts_with(['[', '(']) {
+            return None;
+        }
+        let lower_inclusive = bounds.starts_with('[');
+        let upper_inclusive = bounds.ends_with(']');
+        let bounds = bounds.get(1..bounds.len().saturating_sub(1))?;
+
+        matched |= match bounds.split_once(',') {
+            Some((lower, upper)) => {
+                let lower_ok = match lower.trim() {
+                    "" => true,
+                    lower => match compare(version, &parse_version(lower)?) {
+                        Ordering::Greater => true,
+                        Ordering::Equal => lower_inclusive,
+                        Ordering::Less => false,
+                    },
+                };
+                let upper_ok = match upper.trim() {
+                    "" => true,
+                    upper => match compare(version, &parse_version(upper)?) {
+                        Ordering::Less => true,
+                        Ordering::Equal => upper_inclusive,
+                        Ordering::Greater => false,
+                    },
+                };
+
+                lower_ok && upper_ok
+            }
+            None => compare(version, &parse_version(bounds.trim())?) == Ordering::Equal,
+        };
+    }
+
+    Some(matched)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn predicates(predicates: &[&str]) -> GameVersionRange {
+        GameVersionRange::Predicates(predicates.iter().map(|x| x.to_string()).collect())
+    }
+
+    #[test]
+    fn fabric_predicates() {
+        assert_eq!(predicates(&["*"]).matches("1.20.1"), Some(true));
+        assert_eq!(predicates(&["1.19.x"]).matches("1.19.4"), Some(true));
+        assert_eq!(predicates(&["1.19.x"]).matches("1.20"), Some(false));
+        assert_eq!(predicates(&["1.19"]).matches("1.19.0"), Some(true));
+        assert_eq!(
+            predicates(&[">=1.19.2 <1.20"]).matches("1.19.4"),
+            Some(true)
+        );
+        assert_eq!(predicates(&[">=1.19.2 <1.20"]).matches("1.20"), Some(false));
+        assert_eq!(predicates(&["~1.19.2"]).matches("1.19.4"), Some(true));
+        assert_eq!(predicates(&["~1.19.2"]).matches("1.20"), Some(false));
+        assert_eq!(
+            predicates(&["1.18.2", "1.19.2"]).matches("1.19.2"),
+            Some(true)
+        );
+        assert_eq!(predicates(&[">=1.19"]).matches("23w13a"), None);
+    }
+
+    #[test]
+    fn maven_ranges() {
+        let range = |x: &str| GameVersionRange::Maven(x.to_string());
+
+        assert_eq!(range("[1.19,1.20)").matches("1.19.4"), Some(true));
+        assert_eq!(range("[1.19,1.20)").matches("1.20"), Some(false));
+        assert_eq!(range("[1.19.2]").matches("1.19.2"), Some(true));
+        assert_eq!(range("[1.19.2]").matches("1.19.3"), Some(false));
+        assert_eq!(range("[1.18,)").matches("1.20.1"), Some(true));
+        assert_eq!(range("(,1.18]").matches("1.18"), Some(true));
+        assert_eq!(range("[1.16.5],[1.18,1.19)").matches("1.16.5"), Some(true));
+        assert_eq!(range("[1.16.5],[1.18,1.19)").matches("1.17.1"), Some(false));
+        assert_eq!(range("1.19").matches("1.12.2"), Some(true));
+    }
+
+    #[test]
+    fn malformed_maven_ranges() {
+        let range = |x: &str| GameVersionRange::Maven(x.to_string());
+
+        assert_eq!(range("[1.19,1.20))").matches("1.19.4"), None);
+        assert_eq!(range("[1.19],é1.20]").matches("1.19"), None);
+        assert_eq!(range("[1.19,1.20").matches("1.19.4"), None);
+        assert_eq!(range("[]").matches("1.19.4"), None);
+        assert_eq!(range("[").matches("1.19.4"), None);
+    }
+
+    #[test]
+    fn recommended_versions() {
+        let range = GameVersionRange::Recommended("1.12".to_string());
+
+        assert_eq!(range.matches("1.12.2"), Some(true));
+        assert_eq!(range.matches("1.7.10"), Some(false));
+        assert!(range.is_advisory());
+        assert!(!GameVersionRange::Maven("[1.12,1.13)".to_string()).is_advisory());
+    }
+
+    #[test]
+    fn loaders_are_found_by_their_manifests() {
+        let loaders = |names: &[&str]| loaders_of_manifests(names.iter().copied());
+
+        assert_eq!(loaders(&["fabric.mod.json"]), vec!["fabric", "quilt"]);
+        assert_eq!(
+            loaders(&["META-INF/mods.toml", "fabric.mod.json"]),
+            vec!["fabric", "quilt", "forge", "neoforge"]
+        );
+        assert_eq!(loaders(&["assets/fabric.mod.json"]), Vec::<String>::new());
+    }
+
+    #[test]
+    fn minimum_versions() {
+        let range = GameVersionRange::AtLeast("1.13".to_string());
+
+        assert_eq!(range.matches("1.19.4"), Some(true));
+        assert_eq!(range.matches("1.12.2"), Some(false));
+    }
+}
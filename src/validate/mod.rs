@@ -1,16 +1,22 @@
 use crate::models::pack::PackFormat;
-use crate::models::projects::{FileType, GameVersion, Loader};
+use crate::models::projects::{
+    FileFinding, FileType, FindingCode, FindingSeverity, GameVersion, Loader,
+};
 use crate::validate::datapack::DataPackValidator;
 use crate::validate::fabric::FabricValidator;
-use crate::validate::forge::{ForgeValidator, LegacyForgeValidator};
+use crate::validate::forge::{ForgeValidator, LegacyForgeValidator, NeoForgeValidator};
 use crate::validate::liteloader::LiteLoaderValidator;
+use crate::validate::metadata::ModMetadata;
+use crate::validate::modloader::ModLoaderValidator;
 use crate::validate::modpack::ModpackValidator;
 use crate::validate::plugin::*;
 use crate::validate::quilt::QuiltValidator;
 use crate::validate::resourcepack::{PackValidator, TexturePackValidator};
+use crate::validate::rift::RiftValidator;
 use crate::validate::shader::{CanvasShaderValidator, CoreShaderValidator, ShaderValidator};
+use crate::validate::world::WorldValidator;
 use chrono::{DateTime, Utc};
-use std::io::Cursor;
+use std::fs::File;
 use thiserror::Error;
 use zip::ZipArchive;
 
@@ -18,11 +24,16 @@ mod datapack;
 mod fabric;
 mod forge;
 mod liteloader;
+pub mod metadata;
+mod modloader;
 mod modpack;
 pub mod plugin;
 mod quilt;
 mod resourcepack;
+mod rift;
+pub mod scan;
 mod shader;
+mod world;
 
 #[derive(Error, Debug)]
 pub enum ValidationError {
@@ -45,20 +56,54 @@ pub enum ValidationResult {
         format: PackFormat,
         files: Vec<String>,
     },
+    /// File should be marked as primary with the metadata of its manifest
+    PassWithMetadata(ModMetadata),
     /// File should be marked as primary
     Pass,
-    /// File should not be marked primary, the reason for which is inside the String
-    Warning(&'static str),
+    /// File should not be marked primary, for the reasons of the findings
+    Warning(Vec<FileFinding>),
 }
 
 impl ValidationResult {
     pub fn is_passed(&self) -> bool {
         match self {
             ValidationResult::PassWithPackDataAndFiles { .. } => true,
+            ValidationResult::PassWithMetadata(_) => true,
             ValidationResult::Pass => true,
             ValidationResult::Warning(_) => false,
         }
     }
+
+    /// A warning with a single finding
+    pub fn warning(code: FindingCode, message: &str, path: Option<&str>) -> Self {
+        ValidationResult::Warning(vec![warning(code, message, path)])
+    }
+}
+
+fn warning(code: FindingCode, message: &str, path: Option<&str>) -> FileFinding {
+    FileFinding {
+        severity: FindingSeverity::Warning,
+        code,
+        message: message.to_string(),
+        path: path.map(|x| x.to_string()),
+    }
+}
+
+/// Whether a mod file contains compiled classes, or a finding pointing at its source code
+fn check_compiled(
+    archive: &ZipArchive<File>,
+    is_compiled: fn(&str) -> bool,
+    message: &str,
+) -> Option<FileFinding> {
+    if archive.file_names().any(is_compiled) {
+        None
+    } else {
+        Some(warning(
+            FindingCode::SourceFile,
+            message,
+            archive.file_names().find(|x| x.ends_with(".java")),
+        ))
+    }
 }
 
 pub enum SupportedGameVersions {
@@ -74,10 +119,8 @@ pub trait Validator: Sync {
     fn get_project_types(&self) -> &[&str];
     fn get_supported_loaders(&self) -> &[&str];
     fn get_supported_game_versions(&self) -> SupportedGameVersions;
-    fn validate(
-        &self,
-        archive: &mut ZipArchive<Cursor<bytes::Bytes>>,
-    ) -> Result<ValidationResult, ValidationError>;
+    fn validate(&self, archive: &mut ZipArchive<File>)
+        -> Result<ValidationResult, ValidationError>;
 }
 
 static ALWAYS_ALLOWED_EXT: &[&str] = &["zip", "txt"];
@@ -87,10 +130,14 @@ static VALIDATORS: &[&dyn Validator] = &[
     &FabricValidator,
     &ForgeValidator,
     &LegacyForgeValidator,
+    &NeoForgeValidator,
     &QuiltValidator,
     &LiteLoaderValidator,
+    &RiftValidator,
+    &ModLoaderValidator,
     &PackValidator,
     &TexturePackValidator,
+    &PaperPluginValidator,
     &PluginYmlValidator,
     &BungeeCordValidator,
     &VelocityValidator,
@@ -99,11 +146,12 @@ static VALIDATORS: &[&dyn Validator] = &[
     &ShaderValidator,
     &CoreShaderValidator,
     &DataPackValidator,
+    &WorldValidator,
 ];
 
 /// The return value is whether this file should be marked as primary or not, based on the analysis of the file
 pub async fn validate_file(
-    data: bytes::Bytes,
+    file: File,
     file_extension: String,
     mut project_type: String,
     mut loaders: Vec<Loader>,
@@ -112,8 +160,7 @@ pub async fn validate_file(
     file_type: Option<FileType>,
 ) -> Result<ValidationResult, ValidationError> {
     actix_web::web::block(move || {
-        let reader = Cursor::new(data);
-        let mut zip = ZipArchive::new(reader)?;
+        let mut zip = ZipArchive::new(file)?;
 
         if let Some(file_type) = file_type {
             match file_type {
@@ -138,7 +185,13 @@ pub async fn validate_file(
                 )
             {
                 if validator.get_file_extensions().contains(&&*file_extension) {
-                    return validator.validate(&mut zip);
+                    return Ok(match validator.validate(&mut zip)? {
+                        ValidationResult::PassWithMetadata(mut metadata) => {
+                            metadata.loaders = metadata::loaders_of_manifests(zip.file_names());
+                            ValidationResult::PassWithMetadata(metadata)
+                        }
+                        result => result,
+                    });
                 } else {
                     visited = true;
                 }
@@ -147,8 +200,10 @@ pub async fn validate_file(
 
         if visited {
             if ALWAYS_ALLOWED_EXT.contains(&&*file_extension) {
-                Ok(ValidationResult::Warning(
+                Ok(ValidationResult::warning(
+                    FindingCode::InvalidExtension,
                     "File extension is invalid for input file",
+                    None,
                 ))
             } else {
                 Err(ValidationError::InvalidInput(
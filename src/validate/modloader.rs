@@ -0,0 +1,64 @@
+use crate::models::projects::FindingCode;
+use crate::validate::{
+    check_compiled, warning, SupportedGameVersions, ValidationError, ValidationResult,
+};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use std::fs::File;
+use zip::ZipArchive;
+
+/// Risugami's ModLoader, which has no manifest. Mods are found by their `mod_` classes.
+pub struct ModLoaderValidator;
+
+impl super::Validator for ModLoaderValidator {
+    fn get_file_extensions(&self) -> &[&str] {
+        &["zip", "jar"]
+    }
+
+    fn get_project_types(&self) -> &[&str] {
+        &["mod"]
+    }
+
+    fn get_supported_loaders(&self) -> &[&str] {
+        &["modloader"]
+    }
+
+    fn get_supported_game_versions(&self) -> SupportedGameVersions {
+        // Times up to 1.6.2, the last version ModLoader was released for
+        SupportedGameVersions::Range(
+            DateTime::from_utc(NaiveDateTime::from_timestamp_opt(0, 0).unwrap(), Utc),
+            DateTime::from_utc(
+                NaiveDateTime::from_timestamp_opt(1373029743, 0).unwrap(),
+                Utc,
+            ),
+        )
+    }
+
+    fn validate(
+        &self,
+        archive: &mut ZipArchive<File>,
+    ) -> Result<ValidationResult, ValidationError> {
+        let mut findings = Vec::new();
+
+        if !archive
+            .file_names()
+            .any(|name| name.starts_with("mod_") && name.ends_with(".class") && !name.contains('/'))
+        {
+            findings.push(warning(
+                FindingCode::MissingContent,
+                "No mod_ class present at the root of the ModLoader file.",
+                None,
+            ));
+        }
+        findings.extend(check_compiled(
+            archive,
+            |name| name.ends_with(".class"),
+            "ModLoader mod file is a source file!",
+        ));
+
+        if !findings.is_empty() {
+            return Ok(ValidationResult::Warning(findings));
+        }
+
+        Ok(ValidationResult::Pass)
+    }
+}
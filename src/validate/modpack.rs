@@ -1,7 +1,9 @@
 use crate::models::pack::{PackFileHash, PackFormat};
+use crate::models::projects::FindingCode;
 use crate::util::validate::validation_errors_to_string;
 use crate::validate::{SupportedGameVersions, ValidationError, ValidationResult};
-use std::io::{Cursor, Read};
+use std::fs::File;
+use std::io::Read;
 use std::path::Component;
 use validator::Validate;
 use zip::ZipArchive;
@@ -27,13 +29,17 @@ impl super::Validator for ModpackValidator {
 
     fn validate(
         &self,
-        archive: &mut ZipArchive<Cursor<bytes::Bytes>>,
+        archive: &mut ZipArchive<File>,
     ) -> Result<ValidationResult, ValidationError> {
         let pack: PackFormat = {
             let mut file = if let Ok(file) = archive.by_name("modrinth.index.json") {
                 file
             } else {
-                return Ok(ValidationResult::Warning("Pack manifest is missing."));
+                return Ok(ValidationResult::warning(
+                    FindingCode::MissingManifest,
+                    "Pack manifest is missing.",
+                    Some("modrinth.index.json"),
+                ));
             };
 
             let mut contents = String::new();
@@ -1,5 +1,10 @@
+use crate::models::projects::{DependencyType, FindingCode};
+use crate::validate::metadata::{read_manifest, GameVersionRange, ModEnvironment, ModMetadata};
 use crate::validate::{SupportedGameVersions, ValidationError, ValidationResult};
-use std::io::Cursor;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs::File;
 use zip::ZipArchive;
 
 pub struct PluginYmlValidator;
@@ -30,15 +35,76 @@ impl super::Validator for PluginYmlValidator {
 
     fn validate(
         &self,
-        archive: &mut ZipArchive<Cursor<bytes::Bytes>>,
+        archive: &mut ZipArchive<File>,
     ) -> Result<ValidationResult, ValidationError> {
-        if archive.by_name("plugin.yml").is_err() {
-            return Ok(ValidationResult::Warning(
+        let manifest = if let Ok(file) = archive.by_name("plugin.yml") {
+            read_manifest(file)?
+        } else {
+            return Ok(ValidationResult::warning(
+                FindingCode::MissingManifest,
                 "No plugin.yml present for plugin file.",
+                Some("plugin.yml"),
             ));
+        };
+
+        Ok(match serde_yaml::from_slice::<PluginYml>(&manifest) {
+            Ok(manifest) => ValidationResult::PassWithMetadata(manifest.into()),
+            Err(_) => ValidationResult::Pass,
+        })
+    }
+}
+
+/// Paper and its forks, whose plugins may declare a `paper-plugin.yml` instead of a `plugin.yml`
+pub struct PaperPluginValidator;
+
+impl super::Validator for PaperPluginValidator {
+    fn get_file_extensions(&self) -> &[&str] {
+        &["zip", "jar"]
+    }
+
+    fn get_project_types(&self) -> &[&str] {
+        &["mod"]
+    }
+
+    fn get_supported_loaders(&self) -> &[&str] {
+        &["paper", "purpur", "folia"]
+    }
+
+    fn get_supported_game_versions(&self) -> SupportedGameVersions {
+        // Time since release of 1.19.3, the first version which loads `paper-plugin.yml`
+        SupportedGameVersions::PastDate(DateTime::from_utc(
+            NaiveDateTime::from_timestamp_opt(1670401037, 0).unwrap(),
+            Utc,
+        ))
+    }
+
+    fn validate(
+        &self,
+        archive: &mut ZipArchive<File>,
+    ) -> Result<ValidationResult, ValidationError> {
+        if let Ok(file) = archive.by_name("paper-plugin.yml") {
+            let manifest = read_manifest(file)?;
+
+            return Ok(match serde_yaml::from_slice::<PaperPluginYml>(&manifest) {
+                Ok(manifest) => ValidationResult::PassWithMetadata(manifest.into()),
+                Err(_) => ValidationResult::Pass,
+            });
         }
 
-        Ok(ValidationResult::Pass)
+        let manifest = if let Ok(file) = archive.by_name("plugin.yml") {
+            read_manifest(file)?
+        } else {
+            return Ok(ValidationResult::warning(
+                FindingCode::MissingManifest,
+                "No paper-plugin.yml or plugin.yml present for plugin file.",
+                Some("paper-plugin.yml"),
+            ));
+        };
+
+        Ok(match serde_yaml::from_slice::<PluginYml>(&manifest) {
+            Ok(manifest) => ValidationResult::PassWithMetadata(manifest.into()),
+            Err(_) => ValidationResult::Pass,
+        })
     }
 }
 
@@ -63,15 +129,22 @@ impl super::Validator for BungeeCordValidator {
 
     fn validate(
         &self,
-        archive: &mut ZipArchive<Cursor<bytes::Bytes>>,
+        archive: &mut ZipArchive<File>,
     ) -> Result<ValidationResult, ValidationError> {
-        if archive.by_name("bungee.yml").is_err() {
-            return Ok(ValidationResult::Warning(
+        let manifest = if let Ok(file) = archive.by_name("bungee.yml") {
+            read_manifest(file)?
+        } else {
+            return Ok(ValidationResult::warning(
+                FindingCode::MissingManifest,
                 "No bungee.yml present for plugin file.",
+                Some("bungee.yml"),
             ));
-        }
+        };
 
-        Ok(ValidationResult::Pass)
+        Ok(match serde_yaml::from_slice::<BungeeYml>(&manifest) {
+            Ok(manifest) => ValidationResult::PassWithMetadata(manifest.into()),
+            Err(_) => ValidationResult::Pass,
+        })
     }
 }
 
@@ -96,15 +169,24 @@ impl super::Validator for VelocityValidator {
 
     fn validate(
         &self,
-        archive: &mut ZipArchive<Cursor<bytes::Bytes>>,
+        archive: &mut ZipArchive<File>,
     ) -> Result<ValidationResult, ValidationError> {
-        if archive.by_name("velocity-plugin.json").is_err() {
-            return Ok(ValidationResult::Warning(
+        let manifest = if let Ok(file) = archive.by_name("velocity-plugin.json") {
+            read_manifest(file)?
+        } else {
+            return Ok(ValidationResult::warning(
+                FindingCode::MissingManifest,
                 "No velocity-plugin.json present for plugin file.",
+                Some("velocity-plugin.json"),
             ));
-        }
+        };
 
-        Ok(ValidationResult::Pass)
+        Ok(
+            match serde_json::from_slice::<VelocityPluginJson>(&manifest) {
+                Ok(manifest) => ValidationResult::PassWithMetadata(manifest.into()),
+                Err(_) => ValidationResult::Pass,
+            },
+        )
     }
 }
 
@@ -129,17 +211,261 @@ impl super::Validator for SpongeValidator {
 
     fn validate(
         &self,
-        archive: &mut ZipArchive<Cursor<bytes::Bytes>>,
+        archive: &mut ZipArchive<File>,
     ) -> Result<ValidationResult, ValidationError> {
         if !archive
             .file_names()
             .any(|name| name == "sponge_plugins.json" || name == "mcmod.info")
         {
-            return Ok(ValidationResult::Warning(
+            return Ok(ValidationResult::warning(
+                FindingCode::MissingManifest,
                 "No sponge_plugins.json or mcmod.info present for Sponge plugin.",
+                None,
             ));
         };
 
         Ok(ValidationResult::Pass)
     }
 }
+
+/// A scalar YAML value, as versions are sometimes written as numbers
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum YamlScalar {
+    String(String),
+    Number(serde_yaml::Number),
+}
+
+impl YamlScalar {
+    fn into_string(self) -> String {
+        match self {
+            YamlScalar::String(string) => string,
+            YamlScalar::Number(number) => number.to_string(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct PluginYml {
+    name: String,
+    version: Option<YamlScalar>,
+    #[serde(rename = "api-version")]
+    api_version: Option<YamlScalar>,
+    #[serde(default)]
+    depend: Vec<String>,
+    #[serde(default)]
+    softdepend: Vec<String>,
+}
+
+impl From<PluginYml> for ModMetadata {
+    fn from(manifest: PluginYml) -> Self {
+        let mut metadata = ModMetadata {
+            mod_id: manifest.name,
+            version: manifest.version.map(YamlScalar::into_string),
+            game_versions: manifest
+                .api_version
+                .map(|x| GameVersionRange::AtLeast(x.into_string())),
+            environment: Some(ModEnvironment::Server),
+            ..Default::default()
+        };
+
+        for plugin in manifest.depend {
+            metadata.add_dependency(&plugin, None, DependencyType::Required);
+        }
+        for plugin in manifest.softdepend {
+            metadata.add_dependency(&plugin, None, DependencyType::Optional);
+        }
+
+        metadata
+    }
+}
+
+#[derive(Deserialize)]
+struct PaperPluginYml {
+    name: String,
+    version: Option<YamlScalar>,
+    #[serde(rename = "api-version")]
+    api_version: Option<YamlScalar>,
+    dependencies: Option<PaperDependencies>,
+}
+
+/// Dependencies were first a list, then split into those of the server and of the bootstrapper
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum PaperDependencies {
+    Sections {
+        #[serde(default)]
+        server: BTreeMap<String, PaperDependency>,
+    },
+    List(Vec<PaperListDependency>),
+}
+
+#[derive(Deserialize)]
+struct PaperDependency {
+    #[serde(default = "default_required")]
+    required: bool,
+}
+
+#[derive(Deserialize)]
+struct PaperListDependency {
+    name: String,
+    #[serde(default = "default_required")]
+    required: bool,
+}
+
+fn default_required() -> bool {
+    true
+}
+
+impl From<PaperPluginYml> for ModMetadata {
+    fn from(manifest: PaperPluginYml) -> Self {
+        let mut metadata = ModMetadata {
+            mod_id: manifest.name,
+            version: manifest.version.map(YamlScalar::into_string),
+            game_versions: manifest
+                .api_version
+                .map(|x| GameVersionRange::AtLeast(x.into_string())),
+            environment: Some(ModEnvironment::Server),
+            ..Default::default()
+        };
+
+        let dependencies: Vec<(String, bool)> = match manifest.dependencies {
+            Some(PaperDependencies::Sections { server }) => server
+                .into_iter()
+                .map(|(name, dependency)| (name, dependency.required))
+                .collect(),
+            Some(PaperDependencies::List(list)) => {
+                list.into_iter().map(|x| (x.name, x.required)).collect()
+            }
+            None => Vec::new(),
+        };
+        for (plugin, required) in dependencies {
+            metadata.add_dependency(
+                &plugin,
+                None,
+                if required {
+                    DependencyType::Required
+                } else {
+                    DependencyType::Optional
+                },
+            );
+        }
+
+        metadata
+    }
+}
+
+#[derive(Deserialize)]
+struct BungeeYml {
+    name: String,
+    version: Option<YamlScalar>,
+    #[serde(default)]
+    depends: Vec<String>,
+    #[serde(rename = "softDepends", default)]
+    soft_depends: Vec<String>,
+}
+
+impl From<BungeeYml> for ModMetadata {
+    fn from(manifest: BungeeYml) -> Self {
+        let mut metadata = ModMetadata {
+            mod_id: manifest.name,
+            version: manifest.version.map(YamlScalar::into_string),
+            environment: Some(ModEnvironment::Server),
+            ..Default::default()
+        };
+
+        for plugin in manifest.depends {
+            metadata.add_dependency(&plugin, None, DependencyType::Required);
+        }
+        for plugin in manifest.soft_depends {
+            metadata.add_dependency(&plugin, None, DependencyType::Optional);
+        }
+
+        metadata
+    }
+}
+
+#[derive(Deserialize)]
+struct VelocityPluginJson {
+    id: String,
+    version: Option<String>,
+    #[serde(default)]
+    dependencies: Vec<VelocityDependency>,
+}
+
+#[derive(Deserialize)]
+struct VelocityDependency {
+    id: String,
+    #[serde(default)]
+    optional: bool,
+}
+
+impl From<VelocityPluginJson> for ModMetadata {
+    fn from(manifest: VelocityPluginJson) -> Self {
+        let mut metadata = ModMetadata {
+            mod_id: manifest.id,
+            version: manifest.version,
+            environment: Some(ModEnvironment::Server),
+            ..Default::default()
+        };
+
+        for dependency in manifest.dependencies {
+            let dependency_type = if dependency.optional {
+                DependencyType::Optional
+            } else {
+                DependencyType::Required
+            };
+            metadata.add_dependency(&dependency.id, None, dependency_type);
+        }
+
+        metadata
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paper_plugin_yml_dependencies() {
+        let dependencies = |manifest: &str| {
+            ModMetadata::from(serde_yaml::from_str::<PaperPluginYml>(manifest).unwrap())
+                .dependencies
+                .into_iter()
+                .map(|x| (x.mod_id, x.dependency_type))
+                .collect::<Vec<_>>()
+        };
+
+        let sections = dependencies(
+            "
+name: Example
+version: 1.0
+api-version: '1.20'
+dependencies:
+  server:
+    Vault:
+      load: BEFORE
+    LuckPerms:
+      required: false
+",
+        );
+        let list = dependencies(
+            "
+name: Example
+dependencies:
+  - name: LuckPerms
+    required: false
+  - name: Vault
+",
+        );
+
+        assert_eq!(
+            sections,
+            vec![
+                ("LuckPerms".to_string(), DependencyType::Optional),
+                ("Vault".to_string(), DependencyType::Required)
+            ]
+        );
+        assert_eq!(list, sections);
+    }
+}
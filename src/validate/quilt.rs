@@ -1,6 +1,13 @@
-use crate::validate::{SupportedGameVersions, ValidationError, ValidationResult};
+use crate::models::projects::{DependencyType, FindingCode};
+use crate::validate::metadata::{
+    read_manifest, GameVersionRange, ModEnvironment, ModMetadata, VersionPredicates,
+};
+use crate::validate::{
+    check_compiled, warning, SupportedGameVersions, ValidationError, ValidationResult,
+};
 use chrono::{DateTime, NaiveDateTime, Utc};
-use std::io::Cursor;
+use serde::Deserialize;
+use std::fs::File;
 use zip::ZipArchive;
 
 pub struct QuiltValidator;
@@ -27,23 +34,122 @@ impl super::Validator for QuiltValidator {
 
     fn validate(
         &self,
-        archive: &mut ZipArchive<Cursor<bytes::Bytes>>,
+        archive: &mut ZipArchive<File>,
     ) -> Result<ValidationResult, ValidationError> {
-        if archive.by_name("quilt.mod.json").is_err() {
-            return Ok(ValidationResult::Warning(
-                "No quilt.mod.json present for Quilt file.",
-            ));
-        }
+        let mut findings = Vec::new();
+        let manifest = match archive.by_name("quilt.mod.json") {
+            Ok(file) => Some(read_manifest(file)?),
+            Err(_) => {
+                findings.push(warning(
+                    FindingCode::MissingManifest,
+                    "No quilt.mod.json present for Quilt file.",
+                    Some("quilt.mod.json"),
+                ));
+                None
+            }
+        };
+        findings.extend(check_compiled(
+            archive,
+            |name| name.ends_with("refmap.json") || name.ends_with(".class"),
+            "Quilt mod file is a source file!",
+        ));
+
+        let manifest = match manifest {
+            Some(manifest) if findings.is_empty() => manifest,
+            _ => return Ok(ValidationResult::Warning(findings)),
+        };
+
+        Ok(match serde_json::from_slice::<QuiltModJson>(&manifest) {
+            Ok(manifest) => ValidationResult::PassWithMetadata(manifest.into()),
+            Err(_) => ValidationResult::Pass,
+        })
+    }
+}
+
+#[derive(Deserialize)]
+struct QuiltModJson {
+    quilt_loader: QuiltLoader,
+    minecraft: Option<QuiltMinecraft>,
+}
+
+#[derive(Deserialize)]
+struct QuiltLoader {
+    id: String,
+    version: Option<String>,
+    #[serde(default)]
+    depends: Vec<QuiltDependency>,
+    #[serde(default)]
+    breaks: Vec<QuiltDependency>,
+}
+
+#[derive(Deserialize)]
+struct QuiltMinecraft {
+    environment: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum QuiltDependency {
+    Id(String),
+    Object {
+        id: String,
+        versions: Option<VersionPredicates>,
+        #[serde(default)]
+        optional: bool,
+    },
+}
+
+impl From<QuiltModJson> for ModMetadata {
+    fn from(manifest: QuiltModJson) -> Self {
+        let mut metadata = ModMetadata {
+            mod_id: manifest.quilt_loader.id,
+            version: manifest.quilt_loader.version,
+            environment: match manifest.minecraft.and_then(|x| x.environment).as_deref() {
+                Some("client") => Some(ModEnvironment::Client),
+                Some("dedicated_server") => Some(ModEnvironment::Server),
+                Some("*") | None => Some(ModEnvironment::Both),
+                Some(_) => None,
+            },
+            ..Default::default()
+        };
+
+        let dependencies = manifest
+            .quilt_loader
+            .depends
+            .into_iter()
+            .map(|x| (x, DependencyType::Required))
+            .chain(
+                manifest
+                    .quilt_loader
+                    .breaks
+                    .into_iter()
+                    .map(|x| (x, DependencyType::Incompatible)),
+            );
+        for (dependency, dependency_type) in dependencies {
+            let (mod_id, predicates, dependency_type) = match dependency {
+                QuiltDependency::Id(id) => (id, None, dependency_type),
+                QuiltDependency::Object {
+                    id,
+                    versions,
+                    optional,
+                } => (
+                    id,
+                    versions.and_then(|x| x.into_vec()),
+                    if optional && dependency_type == DependencyType::Required {
+                        DependencyType::Optional
+                    } else {
+                        dependency_type
+                    },
+                ),
+            };
+
+            if mod_id == "minecraft" && dependency_type == DependencyType::Required {
+                metadata.game_versions = predicates.clone().map(GameVersionRange::Predicates);
+            }
 
-        if !archive
-            .file_names()
-            .any(|name| name.ends_with("refmap.json") || name.ends_with(".class"))
-        {
-            return Ok(ValidationResult::Warning(
-                "Quilt mod file is a source file!",
-            ));
+            metadata.add_dependency(&mod_id, predicates.map(|x| x.join(" || ")), dependency_type);
         }
 
-        Ok(ValidationResult::Pass)
+        metadata
     }
 }
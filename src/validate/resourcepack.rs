@@ -1,6 +1,7 @@
+use crate::models::projects::FindingCode;
 use crate::validate::{SupportedGameVersions, ValidationError, ValidationResult};
 use chrono::{DateTime, NaiveDateTime, Utc};
-use std::io::Cursor;
+use std::fs::File;
 use zip::ZipArchive;
 
 pub struct PackValidator;
@@ -28,11 +29,13 @@ impl super::Validator for PackValidator {
 
     fn validate(
         &self,
-        archive: &mut ZipArchive<Cursor<bytes::Bytes>>,
+        archive: &mut ZipArchive<File>,
     ) -> Result<ValidationResult, ValidationError> {
         if archive.by_name("pack.mcmeta").is_err() {
-            return Ok(ValidationResult::Warning(
+            return Ok(ValidationResult::warning(
+                FindingCode::MissingManifest,
                 "No pack.mcmeta present for pack file. Tip: Make sure pack.mcmeta is in the root directory of your pack!",
+                Some("pack.mcmeta"),
             ));
         }
 
@@ -71,11 +74,13 @@ impl super::Validator for TexturePackValidator {
 
     fn validate(
         &self,
-        archive: &mut ZipArchive<Cursor<bytes::Bytes>>,
+        archive: &mut ZipArchive<File>,
     ) -> Result<ValidationResult, ValidationError> {
         if archive.by_name("pack.txt").is_err() {
-            return Ok(ValidationResult::Warning(
+            return Ok(ValidationResult::warning(
+                FindingCode::MissingManifest,
                 "No pack.txt present for pack file.",
+                Some("pack.txt"),
             ));
         }
 
@@ -0,0 +1,81 @@
+use crate::models::projects::FindingCode;
+use crate::validate::metadata::{read_manifest, ModMetadata};
+use crate::validate::{
+    check_compiled, warning, SupportedGameVersions, ValidationError, ValidationResult,
+};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde::Deserialize;
+use std::fs::File;
+use zip::ZipArchive;
+
+pub struct RiftValidator;
+
+impl super::Validator for RiftValidator {
+    fn get_file_extensions(&self) -> &[&str] {
+        &["jar", "zip"]
+    }
+
+    fn get_project_types(&self) -> &[&str] {
+        &["mod"]
+    }
+
+    fn get_supported_loaders(&self) -> &[&str] {
+        &["rift"]
+    }
+
+    fn get_supported_game_versions(&self) -> SupportedGameVersions {
+        // Times between versions 1.13 to 1.13.2, the only versions Rift was released for
+        SupportedGameVersions::Range(
+            DateTime::from_utc(
+                NaiveDateTime::from_timestamp_opt(1531926705, 0).unwrap(),
+                Utc,
+            ),
+            DateTime::from_utc(
+                NaiveDateTime::from_timestamp_opt(1540208468, 0).unwrap(),
+                Utc,
+            ),
+        )
+    }
+
+    fn validate(
+        &self,
+        archive: &mut ZipArchive<File>,
+    ) -> Result<ValidationResult, ValidationError> {
+        let mut findings = Vec::new();
+        let manifest = match archive.by_name("riftmod.json") {
+            Ok(file) => Some(read_manifest(file)?),
+            Err(_) => {
+                findings.push(warning(
+                    FindingCode::MissingManifest,
+                    "No riftmod.json present for Rift file.",
+                    Some("riftmod.json"),
+                ));
+                None
+            }
+        };
+        findings.extend(check_compiled(
+            archive,
+            |name| name.ends_with(".class"),
+            "Rift mod file is a source file!",
+        ));
+
+        let manifest = match manifest {
+            Some(manifest) if findings.is_empty() => manifest,
+            _ => return Ok(ValidationResult::Warning(findings)),
+        };
+
+        // Rift manifests declare neither versions nor dependencies
+        Ok(match serde_json::from_slice::<RiftModJson>(&manifest) {
+            Ok(manifest) => ValidationResult::PassWithMetadata(ModMetadata {
+                mod_id: manifest.id,
+                ..Default::default()
+            }),
+            Err(_) => ValidationResult::Pass,
+        })
+    }
+}
+
+#[derive(Deserialize)]
+struct RiftModJson {
+    id: String,
+}
@@ -0,0 +1,550 @@
+use crate::models::projects::{FileFinding, FindingCode, FindingSeverity};
+use serde::Deserialize;
+use sha2::Digest;
+use std::collections::HashSet;
+use std::io::{Read, Seek, Write};
+use std::path::Path;
+use thiserror::Error;
+use zip::ZipArchive;
+
+/// How many levels of jars inside jars are scanned
+const MAX_NESTING: usize = 3;
+/// Larger class files and nested jars are not read
+const MAX_ENTRY_SIZE: u64 = 64 * 1024 * 1024;
+/// How many bytes of class files and nested jars are read from a file at most, so archives which
+/// decompress to huge sizes can't tie up the scanner
+const MAX_SCANNED_BYTES: u64 = 1024 * 1024 * 1024;
+
+#[derive(Error, Debug)]
+pub enum ScanRulesError {
+    #[error("Unable to read scan rules: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid scan rules: {0}")]
+    SerDe(#[from] serde_json::Error),
+}
+
+/// The outcome of scanning a file, from best to worst
+#[derive(Deserialize, Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd)]
+#[serde(rename_all = "lowercase")]
+pub enum ScanVerdict {
+    Clean,
+    Suspicious,
+    Malicious,
+}
+
+impl ScanVerdict {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ScanVerdict::Clean => "clean",
+            ScanVerdict::Suspicious => "suspicious",
+            ScanVerdict::Malicious => "malicious",
+        }
+    }
+}
+
+/// Known malware, loaded from a local rules file so scanning works offline
+#[derive(Deserialize, Default)]
+pub struct ScanRules {
+    /// SHA1 or SHA512 hashes of known malicious files, in hex
+    #[serde(default)]
+    hashes: HashSet<String>,
+    #[serde(default)]
+    signatures: Vec<Signature>,
+}
+
+/// Strings which all appear in the constant pool of a single class of a known malware
+#[derive(Deserialize)]
+struct Signature {
+    name: String,
+    strings: Vec<String>,
+    #[serde(default = "default_signature_verdict")]
+    verdict: ScanVerdict,
+}
+
+fn default_signature_verdict() -> ScanVerdict {
+    ScanVerdict::Malicious
+}
+
+impl ScanRules {
+    pub fn load(path: &Path) -> Result<ScanRules, ScanRulesError> {
+        let mut rules: ScanRules = serde_json::from_slice(&std::fs::read(path)?)?;
+        rules.hashes = rules.hashes.iter().map(|x| x.to_lowercase()).collect();
+
+        Ok(rules)
+    }
+}
+
+pub struct ScanReport {
+    pub verdict: ScanVerdict,
+    pub findings: Vec<FileFinding>,
+}
+
+/// Scans a file, and the jars nested in it, for known malware and suspicious bytecode. The hashes
+/// of the file are passed in, as they are computed while it is received.
+pub fn scan_file<R: Read + Seek>(
+    reader: R,
+    sha1: &str,
+    sha512: &str,
+    rules: &ScanRules,
+) -> ScanReport {
+    let mut findings = Vec::new();
+    check_hashes(sha1, sha512, "", rules, &mut findings);
+
+    let mut budget = Budget::new(MAX_SCANNED_BYTES);
+    scan_archive(reader, "", 0, rules, &mut budget, &mut findings);
+    if budget.exhausted {
+        findings.push(finding(
+            ScanVerdict::Suspicious,
+            FindingCode::IncompleteScan,
+            format!("The file contains more than {MAX_SCANNED_BYTES} bytes of classes and jars, so it was not scanned completely"),
+            "",
+        ));
+    }
+
+    ScanReport {
+        verdict: findings
+            .iter()
+            .map(|x| match x.severity {
+                FindingSeverity::Critical => ScanVerdict::Malicious,
+                FindingSeverity::Warning => ScanVerdict::Suspicious,
+                FindingSeverity::Info => ScanVerdict::Clean,
+            })
+            .max()
+            .unwrap_or(ScanVerdict::Clean),
+        findings,
+    }
+}
+
+fn finding(verdict: ScanVerdict, code: FindingCode, message: String, path: &str) -> FileFinding {
+    FileFinding {
+        severity: match verdict {
+            ScanVerdict::Clean => FindingSeverity::Info,
+            ScanVerdict::Suspicious => FindingSeverity::Warning,
+            ScanVerdict::Malicious => FindingSeverity::Critical,
+        },
+        code,
+        message,
+        path: if path.is_empty() {
+            None
+        } else {
+            Some(path.to_string())
+        },
+    }
+}
+
+fn check_hashes(
+    sha1: &str,
+    sha512: &str,
+    path: &str,
+    rules: &ScanRules,
+    findings: &mut Vec<FileFinding>,
+) {
+    if rules.hashes.contains(sha1) || rules.hashes.contains(sha512) {
+        findings.push(finding(
+            ScanVerdict::Malicious,
+            FindingCode::MaliciousHash,
+            "The file matches a known malicious file".to_string(),
+            path,
+        ));
+    }
+}
+
+// The bytes which may still be read from a file, shared by the archives nested in it
+struct Budget {
+    remaining: u64,
+    /// Whether an entry was skipped, as it did not fit in the budget
+    exhausted: bool,
+}
+
+impl Budget {
+    fn new(bytes: u64) -> Self {
+        Budget {
+            remaining: bytes,
+            exhausted: false,
+        }
+    }
+
+    // Takes the size of an entry from the budget, unless it does not fit
+    fn take(&mut self, size: u64) -> bool {
+        if size > self.remaining {
+            self.exhausted = true;
+            return false;
+        }
+
+        self.remaining -= size;
+        true
+    }
+}
+
+// `prefix` is the path of the archive inside the uploaded file, such as `a.jar!/`. Nested jars are
+// scanned one at a time, from a temporary file.
+fn scan_archive<R: Read + Seek>(
+    reader: R,
+    prefix: &str,
+    depth: usize,
+    rules: &ScanRules,
+    budget: &mut Budget,
+    findings: &mut Vec<FileFinding>,
+) {
+    let mut archive = match ZipArchive::new(reader) {
+        Ok(archive) => archive,
+        Err(_) => return,
+    };
+
+    for i in 0..archive.len() {
+        let mut file = match archive.by_index(i) {
+            Ok(file) => file,
+            Err(_) => continue,
+        };
+        let path = format!("{prefix}{}", file.name());
+
+        if path.ends_with(".jar") {
+            if depth >= MAX_NESTING {
+                findings.push(finding(
+                    ScanVerdict::Suspicious,
+                    FindingCode::NestedJar,
+                    format!("Jars nested more than {MAX_NESTING} levels deep are not scanned"),
+                    &path,
+                ));
+            } else if let Some((nested, sha1, sha512)) = spool_entry(&mut file, budget) {
+                check_hashes(&sha1, &sha512, &path, rules, findings);
+                scan_archive(
+                    nested,
+                    &format!("{path}!/"),
+                    depth + 1,
+                    rules,
+                    budget,
+                    findings,
+                );
+            }
+        } else if path.ends_with(".class") {
+            if let Some(constants) = read_entry(&mut file, budget).and_then(|x| class_constants(&x))
+            {
+                scan_class(&constants, &path, rules, findings);
+            }
+        }
+    }
+}
+
+// Reads an entry, unless it is too large or does not fit in the budget. No more than the declared
+// size is read, so the budget holds for entries lying about their size.
+fn read_entry(file: &mut zip::read::ZipFile, budget: &mut Budget) -> Option<Vec<u8>> {
+    let size = file.size();
+    if size > MAX_ENTRY_SIZE || !budget.take(size) {
+        return None;
+    }
+
+    let mut contents = Vec::with_capacity(size as usize);
+    file.take(size).read_to_end(&mut contents).ok()?;
+    Some(contents)
+}
+
+// Copies a nested jar to a temporary file, as archives can only be read from seekable readers,
+// returning it along with its SHA1 and SHA512 hashes
+fn spool_entry(
+    file: &mut zip::read::ZipFile,
+    budget: &mut Budget,
+) -> Option<(std::fs::File, String, String)> {
+    let size = file.size();
+    if size > MAX_ENTRY_SIZE || !budget.take(size) {
+        return None;
+    }
+
+    let mut spooled = tempfile::tempfile().ok()?;
+    let mut sha1 = sha1::Sha1::new();
+    let mut sha512 = sha2::Sha512::new();
+
+    let mut reader = file.take(size);
+    let mut buffer = vec![0; 64 * 1024];
+    loop {
+        let read = reader.read(&mut buffer).ok()?;
+        if read == 0 {
+            break;
+        }
+        sha1.update(&buffer[..read]);
+        sha512.update(&buffer[..read]);
+        spooled.write_all(&buffer[..read]).ok()?;
+    }
+    spooled.rewind().ok()?;
+
+    Some((
+        spooled,
+        sha1.digest().to_string(),
+        format!("{:x}", sha512.finalize()),
+    ))
+}
+
+fn scan_class(
+    constants: &HashSet<String>,
+    path: &str,
+    rules: &ScanRules,
+    findings: &mut Vec<FileFinding>,
+) {
+    let has = |x: &str| constants.contains(x);
+    let contains = |x: &str| constants.iter().any(|y| y.contains(x));
+
+    for signature in &rules.signatures {
+        if !signature.strings.is_empty() && signature.strings.iter().all(|x| contains(x)) {
+            findings.push(finding(
+                signature.verdict,
+                FindingCode::MalwareSignature,
+                format!("The class matches the signature of {}", signature.name),
+                path,
+            ));
+        }
+    }
+
+    // URL class loaders are common in mods, so they are only reported when the class also loads
+    // classes and refers to the internet
+    let opens_connections = has("openConnection") || has("openStream");
+    if (has("defineClass") && opens_connections)
+        || (has("java/net/URLClassLoader")
+            && has("loadClass")
+            && (opens_connections || contains("http://") || contains("https://")))
+    {
+        findings.push(finding(
+            ScanVerdict::Suspicious,
+            FindingCode::RemoteClassLoading,
+            "The class loads classes at runtime, possibly from the internet".to_string(),
+            path,
+        ));
+    }
+
+    if has("java/lang/System")
+        && (has("load") || has("loadLibrary"))
+        && (has("java/io/FileOutputStream") || has("java/nio/file/Files"))
+    {
+        findings.push(finding(
+            ScanVerdict::Suspicious,
+            FindingCode::NativeLibraryDrop,
+            "The class writes files and loads native libraries".to_string(),
+            path,
+        ));
+    }
+
+    // Calling `Runtime.exec` through reflection hides it from a plain look at the references
+    // of the class, which is rarely done for a good reason. The names may also be encoded.
+    let runtime_names = [
+        "java.lang.Runtime",
+        "emitnuR.gnal.avaj",
+        "amF2YS5sYW5nLlJ1bnRpbWU",
+    ];
+    if has("java/lang/reflect/Method")
+        && has("invoke")
+        && !has("java/lang/Runtime")
+        && runtime_names.iter().any(|x| contains(x))
+    {
+        findings.push(finding(
+            ScanVerdict::Suspicious,
+            FindingCode::ReflectiveExec,
+            "The class accesses the Java runtime through reflection, which can run commands"
+                .to_string(),
+            path,
+        ));
+    }
+}
+
+/// The strings of the constant pool of a class file, which include the names of the classes,
+/// methods and fields it references and its string literals
+fn class_constants(data: &[u8]) -> Option<HashSet<String>> {
+    let mut reader = data;
+    let mut take = |len: usize| -> Option<&[u8]> {
+        if reader.len() < len {
+            return None;
+        }
+        let (taken, rest) = reader.split_at(len);
+        reader = rest;
+        Some(taken)
+    };
+    let u2 = |x: &[u8]| u16::from_be_bytes([x[0], x[1]]) as usize;
+
+    if take(4)? != [0xCA, 0xFE, 0xBA, 0xBE] {
+        return None;
+    }
+    // Minor and major version
+    take(4)?;
+
+    let count = u2(take(2)?);
+    let mut constants = HashSet::new();
+    let mut index = 1;
+    while index < count {
+        let tag = take(1)?[0];
+        match tag {
+            // UTF-8, in Java's modified encoding which is close enough to compare names
+            1 => {
+                let len = u2(take(2)?);
+                constants.insert(String::from_utf8_lossy(take(len)?).into_owned());
+            }
+            // Class, String, MethodType, Module and Package
+            7 | 8 | 16 | 19 | 20 => {
+                take(2)?;
+            }
+            // MethodHandle
+            15 => {
+                take(3)?;
+            }
+            // Integer, Float, the references, NameAndType, Dynamic and InvokeDynamic
+            3 | 4 | 9 | 10 | 11 | 12 | 17 | 18 => {
+                take(4)?;
+            }
+            // Long and Double, which take two entries
+            5 | 6 => {
+                take(8)?;
+                index += 1;
+            }
+            _ => return None,
+        }
+        index += 1;
+    }
+
+    Some(constants)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    // A class file with only the given UTF-8 constants
+    fn class(constants: &[&str]) -> Vec<u8> {
+        let mut data = vec![0xCA, 0xFE, 0xBA, 0xBE, 0, 0, 0, 52];
+        data.extend(((constants.len() + 3) as u16).to_be_bytes());
+        // A long constant, which takes two entries
+        data.push(5);
+        data.extend(42u64.to_be_bytes());
+        for constant in constants {
+            data.push(1);
+            data.extend((constant.len() as u16).to_be_bytes());
+            data.extend(constant.as_bytes());
+        }
+        data
+    }
+
+    fn scan(data: &[u8], rules: &ScanRules) -> ScanReport {
+        scan_file(
+            Cursor::new(data),
+            &sha1::Sha1::from(data).hexdigest(),
+            &format!("{:x}", sha2::Sha512::digest(data)),
+            rules,
+        )
+    }
+
+    fn jar(files: &[(&str, Vec<u8>)]) -> Vec<u8> {
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, contents) in files {
+            writer
+                .start_file(*name, zip::write::FileOptions::default())
+                .unwrap();
+            writer.write_all(contents).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn constants_are_read() {
+        let constants = class_constants(&class(&["java/lang/Object", "<init>"])).unwrap();
+
+        assert!(constants.contains("java/lang/Object"));
+        assert!(constants.contains("<init>"));
+        assert!(class_constants(b"PK").is_none());
+    }
+
+    #[test]
+    fn clean_files_pass() {
+        let data = jar(&[(
+            "com/example/Mod.class",
+            class(&["java/lang/Object", "onInitialize"]),
+        )]);
+        let report = scan(&data, &ScanRules::default());
+
+        assert_eq!(report.verdict, ScanVerdict::Clean);
+        assert!(report.findings.is_empty());
+    }
+
+    #[test]
+    fn nested_jars_are_scanned() {
+        let inner = jar(&[(
+            "a/B.class",
+            class(&["java/lang/reflect/Method", "invoke", "java.lang.Runtime"]),
+        )]);
+        let data = jar(&[("META-INF/jars/inner.jar", inner)]);
+        let report = scan(&data, &ScanRules::default());
+
+        assert_eq!(report.verdict, ScanVerdict::Suspicious);
+        assert_eq!(report.findings[0].code, FindingCode::ReflectiveExec);
+        assert_eq!(
+            report.findings[0].path.as_deref(),
+            Some("META-INF/jars/inner.jar!/a/B.class")
+        );
+    }
+
+    #[test]
+    fn rules_match_hashes_and_signatures() {
+        let data = jar(&[(
+            "a/B.class",
+            class(&["java/lang/Object", "https://evil.example/stage2"]),
+        )]);
+        let rules: ScanRules = serde_json::from_str(&format!(
+            r#"{{
+                "hashes": ["{}"],
+                "signatures": [{{ "name": "Stage 2", "strings": ["evil.example/stage2"] }}]
+            }}"#,
+            sha1::Sha1::from(&data).hexdigest()
+        ))
+        .unwrap();
+        let report = scan(&data, &rules);
+
+        assert_eq!(report.verdict, ScanVerdict::Malicious);
+        assert_eq!(
+            report.findings.iter().map(|x| x.code).collect::<Vec<_>>(),
+            vec![FindingCode::MaliciousHash, FindingCode::MalwareSignature]
+        );
+    }
+    #[test]
+    fn url_class_loaders_need_other_signals() {
+        let data = jar(&[(
+            "a/B.class",
+            class(&["java/net/URLClassLoader", "loadClass"]),
+        )]);
+        assert_eq!(
+            scan(&data, &ScanRules::default()).verdict,
+            ScanVerdict::Clean
+        );
+
+        let data = jar(&[(
+            "a/B.class",
+            class(&[
+                "java/net/URLClassLoader",
+                "loadClass",
+                "https://evil.example/payload.jar",
+            ]),
+        )]);
+        let report = scan(&data, &ScanRules::default());
+        assert_eq!(report.verdict, ScanVerdict::Suspicious);
+        assert_eq!(report.findings[0].code, FindingCode::RemoteClassLoading);
+    }
+
+    #[test]
+    fn scanning_stops_at_the_budget() {
+        let inner = jar(&[(
+            "a/B.class",
+            class(&["java/lang/reflect/Method", "invoke", "java.lang.Runtime"]),
+        )]);
+        let data = jar(&[("first.jar", inner.clone()), ("second.jar", inner.clone())]);
+
+        let mut budget = Budget::new(inner.len() as u64 * 2);
+        let mut findings = Vec::new();
+        scan_archive(
+            Cursor::new(&data),
+            "",
+            0,
+            &ScanRules::default(),
+            &mut budget,
+            &mut findings,
+        );
+
+        assert!(budget.exhausted);
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].path.as_deref(), Some("first.jar!/a/B.class"));
+    }
+}
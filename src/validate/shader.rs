@@ -1,5 +1,6 @@
+use crate::models::projects::FindingCode;
 use crate::validate::{SupportedGameVersions, ValidationError, ValidationResult};
-use std::io::Cursor;
+use std::fs::File;
 use zip::ZipArchive;
 
 pub struct ShaderValidator;
@@ -23,11 +24,38 @@ impl super::Validator for ShaderValidator {
 
     fn validate(
         &self,
-        archive: &mut ZipArchive<Cursor<bytes::Bytes>>,
+        archive: &mut ZipArchive<File>,
     ) -> Result<ValidationResult, ValidationError> {
         if !archive.file_names().any(|x| x.starts_with("shaders/")) {
-            return Ok(ValidationResult::Warning(
+            // Packs are often zipped with their folder, which OptiFine and Iris do not load
+            if let Some(nested) = archive
+                .file_names()
+                .find_map(|x| x.find("/shaders/").map(|i| &x[..i + "/shaders/".len()]))
+            {
+                return Ok(ValidationResult::warning(
+                    FindingCode::MissingFolder,
+                    "No shaders folder present at the root of the OptiFine/Iris shader. Tip: Zip the contents of your shader folder rather than the folder itself!",
+                    Some(nested),
+                ));
+            }
+
+            return Ok(ValidationResult::warning(
+                FindingCode::MissingFolder,
                 "No shaders folder present for OptiFine/Iris shader.",
+                Some("shaders/"),
+            ));
+        }
+
+        if !archive.file_names().any(|x| {
+            x.starts_with("shaders/")
+                && [".vsh", ".fsh", ".gsh", ".csh"]
+                    .iter()
+                    .any(|extension| x.ends_with(extension))
+        }) {
+            return Ok(ValidationResult::warning(
+                FindingCode::MissingContent,
+                "No shader programs (.vsh, .fsh, .gsh or .csh files) present in the shaders folder.",
+                Some("shaders/"),
             ));
         }
 
@@ -56,17 +84,21 @@ impl super::Validator for CanvasShaderValidator {
 
     fn validate(
         &self,
-        archive: &mut ZipArchive<Cursor<bytes::Bytes>>,
+        archive: &mut ZipArchive<File>,
     ) -> Result<ValidationResult, ValidationError> {
         if archive.by_name("pack.mcmeta").is_err() {
-            return Ok(ValidationResult::Warning(
+            return Ok(ValidationResult::warning(
+                FindingCode::MissingManifest,
                 "No pack.mcmeta present for pack file. Tip: Make sure pack.mcmeta is in the root directory of your pack!",
+                Some("pack.mcmeta"),
             ));
         };
 
         if !archive.file_names().any(|x| x.contains("/pipelines/")) {
-            return Ok(ValidationResult::Warning(
+            return Ok(ValidationResult::warning(
+                FindingCode::MissingFolder,
                 "No pipeline shaders folder present for canvas shaders.",
+                None,
             ));
         }
 
@@ -95,11 +127,13 @@ impl super::Validator for CoreShaderValidator {
 
     fn validate(
         &self,
-        archive: &mut ZipArchive<Cursor<bytes::Bytes>>,
+        archive: &mut ZipArchive<File>,
     ) -> Result<ValidationResult, ValidationError> {
         if archive.by_name("pack.mcmeta").is_err() {
-            return Ok(ValidationResult::Warning(
+            return Ok(ValidationResult::warning(
+                FindingCode::MissingManifest,
                 "No pack.mcmeta present for pack file. Tip: Make sure pack.mcmeta is in the root directory of your pack!",
+                Some("pack.mcmeta"),
             ));
         };
 
@@ -107,8 +141,10 @@ impl super::Validator for CoreShaderValidator {
             .file_names()
             .any(|x| x.starts_with("assets/minecraft/shaders/"))
         {
-            return Ok(ValidationResult::Warning(
+            return Ok(ValidationResult::warning(
+                FindingCode::MissingFolder,
                 "No shaders folder present for vanilla shaders.",
+                Some("assets/minecraft/shaders/"),
             ));
         }
 
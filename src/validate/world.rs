@@ -0,0 +1,46 @@
+use crate::models::projects::FindingCode;
+use crate::validate::{SupportedGameVersions, ValidationError, ValidationResult};
+use std::fs::File;
+use zip::ZipArchive;
+
+pub struct WorldValidator;
+
+impl super::Validator for WorldValidator {
+    fn get_file_extensions(&self) -> &[&str] {
+        &["zip"]
+    }
+
+    fn get_project_types(&self) -> &[&str] {
+        &["world"]
+    }
+
+    fn get_supported_loaders(&self) -> &[&str] {
+        &["minecraft"]
+    }
+
+    fn get_supported_game_versions(&self) -> SupportedGameVersions {
+        SupportedGameVersions::All
+    }
+
+    fn validate(
+        &self,
+        archive: &mut ZipArchive<File>,
+    ) -> Result<ValidationResult, ValidationError> {
+        // Saves are usually zipped with the folder of the world
+        if !archive.file_names().any(|name| {
+            name == "level.dat"
+                || name
+                    .strip_suffix("/level.dat")
+                    .map(|folder| !folder.contains('/'))
+                    .unwrap_or(false)
+        }) {
+            return Ok(ValidationResult::warning(
+                FindingCode::MissingManifest,
+                "No level.dat present for world file. Tip: Make sure level.dat is in the root directory of your world, or of a single folder!",
+                Some("level.dat"),
+            ));
+        }
+
+        Ok(ValidationResult::Pass)
+    }
+}